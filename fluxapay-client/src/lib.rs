@@ -0,0 +1,188 @@
+//! Higher-level helpers over the generated FluxaPay contract clients.
+//!
+//! Backend integrators tend to rebuild the same multi-call flows on top of
+//! `PaymentProcessorClient` and `RefundManagerClient`: create a charge and wait
+//! for the oracle to settle it, request a refund and wait for an operator to
+//! process it, or walk every page of a merchant's payments. This crate wraps
+//! those flows and maps the nested `try_*` results into a single [`ClientError`].
+
+use fluxapay::{
+    Error, PaymentCharge, PaymentProcessorClient, PaymentStatus, Refund, RefundManagerClient,
+    RefundReason, RefundStatus,
+};
+use soroban_sdk::{Address, BytesN, Env, InvokeError, Map, String, Symbol};
+
+/// Page size used when walking paginated contract views
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ClientError {
+    /// The contract rejected the call with one of its own error codes
+    Contract(Error),
+    /// The invocation failed in the host (auth, budget, trap)
+    Invoke(InvokeError),
+    /// The return value could not be decoded
+    Conversion,
+    /// The record was still pending after the allotted polls
+    Timeout,
+}
+
+type TryResult<T, E> = Result<Result<T, E>, Result<Error, InvokeError>>;
+
+/// Flatten the nested result returned by generated `try_*` client methods
+pub fn map_result<T, E>(result: TryResult<T, E>) -> Result<T, ClientError> {
+    match result {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(_)) => Err(ClientError::Conversion),
+        Err(Ok(error)) => Err(ClientError::Contract(error)),
+        Err(Err(error)) => Err(ClientError::Invoke(error)),
+    }
+}
+
+/// Parameters for a new charge
+#[derive(Clone, Debug)]
+pub struct ChargeRequest {
+    pub payment_id: String,
+    pub merchant_id: Address,
+    pub amount: i128,
+    pub currency: Symbol,
+    pub deposit_address: Address,
+    pub expires_at: u64,
+    pub order_reference: String,
+    /// Reuse across retries of the same charge so a lost response never double-creates it
+    pub idempotency_key: Option<String>,
+    pub metadata: Option<Map<Symbol, String>>,
+    /// Pin the payer for invoice-style charges; confirmations from anyone else are rejected
+    pub expected_payer: Option<Address>,
+    /// sha256 of a platform promo code to apply
+    pub promo_code: Option<BytesN<32>>,
+}
+
+pub struct Checkout<'a> {
+    pub client: PaymentProcessorClient<'a>,
+}
+
+impl Checkout<'_> {
+    pub fn new(env: &Env, contract_id: &Address) -> Self {
+        Checkout {
+            client: PaymentProcessorClient::new(env, contract_id),
+        }
+    }
+
+    pub fn create(&self, request: &ChargeRequest) -> Result<PaymentCharge, ClientError> {
+        map_result(self.client.try_create_payment(
+            &request.payment_id,
+            &request.merchant_id,
+            &request.amount,
+            &request.currency,
+            &request.deposit_address,
+            &request.expires_at,
+            &request.order_reference,
+            &request.idempotency_key,
+            &request.metadata,
+            &request.expected_payer,
+            &request.promo_code,
+        ))
+    }
+
+    /// Create a charge, then poll it until it leaves `Pending`.
+    ///
+    /// `wait` runs between polls with the latest state of the charge; a backend
+    /// sleeps there, a test advances the ledger or submits the verification.
+    pub fn create_and_watch(
+        &self,
+        request: &ChargeRequest,
+        max_polls: u32,
+        mut wait: impl FnMut(&PaymentCharge),
+    ) -> Result<PaymentCharge, ClientError> {
+        let mut payment = self.create(request)?;
+        for _ in 0..max_polls {
+            if payment.status != PaymentStatus::Pending {
+                return Ok(payment);
+            }
+            wait(&payment);
+            payment = map_result(self.client.try_get_payment(&request.payment_id))?;
+        }
+
+        if payment.status == PaymentStatus::Pending {
+            return Err(ClientError::Timeout);
+        }
+        Ok(payment)
+    }
+
+    /// Every payment of a merchant, fetched page by page
+    pub fn merchant_payments(&self, merchant_id: &Address) -> Vec<PaymentCharge> {
+        let mut payments = Vec::new();
+        let mut start = 0;
+        loop {
+            let page = self
+                .client
+                .get_merchant_payments(merchant_id, &start, &DEFAULT_PAGE_SIZE);
+            payments.extend(page.payments.iter());
+            start += DEFAULT_PAGE_SIZE;
+            if start >= page.total {
+                return payments;
+            }
+        }
+    }
+}
+
+pub struct Refunds<'a> {
+    pub client: RefundManagerClient<'a>,
+}
+
+impl Refunds<'_> {
+    pub fn new(env: &Env, contract_id: &Address) -> Self {
+        Refunds {
+            client: RefundManagerClient::new(env, contract_id),
+        }
+    }
+
+    /// Request a refund, then poll it until an operator processes it or the merchant
+    /// rejects it.
+    ///
+    /// `wait` runs between polls with the latest state of the refund.
+    pub fn request_and_poll(
+        &self,
+        payment_id: &String,
+        amount: i128,
+        reason: &RefundReason,
+        detail: &Option<String>,
+        requester: &Address,
+        max_polls: u32,
+        mut wait: impl FnMut(&Refund),
+    ) -> Result<Refund, ClientError> {
+        let refund_id = map_result(
+            self.client
+                .try_create_refund(payment_id, &amount, reason, detail, requester),
+        )?;
+
+        let mut refund = map_result(self.client.try_get_refund(&refund_id))?;
+        for _ in 0..max_polls {
+            if is_final(&refund.status) {
+                return Ok(refund);
+            }
+            wait(&refund);
+            refund = map_result(self.client.try_get_refund(&refund_id))?;
+        }
+
+        if !is_final(&refund.status) {
+            return Err(ClientError::Timeout);
+        }
+        Ok(refund)
+    }
+
+    /// Every refund recorded against a payment
+    pub fn for_payment(&self, payment_id: &String) -> Result<Vec<Refund>, ClientError> {
+        let refunds = map_result(self.client.try_get_payment_refunds(payment_id))?;
+        Ok(refunds.iter().collect())
+    }
+}
+
+// Approved refunds still wait on an operator
+fn is_final(status: &RefundStatus) -> bool {
+    matches!(status, RefundStatus::Completed | RefundStatus::Rejected)
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,150 @@
+use super::*;
+use fluxapay::merchant_registry::{MerchantRegistry, MerchantRegistryClient};
+use fluxapay::{PaymentProcessor, RefundManager};
+use soroban_sdk::testutils::{Address as _, BytesN as _, Ledger};
+use soroban_sdk::BytesN;
+
+struct Deployment<'a> {
+    env: Env,
+    admin: Address,
+    merchant_id: Address,
+    checkout: Checkout<'a>,
+    refunds: Refunds<'a>,
+}
+
+fn deploy<'a>() -> Deployment<'a> {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+
+    let registry = MerchantRegistryClient::new(&env, &env.register(MerchantRegistry, ()));
+    registry.initialize(&admin);
+    let merchant_id = Address::generate(&env);
+    registry.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Client Shop"),
+        &String::from_str(&env, "USD"),
+    );
+    registry.verify_merchant(&admin, &merchant_id);
+
+    let checkout = Checkout::new(&env, &env.register(PaymentProcessor, ()));
+    checkout.client.initialize(
+        &admin,
+        &registry.address,
+        &Map::from_array(&env, [(Symbol::new(&env, "USDC"), Address::generate(&env))]),
+        &None,
+    );
+    let refunds = Refunds::new(&env, &env.register(RefundManager, ()));
+    refunds.client.initialize(&admin, &None);
+
+    Deployment {
+        env,
+        admin,
+        merchant_id,
+        checkout,
+        refunds,
+    }
+}
+
+fn request(d: &Deployment, payment_id: &str) -> ChargeRequest {
+    ChargeRequest {
+        payment_id: String::from_str(&d.env, payment_id),
+        merchant_id: d.merchant_id.clone(),
+        amount: 1_000,
+        currency: Symbol::new(&d.env, "USDC"),
+        deposit_address: Address::generate(&d.env),
+        expires_at: d.env.ledger().timestamp() + 3600,
+        order_reference: String::from_str(&d.env, "ORDER-1"),
+        idempotency_key: None,
+        metadata: None,
+        expected_payer: None,
+        promo_code: None,
+    }
+}
+
+#[test]
+fn test_create_and_watch_until_verified() {
+    let d = deploy();
+    let oracle = Address::generate(&d.env);
+    d.checkout
+        .client
+        .grant_role(&d.admin, &Symbol::new(&d.env, "ORACLE"), &oracle);
+
+    let mut polls = 0;
+    let payment = d
+        .checkout
+        .create_and_watch(&request(&d, "watched"), 5, |payment| {
+            polls += 1;
+            // The oracle confirms the transfer on the second poll
+            if polls == 2 {
+                d.checkout.client.verify_payment(
+                    &oracle,
+                    &payment.payment_id,
+                    &BytesN::<32>::random(&d.env),
+                    &Address::generate(&d.env),
+                    &payment.amount,
+                    &1,
+                );
+            }
+        })
+        .unwrap();
+    assert_eq!(payment.status, PaymentStatus::Confirmed);
+    assert_eq!(polls, 2);
+}
+
+#[test]
+fn test_create_and_watch_times_out_and_maps_errors() {
+    let d = deploy();
+    let result = d.checkout.create_and_watch(&request(&d, "slow"), 3, |_| {
+        d.env.ledger().set_timestamp(10)
+    });
+    assert_eq!(result, Err(ClientError::Timeout));
+
+    // Creating the same charge again surfaces the contract error
+    let result = d.checkout.create(&request(&d, "slow"));
+    assert_eq!(
+        result,
+        Err(ClientError::Contract(Error::PaymentAlreadyExists))
+    );
+}
+
+#[test]
+fn test_merchant_payments_walks_all_pages() {
+    let d = deploy();
+    for i in 0..(DEFAULT_PAGE_SIZE + 5) {
+        d.checkout.create(&request(&d, &format!("p_{i}"))).unwrap();
+    }
+    let payments = d.checkout.merchant_payments(&d.merchant_id);
+    assert_eq!(payments.len() as u32, DEFAULT_PAGE_SIZE + 5);
+}
+
+#[test]
+fn test_request_and_poll_refund() {
+    let d = deploy();
+    let operator = Address::generate(&d.env);
+    d.refunds.client.grant_role(
+        &d.admin,
+        &Symbol::new(&d.env, "SETTLEMENT_OPERATOR"),
+        &operator,
+    );
+
+    let payment_id = String::from_str(&d.env, "refunded");
+    let refund = d
+        .refunds
+        .request_and_poll(
+            &payment_id,
+            500,
+            &RefundReason::ProductNotReceived,
+            &Some(String::from_str(&d.env, "Damaged")),
+            &Address::generate(&d.env),
+            3,
+            |refund| {
+                d.refunds
+                    .client
+                    .process_refund(&operator, &refund.refund_id)
+            },
+        )
+        .unwrap();
+    assert_eq!(refund.status, RefundStatus::Completed);
+    assert_eq!(d.refunds.for_payment(&payment_id).unwrap().len(), 1);
+}
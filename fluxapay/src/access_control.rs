@@ -1,4 +1,8 @@
-use soroban_sdk::{contracterror, contracttype, Address, Env, Symbol};
+use soroban_sdk::{contracterror, contracttype, vec, Address, BytesN, Env, Symbol, Vec};
+
+use crate::audit::{AuditEntity, AuditLog};
+use crate::events;
+use crate::ttl;
 
 // Role-based access control implementation
 pub fn role_admin(env: &Env) -> Symbol {
@@ -9,7 +13,6 @@ pub fn role_oracle(env: &Env) -> Symbol {
     Symbol::new(env, "ORACLE")
 }
 
-#[allow(dead_code)]
 pub fn role_merchant(env: &Env) -> Symbol {
     Symbol::new(env, "MERCHANT")
 }
@@ -18,6 +21,18 @@ pub fn role_settlement_operator(env: &Env) -> Symbol {
     Symbol::new(env, "SETTLEMENT_OPERATOR")
 }
 
+pub fn role_arbiter(env: &Env) -> Symbol {
+    Symbol::new(env, "ARBITER")
+}
+
+pub fn role_reader(env: &Env) -> Symbol {
+    Symbol::new(env, "READER")
+}
+
+pub fn role_compliance(env: &Env) -> Symbol {
+    Symbol::new(env, "COMPLIANCE")
+}
+
 #[contracterror]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum AccessControlError {
@@ -26,12 +41,27 @@ pub enum AccessControlError {
     RoleNotGranted = 3,
     CannotRenounceAdmin = 4,
     InvalidAdmin = 5,
+    RoleNotDefined = 6,
+    InvalidExpiry = 7,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleDefinition {
+    pub role: Symbol,
+    pub description_hash: BytesN<32>,
+    pub admin_role: Symbol,
 }
 
 #[contracttype]
 pub enum AccessControlDataKey {
     Role(Symbol, Address),
     Admin,
+    RoleDefinition(Symbol),
+    DefinedRoles,
+    RoleMembers(Symbol),         // role -> Vec<Address>
+    AccountRoles(Address),       // account -> Vec<Symbol>
+    RoleExpiry(Symbol, Address), // (role, account) -> u64 timestamp the grant lapses at
 }
 
 pub struct AccessControl;
@@ -42,49 +72,210 @@ impl AccessControl {
             .persistent()
             .set(&AccessControlDataKey::Admin, &admin);
         Self::grant_role_internal(env, &role_admin(env), &admin);
+
+        // Seed the built-in roles so existing flows keep working
+        for role in [
+            role_admin(env),
+            role_oracle(env),
+            role_merchant(env),
+            role_settlement_operator(env),
+            role_arbiter(env),
+            role_reader(env),
+            role_compliance(env),
+        ] {
+            Self::define_role_internal(
+                env,
+                role,
+                BytesN::from_array(env, &[0; 32]),
+                role_admin(env),
+            );
+        }
     }
 
-    pub fn grant_role(
+    pub fn define_role(
         env: &Env,
         admin: Address,
         role: Symbol,
-        account: Address,
+        description_hash: BytesN<32>,
+        admin_role: Symbol,
+    ) -> Result<(), AccessControlError> {
+        admin.require_auth();
+        if !Self::has_role(env, &role_admin(env), &admin) {
+            return Err(AccessControlError::Unauthorized);
+        }
+
+        if Self::get_role_definition(env, &admin_role).is_none() && admin_role != role {
+            return Err(AccessControlError::RoleNotDefined);
+        }
+
+        Self::define_role_internal(env, role, description_hash, admin_role);
+        Ok(())
+    }
+
+    pub fn get_role_definition(env: &Env, role: &Symbol) -> Option<RoleDefinition> {
+        env.storage()
+            .persistent()
+            .get(&AccessControlDataKey::RoleDefinition(role.clone()))
+    }
+
+    pub fn get_defined_roles(env: &Env) -> Vec<Symbol> {
+        env.storage()
+            .persistent()
+            .get(&AccessControlDataKey::DefinedRoles)
+            .unwrap_or(vec![env])
+    }
+
+    /// Point `role` at a different administering role, whose holders then grant and revoke
+    /// it (admin only)
+    pub fn set_role_admin(
+        env: &Env,
+        admin: Address,
+        role: Symbol,
+        admin_role: Symbol,
     ) -> Result<(), AccessControlError> {
+        admin.require_auth();
         if !Self::has_role(env, &role_admin(env), &admin) {
             return Err(AccessControlError::Unauthorized);
         }
 
+        let mut definition =
+            Self::get_role_definition(env, &role).ok_or(AccessControlError::RoleNotDefined)?;
+        if Self::get_role_definition(env, &admin_role).is_none() {
+            return Err(AccessControlError::RoleNotDefined);
+        }
+
+        definition.admin_role = admin_role.clone();
+        env.storage().persistent().set(
+            &AccessControlDataKey::RoleDefinition(role.clone()),
+            &definition,
+        );
+        events::role_admin_changed(env, &role, &admin_role, &admin);
+        Ok(())
+    }
+
+    /// Grant `role` to `account`; `caller` must hold the role's admin role
+    pub fn grant_role(
+        env: &Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+    ) -> Result<(), AccessControlError> {
+        caller.require_auth();
+        Self::require_role_admin(env, &role, &caller)?;
+
         if Self::has_role(env, &role, &account) {
             return Err(AccessControlError::RoleAlreadyGranted);
         }
 
         Self::grant_role_internal(env, &role, &account);
+        AuditLog::append(env, &caller, "ROLE_GRANTED", account.to_string());
+        events::role(env, "GRANTED", &role, &account, &caller);
         Ok(())
     }
 
-    pub fn revoke_role(
+    /// Grant `role` until `expires_at`, after which `has_role` treats it as absent
+    pub fn grant_role_until(
         env: &Env,
-        admin: Address,
+        caller: Address,
         role: Symbol,
         account: Address,
+        expires_at: u64,
     ) -> Result<(), AccessControlError> {
-        if !Self::has_role(env, &role_admin(env), &admin) {
-            return Err(AccessControlError::Unauthorized);
+        if expires_at <= env.ledger().timestamp() {
+            return Err(AccessControlError::InvalidExpiry);
         }
 
+        Self::grant_role(env, caller, role.clone(), account.clone())?;
+        env.storage().persistent().set(
+            &AccessControlDataKey::RoleExpiry(role, account),
+            &expires_at,
+        );
+        Ok(())
+    }
+
+    pub fn get_role_expiry(env: &Env, role: &Symbol, account: &Address) -> Option<u64> {
+        env.storage()
+            .persistent()
+            .get(&AccessControlDataKey::RoleExpiry(
+                role.clone(),
+                account.clone(),
+            ))
+    }
+
+    /// Remove up to `limit` lapsed grants from storage, returning how many were removed
+    pub fn prune_expired_roles(env: &Env, limit: u32) -> u32 {
+        let now = env.ledger().timestamp();
+        let mut pruned = 0;
+        for role in Self::get_defined_roles(env).iter() {
+            let members: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&AccessControlDataKey::RoleMembers(role.clone()))
+                .unwrap_or(vec![env]);
+            for account in members.iter() {
+                if pruned >= limit {
+                    return pruned;
+                }
+                if let Some(expires_at) = Self::get_role_expiry(env, &role, &account) {
+                    if now >= expires_at {
+                        Self::revoke_role_internal(env, &role, &account);
+                        pruned += 1;
+                    }
+                }
+            }
+        }
+        pruned
+    }
+
+    /// Revoke `role` from `account`; `caller` must hold the role's admin role
+    pub fn revoke_role(
+        env: &Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+    ) -> Result<(), AccessControlError> {
+        caller.require_auth();
+        Self::require_role_admin(env, &role, &caller)?;
+
         if !Self::has_role(env, &role, &account) {
             return Err(AccessControlError::RoleNotGranted);
         }
 
         Self::revoke_role_internal(env, &role, &account);
+        AuditLog::append(env, &caller, "ROLE_REVOKED", account.to_string());
+        events::role(env, "REVOKED", &role, &account, &caller);
         Ok(())
     }
 
+    // Grants and revokes of a role are reserved to holders of its admin role
+    fn require_role_admin(
+        env: &Env,
+        role: &Symbol,
+        caller: &Address,
+    ) -> Result<(), AccessControlError> {
+        let definition =
+            Self::get_role_definition(env, role).ok_or(AccessControlError::RoleNotDefined)?;
+        Self::require_role(env, &definition.admin_role, caller)
+    }
+
     pub fn has_role(env: &Env, role: &Symbol, account: &Address) -> bool {
-        env.storage()
-            .persistent()
-            .get(&AccessControlDataKey::Role(role.clone(), account.clone()))
-            .unwrap_or(false)
+        let key = AccessControlDataKey::Role(role.clone(), account.clone());
+        let granted = env.storage().persistent().get(&key).unwrap_or(false);
+        if granted {
+            ttl::extend(env, &key);
+        }
+
+        // Time-limited grants lapse without a revoke
+        match Self::get_role_expiry(env, role, account) {
+            Some(expires_at) => granted && env.ledger().timestamp() < expires_at,
+            None => granted,
+        }
+    }
+
+    /// Whether `account` may read sensitive aggregate views (readers and admins)
+    pub fn can_read(env: &Env, account: &Address) -> bool {
+        Self::has_role(env, &role_reader(env), account)
+            || Self::has_role(env, &role_admin(env), account)
     }
 
     pub fn renounce_role(
@@ -92,6 +283,7 @@ impl AccessControl {
         account: Address,
         role: Symbol,
     ) -> Result<(), AccessControlError> {
+        account.require_auth();
         if role == role_admin(env) {
             return Err(AccessControlError::CannotRenounceAdmin);
         }
@@ -101,6 +293,8 @@ impl AccessControl {
         }
 
         Self::revoke_role_internal(env, &role, &account);
+        AuditLog::append(env, &account, "ROLE_RENOUNCED", account.to_string());
+        events::role(env, "RENOUNCED", &role, &account, &account);
         Ok(())
     }
 
@@ -109,17 +303,31 @@ impl AccessControl {
         current_admin: Address,
         new_admin: Address,
     ) -> Result<(), AccessControlError> {
+        current_admin.require_auth();
         if !Self::has_role(env, &role_admin(env), &current_admin) {
             return Err(AccessControlError::Unauthorized);
         }
 
         Self::revoke_role_internal(env, &role_admin(env), &current_admin);
         Self::grant_role_internal(env, &role_admin(env), &new_admin);
+        AuditLog::append(
+            env,
+            &current_admin,
+            "ADMIN_TRANSFERRED",
+            new_admin.to_string(),
+        );
 
         env.storage()
             .persistent()
             .set(&AccessControlDataKey::Admin, &new_admin);
 
+        events::role(
+            env,
+            "ADMIN_TRANSFERRED",
+            &role_admin(env),
+            &new_admin,
+            &current_admin,
+        );
         Ok(())
     }
 
@@ -127,7 +335,6 @@ impl AccessControl {
         env.storage().persistent().get(&AccessControlDataKey::Admin)
     }
 
-    #[allow(dead_code)]
     pub fn require_role(
         env: &Env,
         role: &Symbol,
@@ -139,16 +346,138 @@ impl AccessControl {
         Ok(())
     }
 
-    fn grant_role_internal(env: &Env, role: &Symbol, account: &Address) {
+    fn define_role_internal(
+        env: &Env,
+        role: Symbol,
+        description_hash: BytesN<32>,
+        admin_role: Symbol,
+    ) {
+        let mut defined = Self::get_defined_roles(env);
+        if !defined.contains(&role) {
+            defined.push_back(role.clone());
+            env.storage()
+                .persistent()
+                .set(&AccessControlDataKey::DefinedRoles, &defined);
+        }
+
         env.storage().persistent().set(
-            &AccessControlDataKey::Role(role.clone(), account.clone()),
-            &true,
+            &AccessControlDataKey::RoleDefinition(role.clone()),
+            &RoleDefinition {
+                role,
+                description_hash,
+                admin_role,
+            },
         );
     }
 
+    /// Holders of `role`, `limit` at a time from `offset`
+    pub fn get_role_members(env: &Env, role: &Symbol, offset: u32, limit: u32) -> Vec<Address> {
+        let members: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&AccessControlDataKey::RoleMembers(role.clone()))
+            .unwrap_or(vec![env]);
+        let end = offset.saturating_add(limit).min(members.len());
+        if offset >= end {
+            return vec![env];
+        }
+        members.slice(offset..end)
+    }
+
+    /// Roles held by `account`, `limit` at a time from `offset`
+    pub fn get_account_roles(env: &Env, account: &Address, offset: u32, limit: u32) -> Vec<Symbol> {
+        let roles: Vec<Symbol> = env
+            .storage()
+            .persistent()
+            .get(&AccessControlDataKey::AccountRoles(account.clone()))
+            .unwrap_or(vec![env]);
+        let end = offset.saturating_add(limit).min(roles.len());
+        if offset >= end {
+            return vec![env];
+        }
+        roles.slice(offset..end)
+    }
+
+    fn grant_role_internal(env: &Env, role: &Symbol, account: &Address) {
+        AuditLog::record(
+            env,
+            AuditEntity::Account(account.clone()),
+            "ROLE_GRANTED",
+            Some(role.clone()),
+            None,
+        );
+        let key = AccessControlDataKey::Role(role.clone(), account.clone());
+        env.storage().persistent().set(&key, &true);
+        ttl::extend(env, &key);
+        // A fresh grant is permanent unless the caller sets an expiry afterwards
+        env.storage()
+            .persistent()
+            .remove(&AccessControlDataKey::RoleExpiry(
+                role.clone(),
+                account.clone(),
+            ));
+
+        let members_key = AccessControlDataKey::RoleMembers(role.clone());
+        let mut members: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&members_key)
+            .unwrap_or(vec![env]);
+        if !members.contains(account) {
+            members.push_back(account.clone());
+            env.storage().persistent().set(&members_key, &members);
+        }
+
+        let roles_key = AccessControlDataKey::AccountRoles(account.clone());
+        let mut roles: Vec<Symbol> = env
+            .storage()
+            .persistent()
+            .get(&roles_key)
+            .unwrap_or(vec![env]);
+        if !roles.contains(role) {
+            roles.push_back(role.clone());
+            env.storage().persistent().set(&roles_key, &roles);
+        }
+    }
+
     fn revoke_role_internal(env: &Env, role: &Symbol, account: &Address) {
+        AuditLog::record(
+            env,
+            AuditEntity::Account(account.clone()),
+            "ROLE_REVOKED",
+            Some(role.clone()),
+            None,
+        );
         env.storage()
             .persistent()
             .remove(&AccessControlDataKey::Role(role.clone(), account.clone()));
+        env.storage()
+            .persistent()
+            .remove(&AccessControlDataKey::RoleExpiry(
+                role.clone(),
+                account.clone(),
+            ));
+
+        let members_key = AccessControlDataKey::RoleMembers(role.clone());
+        let mut members: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&members_key)
+            .unwrap_or(vec![env]);
+        if let Some(i) = members.first_index_of(account) {
+            members.remove(i);
+            env.storage().persistent().set(&members_key, &members);
+        }
+
+        let roles_key = AccessControlDataKey::AccountRoles(account.clone());
+        let mut roles: Vec<Symbol> = env
+            .storage()
+            .persistent()
+            .get(&roles_key)
+            .unwrap_or(vec![env]);
+        if let Some(i) = roles.first_index_of(role) {
+            roles.remove(i);
+            env.storage().persistent().set(&roles_key, &roles);
+        }
     }
 }
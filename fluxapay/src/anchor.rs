@@ -0,0 +1,64 @@
+use soroban_sdk::{contracttype, Address, Env, String};
+
+use crate::Error;
+
+// SEP-24/SEP-6 style references for settlements that leave through a fiat off-ramp, so the
+// anchor's transaction can be matched back to the on-chain settlement automatically
+pub const MAX_MEMO_TEXT_LEN: u32 = 28; // Stellar text memos are at most 28 bytes
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MemoType {
+    None,
+    Text,
+    Id,   // u64 rendered as decimal text
+    Hash, // 32 bytes rendered as hex
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnchorReference {
+    pub settlement_id: String,           // settled payment_id or batch_id
+    pub external_transaction_id: String, // anchor's id for the off-ramp leg
+    pub anchor_account: Address,         // account the payout was sent to at the anchor
+    pub memo_type: MemoType,
+    pub memo: String, // empty for MemoType::None
+    pub recorded_by: Address,
+    pub recorded_at: u64,
+}
+
+#[contracttype]
+pub enum AnchorDataKey {
+    Reference(String), // settlement_id -> AnchorReference
+}
+
+pub struct AnchorReferences;
+
+impl AnchorReferences {
+    pub fn validate_memo(memo_type: &MemoType, memo: &String) -> Result<(), Error> {
+        let valid = match memo_type {
+            MemoType::None => memo.is_empty(),
+            MemoType::Text => memo.len() <= MAX_MEMO_TEXT_LEN,
+            MemoType::Id => !memo.is_empty() && memo.len() <= 20, // u64::MAX has 20 digits
+            MemoType::Hash => memo.len() == 64,
+        };
+        if !valid {
+            return Err(Error::InvalidAnchorReference);
+        }
+        Ok(())
+    }
+
+    /// Store the reference, replacing any earlier one for the same settlement
+    pub fn record(env: &Env, reference: &AnchorReference) {
+        env.storage().persistent().set(
+            &AnchorDataKey::Reference(reference.settlement_id.clone()),
+            reference,
+        );
+    }
+
+    pub fn get(env: &Env, settlement_id: &String) -> Option<AnchorReference> {
+        env.storage()
+            .persistent()
+            .get(&AnchorDataKey::Reference(settlement_id.clone()))
+    }
+}
@@ -0,0 +1,39 @@
+use soroban_sdk::Env;
+
+use crate::config::Configs;
+use crate::fees::MAX_FEE_BPS;
+
+// Verification behaviour a merchant gets at its pinned API version. Upgrades that change
+// behaviour add a version here, and each merchant opts in when ready
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApiBehavior {
+    pub amount_tolerance_bps: u32, // underpayment accepted as a share of the charge
+    pub refund_overpayment: bool,  // confirm overpaid charges and refund the excess
+}
+
+impl ApiBehavior {
+    pub fn for_version(env: &Env, api_version: u32) -> Self {
+        match api_version {
+            // v1: the received amount must match the charge exactly
+            0 | 1 => ApiBehavior {
+                amount_tolerance_bps: 0,
+                refund_overpayment: false,
+            },
+            // v2: absorb small shortfalls (e.g. wallet rounding, 1% by default) and refund any
+            // excess
+            _ => ApiBehavior {
+                amount_tolerance_bps: Configs::get(env).tolerance_bps,
+                refund_overpayment: true,
+            },
+        }
+    }
+
+    /// Whether `received` settles a charge of `expected`
+    pub fn accepts(&self, expected: i128, received: i128) -> bool {
+        if received > expected {
+            return self.refund_overpayment;
+        }
+        let tolerance = expected * self.amount_tolerance_bps as i128 / MAX_FEE_BPS as i128;
+        expected - received <= tolerance
+    }
+}
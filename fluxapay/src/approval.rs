@@ -0,0 +1,97 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Vec};
+
+use crate::Error;
+
+// Actions moving more than `threshold` need `required` distinct operator approvals first
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApprovalPolicy {
+    pub threshold: i128,
+    pub required: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Approval {
+    pub action_id: String, // payment_id being settled or refund_id being processed
+    pub amount: i128,
+    pub approvers: Vec<Address>,
+    pub opened_at: u64,
+}
+
+#[contracttype]
+pub enum ApprovalDataKey {
+    Policy,
+    Approval(String), // action_id -> Approval
+}
+
+pub struct Approvals;
+
+impl Approvals {
+    pub fn set_policy(env: &Env, policy: ApprovalPolicy) -> Result<(), Error> {
+        if policy.threshold <= 0 || policy.required == 0 {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage()
+            .persistent()
+            .set(&ApprovalDataKey::Policy, &policy);
+        Ok(())
+    }
+
+    pub fn clear_policy(env: &Env) {
+        env.storage().persistent().remove(&ApprovalDataKey::Policy);
+    }
+
+    pub fn get_policy(env: &Env) -> Option<ApprovalPolicy> {
+        env.storage().persistent().get(&ApprovalDataKey::Policy)
+    }
+
+    pub fn get(env: &Env, action_id: &String) -> Option<Approval> {
+        env.storage()
+            .persistent()
+            .get(&ApprovalDataKey::Approval(action_id.clone()))
+    }
+
+    /// Add `approver` to the action's approvals, opening the record on the first one
+    pub fn approve(
+        env: &Env,
+        action_id: &String,
+        amount: i128,
+        approver: &Address,
+    ) -> Result<Approval, Error> {
+        let mut approval = Self::get(env, action_id).unwrap_or(Approval {
+            action_id: action_id.clone(),
+            amount,
+            approvers: vec![env],
+            opened_at: env.ledger().timestamp(),
+        });
+        if approval.approvers.contains(approver) {
+            return Err(Error::AlreadyApproved);
+        }
+        approval.approvers.push_back(approver.clone());
+        env.storage()
+            .persistent()
+            .set(&ApprovalDataKey::Approval(action_id.clone()), &approval);
+        Ok(approval)
+    }
+
+    /// Whether the action is under the threshold or has gathered enough approvals
+    pub fn is_satisfied(env: &Env, action_id: &String, amount: i128) -> bool {
+        match Self::get_policy(env) {
+            Some(policy) if amount > policy.threshold => Self::get(env, action_id)
+                .is_some_and(|approval| approval.approvers.len() >= policy.required),
+            _ => true,
+        }
+    }
+
+    /// Use up the action's approvals as it executes, so they cannot be replayed
+    pub fn consume(env: &Env, action_id: &String, amount: i128) -> Result<(), Error> {
+        if !Self::is_satisfied(env, action_id, amount) {
+            return Err(Error::ApprovalRequired);
+        }
+        env.storage()
+            .persistent()
+            .remove(&ApprovalDataKey::Approval(action_id.clone()));
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use soroban_sdk::{contracttype, Address, Env, String, Symbol};
+
+use crate::{Error, PaymentCharge, PaymentStatus};
+
+// Old terminal payments can be swapped for a compact summary to cut storage rent while
+// keeping what reconciliation needs
+pub const DEFAULT_ARCHIVE_RETENTION: u64 = 90 * 24 * 3600;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentSummary {
+    pub payment_id: String,
+    pub merchant_id: Address,
+    pub amount: i128,
+    pub currency: Symbol,
+    pub status: PaymentStatus,
+    pub fee_amount: i128,
+    pub refunded_amount: i128,
+    pub settled_at: Option<u64>,
+    pub archived_at: Option<u64>, // None while the full record is still stored
+}
+
+#[contracttype]
+pub enum ArchiveDataKey {
+    Summary(String), // payment_id -> PaymentSummary, once archived
+    Retention,       // u64 seconds a terminal payment is kept in full
+}
+
+pub struct Archive;
+
+impl Archive {
+    pub fn summarize(payment: &PaymentCharge, archived_at: Option<u64>) -> PaymentSummary {
+        PaymentSummary {
+            payment_id: payment.payment_id.clone(),
+            merchant_id: payment.merchant_id.clone(),
+            amount: payment.amount,
+            currency: payment.currency.clone(),
+            status: payment.status.clone(),
+            fee_amount: payment.fee_amount,
+            refunded_amount: payment.refunded_amount,
+            settled_at: payment.settled_at,
+            archived_at,
+        }
+    }
+
+    /// Whether the payment has reached a final status and aged past the retention period, with
+    /// no overpayment still owed back to its payer
+    pub fn is_archivable(env: &Env, payment: &PaymentCharge) -> bool {
+        let terminal = matches!(
+            payment.status,
+            PaymentStatus::Settled
+                | PaymentStatus::Expired
+                | PaymentStatus::Failed
+                | PaymentStatus::Cancelled
+        );
+        let closed_at = payment.settled_at.unwrap_or(payment.created_at);
+        terminal
+            && payment.overpaid_amount == 0
+            && env.ledger().timestamp() >= closed_at.saturating_add(Self::get_retention(env))
+    }
+
+    pub fn store(env: &Env, summary: &PaymentSummary) {
+        env.storage().persistent().set(
+            &ArchiveDataKey::Summary(summary.payment_id.clone()),
+            summary,
+        );
+    }
+
+    pub fn get(env: &Env, payment_id: &String) -> Option<PaymentSummary> {
+        env.storage()
+            .persistent()
+            .get(&ArchiveDataKey::Summary(payment_id.clone()))
+    }
+
+    pub fn is_archived(env: &Env, payment_id: &String) -> bool {
+        env.storage()
+            .persistent()
+            .has(&ArchiveDataKey::Summary(payment_id.clone()))
+    }
+
+    pub fn set_retention(env: &Env, retention: u64) -> Result<(), Error> {
+        if retention == 0 {
+            return Err(Error::InvalidRetention);
+        }
+        env.storage()
+            .persistent()
+            .set(&ArchiveDataKey::Retention, &retention);
+        Ok(())
+    }
+
+    pub fn get_retention(env: &Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&ArchiveDataKey::Retention)
+            .unwrap_or(DEFAULT_ARCHIVE_RETENTION)
+    }
+}
@@ -0,0 +1,67 @@
+use soroban_sdk::{contracttype, xdr::ToXdr, Address, Bytes, BytesN, Env, String};
+
+use crate::Error;
+
+// Signed oracle attestations: an off-chain watcher signs what it saw arrive with an ed25519
+// key registered for an oracle, so a relayer can submit the verification on its behalf
+#[contracttype]
+pub enum AttestationDataKey {
+    OracleKey(BytesN<32>), // ed25519 public key -> oracle address it attests for
+}
+
+pub struct Attestations;
+
+impl Attestations {
+    pub fn register_key(env: &Env, public_key: &BytesN<32>, oracle: &Address) {
+        env.storage()
+            .persistent()
+            .set(&AttestationDataKey::OracleKey(public_key.clone()), oracle);
+    }
+
+    pub fn remove_key(env: &Env, public_key: &BytesN<32>) -> Result<(), Error> {
+        let key = AttestationDataKey::OracleKey(public_key.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::OracleKeyNotFound);
+        }
+        env.storage().persistent().remove(&key);
+        Ok(())
+    }
+
+    pub fn get_oracle(env: &Env, public_key: &BytesN<32>) -> Option<Address> {
+        env.storage()
+            .persistent()
+            .get(&AttestationDataKey::OracleKey(public_key.clone()))
+    }
+
+    /// Bytes the oracle signs: XDR of (payment_id, transaction_hash, amount_received, payer,
+    /// nonce)
+    pub fn message(
+        env: &Env,
+        payment_id: &String,
+        transaction_hash: &BytesN<32>,
+        amount_received: i128,
+        payer: &Address,
+        nonce: u64,
+    ) -> Bytes {
+        (
+            payment_id.clone(),
+            transaction_hash.clone(),
+            amount_received,
+            payer.clone(),
+            nonce,
+        )
+            .to_xdr(env)
+    }
+
+    /// Oracle the key attests for; an invalid signature aborts the invocation
+    pub fn verify(
+        env: &Env,
+        public_key: &BytesN<32>,
+        message: &Bytes,
+        signature: &BytesN<64>,
+    ) -> Result<Address, Error> {
+        let oracle = Self::get_oracle(env, public_key).ok_or(Error::OracleKeyNotFound)?;
+        env.crypto().ed25519_verify(public_key, message, signature);
+        Ok(oracle)
+    }
+}
@@ -0,0 +1,152 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Symbol, Vec};
+
+// Append-only per-entity history for investigations: status changes, config and role changes,
+// refunds and disputes
+pub const AUDIT_PAGE_SIZE: u32 = 20;
+
+/// Records kept in the contract-wide journal; older ones are overwritten in place
+pub const AUDIT_JOURNAL_CAP: u64 = 1000;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuditEntity {
+    Payment(String),
+    Merchant(Address),
+    Account(Address),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditEntry {
+    pub recorded_at: u64,
+    pub action: Symbol,            // e.g. STATUS, ROLE_GRANTED, REFUND_CREATED
+    pub detail: Option<Symbol>,    // new status, role, dispute outcome
+    pub reference: Option<String>, // related payment, refund or dispute id
+}
+
+/// One state-changing call in the contract-wide journal, numbered in call order
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub actor: Address, // caller, or the contract itself for permissionless calls
+    pub action: Symbol, // e.g. CREATE, VERIFY, CANCEL, REFUND, ROLE_GRANTED
+    pub subject_id: String, // payment, refund or account the call acted on
+    pub timestamp: u64,
+}
+
+#[contracttype]
+pub enum AuditDataKey {
+    Trail(AuditEntity), // entity -> Vec<AuditEntry> in recording order
+    NextSeq,            // seq the next journal record gets
+    Record(u64),        // seq % AUDIT_JOURNAL_CAP -> AuditRecord
+}
+
+pub struct AuditLog;
+
+impl AuditLog {
+    pub fn record(
+        env: &Env,
+        entity: AuditEntity,
+        action: &str,
+        detail: Option<Symbol>,
+        reference: Option<String>,
+    ) {
+        let key = AuditDataKey::Trail(entity);
+        let mut trail: Vec<AuditEntry> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        trail.push_back(AuditEntry {
+            recorded_at: env.ledger().timestamp(),
+            action: Symbol::new(env, action),
+            detail,
+            reference,
+        });
+        env.storage().persistent().set(&key, &trail);
+    }
+
+    pub fn get_all(env: &Env, entity: &AuditEntity) -> Vec<AuditEntry> {
+        env.storage()
+            .persistent()
+            .get(&AuditDataKey::Trail(entity.clone()))
+            .unwrap_or(vec![env])
+    }
+
+    /// `AUDIT_PAGE_SIZE` entries of a chronological list, starting at page `page`
+    pub fn page(env: &Env, entries: &Vec<AuditEntry>, page: u32) -> Vec<AuditEntry> {
+        let start = page.saturating_mul(AUDIT_PAGE_SIZE).min(entries.len());
+        let end = start.saturating_add(AUDIT_PAGE_SIZE).min(entries.len());
+        let mut results = vec![env];
+        for i in start..end {
+            results.push_back(entries.get_unchecked(i));
+        }
+        results
+    }
+
+    /// Merge two chronological trails, keeping `first` ahead on equal timestamps
+    pub fn merge(env: &Env, first: &Vec<AuditEntry>, second: &Vec<AuditEntry>) -> Vec<AuditEntry> {
+        let mut merged = vec![env];
+        let (mut i, mut j) = (0, 0);
+        while i < first.len() || j < second.len() {
+            let take_first = match (first.get(i), second.get(j)) {
+                (Some(a), Some(b)) => a.recorded_at <= b.recorded_at,
+                (Some(_), None) => true,
+                _ => false,
+            };
+            if take_first {
+                merged.push_back(first.get_unchecked(i));
+                i += 1;
+            } else {
+                merged.push_back(second.get_unchecked(j));
+                j += 1;
+            }
+        }
+        merged
+    }
+
+    /// Append a record to the contract-wide journal, returning its sequence number
+    pub fn append(env: &Env, actor: &Address, action: &str, subject_id: String) -> u64 {
+        let seq: u64 = env
+            .storage()
+            .persistent()
+            .get(&AuditDataKey::NextSeq)
+            .unwrap_or(0);
+        env.storage().persistent().set(
+            &AuditDataKey::Record(seq % AUDIT_JOURNAL_CAP),
+            &AuditRecord {
+                seq,
+                actor: actor.clone(),
+                action: Symbol::new(env, action),
+                subject_id,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
+        env.storage()
+            .persistent()
+            .set(&AuditDataKey::NextSeq, &(seq + 1));
+        seq
+    }
+
+    /// Up to `limit` journal records (at most `AUDIT_PAGE_SIZE`) from `from_seq` on; records
+    /// already overwritten are skipped
+    pub fn entries(env: &Env, from_seq: u64, limit: u32) -> Vec<AuditRecord> {
+        let next: u64 = env
+            .storage()
+            .persistent()
+            .get(&AuditDataKey::NextSeq)
+            .unwrap_or(0);
+        let start = from_seq.max(next.saturating_sub(AUDIT_JOURNAL_CAP));
+        let end = start
+            .saturating_add(limit.min(AUDIT_PAGE_SIZE) as u64)
+            .min(next);
+        let mut results = vec![env];
+        for seq in start..end {
+            if let Some(record) = env
+                .storage()
+                .persistent()
+                .get(&AuditDataKey::Record(seq % AUDIT_JOURNAL_CAP))
+            {
+                results.push_back(record);
+            }
+        }
+        results
+    }
+}
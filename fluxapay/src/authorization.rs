@@ -0,0 +1,75 @@
+use soroban_sdk::{contracttype, Address, Env, String};
+
+use crate::clock::Clock;
+use crate::Error;
+
+pub const DEFAULT_CAPTURE_WINDOW: u64 = 7 * 24 * 3600;
+
+// Funds a payer has placed on hold with the processor for a charge, card-style, until the
+// merchant captures some or all of them or the hold is released
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Authorization {
+    pub payment_id: String,
+    pub payer: Address,
+    pub amount: i128, // held by this contract
+    pub authorized_at: u64,
+    pub capture_deadline: u64, // after this only expiry, back to the payer, is possible
+}
+
+#[contracttype]
+pub enum AuthorizationDataKey {
+    Authorization(String), // payment_id -> Authorization
+    CaptureWindow,         // u64 seconds an authorization stays capturable
+}
+
+pub struct Authorizations;
+
+impl Authorizations {
+    pub fn open(env: &Env, payment_id: &String, payer: &Address, amount: i128) -> Authorization {
+        let now = Clock::now(env);
+        let authorization = Authorization {
+            payment_id: payment_id.clone(),
+            payer: payer.clone(),
+            amount,
+            authorized_at: now,
+            capture_deadline: now + Self::get_capture_window(env),
+        };
+        env.storage().persistent().set(
+            &AuthorizationDataKey::Authorization(payment_id.clone()),
+            &authorization,
+        );
+        authorization
+    }
+
+    pub fn get(env: &Env, payment_id: &String) -> Result<Authorization, Error> {
+        env.storage()
+            .persistent()
+            .get(&AuthorizationDataKey::Authorization(payment_id.clone()))
+            .ok_or(Error::PaymentNotAuthorized)
+    }
+
+    /// Drop the hold once its funds have been captured or returned
+    pub fn close(env: &Env, payment_id: &String) {
+        env.storage()
+            .persistent()
+            .remove(&AuthorizationDataKey::Authorization(payment_id.clone()));
+    }
+
+    pub fn set_capture_window(env: &Env, window: u64) -> Result<(), Error> {
+        if window == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        env.storage()
+            .persistent()
+            .set(&AuthorizationDataKey::CaptureWindow, &window);
+        Ok(())
+    }
+
+    pub fn get_capture_window(env: &Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&AuthorizationDataKey::CaptureWindow)
+            .unwrap_or(DEFAULT_CAPTURE_WINDOW)
+    }
+}
@@ -0,0 +1,162 @@
+use soroban_sdk::{contracttype, vec, Address, Env, Symbol, Vec};
+
+use crate::clock::Clock;
+use crate::Error;
+
+// Merchant risk policy: queue a settlement once escrowed funds grow too large or too old
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AutoSettleRule {
+    pub max_balance: i128, // 0 disables the balance trigger
+    pub max_age: u64,      // seconds since the oldest unsettled confirmation; 0 disables
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnsettledBalance {
+    pub amount: i128,
+    pub oldest_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueuedSettlement {
+    pub merchant_id: Address,
+    pub currency: Symbol,
+    pub amount: i128,
+    pub queued_at: u64,
+}
+
+#[contracttype]
+pub enum AutoSettleDataKey {
+    Rule(Address),              // merchant -> AutoSettleRule
+    Unsettled(Address, Symbol), // (merchant, currency) -> UnsettledBalance
+    Queue,                      // Vec<QueuedSettlement> awaiting an operator
+}
+
+pub struct AutoSettle;
+
+impl AutoSettle {
+    pub fn set_rule(env: &Env, merchant: &Address, rule: AutoSettleRule) -> Result<(), Error> {
+        if rule.max_balance < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage()
+            .persistent()
+            .set(&AutoSettleDataKey::Rule(merchant.clone()), &rule);
+        Ok(())
+    }
+
+    pub fn get_rule(env: &Env, merchant: &Address) -> Option<AutoSettleRule> {
+        env.storage()
+            .persistent()
+            .get(&AutoSettleDataKey::Rule(merchant.clone()))
+    }
+
+    pub fn get_unsettled(env: &Env, merchant: &Address, currency: &Symbol) -> UnsettledBalance {
+        env.storage()
+            .persistent()
+            .get(&AutoSettleDataKey::Unsettled(
+                merchant.clone(),
+                currency.clone(),
+            ))
+            .unwrap_or(UnsettledBalance {
+                amount: 0,
+                oldest_at: 0,
+            })
+    }
+
+    /// Book newly escrowed funds, then check the merchant's rule
+    pub fn add(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        let mut balance = Self::get_unsettled(env, merchant, currency);
+        if balance.amount == 0 {
+            balance.oldest_at = Clock::now(env);
+        }
+        balance.amount += amount;
+        Self::set_unsettled(env, merchant, currency, &balance);
+        Self::evaluate(env, merchant, currency);
+    }
+
+    /// Release funds that left escrow, clearing the queue entry once nothing is left
+    pub fn remove(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        let mut balance = Self::get_unsettled(env, merchant, currency);
+        balance.amount = (balance.amount - amount).max(0);
+        Self::set_unsettled(env, merchant, currency, &balance);
+
+        if balance.amount == 0 {
+            let mut queue = Self::get_queue(env);
+            if let Some(i) = Self::queue_position(&queue, merchant, currency) {
+                queue.remove(i);
+                env.storage()
+                    .persistent()
+                    .set(&AutoSettleDataKey::Queue, &queue);
+            }
+        }
+    }
+
+    /// Queue a settlement if the rule is breached; returns whether one is queued
+    pub fn evaluate(env: &Env, merchant: &Address, currency: &Symbol) -> bool {
+        let rule = match Self::get_rule(env, merchant) {
+            Some(rule) => rule,
+            None => return false,
+        };
+        let balance = Self::get_unsettled(env, merchant, currency);
+        if balance.amount == 0 {
+            return false;
+        }
+
+        let now = Clock::now(env);
+        let too_large = rule.max_balance > 0 && balance.amount > rule.max_balance;
+        let too_old = rule.max_age > 0 && now - balance.oldest_at > rule.max_age;
+        if !too_large && !too_old {
+            return false;
+        }
+
+        let mut queue = Self::get_queue(env);
+        let entry = QueuedSettlement {
+            merchant_id: merchant.clone(),
+            currency: currency.clone(),
+            amount: balance.amount,
+            queued_at: now,
+        };
+        match Self::queue_position(&queue, merchant, currency) {
+            Some(i) => queue.set(i, entry.clone()),
+            None => {
+                queue.push_back(entry.clone());
+                env.events().publish(
+                    (Symbol::new(env, "SETTLEMENT"), Symbol::new(env, "QUEUED")),
+                    entry,
+                );
+            }
+        }
+        env.storage()
+            .persistent()
+            .set(&AutoSettleDataKey::Queue, &queue);
+        true
+    }
+
+    pub fn get_queue(env: &Env) -> Vec<QueuedSettlement> {
+        env.storage()
+            .persistent()
+            .get(&AutoSettleDataKey::Queue)
+            .unwrap_or(vec![env])
+    }
+
+    fn queue_position(
+        queue: &Vec<QueuedSettlement>,
+        merchant: &Address,
+        currency: &Symbol,
+    ) -> Option<u32> {
+        queue
+            .iter()
+            .position(|entry| &entry.merchant_id == merchant && &entry.currency == currency)
+            .map(|i| i as u32)
+    }
+
+    fn set_unsettled(env: &Env, merchant: &Address, currency: &Symbol, balance: &UnsettledBalance) {
+        env.storage().persistent().set(
+            &AutoSettleDataKey::Unsettled(merchant.clone(), currency.clone()),
+            balance,
+        );
+    }
+}
@@ -0,0 +1,178 @@
+#![cfg(test)]
+
+use super::*;
+use access_control::{role_oracle, role_settlement_operator};
+use merchant_registry::{MerchantRegistry, MerchantRegistryClient};
+use soroban_sdk::{
+    testutils::{Address as _, BytesN as _},
+    Address, BytesN, Env, Map, String, Symbol,
+};
+
+// Upper bounds per entry point invocation. Mainnet currently allows 100M CPU
+// instructions and ~40MB of memory per transaction; these leave headroom for
+// the surrounding transaction while still catching regressions.
+const MAX_CPU_INSTRUCTIONS: u64 = 10_000_000;
+const MAX_MEMORY_BYTES: u64 = 5_000_000;
+
+// Number of existing records to seed before measuring, so index reads and
+// writes are costed at a realistic size rather than against empty storage.
+const INDEX_SIZE: u32 = 50;
+
+// Builds "pay_000".."pay_999" without needing alloc in this no_std crate
+fn payment_id(env: &Env, n: u32) -> String {
+    let buf = [
+        b'p',
+        b'a',
+        b'y',
+        b'_',
+        b'0' + (n / 100 % 10) as u8,
+        b'0' + (n / 10 % 10) as u8,
+        b'0' + (n % 10) as u8,
+    ];
+    String::from_bytes(env, &buf)
+}
+
+fn assert_within_budget(env: &Env, entry_point: &str) {
+    let budget = env.cost_estimate().budget();
+    let cpu = budget.cpu_instruction_cost();
+    let mem = budget.memory_bytes_cost();
+    assert!(
+        cpu <= MAX_CPU_INSTRUCTIONS,
+        "{} used {} CPU instructions (limit {})",
+        entry_point,
+        cpu,
+        MAX_CPU_INSTRUCTIONS
+    );
+    assert!(
+        mem <= MAX_MEMORY_BYTES,
+        "{} used {} memory bytes (limit {})",
+        entry_point,
+        mem,
+        MAX_MEMORY_BYTES
+    );
+}
+
+fn setup_payments(env: &Env) -> (Address, Address, PaymentProcessorClient<'_>) {
+    env.mock_all_auths();
+    let admin = Address::generate(env);
+    let registry = MerchantRegistryClient::new(env, &env.register(MerchantRegistry, ()));
+    registry.initialize(&admin);
+
+    let client = PaymentProcessorClient::new(env, &env.register(PaymentProcessor, ()));
+    client.initialize(
+        &admin,
+        &registry.address,
+        &Map::from_array(env, [(Symbol::new(env, "USDC"), Address::generate(env))]),
+        &None,
+    );
+    let oracle = Address::generate(env);
+    client.grant_role(&admin, &role_oracle(env), &oracle);
+
+    let merchant_id = Address::generate(env);
+    registry.register_merchant(
+        &merchant_id,
+        &String::from_str(env, "Merchant"),
+        &String::from_str(env, "USD"),
+    );
+    registry.verify_merchant(&admin, &merchant_id);
+    for n in 0..INDEX_SIZE {
+        client.create_payment(
+            &payment_id(env, n),
+            &merchant_id,
+            &1_000_000i128,
+            &Symbol::new(env, "USDC"),
+            &Address::generate(env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    }
+    (oracle, merchant_id, client)
+}
+
+#[test]
+fn test_budget_create_payment() {
+    let env = Env::default();
+    let (_oracle, merchant_id, client) = setup_payments(&env);
+
+    env.cost_estimate().budget().reset_default();
+    client.create_payment(
+        &payment_id(&env, INDEX_SIZE),
+        &merchant_id,
+        &1_000_000i128,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_within_budget(&env, "create_payment");
+}
+
+#[test]
+fn test_budget_verify_payment() {
+    let env = Env::default();
+    let (oracle, _merchant_id, client) = setup_payments(&env);
+    let transaction_hash = BytesN::<32>::random(&env);
+    let payer = Address::generate(&env);
+
+    env.cost_estimate().budget().reset_default();
+    client.verify_payment(
+        &oracle,
+        &payment_id(&env, 0),
+        &transaction_hash,
+        &payer,
+        &1_000_000i128,
+        &1,
+    );
+    assert_within_budget(&env, "verify_payment");
+}
+
+#[test]
+fn test_budget_refunds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = RefundManagerClient::new(&env, &env.register(RefundManager, ()));
+    let admin = Address::generate(&env);
+    client.initialize(&admin, &None);
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+    // Every refund against the same payment grows its refund index
+    let payment = payment_id(&env, 0);
+    let requester = Address::generate(&env);
+    let reason = String::from_str(&env, "Customer requested refund");
+    for _ in 0..INDEX_SIZE {
+        client.create_refund(
+            &payment,
+            &1i128,
+            &RefundReason::CustomerRequest,
+            &Some(reason.clone()),
+            &requester,
+        );
+    }
+
+    env.cost_estimate().budget().reset_default();
+    let refund_id = client.create_refund(
+        &payment,
+        &1i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+    assert_within_budget(&env, "create_refund");
+
+    env.cost_estimate().budget().reset_default();
+    client.process_refund(&operator, &refund_id);
+    assert_within_budget(&env, "process_refund");
+
+    env.cost_estimate().budget().reset_default();
+    client.get_payment_refunds(&payment);
+    assert_within_budget(&env, "get_payment_refunds");
+}
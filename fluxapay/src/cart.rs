@@ -0,0 +1,115 @@
+use soroban_sdk::{contracttype, Address, Env, String, Symbol, Vec};
+
+use crate::ids::{IdBuilder, CART_PREFIX};
+use crate::Error;
+
+pub const MAX_CART_LEGS: u32 = 10;
+
+// One merchant's share of a marketplace checkout
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CartLeg {
+    pub merchant_id: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CartStatus {
+    Pending,
+    Paid,          // every leg confirmed
+    PartiallyPaid, // some legs confirmed, the rest failed
+    Failed,
+    Expired,
+}
+
+// A single customer checkout paid with one transfer and split into a charge per merchant
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cart {
+    pub cart_id: String,
+    pub currency: Symbol,
+    pub deposit_address: Address,
+    pub payment_ids: Vec<String>, // child charge per leg, in leg order
+    pub total: i128,
+    pub status: CartStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+#[contracttype]
+pub enum CartDataKey {
+    Cart(String), // cart_id -> Cart
+    CartCounter,  // u64 counter for cart IDs
+}
+
+pub struct Carts;
+
+impl Carts {
+    /// Check the legs and return the cart total
+    pub fn validate(legs: &Vec<CartLeg>) -> Result<i128, Error> {
+        if legs.is_empty() || legs.len() > MAX_CART_LEGS {
+            return Err(Error::InvalidCart);
+        }
+        let mut total = 0;
+        for leg in legs.iter() {
+            if leg.amount <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            total += leg.amount;
+        }
+        Ok(total)
+    }
+
+    pub fn next_id(env: &Env) -> u64 {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&CartDataKey::CartCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&CartDataKey::CartCounter, &counter);
+        counter
+    }
+
+    /// ID of the charge for leg `index` of cart `counter`, e.g. "cart_4_2"
+    pub fn leg_payment_id(env: &Env, counter: u64, index: u32) -> String {
+        IdBuilder::new(CART_PREFIX)
+            .push_u64(counter)
+            .push_str("_")
+            .push_u64(index as u64 + 1)
+            .build(env)
+    }
+
+    /// Split `received` across legs in proportion to their amounts; the last leg takes
+    /// the rounding remainder so the shares always add up to `received`
+    pub fn allocate(env: &Env, legs: &Vec<i128>, total: i128, received: i128) -> Vec<i128> {
+        let mut shares = Vec::new(env);
+        let mut allocated = 0;
+        for (i, amount) in legs.iter().enumerate() {
+            let share = if i as u32 + 1 == legs.len() {
+                received - allocated
+            } else {
+                received * amount / total
+            };
+            allocated += share;
+            shares.push_back(share);
+        }
+        shares
+    }
+
+    pub fn get(env: &Env, cart_id: &String) -> Result<Cart, Error> {
+        env.storage()
+            .persistent()
+            .get(&CartDataKey::Cart(cart_id.clone()))
+            .ok_or(Error::CartNotFound)
+    }
+
+    pub fn save(env: &Env, cart: &Cart) {
+        env.storage()
+            .persistent()
+            .set(&CartDataKey::Cart(cart.cart_id.clone()), cart);
+    }
+}
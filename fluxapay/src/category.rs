@@ -0,0 +1,55 @@
+use soroban_sdk::{contracttype, Env, Symbol};
+
+use crate::fees::MAX_FEE_BPS;
+use crate::Error;
+
+// Admin policy for every merchant in an MCC-style category. Unset fields defer to the
+// merchant's own settings or the platform defaults; merchant-specific settings still win
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CategoryPolicy {
+    pub max_amount: i128,           // largest single charge; 0 means no cap
+    pub refund_window: Option<u64>, // seconds after confirmation refunds may be requested
+    pub fee_bps: Option<u32>,       // processing fee before volume discounts
+}
+
+#[contracttype]
+pub enum CategoryDataKey {
+    Policy(Symbol), // category -> CategoryPolicy
+}
+
+pub struct CategoryPolicies;
+
+impl CategoryPolicies {
+    pub fn set(env: &Env, category: &Symbol, policy: &CategoryPolicy) -> Result<(), Error> {
+        if policy.max_amount < 0
+            || policy.refund_window == Some(0)
+            || policy.fee_bps.is_some_and(|fee_bps| fee_bps > MAX_FEE_BPS)
+        {
+            return Err(Error::InvalidCategoryPolicy);
+        }
+        env.storage()
+            .persistent()
+            .set(&CategoryDataKey::Policy(category.clone()), policy);
+        Ok(())
+    }
+
+    pub fn remove(env: &Env, category: &Symbol) {
+        env.storage()
+            .persistent()
+            .remove(&CategoryDataKey::Policy(category.clone()));
+    }
+
+    pub fn get(env: &Env, category: &Symbol) -> Option<CategoryPolicy> {
+        env.storage()
+            .persistent()
+            .get(&CategoryDataKey::Policy(category.clone()))
+    }
+
+    pub fn check_amount(policy: &CategoryPolicy, amount: i128) -> Result<(), Error> {
+        if policy.max_amount > 0 && amount > policy.max_amount {
+            return Err(Error::LimitExceeded);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(feature = "sim-clock")]
+use soroban_sdk::contracttype;
+use soroban_sdk::Env;
+
+// Time source for expiry and settlement checks. Builds with the `sim-clock` feature add an
+// admin-set offset to the ledger time, so integration environments can jump to schedule
+// boundaries (end-of-day settlement, expiry sweeps) without rewriting the ledger
+#[cfg(feature = "sim-clock")]
+#[contracttype]
+pub enum ClockDataKey {
+    Offset, // u64 seconds added to the ledger timestamp
+}
+
+pub struct Clock;
+
+impl Clock {
+    pub fn now(env: &Env) -> u64 {
+        env.ledger()
+            .timestamp()
+            .saturating_add(Self::get_offset(env))
+    }
+
+    #[cfg(feature = "sim-clock")]
+    pub fn set_offset(env: &Env, offset: u64) {
+        env.storage()
+            .persistent()
+            .set(&ClockDataKey::Offset, &offset);
+    }
+
+    #[cfg(feature = "sim-clock")]
+    pub fn get_offset(env: &Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&ClockDataKey::Offset)
+            .unwrap_or(0)
+    }
+
+    #[cfg(not(feature = "sim-clock"))]
+    pub fn get_offset(_env: &Env) -> u64 {
+        0
+    }
+}
@@ -0,0 +1,85 @@
+use soroban_sdk::{contracttype, Address, Env, String};
+
+use crate::Error;
+
+// Why and by whom an address was screened out
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockRecord {
+    pub address: Address,
+    pub reason: String,
+    pub blocked_by: Address,
+    pub blocked_at: u64,
+}
+
+#[contracttype]
+pub enum ComplianceDataKey {
+    Blocked(Address),       // address -> BlockRecord
+    RefundOverride(String), // refund_id -> bool, admin cleared it to pay a blocked address
+}
+
+pub struct Compliance;
+
+impl Compliance {
+    pub fn block(
+        env: &Env,
+        address: &Address,
+        reason: String,
+        blocked_by: &Address,
+    ) -> BlockRecord {
+        let record = BlockRecord {
+            address: address.clone(),
+            reason,
+            blocked_by: blocked_by.clone(),
+            blocked_at: env.ledger().timestamp(),
+        };
+        env.storage()
+            .persistent()
+            .set(&ComplianceDataKey::Blocked(address.clone()), &record);
+        record
+    }
+
+    pub fn unblock(env: &Env, address: &Address) -> Result<(), Error> {
+        let key = ComplianceDataKey::Blocked(address.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::AddressNotBlocked);
+        }
+        env.storage().persistent().remove(&key);
+        Ok(())
+    }
+
+    pub fn get_block(env: &Env, address: &Address) -> Option<BlockRecord> {
+        env.storage()
+            .persistent()
+            .get(&ComplianceDataKey::Blocked(address.clone()))
+    }
+
+    pub fn is_blocked(env: &Env, address: &Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&ComplianceDataKey::Blocked(address.clone()))
+    }
+
+    /// Fails if any of the given parties, where known, is blocked
+    pub fn require_clear(env: &Env, parties: &[Option<&Address>]) -> Result<(), Error> {
+        for address in parties.iter().flatten() {
+            if Self::is_blocked(env, address) {
+                return Err(Error::AddressBlocked);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_refund_override(env: &Env, refund_id: &String) {
+        env.storage()
+            .persistent()
+            .set(&ComplianceDataKey::RefundOverride(refund_id.clone()), &true);
+    }
+
+    pub fn has_refund_override(env: &Env, refund_id: &String) -> bool {
+        env.storage()
+            .persistent()
+            .get(&ComplianceDataKey::RefundOverride(refund_id.clone()))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,67 @@
+use soroban_sdk::{contracttype, Env};
+
+use crate::fees::MAX_FEE_BPS;
+use crate::Error;
+
+pub const DEFAULT_PAYMENT_EXPIRY: u64 = 24 * 3600;
+pub const DEFAULT_MAX_PAYMENT_EXPIRY: u64 = 30 * 24 * 3600;
+pub const DEFAULT_TOLERANCE_BPS: u32 = 100;
+pub const DEFAULT_MAX_METADATA_SIZE: u32 = 10;
+
+// Contract-wide settings in one place; modules read them through `Configs::get`, which falls
+// back to the defaults until an admin sets them
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    pub default_expiry: u64, // life in seconds of a charge created without an expiry
+    pub max_expiry: u64,     // longest life in seconds a charge may be created with
+    pub fee_bps: u32,        // platform fee for merchants without their own or a category's
+    pub tolerance_bps: u32,  // underpayment accepted for merchants on API v2 and later
+    pub refund_window: u64,  // seconds after confirmation refunds may be requested; 0 for none
+    pub max_metadata_size: u32, // metadata entries allowed on a charge
+    pub paused: bool,        // every state-changing flow halted
+}
+
+#[contracttype]
+pub enum ConfigDataKey {
+    Config, // Config in instance storage
+}
+
+pub struct Configs;
+
+impl Configs {
+    pub fn get(env: &Env) -> Config {
+        env.storage()
+            .instance()
+            .get(&ConfigDataKey::Config)
+            .unwrap_or(Config {
+                default_expiry: DEFAULT_PAYMENT_EXPIRY,
+                max_expiry: DEFAULT_MAX_PAYMENT_EXPIRY,
+                fee_bps: 0,
+                tolerance_bps: DEFAULT_TOLERANCE_BPS,
+                refund_window: 0,
+                max_metadata_size: DEFAULT_MAX_METADATA_SIZE,
+                paused: false,
+            })
+    }
+
+    pub fn set(env: &Env, config: &Config) -> Result<(), Error> {
+        if config.fee_bps > MAX_FEE_BPS || config.tolerance_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee);
+        }
+        if config.default_expiry == 0 || config.default_expiry > config.max_expiry {
+            return Err(Error::InvalidExpiry);
+        }
+        env.storage().instance().set(&ConfigDataKey::Config, config);
+        Ok(())
+    }
+
+    /// Change one setting in place, for the modules whose own setters predate `Config`
+    pub fn update(env: &Env, change: impl FnOnce(&mut Config)) {
+        let mut config = Self::get(env);
+        change(&mut config);
+        env.storage()
+            .instance()
+            .set(&ConfigDataKey::Config, &config);
+    }
+}
@@ -0,0 +1,150 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Vec};
+
+use crate::Error;
+
+// Per-merchant deposit address pools with rotation
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RotationPolicy {
+    /// Cycle through the pool, reusing addresses in order
+    RoundRobin,
+    /// Hand out each address once, then retire it from the pool
+    PerPayment,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepositAddressPool {
+    pub addresses: Vec<Address>,
+    pub policy: RotationPolicy,
+    pub next_index: u32,
+}
+
+#[contracttype]
+pub enum DepositPoolDataKey {
+    Pool(Address),          // merchant_id -> DepositAddressPool
+    ActiveDeposit(Address), // deposit_address -> payment_id of the Pending charge using it
+}
+
+pub struct DepositPool;
+
+impl DepositPool {
+    pub fn get(env: &Env, merchant_id: &Address) -> DepositAddressPool {
+        env.storage()
+            .persistent()
+            .get(&DepositPoolDataKey::Pool(merchant_id.clone()))
+            .unwrap_or(DepositAddressPool {
+                addresses: vec![env],
+                policy: RotationPolicy::RoundRobin,
+                next_index: 0,
+            })
+    }
+
+    pub fn add_address(env: &Env, merchant_id: &Address, address: Address) -> Result<(), Error> {
+        let mut pool = Self::get(env, merchant_id);
+        if pool.addresses.contains(&address) {
+            return Err(Error::DepositAddressAlreadyExists);
+        }
+        pool.addresses.push_back(address);
+        Self::save(env, merchant_id, &pool);
+        Ok(())
+    }
+
+    pub fn remove_address(
+        env: &Env,
+        merchant_id: &Address,
+        address: &Address,
+    ) -> Result<(), Error> {
+        let mut pool = Self::get(env, merchant_id);
+        let index = pool
+            .addresses
+            .first_index_of(address)
+            .ok_or(Error::DepositAddressNotFound)?;
+        pool.addresses.remove(index);
+
+        // Keep the rotation cursor pointing at the same upcoming address
+        if index < pool.next_index {
+            pool.next_index -= 1;
+        }
+        if pool.next_index >= pool.addresses.len() {
+            pool.next_index = 0;
+        }
+
+        Self::save(env, merchant_id, &pool);
+        Ok(())
+    }
+
+    pub fn set_policy(env: &Env, merchant_id: &Address, policy: RotationPolicy) {
+        let mut pool = Self::get(env, merchant_id);
+        pool.policy = policy;
+        Self::save(env, merchant_id, &pool);
+    }
+
+    /// Select the next deposit address according to the merchant's policy
+    pub fn next_address(env: &Env, merchant_id: &Address) -> Result<Address, Error> {
+        let mut pool = Self::get(env, merchant_id);
+        if pool.addresses.is_empty() {
+            return Err(Error::DepositPoolEmpty);
+        }
+
+        let address = match pool.policy {
+            RotationPolicy::RoundRobin => {
+                // Skip addresses still attached to a Pending charge
+                let len = pool.addresses.len();
+                let index = (0..len)
+                    .map(|offset| (pool.next_index + offset) % len)
+                    .find(|&i| {
+                        Self::active_payment(env, &pool.addresses.get_unchecked(i)).is_none()
+                    })
+                    .ok_or(Error::DepositAddressInUse)?;
+                pool.next_index = (index + 1) % len;
+                pool.addresses.get_unchecked(index)
+            }
+            RotationPolicy::PerPayment => {
+                let address = pool.addresses.get_unchecked(pool.next_index);
+                pool.addresses.remove(pool.next_index);
+                if pool.next_index >= pool.addresses.len() {
+                    pool.next_index = 0;
+                }
+                address
+            }
+        };
+
+        Self::save(env, merchant_id, &pool);
+        Ok(address)
+    }
+
+    /// Payment currently awaiting funds at `address`, if any
+    pub fn active_payment(env: &Env, address: &Address) -> Option<String> {
+        env.storage()
+            .persistent()
+            .get(&DepositPoolDataKey::ActiveDeposit(address.clone()))
+    }
+
+    /// Attach `address` to a Pending charge, rejecting reuse across concurrent charges
+    pub fn activate(env: &Env, address: &Address, payment_id: &String) -> Result<(), Error> {
+        if Self::active_payment(env, address).is_some() {
+            return Err(Error::DepositAddressInUse);
+        }
+        env.storage().persistent().set(
+            &DepositPoolDataKey::ActiveDeposit(address.clone()),
+            payment_id,
+        );
+        Ok(())
+    }
+
+    /// Free `address` once the charge using it leaves Pending
+    pub fn release(env: &Env, address: &Address, payment_id: &String) {
+        if Self::active_payment(env, address).as_ref() == Some(payment_id) {
+            env.storage()
+                .persistent()
+                .remove(&DepositPoolDataKey::ActiveDeposit(address.clone()));
+        }
+    }
+
+    fn save(env: &Env, merchant_id: &Address, pool: &DepositAddressPool) {
+        env.storage()
+            .persistent()
+            .set(&DepositPoolDataKey::Pool(merchant_id.clone()), pool);
+    }
+}
@@ -0,0 +1,153 @@
+use soroban_sdk::{contracttype, vec, Address, BytesN, Env, String, Vec};
+
+use crate::ids::{IdBuilder, DISPUTE_PREFIX};
+use crate::Error;
+
+// Payer/merchant disputes over a confirmed payment, decided by an ARBITER
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisputeStatus {
+    Open,
+    ResolvedForPayer,
+    ResolvedForMerchant,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisputeOutcome {
+    /// Refund the payer in full
+    Payer,
+    /// Keep the funds with the merchant
+    Merchant,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dispute {
+    pub dispute_id: String,
+    pub payment_id: String,
+    pub opener: Address,
+    pub reason: String,
+    pub evidence_hash: BytesN<32>,
+    pub status: DisputeStatus,
+    pub opened_at: u64,
+    pub resolved_at: Option<u64>,
+    pub refund_id: Option<String>, // refund raised when resolved for the payer
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Evidence {
+    pub submitter: Address,
+    pub evidence_hash: BytesN<32>,
+    pub submitted_at: u64,
+}
+
+#[contracttype]
+pub enum DisputeDataKey {
+    Dispute(String),        // dispute_id -> Dispute
+    PaymentDispute(String), // payment_id -> dispute_id of its open dispute
+    Evidence(String),       // dispute_id -> Vec<Evidence>
+    DisputeCounter,         // u64 counter for dispute IDs
+}
+
+pub struct Disputes;
+
+impl Disputes {
+    pub fn open(
+        env: &Env,
+        payment_id: String,
+        opener: Address,
+        reason: String,
+        evidence_hash: BytesN<32>,
+    ) -> Result<Dispute, Error> {
+        let payment_key = DisputeDataKey::PaymentDispute(payment_id.clone());
+        if env.storage().persistent().has(&payment_key) {
+            return Err(Error::DisputeAlreadyOpen);
+        }
+
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&DisputeDataKey::DisputeCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&DisputeDataKey::DisputeCounter, &counter);
+
+        let dispute = Dispute {
+            dispute_id: IdBuilder::new(DISPUTE_PREFIX).push_u64(counter).build(env),
+            payment_id,
+            opener: opener.clone(),
+            reason,
+            evidence_hash: evidence_hash.clone(),
+            status: DisputeStatus::Open,
+            opened_at: env.ledger().timestamp(),
+            resolved_at: None,
+            refund_id: None,
+        };
+        env.storage()
+            .persistent()
+            .set(&payment_key, &dispute.dispute_id);
+        Self::save(env, &dispute);
+        Self::add_evidence(env, &dispute.dispute_id, opener, evidence_hash);
+        Ok(dispute)
+    }
+
+    pub fn get(env: &Env, dispute_id: &String) -> Result<Dispute, Error> {
+        env.storage()
+            .persistent()
+            .get(&DisputeDataKey::Dispute(dispute_id.clone()))
+            .ok_or(Error::DisputeNotFound)
+    }
+
+    pub fn get_for_payment(env: &Env, payment_id: &String) -> Option<String> {
+        env.storage()
+            .persistent()
+            .get(&DisputeDataKey::PaymentDispute(payment_id.clone()))
+    }
+
+    pub fn add_evidence(
+        env: &Env,
+        dispute_id: &String,
+        submitter: Address,
+        evidence_hash: BytesN<32>,
+    ) {
+        let key = DisputeDataKey::Evidence(dispute_id.clone());
+        let mut evidence: Vec<Evidence> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        evidence.push_back(Evidence {
+            submitter,
+            evidence_hash,
+            submitted_at: env.ledger().timestamp(),
+        });
+        env.storage().persistent().set(&key, &evidence);
+    }
+
+    pub fn get_evidence(env: &Env, dispute_id: &String) -> Vec<Evidence> {
+        env.storage()
+            .persistent()
+            .get(&DisputeDataKey::Evidence(dispute_id.clone()))
+            .unwrap_or(vec![env])
+    }
+
+    /// Close an open dispute, freeing its payment for a future dispute
+    pub fn resolve(env: &Env, dispute: &mut Dispute, outcome: &DisputeOutcome) {
+        dispute.status = match outcome {
+            DisputeOutcome::Payer => DisputeStatus::ResolvedForPayer,
+            DisputeOutcome::Merchant => DisputeStatus::ResolvedForMerchant,
+        };
+        dispute.resolved_at = Some(env.ledger().timestamp());
+        env.storage()
+            .persistent()
+            .remove(&DisputeDataKey::PaymentDispute(dispute.payment_id.clone()));
+        Self::save(env, dispute);
+    }
+
+    pub fn save(env: &Env, dispute: &Dispute) {
+        env.storage().persistent().set(
+            &DisputeDataKey::Dispute(dispute.dispute_id.clone()),
+            dispute,
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use soroban_sdk::{contracttype, token, Address, Env, Symbol};
+
+use crate::Error;
+
+pub const DEFAULT_EMERGENCY_TIMELOCK: u64 = 48 * 3600;
+pub const MIN_EMERGENCY_TIMELOCK: u64 = 24 * 3600; // the admin cannot shorten it past this
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WithdrawalStatus {
+    Pending,
+    Executed,
+    Cancelled,
+}
+
+// Recovery of funds stuck in the contract, held back for a timelock so that a compromised
+// admin key cannot drain it before anyone notices
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmergencyWithdrawal {
+    pub withdrawal_id: u64,
+    pub token: Address,
+    pub amount: i128,
+    pub to: Address,
+    pub proposed_by: Address,
+    pub proposed_at: u64,
+    pub executable_at: u64, // fixed at proposal; later timelock changes do not move it
+    pub status: WithdrawalStatus,
+}
+
+#[contracttype]
+pub enum EmergencyDataKey {
+    Withdrawal(u64),   // withdrawal_id -> EmergencyWithdrawal
+    WithdrawalCounter, // u64 counter for withdrawal IDs
+    Timelock,          // u64 seconds between proposal and execution
+}
+
+pub struct Emergency;
+
+impl Emergency {
+    pub fn propose(
+        env: &Env,
+        admin: &Address,
+        token: Address,
+        amount: i128,
+        to: Address,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let withdrawal_id: u64 = env
+            .storage()
+            .persistent()
+            .get(&EmergencyDataKey::WithdrawalCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&EmergencyDataKey::WithdrawalCounter, &withdrawal_id);
+
+        let now = env.ledger().timestamp();
+        let withdrawal = EmergencyWithdrawal {
+            withdrawal_id,
+            token,
+            amount,
+            to,
+            proposed_by: admin.clone(),
+            proposed_at: now,
+            executable_at: now + Self::get_timelock(env),
+            status: WithdrawalStatus::Pending,
+        };
+        Self::save(env, &withdrawal, "PROPOSED");
+        Ok(withdrawal)
+    }
+
+    /// Pay out a pending withdrawal from this contract's balance once its timelock has passed
+    pub fn execute(env: &Env, withdrawal_id: u64) -> Result<EmergencyWithdrawal, Error> {
+        let mut withdrawal = Self::get_pending(env, withdrawal_id)?;
+        if env.ledger().timestamp() < withdrawal.executable_at {
+            return Err(Error::WithdrawalTimelocked);
+        }
+        token::Client::new(env, &withdrawal.token).transfer(
+            &env.current_contract_address(),
+            &withdrawal.to,
+            &withdrawal.amount,
+        );
+        withdrawal.status = WithdrawalStatus::Executed;
+        Self::save(env, &withdrawal, "EXECUTED");
+        Ok(withdrawal)
+    }
+
+    pub fn cancel(env: &Env, withdrawal_id: u64) -> Result<EmergencyWithdrawal, Error> {
+        let mut withdrawal = Self::get_pending(env, withdrawal_id)?;
+        withdrawal.status = WithdrawalStatus::Cancelled;
+        Self::save(env, &withdrawal, "CANCELLED");
+        Ok(withdrawal)
+    }
+
+    pub fn get(env: &Env, withdrawal_id: u64) -> Result<EmergencyWithdrawal, Error> {
+        env.storage()
+            .persistent()
+            .get(&EmergencyDataKey::Withdrawal(withdrawal_id))
+            .ok_or(Error::WithdrawalNotFound)
+    }
+
+    pub fn set_timelock(env: &Env, timelock: u64) -> Result<(), Error> {
+        if timelock < MIN_EMERGENCY_TIMELOCK {
+            return Err(Error::InvalidInterval);
+        }
+        env.storage()
+            .persistent()
+            .set(&EmergencyDataKey::Timelock, &timelock);
+        Ok(())
+    }
+
+    pub fn get_timelock(env: &Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&EmergencyDataKey::Timelock)
+            .unwrap_or(DEFAULT_EMERGENCY_TIMELOCK)
+    }
+
+    fn get_pending(env: &Env, withdrawal_id: u64) -> Result<EmergencyWithdrawal, Error> {
+        let withdrawal = Self::get(env, withdrawal_id)?;
+        if withdrawal.status != WithdrawalStatus::Pending {
+            return Err(Error::WithdrawalNotPending);
+        }
+        Ok(withdrawal)
+    }
+
+    // Store the withdrawal and announce the step, so watchers can react within the timelock
+    fn save(env: &Env, withdrawal: &EmergencyWithdrawal, step: &str) {
+        env.storage().persistent().set(
+            &EmergencyDataKey::Withdrawal(withdrawal.withdrawal_id),
+            withdrawal,
+        );
+        env.events().publish(
+            (Symbol::new(env, "EMERGENCY"), Symbol::new(env, step)),
+            withdrawal.clone(),
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use soroban_sdk::{contracttype, Address, Env, Symbol};
+
+// Booked vs actual escrow for one currency. Held falls short of booked while paid deposit
+// addresses await their sweep; it runs over by any mass-refund pools and stray transfers.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EscrowReconciliation {
+    pub currency: Symbol,
+    pub booked: i128,   // what the accounting says escrow owes
+    pub held: i128,     // the escrow contract's token balance
+    pub balanced: bool, // held covers booked
+}
+
+#[contracttype]
+pub enum EscrowDataKey {
+    Total(Symbol),                    // currency -> i128 booked into escrow
+    MerchantPending(Address, Symbol), // (merchant, currency) -> i128 confirmed but unsettled
+}
+
+pub struct EscrowLedger;
+
+impl EscrowLedger {
+    pub fn get_total(env: &Env, currency: &Symbol) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&EscrowDataKey::Total(currency.clone()))
+            .unwrap_or(0)
+    }
+
+    pub fn get_pending(env: &Env, merchant: &Address, currency: &Symbol) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&EscrowDataKey::MerchantPending(
+                merchant.clone(),
+                currency.clone(),
+            ))
+            .unwrap_or(0)
+    }
+
+    /// Book a confirmed escrow charge as owed to its merchant
+    pub fn credit_merchant(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        Self::set_pending(
+            env,
+            merchant,
+            currency,
+            Self::get_pending(env, merchant, currency) + amount,
+        );
+        Self::credit(env, currency, amount);
+    }
+
+    /// Release merchant funds that left escrow through settlement or a refund
+    pub fn debit_merchant(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        Self::set_pending(
+            env,
+            merchant,
+            currency,
+            (Self::get_pending(env, merchant, currency) - amount).max(0),
+        );
+        Self::debit(env, currency, amount);
+    }
+
+    /// Book funds held for a payer rather than a merchant, e.g. an overpayment
+    pub fn credit(env: &Env, currency: &Symbol, amount: i128) {
+        let total = Self::get_total(env, currency) + amount;
+        env.storage()
+            .persistent()
+            .set(&EscrowDataKey::Total(currency.clone()), &total);
+    }
+
+    pub fn debit(env: &Env, currency: &Symbol, amount: i128) {
+        let total = (Self::get_total(env, currency) - amount).max(0);
+        env.storage()
+            .persistent()
+            .set(&EscrowDataKey::Total(currency.clone()), &total);
+    }
+
+    pub fn reconcile(env: &Env, currency: &Symbol, held: i128) -> EscrowReconciliation {
+        let booked = Self::get_total(env, currency);
+        EscrowReconciliation {
+            currency: currency.clone(),
+            booked,
+            held,
+            balanced: held >= booked,
+        }
+    }
+
+    fn set_pending(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        env.storage().persistent().set(
+            &EscrowDataKey::MerchantPending(merchant.clone(), currency.clone()),
+            &amount,
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use soroban_sdk::{contracttype, Address, Env, String, Symbol};
+
+use crate::merchant_registry::Merchant;
+use crate::{PaymentCharge, PaymentStatus, Refund, RefundStatus};
+
+// Structured payloads for lifecycle events, so indexers need not re-query the record.
+// Topic schemas are stable; new transitions add topics rather than change existing ones:
+//
+//   ("PAYMENT", CREATED | AUTHORIZED | CAPTURED | RELEASED | COLLECTED | VERIFIED | FAILED |
+//    LATE_CONFIRMED | CANCELLED | EXPIRED | SETTLED | ARCHIVED)        -> PaymentEvent
+//   ("REFUND", CREATED | APPROVED | REJECTED | BLOCK_OVERRIDE | COMPLETED) -> RefundEvent
+//   ("MERCHANT", REGISTERED | UPDATED | VERIFIED | KYC_SUBMITTED | KYC_LEVEL) -> MerchantEvent
+//   ("ROLE", GRANTED | REVOKED | RENOUNCED | ADMIN_CHANGED | ADMIN_TRANSFERRED) -> RoleEvent
+//
+// Annotations that are not transitions (e.g. ("PAYMENT", OVERPAID | TIPPED | EXTENDED)) keep their
+// own tuples.
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentEvent {
+    pub payment_id: String,
+    pub merchant_id: Address,
+    pub amount: i128,
+    pub currency: Symbol,
+    pub fee_amount: i128,       // set once confirmed (self-custody) or settled
+    pub status: PaymentStatus,  // after the transition
+    pub actor: Option<Address>, // None for permissionless sweeps
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RefundEvent {
+    pub refund_id: String,
+    pub payment_id: String,
+    pub merchant_id: Option<Address>, // None without a linked PaymentProcessor
+    pub amount: i128,
+    pub currency: Option<Symbol>,
+    pub status: RefundStatus,
+    pub actor: Address,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantEvent {
+    pub merchant_id: Address,
+    pub verified: bool,
+    pub active: bool,
+    pub kyc_level: u32,
+    pub actor: Address,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleEvent {
+    pub role: Symbol,
+    pub account: Option<Address>, // account gaining or losing the role; None on ADMIN_CHANGED
+    pub admin_role: Option<Symbol>, // role's new admin role, on ADMIN_CHANGED only
+    pub actor: Address,
+    pub timestamp: u64,
+}
+
+pub fn payment(env: &Env, transition: &str, payment: &PaymentCharge, actor: Option<&Address>) {
+    env.events().publish(
+        (Symbol::new(env, "PAYMENT"), Symbol::new(env, transition)),
+        PaymentEvent {
+            payment_id: payment.payment_id.clone(),
+            merchant_id: payment.merchant_id.clone(),
+            amount: payment.amount,
+            currency: payment.currency.clone(),
+            fee_amount: payment.fee_amount,
+            status: payment.status.clone(),
+            actor: actor.cloned(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
+
+pub fn refund(
+    env: &Env,
+    transition: &str,
+    refund: &Refund,
+    payment: Option<&PaymentCharge>,
+    actor: &Address,
+) {
+    env.events().publish(
+        (Symbol::new(env, "REFUND"), Symbol::new(env, transition)),
+        RefundEvent {
+            refund_id: refund.refund_id.clone(),
+            payment_id: refund.payment_id.clone(),
+            merchant_id: payment.map(|payment| payment.merchant_id.clone()),
+            amount: refund.amount,
+            currency: payment.map(|payment| payment.currency.clone()),
+            status: refund.status.clone(),
+            actor: actor.clone(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
+
+pub fn merchant(env: &Env, transition: &str, merchant: &Merchant, actor: &Address) {
+    env.events().publish(
+        (Symbol::new(env, "MERCHANT"), Symbol::new(env, transition)),
+        MerchantEvent {
+            merchant_id: merchant.merchant_id.clone(),
+            verified: merchant.verified,
+            active: merchant.active,
+            kyc_level: merchant.kyc_level,
+            actor: actor.clone(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
+
+pub fn role(env: &Env, transition: &str, role: &Symbol, account: &Address, actor: &Address) {
+    env.events().publish(
+        (Symbol::new(env, "ROLE"), Symbol::new(env, transition)),
+        RoleEvent {
+            role: role.clone(),
+            account: Some(account.clone()),
+            admin_role: None,
+            actor: actor.clone(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
+
+pub fn role_admin_changed(env: &Env, role: &Symbol, admin_role: &Symbol, actor: &Address) {
+    env.events().publish(
+        (Symbol::new(env, "ROLE"), Symbol::new(env, "ADMIN_CHANGED")),
+        RoleEvent {
+            role: role.clone(),
+            account: None,
+            admin_role: Some(admin_role.clone()),
+            actor: actor.clone(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
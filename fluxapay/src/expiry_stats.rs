@@ -0,0 +1,106 @@
+use soroban_sdk::{contracttype, Address, Env, Symbol};
+
+use crate::clock::Clock;
+use crate::Error;
+
+pub const EXPIRY_WINDOW_SECONDS: u64 = 7 * 24 * 3600;
+
+// Per-merchant created vs expired counts; a high expiry rate points at a broken checkout
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpiryStats {
+    pub window_start: u64,
+    pub created: u32,
+    pub expired: u32,
+    pub alerted: bool, // alert already raised in this window
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpiryAlertConfig {
+    pub threshold_bps: u32,
+    pub min_created: u32, // don't alert on a handful of charges
+}
+
+#[contracttype]
+pub enum ExpiryDataKey {
+    AlertConfig,
+    Stats(Address), // merchant -> ExpiryStats
+}
+
+pub struct ExpiryTracker;
+
+impl ExpiryTracker {
+    pub fn set_alert_config(env: &Env, config: ExpiryAlertConfig) -> Result<(), Error> {
+        if config.threshold_bps == 0 || config.threshold_bps > 10_000 {
+            return Err(Error::InvalidFee);
+        }
+        env.storage()
+            .persistent()
+            .set(&ExpiryDataKey::AlertConfig, &config);
+        Ok(())
+    }
+
+    pub fn get_alert_config(env: &Env) -> Option<ExpiryAlertConfig> {
+        env.storage().persistent().get(&ExpiryDataKey::AlertConfig)
+    }
+
+    /// Counts for the merchant's current window
+    pub fn get_stats(env: &Env, merchant: &Address) -> ExpiryStats {
+        let now = Clock::now(env);
+        match env
+            .storage()
+            .persistent()
+            .get::<_, ExpiryStats>(&ExpiryDataKey::Stats(merchant.clone()))
+        {
+            Some(stats) if now < stats.window_start + EXPIRY_WINDOW_SECONDS => stats,
+            _ => ExpiryStats {
+                window_start: now,
+                created: 0,
+                expired: 0,
+                alerted: false,
+            },
+        }
+    }
+
+    /// Expired charges as basis points of charges created in the current window
+    pub fn rate_bps(stats: &ExpiryStats) -> u32 {
+        if stats.created == 0 {
+            return 0;
+        }
+        // Charges created late in the previous window can expire in this one
+        let rate = stats.expired as u64 * 10_000 / stats.created as u64;
+        rate.min(10_000) as u32
+    }
+
+    pub fn record_created(env: &Env, merchant: &Address) {
+        let mut stats = Self::get_stats(env, merchant);
+        stats.created += 1;
+        Self::save(env, merchant, &stats);
+    }
+
+    /// Count an expiry, emitting an alert the first time the rate crosses the threshold
+    pub fn record_expired(env: &Env, merchant: &Address) {
+        let mut stats = Self::get_stats(env, merchant);
+        stats.expired += 1;
+
+        if let Some(config) = Self::get_alert_config(env) {
+            let rate = Self::rate_bps(&stats);
+            if !stats.alerted && stats.created >= config.min_created && rate >= config.threshold_bps
+            {
+                stats.alerted = true;
+                env.events().publish(
+                    (Symbol::new(env, "EXPIRY"), Symbol::new(env, "ALERT")),
+                    (merchant.clone(), rate),
+                );
+            }
+        }
+        Self::save(env, merchant, &stats);
+    }
+
+    fn save(env: &Env, merchant: &Address, stats: &ExpiryStats) {
+        env.storage()
+            .persistent()
+            .set(&ExpiryDataKey::Stats(merchant.clone()), stats);
+    }
+}
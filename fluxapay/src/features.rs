@@ -0,0 +1,65 @@
+use soroban_sdk::{contracttype, vec, Env, Symbol, Vec};
+
+use crate::Error;
+
+// Admin-managed feature flags; newer subsystems ship disabled until turned on
+pub fn feature_subscriptions(env: &Env) -> Symbol {
+    Symbol::new(env, "SUBSCRIPTIONS")
+}
+
+pub fn feature_private_payments(env: &Env) -> Symbol {
+    Symbol::new(env, "PRIVATE_PAYMENTS")
+}
+
+pub fn feature_disputes(env: &Env) -> Symbol {
+    Symbol::new(env, "DISPUTES")
+}
+
+#[contracttype]
+pub enum FeatureDataKey {
+    Feature(Symbol), // flag -> bool
+}
+
+pub struct Features;
+
+impl Features {
+    /// Every flag the contracts know about
+    pub fn known(env: &Env) -> Vec<Symbol> {
+        vec![
+            env,
+            feature_subscriptions(env),
+            feature_private_payments(env),
+            feature_disputes(env),
+        ]
+    }
+
+    pub fn enabled(env: &Env) -> Vec<Symbol> {
+        let mut enabled = vec![env];
+        for flag in Self::known(env).iter() {
+            if Self::is_enabled(env, &flag) {
+                enabled.push_back(flag);
+            }
+        }
+        enabled
+    }
+
+    pub fn set(env: &Env, flag: Symbol, enabled: bool) {
+        env.storage()
+            .persistent()
+            .set(&FeatureDataKey::Feature(flag), &enabled);
+    }
+
+    pub fn is_enabled(env: &Env, flag: &Symbol) -> bool {
+        env.storage()
+            .persistent()
+            .get(&FeatureDataKey::Feature(flag.clone()))
+            .unwrap_or(false)
+    }
+
+    pub fn require(env: &Env, flag: &Symbol) -> Result<(), Error> {
+        if !Self::is_enabled(env, flag) {
+            return Err(Error::FeatureDisabled);
+        }
+        Ok(())
+    }
+}
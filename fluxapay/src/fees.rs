@@ -0,0 +1,353 @@
+use soroban_sdk::{contracttype, vec, Address, Env, Symbol, Vec};
+
+use crate::config::Configs;
+use crate::Error;
+
+pub const MAX_FEE_BPS: u32 = 10_000;
+pub const VOLUME_WINDOW_SECONDS: u64 = 30 * 24 * 3600;
+
+// Platform fee taken from each settled payment; `fee_bps` mirrors `Config::fee_bps`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfig {
+    pub fee_bps: u32,
+    pub fee_collector: Address,
+}
+
+// Discount applied once a merchant's rolling settled volume reaches `min_volume`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeTier {
+    pub min_volume: i128,
+    pub discount_bps: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantVolume {
+    pub window_start: u64,
+    pub volume: i128,
+}
+
+/// What a fee charged to a merchant was for
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeeKind {
+    Processing, // percentage fee on each settled (or self-custody) charge
+    Fx,         // spread on payouts converted into the settlement currency
+    Dispute,    // flat fee on each dispute the merchant loses
+}
+
+// Fees charged to a merchant in one currency over a statement period
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeInvoice {
+    pub merchant_id: Address,
+    pub currency: Symbol,
+    pub invoice_number: u32,
+    pub period_start: u64,
+    pub period_end: u64, // 0 while the period is open
+    pub charge_count: u32,
+    pub processing_fees: i128,
+    pub fx_fees: i128,
+    pub dispute_fees: i128,
+    pub total_fees: i128,
+    pub partner_fees: i128, // part of processing_fees passed on to referral partners
+}
+
+#[contracttype]
+pub enum FeeDataKey {
+    Config,
+    CollectedFees(Symbol),         // currency -> i128 total fees collected
+    MerchantFee(Address),          // merchant -> u32 fee_bps override
+    Tiers,                         // Vec<FeeTier>
+    MerchantVolume(Address),       // merchant -> MerchantVolume
+    FeesOwed(Address, Symbol),     // (merchant, currency) -> i128 invoiced, not yet paid
+    FxFee,                         // u32 fx_fee_bps on converted payouts
+    DisputeFee,                    // i128 flat fee per lost dispute, in the payment currency
+    OpenInvoice(Address, Symbol),  // (merchant, currency) -> FeeInvoice for the open period
+    Invoice(Address, Symbol, u32), // (merchant, currency, invoice_number) -> closed FeeInvoice
+}
+
+pub struct Fees;
+
+impl Fees {
+    pub fn set_config(env: &Env, fee_bps: u32, fee_collector: Address) -> Result<(), Error> {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee);
+        }
+        env.storage().persistent().set(
+            &FeeDataKey::Config,
+            &FeeConfig {
+                fee_bps,
+                fee_collector,
+            },
+        );
+        Configs::update(env, |config| config.fee_bps = fee_bps);
+        Ok(())
+    }
+
+    pub fn get_config(env: &Env) -> Option<FeeConfig> {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::Config)
+            .map(|config: FeeConfig| FeeConfig {
+                fee_bps: Configs::get(env).fee_bps,
+                ..config
+            })
+    }
+
+    /// Fee owed on `amount` at `fee_bps`, rounded down
+    pub fn compute_fee(amount: i128, fee_bps: u32) -> i128 {
+        amount * fee_bps as i128 / MAX_FEE_BPS as i128
+    }
+
+    pub fn accrue(env: &Env, currency: &Symbol, fee: i128) {
+        let key = FeeDataKey::CollectedFees(currency.clone());
+        let collected: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(collected + fee));
+    }
+
+    pub fn get_collected(env: &Env, currency: &Symbol) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::CollectedFees(currency.clone()))
+            .unwrap_or(0)
+    }
+
+    pub fn set_merchant_fee(env: &Env, merchant: &Address, fee_bps: u32) -> Result<(), Error> {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee);
+        }
+        env.storage()
+            .persistent()
+            .set(&FeeDataKey::MerchantFee(merchant.clone()), &fee_bps);
+        Ok(())
+    }
+
+    pub fn set_tiers(env: &Env, tiers: Vec<FeeTier>) -> Result<(), Error> {
+        for tier in tiers.iter() {
+            if tier.discount_bps > MAX_FEE_BPS || tier.min_volume < 0 {
+                return Err(Error::InvalidFee);
+            }
+        }
+        env.storage().persistent().set(&FeeDataKey::Tiers, &tiers);
+        Ok(())
+    }
+
+    pub fn get_tiers(env: &Env) -> Vec<FeeTier> {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::Tiers)
+            .unwrap_or(vec![env])
+    }
+
+    /// Settled volume in the merchant's current window, zero once it has lapsed
+    pub fn get_volume(env: &Env, merchant: &Address) -> i128 {
+        let volume: Option<MerchantVolume> = env
+            .storage()
+            .persistent()
+            .get(&FeeDataKey::MerchantVolume(merchant.clone()));
+        match volume {
+            Some(v) if env.ledger().timestamp() < v.window_start + VOLUME_WINDOW_SECONDS => {
+                v.volume
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn record_volume(env: &Env, merchant: &Address, amount: i128) {
+        let now = env.ledger().timestamp();
+        let key = FeeDataKey::MerchantVolume(merchant.clone());
+        let fresh = MerchantVolume {
+            window_start: now,
+            volume: 0,
+        };
+        let mut volume = match env.storage().persistent().get::<_, MerchantVolume>(&key) {
+            Some(v) if now < v.window_start + VOLUME_WINDOW_SECONDS => v,
+            _ => fresh,
+        };
+        volume.volume += amount;
+        env.storage().persistent().set(&key, &volume);
+    }
+
+    /// Merchant override, else its category's fee, else the configured fee, less the best tier
+    /// discount earned
+    pub fn effective_fee_bps(env: &Env, merchant: &Address, category_fee_bps: Option<u32>) -> u32 {
+        let base = env
+            .storage()
+            .persistent()
+            .get(&FeeDataKey::MerchantFee(merchant.clone()))
+            .or(category_fee_bps)
+            .unwrap_or_else(|| Configs::get(env).fee_bps);
+
+        let volume = Self::get_volume(env, merchant);
+        let discount = Self::get_tiers(env)
+            .iter()
+            .filter(|tier| volume >= tier.min_volume)
+            .map(|tier| tier.discount_bps)
+            .max()
+            .unwrap_or(0);
+
+        base.saturating_sub(discount)
+    }
+
+    /// Invoice a fee to a self-custody merchant, whose funds never pass through escrow
+    pub fn accrue_owed(env: &Env, merchant: &Address, currency: &Symbol, fee: i128) {
+        let key = FeeDataKey::FeesOwed(merchant.clone(), currency.clone());
+        let owed: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(owed + fee));
+    }
+
+    pub fn get_owed(env: &Env, merchant: &Address, currency: &Symbol) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::FeesOwed(merchant.clone(), currency.clone()))
+            .unwrap_or(0)
+    }
+
+    /// Record payment against a merchant's fee invoice, moving it into collected fees
+    pub fn settle_owed(
+        env: &Env,
+        merchant: &Address,
+        currency: &Symbol,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        let owed = Self::get_owed(env, merchant, currency);
+        if amount <= 0 || amount > owed {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage().persistent().set(
+            &FeeDataKey::FeesOwed(merchant.clone(), currency.clone()),
+            &(owed - amount),
+        );
+        Self::accrue(env, currency, amount);
+        Ok(owed - amount)
+    }
+
+    pub fn set_fx_fee(env: &Env, fx_fee_bps: u32) -> Result<(), Error> {
+        if fx_fee_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee);
+        }
+        env.storage()
+            .persistent()
+            .set(&FeeDataKey::FxFee, &fx_fee_bps);
+        Ok(())
+    }
+
+    pub fn get_fx_fee(env: &Env) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::FxFee)
+            .unwrap_or(0)
+    }
+
+    pub fn set_dispute_fee(env: &Env, fee: i128) -> Result<(), Error> {
+        if fee < 0 {
+            return Err(Error::InvalidFee);
+        }
+        env.storage()
+            .persistent()
+            .set(&FeeDataKey::DisputeFee, &fee);
+        Ok(())
+    }
+
+    pub fn get_dispute_fee(env: &Env) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::DisputeFee)
+            .unwrap_or(0)
+    }
+
+    pub fn get_open_invoice(env: &Env, merchant: &Address, currency: &Symbol) -> FeeInvoice {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::OpenInvoice(merchant.clone(), currency.clone()))
+            .unwrap_or(FeeInvoice {
+                merchant_id: merchant.clone(),
+                currency: currency.clone(),
+                invoice_number: 1,
+                period_start: env.ledger().timestamp(),
+                period_end: 0,
+                charge_count: 0,
+                processing_fees: 0,
+                fx_fees: 0,
+                dispute_fees: 0,
+                total_fees: 0,
+                partner_fees: 0,
+            })
+    }
+
+    /// Add a fee to the merchant's open invoice; processing fees count one charge each
+    pub fn invoice(env: &Env, merchant: &Address, currency: &Symbol, kind: FeeKind, fee: i128) {
+        let mut invoice = Self::get_open_invoice(env, merchant, currency);
+        match kind {
+            FeeKind::Processing => {
+                invoice.charge_count += 1;
+                invoice.processing_fees += fee;
+            }
+            FeeKind::Fx => invoice.fx_fees += fee,
+            FeeKind::Dispute => invoice.dispute_fees += fee,
+        }
+        invoice.total_fees += fee;
+        env.storage().persistent().set(
+            &FeeDataKey::OpenInvoice(merchant.clone(), currency.clone()),
+            &invoice,
+        );
+    }
+
+    /// Note the share of already-invoiced processing fees that went to a referral partner
+    pub fn invoice_partner_share(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        let mut invoice = Self::get_open_invoice(env, merchant, currency);
+        invoice.partner_fees += amount;
+        env.storage().persistent().set(
+            &FeeDataKey::OpenInvoice(merchant.clone(), currency.clone()),
+            &invoice,
+        );
+    }
+
+    /// Close the open invoice, keeping it under its number, and start the next one
+    pub fn close_invoice(env: &Env, merchant: &Address, currency: &Symbol) -> FeeInvoice {
+        let mut invoice = Self::get_open_invoice(env, merchant, currency);
+        let now = env.ledger().timestamp();
+        invoice.period_end = now;
+        env.storage().persistent().set(
+            &FeeDataKey::Invoice(merchant.clone(), currency.clone(), invoice.invoice_number),
+            &invoice,
+        );
+
+        let next = FeeInvoice {
+            invoice_number: invoice.invoice_number + 1,
+            period_start: now,
+            period_end: 0,
+            charge_count: 0,
+            processing_fees: 0,
+            fx_fees: 0,
+            dispute_fees: 0,
+            total_fees: 0,
+            partner_fees: 0,
+            ..invoice.clone()
+        };
+        env.storage().persistent().set(
+            &FeeDataKey::OpenInvoice(merchant.clone(), currency.clone()),
+            &next,
+        );
+        invoice
+    }
+
+    pub fn get_invoice(
+        env: &Env,
+        merchant: &Address,
+        currency: &Symbol,
+        invoice_number: u32,
+    ) -> Result<FeeInvoice, Error> {
+        env.storage()
+            .persistent()
+            .get(&FeeDataKey::Invoice(
+                merchant.clone(),
+                currency.clone(),
+                invoice_number,
+            ))
+            .ok_or(Error::FeeInvoiceNotFound)
+    }
+}
@@ -0,0 +1,115 @@
+use soroban_sdk::{Env, String};
+
+use crate::Error;
+
+pub const MAX_ID_LEN: usize = 64;
+
+// Prefixes of contract-generated IDs, one namespace per entity type
+pub const REFUND_PREFIX: &str = "refund_";
+pub const BATCH_PREFIX: &str = "batch_";
+pub const DISPUTE_PREFIX: &str = "dispute_";
+pub const CART_PREFIX: &str = "cart_";
+pub const SUBSCRIPTION_PREFIX: &str = "sub_";
+pub const INTENT_PREFIX: &str = "intent_";
+pub const INVOICE_PREFIX: &str = "invoice_";
+pub const LINK_PREFIX: &str = "link_";
+pub const PLAN_PREFIX: &str = "plan_";
+
+const RESERVED_PREFIXES: [&str; 9] = [
+    REFUND_PREFIX,
+    BATCH_PREFIX,
+    DISPUTE_PREFIX,
+    CART_PREFIX,
+    SUBSCRIPTION_PREFIX,
+    INTENT_PREFIX,
+    INVOICE_PREFIX,
+    LINK_PREFIX,
+    PLAN_PREFIX,
+];
+
+/// Check an ID is non-empty, at most MAX_ID_LEN bytes and URL- and memo-safe
+/// (ASCII letters, digits, '-', '.', '_' and '~')
+pub fn validate_id(id: &String) -> Result<(), Error> {
+    let len = id.len() as usize;
+    if len == 0 {
+        return Err(Error::InvalidPaymentId);
+    }
+    if len > MAX_ID_LEN {
+        return Err(Error::IdTooLong);
+    }
+    let mut buf = [0u8; MAX_ID_LEN];
+    id.copy_into_slice(&mut buf[..len]);
+    let allowed = |b: &u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~');
+    if !buf[..len].iter().all(allowed) {
+        return Err(Error::InvalidIdCharacter);
+    }
+    Ok(())
+}
+
+/// `validate_id`, and additionally keep caller-chosen IDs out of the generated namespaces
+pub fn validate_external_id(id: &String) -> Result<(), Error> {
+    validate_id(id)?;
+    let len = id.len() as usize;
+    let mut buf = [0u8; MAX_ID_LEN];
+    id.copy_into_slice(&mut buf[..len]);
+    if RESERVED_PREFIXES
+        .iter()
+        .any(|prefix| buf[..len].starts_with(prefix.as_bytes()))
+    {
+        return Err(Error::ReservedIdPrefix);
+    }
+    Ok(())
+}
+
+// Builds contract-generated identifiers such as "sub_3_12" without alloc
+pub struct IdBuilder {
+    buf: [u8; MAX_ID_LEN],
+    len: usize,
+}
+
+impl IdBuilder {
+    pub fn new(prefix: &str) -> Self {
+        IdBuilder {
+            buf: [0; MAX_ID_LEN],
+            len: 0,
+        }
+        .push_str(prefix)
+    }
+
+    pub fn push_str(mut self, s: &str) -> Self {
+        for b in s.bytes() {
+            self.push_byte(b);
+        }
+        self
+    }
+
+    pub fn push_u64(mut self, n: u64) -> Self {
+        let mut digits = [0u8; 20];
+        let mut count = 0;
+        let mut rest = n;
+        loop {
+            digits[count] = b'0' + (rest % 10) as u8;
+            count += 1;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        while count > 0 {
+            count -= 1;
+            self.push_byte(digits[count]);
+        }
+        self
+    }
+
+    pub fn build(&self, env: &Env) -> String {
+        String::from_bytes(env, &self.buf[..self.len])
+    }
+
+    fn push_byte(&mut self, b: u8) {
+        if self.len < MAX_ID_LEN {
+            self.buf[self.len] = b;
+            self.len += 1;
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Symbol, Vec};
+
+use crate::ids::{IdBuilder, PLAN_PREFIX};
+use crate::Error;
+
+pub const MAX_INSTALMENTS: u32 = 24;
+pub const INSTALMENT_ISSUE_LEAD: u64 = 3 * 24 * 3600; // how early an instalment's charge may be raised
+pub const INSTALMENT_GRACE_PERIOD: u64 = 7 * 24 * 3600; // unpaid this long past due, the plan defaults
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PlanStatus {
+    OnTrack,   // every instalment due so far is paid
+    Late,      // the next instalment is past due but within the grace period
+    Defaulted, // the next instalment went unpaid past the grace period
+    Completed, // every instalment is paid
+}
+
+// A total split into equal charges due every `interval` seconds; each instalment's
+// PaymentCharge is raised only as it comes due
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstalmentPlan {
+    pub plan_id: u64,
+    pub merchant_id: Address,
+    pub payer: Address,
+    pub currency: Symbol,
+    pub total_amount: i128,
+    pub instalment_count: u32,
+    pub interval: u64,
+    pub first_due_at: u64,
+    pub paid_count: u32, // instalments confirmed, always the first `paid_count`
+    pub charges_raised: u32,
+    pub status: PlanStatus,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduledInstalment {
+    pub index: u32, // 1-based
+    pub amount: i128,
+    pub due_at: u64,
+    pub payment_id: Option<String>, // latest charge raised for it
+    pub paid: bool,
+}
+
+#[contracttype]
+pub enum InstalmentDataKey {
+    Plan(u64),           // plan_id -> InstalmentPlan
+    Charge(u64, u32),    // (plan_id, index) -> payment_id of its latest charge
+    PaymentPlan(String), // payment_id -> plan_id it pays into
+    PlanCounter,
+}
+
+pub struct InstalmentPlans;
+
+impl InstalmentPlans {
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        env: &Env,
+        merchant_id: Address,
+        payer: Address,
+        currency: Symbol,
+        total_amount: i128,
+        instalment_count: u32,
+        interval: u64,
+        first_due_at: u64,
+    ) -> Result<InstalmentPlan, Error> {
+        if instalment_count == 0 || instalment_count > MAX_INSTALMENTS {
+            return Err(Error::InvalidInstalmentPlan);
+        }
+        if total_amount < instalment_count as i128 {
+            return Err(Error::InvalidAmount);
+        }
+        if interval == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        if first_due_at < env.ledger().timestamp() {
+            return Err(Error::InvalidInstalmentPlan);
+        }
+
+        let plan = InstalmentPlan {
+            plan_id: Self::next_id(env),
+            merchant_id,
+            payer,
+            currency,
+            total_amount,
+            instalment_count,
+            interval,
+            first_due_at,
+            paid_count: 0,
+            charges_raised: 0,
+            status: PlanStatus::OnTrack,
+            created_at: env.ledger().timestamp(),
+        };
+        Self::save(env, &plan);
+        Ok(plan)
+    }
+
+    /// The plan with its status brought up to date with the ledger clock
+    pub fn get(env: &Env, plan_id: u64) -> Result<InstalmentPlan, Error> {
+        let mut plan: InstalmentPlan = env
+            .storage()
+            .persistent()
+            .get(&InstalmentDataKey::Plan(plan_id))
+            .ok_or(Error::InstalmentPlanNotFound)?;
+        plan.status = Self::current_status(env, &plan);
+        Ok(plan)
+    }
+
+    pub fn schedule(env: &Env, plan: &InstalmentPlan) -> Vec<ScheduledInstalment> {
+        let mut schedule = vec![env];
+        for index in 1..=plan.instalment_count {
+            schedule.push_back(ScheduledInstalment {
+                index,
+                amount: Self::amount_of(plan, index),
+                due_at: Self::due_at(plan, index),
+                payment_id: Self::get_charge(env, plan.plan_id, index),
+                paid: index <= plan.paid_count,
+            });
+        }
+        schedule
+    }
+
+    /// The next unpaid instalment's index, once the plan is active and it is near enough due
+    pub fn next_due(env: &Env, plan: &InstalmentPlan) -> Result<u32, Error> {
+        if matches!(plan.status, PlanStatus::Completed | PlanStatus::Defaulted) {
+            return Err(Error::InstalmentPlanNotActive);
+        }
+        let index = plan.paid_count + 1;
+        if env.ledger().timestamp() + INSTALMENT_ISSUE_LEAD < Self::due_at(plan, index) {
+            return Err(Error::InstalmentNotDue);
+        }
+        Ok(index)
+    }
+
+    /// Amount of an instalment; the first absorbs the rounding remainder
+    pub fn amount_of(plan: &InstalmentPlan, index: u32) -> i128 {
+        let count = plan.instalment_count as i128;
+        let base = plan.total_amount / count;
+        if index == 1 {
+            base + plan.total_amount % count
+        } else {
+            base
+        }
+    }
+
+    pub fn due_at(plan: &InstalmentPlan, index: u32) -> u64 {
+        plan.first_due_at + plan.interval * (index as u64 - 1)
+    }
+
+    pub fn get_charge(env: &Env, plan_id: u64, index: u32) -> Option<String> {
+        env.storage()
+            .persistent()
+            .get(&InstalmentDataKey::Charge(plan_id, index))
+    }
+
+    /// Id for the next charge raised under the plan, e.g. "plan_4_2" for its second
+    pub fn next_payment_id(env: &Env, plan: &mut InstalmentPlan) -> String {
+        plan.charges_raised += 1;
+        IdBuilder::new(PLAN_PREFIX)
+            .push_u64(plan.plan_id)
+            .push_str("_")
+            .push_u64(plan.charges_raised as u64)
+            .build(env)
+    }
+
+    /// Link a new charge to an instalment, superseding any earlier attempt
+    pub fn link_payment(env: &Env, plan: &InstalmentPlan, index: u32, payment_id: &String) {
+        env.storage()
+            .persistent()
+            .set(&InstalmentDataKey::Charge(plan.plan_id, index), payment_id);
+        env.storage().persistent().set(
+            &InstalmentDataKey::PaymentPlan(payment_id.clone()),
+            &plan.plan_id,
+        );
+        Self::save(env, plan);
+    }
+
+    /// Count a confirmed charge toward its plan if it pays the next instalment; returns the
+    /// plan if it did
+    pub fn mark_paid(env: &Env, payment_id: &String) -> Option<InstalmentPlan> {
+        let plan_id: u64 = env
+            .storage()
+            .persistent()
+            .get(&InstalmentDataKey::PaymentPlan(payment_id.clone()))?;
+        let mut plan = Self::get(env, plan_id).ok()?;
+        if plan.paid_count >= plan.instalment_count
+            || Self::get_charge(env, plan_id, plan.paid_count + 1).as_ref() != Some(payment_id)
+        {
+            return None;
+        }
+        plan.paid_count += 1;
+        plan.status = Self::current_status(env, &plan);
+        Self::save(env, &plan);
+        Some(plan)
+    }
+
+    // Completed and Defaulted are final; otherwise judge the next instalment against the clock
+    fn current_status(env: &Env, plan: &InstalmentPlan) -> PlanStatus {
+        if plan.paid_count >= plan.instalment_count {
+            return PlanStatus::Completed;
+        }
+        if plan.status == PlanStatus::Defaulted {
+            return PlanStatus::Defaulted;
+        }
+        let due_at = Self::due_at(plan, plan.paid_count + 1);
+        let now = env.ledger().timestamp();
+        if now > due_at + INSTALMENT_GRACE_PERIOD {
+            PlanStatus::Defaulted
+        } else if now > due_at {
+            PlanStatus::Late
+        } else {
+            PlanStatus::OnTrack
+        }
+    }
+
+    fn save(env: &Env, plan: &InstalmentPlan) {
+        env.storage()
+            .persistent()
+            .set(&InstalmentDataKey::Plan(plan.plan_id), plan);
+    }
+
+    fn next_id(env: &Env) -> u64 {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&InstalmentDataKey::PlanCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&InstalmentDataKey::PlanCounter, &counter);
+        counter
+    }
+}
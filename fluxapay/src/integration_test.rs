@@ -0,0 +1,2400 @@
+#![cfg(test)]
+
+use super::merchant_registry::{MerchantRegistry, MerchantRegistryClient};
+use super::*;
+use access_control::{role_compliance, role_oracle, role_settlement_operator};
+use soroban_sdk::{
+    testutils::{Address as _, BytesN as _, Ledger, MockAuth, MockAuthInvoke},
+    token::{StellarAssetClient, TokenClient},
+    Address, Bytes, BytesN, Env, IntoVal, InvokeError, Map, String, Symbol, Vec,
+};
+
+/// All FluxaPay contracts plus a mock USDC token registered in a single Env
+pub struct TestHarness<'a> {
+    pub env: Env,
+    pub admin: Address,
+    pub oracle: Address,
+    pub operator: Address,
+    pub token: Address,
+    pub payments: PaymentProcessorClient<'a>,
+    pub refunds: RefundManagerClient<'a>,
+    pub merchants: MerchantRegistryClient<'a>,
+}
+
+impl<'a> TestHarness<'a> {
+    pub fn setup() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let admin = Address::generate(&env);
+        let oracle = Address::generate(&env);
+        let operator = Address::generate(&env);
+
+        let token = env
+            .register_stellar_asset_contract_v2(admin.clone())
+            .address();
+
+        let merchants = MerchantRegistryClient::new(&env, &env.register(MerchantRegistry, ()));
+        merchants.initialize(&admin);
+
+        let payments = PaymentProcessorClient::new(&env, &env.register(PaymentProcessor, ()));
+        payments.initialize(
+            &admin,
+            &merchants.address,
+            &Map::from_array(&env, [(Symbol::new(&env, "USDC"), token.clone())]),
+            &None,
+        );
+        payments.grant_role(&admin, &role_oracle(&env), &oracle);
+
+        let refunds = RefundManagerClient::new(&env, &env.register(RefundManager, ()));
+        refunds.initialize(&admin, &None);
+        payments.set_refund_manager(&admin, &refunds.address);
+        refunds.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+        TestHarness {
+            env,
+            admin,
+            oracle,
+            operator,
+            token,
+            payments,
+            refunds,
+            merchants,
+        }
+    }
+
+    /// Register and verify a merchant, returning its address
+    pub fn onboard_merchant(&self, business_name: &str) -> Address {
+        let merchant_id = Address::generate(&self.env);
+        self.merchants.register_merchant(
+            &merchant_id,
+            &String::from_str(&self.env, business_name),
+            &String::from_str(&self.env, "USDC"),
+        );
+        self.merchants.verify_merchant(&self.admin, &merchant_id);
+        merchant_id
+    }
+
+    /// Create a pending charge for the merchant
+    pub fn charge(&self, payment_id: &str, merchant_id: &Address, amount: i128) -> PaymentCharge {
+        let deposit_address = Address::generate(&self.env);
+        self.payments.create_payment(
+            &String::from_str(&self.env, payment_id),
+            merchant_id,
+            &amount,
+            &Symbol::new(&self.env, "USDC"),
+            &deposit_address,
+            &(self.env.ledger().timestamp() + 3600),
+            &String::from_str(&self.env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    }
+
+    /// Fund a new payer, send `amount` to the charge's deposit address and have the oracle verify it
+    pub fn pay(&self, payment: &PaymentCharge, amount: i128) -> (Address, PaymentStatus) {
+        let payer = Address::generate(&self.env);
+        StellarAssetClient::new(&self.env, &self.token).mint(&payer, &amount);
+        TokenClient::new(&self.env, &self.token).transfer(
+            &payer,
+            &payment.deposit_address,
+            &amount,
+        );
+
+        let status = self.payments.verify_payment(
+            &self.oracle,
+            &payment.payment_id,
+            &BytesN::<32>::random(&self.env),
+            &payer,
+            &amount,
+            &(self.payments.get_oracle_nonce(&self.oracle) + 1),
+        );
+        (payer, status)
+    }
+
+    /// Move a paid charge's funds from its deposit address into the RefundManager escrow
+    pub fn sweep_to_escrow(&self, payment: &PaymentCharge) {
+        let token = TokenClient::new(&self.env, &self.token);
+        token.transfer(
+            &payment.deposit_address,
+            &self.refunds.address,
+            &token.balance(&payment.deposit_address),
+        );
+    }
+
+    pub fn balance(&self, account: &Address) -> i128 {
+        TokenClient::new(&self.env, &self.token).balance(account)
+    }
+}
+
+#[test]
+fn test_end_to_end_charge_verify_refund() {
+    let h = TestHarness::setup();
+
+    let merchant_id = h.onboard_merchant("Coffee Shop");
+    assert!(h.merchants.get_merchant(&merchant_id).verified);
+
+    let payment = h.charge("order_1", &merchant_id, 5_000_000);
+    let (payer, status) = h.pay(&payment, 5_000_000);
+    assert_eq!(status, PaymentStatus::Confirmed);
+    assert_eq!(h.balance(&payment.deposit_address), 5_000_000);
+
+    let confirmed = h.payments.get_payment(&payment.payment_id);
+    assert_eq!(confirmed.payer_address, Some(payer.clone()));
+
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &2_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Item out of stock")),
+        &payer,
+    );
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(
+        h.refunds.get_refund(&refund_id).status,
+        RefundStatus::Completed
+    );
+}
+
+#[test]
+fn test_end_to_end_underpayment_fails() {
+    let h = TestHarness::setup();
+
+    let merchant_id = h.onboard_merchant("Book Store");
+    let payment = h.charge("order_2", &merchant_id, 5_000_000);
+    let (_payer, status) = h.pay(&payment, 4_000_000);
+
+    assert_eq!(status, PaymentStatus::Failed);
+    assert_eq!(
+        h.payments.get_payment(&payment.payment_id).status,
+        PaymentStatus::Failed
+    );
+}
+
+#[test]
+fn test_merchant_refund_cap_override() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.refunds.set_max_refunds_per_payment(&h.admin, &5);
+
+    let merchant_id = h.onboard_merchant("Micro Refunds Ltd");
+    h.refunds
+        .set_merchant_max_refunds(&h.admin, &merchant_id, &1);
+    assert_eq!(
+        h.refunds.get_max_refunds(&Some(merchant_id.clone())),
+        Some(1)
+    );
+
+    let payment = h.charge("order_3", &merchant_id, 5_000_000);
+    let (payer, _status) = h.pay(&payment, 5_000_000);
+
+    let reason = String::from_str(&h.env, "Damaged");
+    h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let result = h.refunds.try_create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    assert_eq!(result, Err(Ok(Error::TooManyRefunds)));
+}
+
+#[test]
+fn test_find_by_time_range() {
+    let h = TestHarness::setup();
+    let merchant_id = h.onboard_merchant("Support Desk Co");
+
+    h.env.ledger().set_timestamp(10 * 3600 + 100);
+    let early = h.charge("early", &merchant_id, 10_000_000);
+    h.env.ledger().set_timestamp(15 * 3600 + 1800);
+    let target = h.charge("around_3pm", &merchant_id, 50_000_000);
+    let (payer, _status) = h.pay(&target, 50_000_000);
+    h.refunds.create_refund(
+        &target.payment_id,
+        &50_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Wrong size")),
+        &payer,
+    );
+
+    let payments =
+        h.payments
+            .find_by_time_range(&RecordKind::Payment, &(15 * 3600), &(16 * 3600), &0);
+    assert_eq!(payments.len(), 1);
+    let entry = payments.get(0).unwrap();
+    assert_eq!(entry.id, target.payment_id);
+    assert_eq!(entry.amount, 50_000_000);
+    assert_ne!(entry.id, early.payment_id);
+
+    let refunds = h
+        .refunds
+        .find_by_time_range(&RecordKind::Refund, &(15 * 3600), &(16 * 3600), &0);
+    assert_eq!(refunds.len(), 1);
+
+    // Ranges spanning more than a week of buckets are rejected
+    let result = h
+        .payments
+        .try_find_by_time_range(&RecordKind::Payment, &0, &(8 * 24 * 3600), &0);
+    assert_eq!(result, Err(Ok(Error::InvalidTimeRange)));
+}
+
+#[test]
+fn test_keeper_stake_bounty_and_quorum_slash() {
+    let h = TestHarness::setup();
+    let second_admin = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &Symbol::new(&h.env, "ADMIN"), &second_admin);
+    h.payments.set_keeper_config(
+        &h.admin,
+        &KeeperConfig {
+            stake_token: h.token.clone(),
+            min_stake: 1_000,
+            bounty: 10,
+            slash_quorum: 2,
+        },
+    );
+
+    let keeper = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&keeper, &1_000);
+    let result = h.payments.try_register_keeper(&keeper, &999);
+    assert_eq!(result, Err(Ok(Error::InsufficientStake)));
+    h.payments.register_keeper(&keeper, &1_000);
+    assert_eq!(h.balance(&h.payments.address), 1_000);
+
+    // Sweeping two stale charges earns two bounties
+    let merchant_id = h.onboard_merchant("Keeper Shop");
+    h.charge("stale_1", &merchant_id, 100);
+    h.charge("stale_2", &merchant_id, 100);
+    h.env
+        .ledger()
+        .set_timestamp(h.env.ledger().timestamp() + 3601);
+    assert_eq!(h.payments.keeper_sweep(&keeper, &10), 2);
+    assert_eq!(h.payments.get_keeper(&keeper).unwrap().rewards, 20);
+
+    // A slash needs two admins and blocks exit until it executes
+    let proposal = h
+        .payments
+        .propose_slash(&h.admin, &keeper, &400, &BytesN::<32>::random(&h.env));
+    assert!(!proposal.executed);
+    let result = h.payments.try_deregister_keeper(&keeper);
+    assert_eq!(result, Err(Ok(Error::SlashPending)));
+    let result = h
+        .payments
+        .try_approve_slash(&h.admin, &proposal.proposal_id);
+    assert_eq!(result, Err(Ok(Error::SlashAlreadyApproved)));
+
+    let proposal = h
+        .payments
+        .approve_slash(&second_admin, &proposal.proposal_id);
+    assert!(proposal.executed);
+    assert_eq!(h.payments.get_keeper(&keeper).unwrap().stake, 600);
+
+    // Remaining stake plus rewards are returned on exit
+    assert_eq!(h.payments.deregister_keeper(&keeper), 620);
+    assert_eq!(h.balance(&keeper), 620);
+    assert!(h.payments.get_keeper(&keeper).is_none());
+}
+
+#[test]
+fn test_dispute_resolved_for_payer_refunds_escrow() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let arbiter = Address::generate(&h.env);
+    h.refunds
+        .grant_role(&h.admin, &Symbol::new(&h.env, "ARBITER"), &arbiter);
+
+    h.payments.set_dispute_fee(&h.admin, &15_000);
+
+    let merchant_id = h.onboard_merchant("Gadget Hub");
+    let payment = h.charge("disputed", &merchant_id, 5_000_000);
+    let (payer, _status) = h.pay(&payment, 5_000_000);
+    h.sweep_to_escrow(&payment);
+    let reason = String::from_str(&h.env, "Item never arrived");
+
+    // Disputes ship dark until the admin enables them
+    let result = h.refunds.try_open_dispute(
+        &payer,
+        &payment.payment_id,
+        &reason,
+        &BytesN::<32>::random(&h.env),
+    );
+    assert_eq!(result, Err(Ok(Error::FeatureDisabled)));
+    h.refunds
+        .set_feature(&h.admin, &Symbol::new(&h.env, "DISPUTES"), &true);
+
+    // Strangers cannot open a dispute on someone else's payment
+    let result = h.refunds.try_open_dispute(
+        &Address::generate(&h.env),
+        &payment.payment_id,
+        &reason,
+        &BytesN::<32>::random(&h.env),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    let dispute = h.refunds.open_dispute(
+        &payer,
+        &payment.payment_id,
+        &reason,
+        &BytesN::<32>::random(&h.env),
+    );
+    assert_eq!(dispute.status, DisputeStatus::Open);
+    let result = h.refunds.try_open_dispute(
+        &merchant_id,
+        &payment.payment_id,
+        &reason,
+        &BytesN::<32>::random(&h.env),
+    );
+    assert_eq!(result, Err(Ok(Error::DisputeAlreadyOpen)));
+
+    h.refunds.submit_evidence(
+        &merchant_id,
+        &dispute.dispute_id,
+        &BytesN::<32>::random(&h.env),
+    );
+    assert_eq!(h.refunds.get_dispute_evidence(&dispute.dispute_id).len(), 2);
+
+    let result =
+        h.refunds
+            .try_resolve_dispute(&h.operator, &dispute.dispute_id, &DisputeOutcome::Payer);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let resolved = h
+        .refunds
+        .resolve_dispute(&arbiter, &dispute.dispute_id, &DisputeOutcome::Payer);
+    assert_eq!(resolved.status, DisputeStatus::ResolvedForPayer);
+
+    // The refund and dispute point at each other, and escrow funds are refunded at once
+    let refund = h.refunds.get_refund(&resolved.refund_id.unwrap());
+    assert_eq!(refund.dispute_id, Some(dispute.dispute_id.clone()));
+    assert_eq!(refund.amount, 5_000_000);
+    assert_eq!(refund.requester, payer);
+    assert_eq!(refund.status, RefundStatus::Completed);
+    assert_eq!(h.balance(&payer), 5_000_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+    assert!(h.refunds.get_payment_dispute(&payment.payment_id).is_none());
+
+    // Losing the dispute is invoiced to the merchant
+    let usdc = Symbol::new(&h.env, "USDC");
+    assert_eq!(
+        h.payments.get_fees_owed(&merchant_id, &merchant_id, &usdc),
+        15_000
+    );
+    let invoice = h.payments.get_open_fee_invoice(&merchant_id, &usdc);
+    assert_eq!(invoice.dispute_fees, 15_000);
+    assert_eq!(invoice.total_fees, 15_000);
+
+    let result =
+        h.refunds
+            .try_resolve_dispute(&arbiter, &dispute.dispute_id, &DisputeOutcome::Merchant);
+    assert_eq!(result, Err(Ok(Error::DisputeNotOpen)));
+}
+
+#[test]
+fn test_dispute_for_payer_refunds_what_is_left() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.refunds
+        .set_feature(&h.admin, &Symbol::new(&h.env, "DISPUTES"), &true);
+    h.refunds.set_max_refunds_per_payment(&h.admin, &1);
+    let arbiter = Address::generate(&h.env);
+    h.refunds
+        .grant_role(&h.admin, &Symbol::new(&h.env, "ARBITER"), &arbiter);
+
+    let merchant_id = h.onboard_merchant("Gadget Hub");
+    let payment = h.charge("part_refunded", &merchant_id, 5_000_000);
+    let (payer, _status) = h.pay(&payment, 5_000_000);
+    h.sweep_to_escrow(&payment);
+
+    // Strangers cannot tie up the payment with refunds of their own
+    let result = h.refunds.try_create_refund(
+        &payment.payment_id,
+        &5_000_000,
+        &RefundReason::CustomerRequest,
+        &None,
+        &Address::generate(&h.env),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    // A partial refund uses up the payment's only refund slot
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &2_000_000,
+        &RefundReason::CustomerRequest,
+        &None,
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 2_000_000);
+
+    // The payer's win refunds the rest, past the cap
+    let dispute = h.refunds.open_dispute(
+        &payer,
+        &payment.payment_id,
+        &String::from_str(&h.env, "Rest of the order never arrived"),
+        &BytesN::<32>::random(&h.env),
+    );
+    let resolved = h
+        .refunds
+        .resolve_dispute(&arbiter, &dispute.dispute_id, &DisputeOutcome::Payer);
+    assert_eq!(resolved.status, DisputeStatus::ResolvedForPayer);
+    let refund = h.refunds.get_refund(&resolved.refund_id.unwrap());
+    assert_eq!(refund.amount, 3_000_000);
+    assert_eq!(refund.status, RefundStatus::Completed);
+    assert_eq!(h.balance(&payer), 5_000_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+}
+
+#[test]
+fn test_audit_trail_stitches_payment_and_dispute_history() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.refunds
+        .set_feature(&h.admin, &Symbol::new(&h.env, "DISPUTES"), &true);
+    let arbiter = Address::generate(&h.env);
+    h.refunds
+        .grant_role(&h.admin, &Symbol::new(&h.env, "ARBITER"), &arbiter);
+
+    let merchant_id = h.onboard_merchant("Audit Books");
+    h.env.ledger().set_timestamp(100);
+    let payment = h.charge("audited", &merchant_id, 2_000_000);
+    h.env.ledger().set_timestamp(200);
+    let (payer, _status) = h.pay(&payment, 2_000_000);
+    h.env.ledger().set_timestamp(300);
+    let dispute = h.refunds.open_dispute(
+        &payer,
+        &payment.payment_id,
+        &String::from_str(&h.env, "Wrong edition"),
+        &BytesN::<32>::random(&h.env),
+    );
+    h.env.ledger().set_timestamp(400);
+    h.refunds
+        .resolve_dispute(&arbiter, &dispute.dispute_id, &DisputeOutcome::Merchant);
+
+    let entity = AuditEntity::Payment(payment.payment_id.clone());
+    let trail = h.refunds.get_audit_trail(&entity, &0);
+    let actions: [&str; 4] = ["STATUS", "STATUS", "DISPUTE_OPENED", "DISPUTE_RESOLVED"];
+    assert_eq!(trail.len(), actions.len() as u32);
+    for (entry, action) in trail.iter().zip(actions) {
+        assert_eq!(entry.action, Symbol::new(&h.env, action));
+    }
+    assert_eq!(
+        trail.get(1).unwrap().detail,
+        Some(Symbol::new(&h.env, "CONFIRMED"))
+    );
+    assert_eq!(
+        trail.get(3).unwrap().reference,
+        Some(dispute.dispute_id.clone())
+    );
+    assert_eq!(trail.get(3).unwrap().recorded_at, 400);
+
+    // The merchant's trail carries the same events, referencing the payment and dispute
+    let merchant_trail = h
+        .refunds
+        .get_audit_trail(&AuditEntity::Merchant(merchant_id.clone()), &0);
+    assert_eq!(merchant_trail.len(), 4);
+    assert_eq!(
+        merchant_trail.get(0).unwrap().reference,
+        Some(payment.payment_id.clone())
+    );
+
+    // Role changes land on the account's trail
+    let arbiter_trail = h
+        .refunds
+        .get_audit_trail(&AuditEntity::Account(arbiter.clone()), &0);
+    assert_eq!(arbiter_trail.len(), 1);
+    assert_eq!(
+        arbiter_trail.get(0).unwrap().detail,
+        Some(Symbol::new(&h.env, "ARBITER"))
+    );
+    assert_eq!(h.refunds.get_audit_trail(&entity, &1).len(), 0);
+}
+
+#[test]
+fn test_refund_transfers_from_escrow() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let operator = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &operator);
+
+    let merchant_id = h.onboard_merchant("Shoe Outlet");
+    let usdc = Symbol::new(&h.env, "USDC");
+    let payment = h.charge("escrowed", &merchant_id, 4_000_000);
+    let (payer, _status) = h.pay(&payment, 4_000_000);
+    let reason = String::from_str(&h.env, "Wrong size");
+
+    // Nothing is in escrow until the deposit address is swept
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    let result = h.refunds.try_process_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::InsufficientEscrow)));
+
+    h.sweep_to_escrow(&payment);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 1_500_000);
+    assert_eq!(h.balance(&h.refunds.address), 2_500_000);
+    assert_eq!(
+        h.payments
+            .get_merchant_ledger(&merchant_id, &merchant_id, &usdc)
+            .refund_debits,
+        1_500_000
+    );
+
+    // Once settled, the funds are no longer in escrow
+    let second = h.refunds.create_refund(
+        &payment.payment_id,
+        &500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &second);
+    h.payments.settle_payment(&operator, &payment.payment_id);
+    assert_eq!(h.balance(&merchant_id), 2_500_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+    let result = h.refunds.try_process_refund(&h.operator, &second);
+    assert_eq!(result, Err(Ok(Error::InsufficientEscrow)));
+}
+
+#[test]
+fn test_mass_refund_merkle_claims() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let merchant_id = h.onboard_merchant("Festival Tickets");
+    StellarAssetClient::new(&h.env, &h.token).mint(&merchant_id, &10_000_000);
+
+    let payers: [(Address, i128); 4] = [
+        (Address::generate(&h.env), 1_000_000),
+        (Address::generate(&h.env), 2_000_000),
+        (Address::generate(&h.env), 3_000_000),
+        (Address::generate(&h.env), 500_000),
+    ];
+    let leaves: [BytesN<32>; 4] =
+        core::array::from_fn(|i| MassRefunds::leaf(&h.env, &payers[i].0, payers[i].1));
+    let left = MassRefunds::hash_pair(&h.env, &leaves[0], &leaves[1]);
+    let right = MassRefunds::hash_pair(&h.env, &leaves[2], &leaves[3]);
+    let root = MassRefunds::hash_pair(&h.env, &left, &right);
+
+    let pool = h.refunds.fund_mass_refund(
+        &merchant_id,
+        &Symbol::new(&h.env, "USDC"),
+        &6_500_000,
+        &root,
+        &1_000,
+    );
+    assert_eq!(h.balance(&h.refunds.address), 6_500_000);
+    assert_eq!(h.balance(&merchant_id), 3_500_000);
+    assert_eq!(h.refunds.get_mass_refund_liability(&h.token), 6_500_000);
+
+    // Pool funds are not escrow: they neither back other refunds nor count as a surplus
+    let line = h.payments.reconcile(&h.admin).get(0).unwrap();
+    assert_eq!((line.booked, line.held), (0, 0));
+    let payment = h.charge("unswept", &merchant_id, 1_000_000);
+    let (buyer, _status) = h.pay(&payment, 1_000_000);
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &None,
+        &buyer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    let result = h.refunds.try_process_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::InsufficientEscrow)));
+
+    let proof = Vec::from_array(&h.env, [leaves[1].clone(), right.clone()]);
+    let (payer, amount) = &payers[0];
+
+    // A proof only opens the amount it was built for
+    let result = h
+        .refunds
+        .try_claim_mass_refund(payer, &pool.pool_id, &proof, &(amount + 1));
+    assert_eq!(result, Err(Ok(Error::InvalidProof)));
+
+    h.refunds
+        .claim_mass_refund(payer, &pool.pool_id, &proof, amount);
+    assert_eq!(h.balance(payer), *amount);
+    assert!(h.refunds.is_mass_refund_claimed(&pool.pool_id, payer));
+    let result = h
+        .refunds
+        .try_claim_mass_refund(payer, &pool.pool_id, &proof, amount);
+    assert_eq!(result, Err(Ok(Error::MassRefundAlreadyClaimed)));
+
+    let (payer, amount) = &payers[2];
+    let proof = Vec::from_array(&h.env, [leaves[3].clone(), left.clone()]);
+    h.refunds
+        .claim_mass_refund(payer, &pool.pool_id, &proof, amount);
+    assert_eq!(h.refunds.get_mass_refund(&pool.pool_id).claimed, 4_000_000);
+
+    // Unclaimed funds go back to the merchant once the window closes
+    let result = h
+        .refunds
+        .try_reclaim_mass_refund(&merchant_id, &pool.pool_id);
+    assert_eq!(result, Err(Ok(Error::ClaimWindowOpen)));
+    h.env.ledger().set_timestamp(1_001);
+    let (payer, amount) = &payers[3];
+    let proof = Vec::from_array(&h.env, [leaves[2].clone(), left]);
+    let result = h
+        .refunds
+        .try_claim_mass_refund(payer, &pool.pool_id, &proof, amount);
+    assert_eq!(result, Err(Ok(Error::ClaimWindowClosed)));
+
+    assert_eq!(
+        h.refunds.reclaim_mass_refund(&merchant_id, &pool.pool_id),
+        2_500_000
+    );
+    assert_eq!(h.balance(&merchant_id), 6_000_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+    assert_eq!(h.refunds.get_mass_refund_liability(&h.token), 0);
+}
+
+#[test]
+fn test_contract_info_discovery() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .set_feature(&h.admin, &Symbol::new(&h.env, "SUBSCRIPTIONS"), &true);
+
+    let info = h.payments.get_contract_info();
+    assert_eq!(info.kind, Symbol::new(&h.env, "PAYMENT_PROCESSOR"));
+    assert_eq!(
+        info.version,
+        String::from_str(&h.env, env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(
+        info.linked_contracts
+            .get(Symbol::new(&h.env, "MERCHANT_REGISTRY")),
+        Some(h.merchants.address.clone())
+    );
+    assert_eq!(
+        info.linked_contracts
+            .get(Symbol::new(&h.env, "REFUND_MANAGER")),
+        Some(h.refunds.address.clone())
+    );
+    assert_eq!(
+        info.features,
+        Vec::from_array(&h.env, [Symbol::new(&h.env, "SUBSCRIPTIONS")])
+    );
+    assert_eq!(
+        info.currencies,
+        Vec::from_array(&h.env, [Symbol::new(&h.env, "USDC")])
+    );
+    assert!(!info.paused);
+
+    // The refund side reports the processor it is linked to and inherits its currencies
+    let info = h.refunds.get_contract_info();
+    assert_eq!(info.kind, Symbol::new(&h.env, "REFUND_MANAGER"));
+    assert_eq!(
+        info.linked_contracts
+            .get(Symbol::new(&h.env, "PAYMENT_PROCESSOR")),
+        Some(h.payments.address.clone())
+    );
+    assert_eq!(info.features.len(), 0);
+    assert_eq!(info.currencies.len(), 1);
+}
+
+#[test]
+fn test_refund_requires_merchant_approval() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+
+    let merchant_id = h.onboard_merchant("Approval Goods");
+    let payment = h.charge("needs_approval", &merchant_id, 3_000_000);
+    let (payer, _status) = h.pay(&payment, 3_000_000);
+    h.sweep_to_escrow(&payment);
+    let reason = String::from_str(&h.env, "Changed my mind");
+
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let result = h.refunds.try_process_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::RefundNotApproved)));
+
+    // Only the payment's merchant can decide
+    let result = h
+        .refunds
+        .try_approve_refund(&Address::generate(&h.env), &refund_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    assert_eq!(
+        h.refunds.get_refund(&refund_id).status,
+        RefundStatus::Approved
+    );
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 1_000_000);
+
+    let rejected_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let rejection = String::from_str(&h.env, "Outside return window");
+    h.refunds
+        .reject_refund(&merchant_id, &rejected_id, &rejection);
+    let rejected = h.refunds.get_refund(&rejected_id);
+    assert_eq!(rejected.status, RefundStatus::Rejected);
+    assert_eq!(rejected.rejection_reason, Some(rejection));
+
+    let result = h.refunds.try_approve_refund(&merchant_id, &rejected_id);
+    assert_eq!(result, Err(Ok(Error::RefundAlreadyProcessed)));
+    let result = h.refunds.try_process_refund(&h.operator, &rejected_id);
+    assert_eq!(result, Err(Ok(Error::RefundAlreadyProcessed)));
+}
+
+#[test]
+fn test_payer_history_and_receipts() {
+    let h = TestHarness::setup();
+    let merchant_id = h.onboard_merchant("Book Nook");
+
+    let first = h.charge("novel", &merchant_id, 1_500_000);
+    let (payer, _status) = h.pay(&first, 1_500_000);
+    let second = h.charge("atlas", &merchant_id, 2_500_000);
+    let transaction_hash = BytesN::<32>::random(&h.env);
+    h.payments.verify_payment(
+        &h.oracle,
+        &second.payment_id,
+        &transaction_hash,
+        &payer,
+        &2_500_000,
+        &(h.payments.get_oracle_nonce(&h.oracle) + 1),
+    );
+    let unpaid = h.charge("poster", &merchant_id, 500_000);
+
+    let history = h.payments.get_payer_payments(&payer, &0, &10);
+    assert_eq!(history.total, 2);
+    assert_eq!(
+        history.payments.get(0).unwrap().payment_id,
+        first.payment_id
+    );
+    assert_eq!(
+        history.payments.get(1).unwrap().payment_id,
+        second.payment_id
+    );
+    assert_eq!(
+        h.payments
+            .get_payer_payments(&payer, &1, &10)
+            .payments
+            .len(),
+        1
+    );
+
+    let receipt = h.payments.get_receipt(&second.payment_id);
+    assert_eq!(receipt.merchant_name, String::from_str(&h.env, "Book Nook"));
+    assert_eq!(receipt.amount, 2_500_000);
+    assert_eq!(receipt.currency, Symbol::new(&h.env, "USDC"));
+    assert_eq!(receipt.transaction_hash, Some(transaction_hash));
+    assert_eq!(receipt.confirmed_at, h.env.ledger().timestamp());
+    assert_eq!(
+        h.payments.try_get_receipt(&unpaid.payment_id),
+        Err(Ok(Error::PaymentNotConfirmed))
+    );
+}
+
+#[test]
+fn test_fraud_refund_requires_admin_approval() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+
+    let merchant_id = h.onboard_merchant("Fraud Target");
+    let payment = h.charge("stolen_card", &merchant_id, 2_000_000);
+    let (payer, _status) = h.pay(&payment, 2_000_000);
+    h.sweep_to_escrow(&payment);
+
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &2_000_000,
+        &RefundReason::Fraud,
+        &Some(String::from_str(&h.env, "Card reported stolen")),
+        &payer,
+    );
+    assert_eq!(
+        h.refunds.try_approve_refund(&merchant_id, &refund_id),
+        Err(Ok(Error::AdminApprovalRequired))
+    );
+    assert_eq!(
+        h.refunds.try_process_refund(&h.operator, &refund_id),
+        Err(Ok(Error::RefundNotApproved))
+    );
+
+    h.refunds.approve_fraud_refund(&h.admin, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 2_000_000);
+
+    let stats = h.refunds.get_refund_reason_stats(&RefundReason::Fraud);
+    assert_eq!(stats.count, 1);
+    assert_eq!(stats.amount, 2_000_000);
+    assert_eq!(
+        h.refunds
+            .get_refund_reason_stats(&RefundReason::Duplicate)
+            .count,
+        0
+    );
+}
+
+#[test]
+fn test_partial_refunds_bounded_by_payment() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+
+    let merchant_id = h.onboard_merchant("Partial Parts");
+    let payment = h.charge("partial", &merchant_id, 3_000_000);
+    let (payer, _status) = h.pay(&payment, 3_000_000);
+    h.sweep_to_escrow(&payment);
+    let reason = String::from_str(&h.env, "Missing items");
+
+    let first = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let second = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    assert_eq!(
+        h.refunds.get_refundable_amount(&payment.payment_id),
+        500_000
+    );
+    let result = h.refunds.try_create_refund(
+        &payment.payment_id,
+        &600_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    assert_eq!(result, Err(Ok(Error::RefundExceedsPayment)));
+
+    // Rejected refunds free their share again
+    h.refunds.reject_refund(&merchant_id, &second, &reason);
+    assert_eq!(
+        h.refunds.get_refundable_amount(&payment.payment_id),
+        2_000_000
+    );
+
+    h.refunds.approve_refund(&merchant_id, &first);
+    h.refunds.process_refund(&h.operator, &first);
+    assert_eq!(
+        h.payments.get_payment(&payment.payment_id).refunded_amount,
+        1_000_000
+    );
+
+    let rest = h.refunds.create_refund(
+        &payment.payment_id,
+        &2_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &rest);
+    h.refunds.process_refund(&h.operator, &rest);
+    assert_eq!(h.balance(&payer), 3_000_000);
+    assert_eq!(
+        h.payments.get_payment(&payment.payment_id).refunded_amount,
+        3_000_000
+    );
+    assert_eq!(h.refunds.get_refundable_amount(&payment.payment_id), 0);
+}
+
+#[test]
+fn test_api_version_gates_amount_matching() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let merchant_id = h.onboard_merchant("Versioned Goods");
+    assert_eq!(h.merchants.get_merchant(&merchant_id).api_version, 1);
+
+    // v1 merchants keep exact matching
+    let payment = h.charge("v1_over", &merchant_id, 1_000_000);
+    let (_payer, status) = h.pay(&payment, 1_200_000);
+    assert_eq!(status, PaymentStatus::Failed);
+
+    assert!(h.merchants.try_set_api_version(&merchant_id, &3).is_err());
+    h.merchants.set_api_version(&merchant_id, &2);
+
+    // v2 confirms overpayments and returns the excess from escrow
+    let payment = h.charge("v2_over", &merchant_id, 1_000_000);
+    let (payer, status) = h.pay(&payment, 1_200_000);
+    assert_eq!(status, PaymentStatus::Confirmed);
+    let confirmed = h.payments.get_payment(&payment.payment_id);
+    assert_eq!(confirmed.amount, 1_000_000);
+    assert_eq!(confirmed.overpaid_amount, 200_000);
+
+    h.sweep_to_escrow(&payment);
+    assert_eq!(h.refunds.refund_overpayment(&payment.payment_id), 200_000);
+    assert_eq!(h.balance(&payer), 200_000);
+    assert_eq!(h.balance(&h.refunds.address), 1_000_000);
+    assert_eq!(
+        h.refunds.try_refund_overpayment(&payment.payment_id),
+        Err(Ok(Error::NoOverpayment))
+    );
+
+    // Shortfalls within 1% are confirmed for the amount received; larger ones still fail
+    let payment = h.charge("v2_short", &merchant_id, 1_000_000);
+    let (_payer, status) = h.pay(&payment, 995_000);
+    assert_eq!(status, PaymentStatus::Confirmed);
+    assert_eq!(h.payments.get_payment(&payment.payment_id).amount, 995_000);
+    let payment = h.charge("v2_shorter", &merchant_id, 1_000_000);
+    let (_payer, status) = h.pay(&payment, 980_000);
+    assert_eq!(status, PaymentStatus::Failed);
+}
+
+#[test]
+fn test_can_process_refund_dry_run() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let merchant_id = h.onboard_merchant("Preflight Supplies");
+    let payment = h.charge("preflight", &merchant_id, 1_000_000);
+    let (payer, _status) = h.pay(&payment, 1_000_000);
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &400_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Damaged box")),
+        &payer,
+    );
+
+    // Not yet approved, and the funds still sit at the deposit address
+    let check = h.refunds.can_process_refund(&h.operator, &refund_id);
+    assert!(!check.can_process);
+    assert!(!check.status_ok);
+    assert!(!check.escrow_funded);
+    assert!(check.operator_ok);
+    assert!(check.destination_ok);
+
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    h.sweep_to_escrow(&payment);
+    assert!(
+        h.refunds
+            .can_process_refund(&h.operator, &refund_id)
+            .can_process
+    );
+
+    // Strangers and operators over their limits would fail
+    let check = h
+        .refunds
+        .can_process_refund(&Address::generate(&h.env), &refund_id);
+    assert!(!check.operator_ok);
+    h.refunds.set_account_spend_limit(
+        &h.admin,
+        &h.operator,
+        &SpendLimit {
+            per_transaction: 100_000,
+            per_day: 500_000,
+        },
+    );
+    let check = h.refunds.can_process_refund(&h.operator, &refund_id);
+    assert!(!check.can_process);
+    assert!(!check.operator_ok);
+
+    // The dry run leaves no trace
+    assert_eq!(h.refunds.get_daily_spend(&h.operator, &h.operator), 0);
+    assert_eq!(
+        h.refunds.get_refund(&refund_id).status,
+        RefundStatus::Approved
+    );
+}
+
+#[test]
+fn test_cart_splits_one_transfer_across_merchants() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    let books = h.onboard_merchant("Corner Books");
+    let prints = h.onboard_merchant("Poster Prints");
+    let usdc = Symbol::new(&h.env, "USDC");
+    let deposit_address = Address::generate(&h.env);
+
+    let legs = Vec::from_array(
+        &h.env,
+        [
+            CartLeg {
+                merchant_id: books.clone(),
+                amount: 3_000_000,
+            },
+            CartLeg {
+                merchant_id: prints.clone(),
+                amount: 1_000_000,
+            },
+        ],
+    );
+    let cart = h.payments.create_cart(
+        &usdc,
+        &deposit_address,
+        &legs,
+        &(h.env.ledger().timestamp() + 3600),
+    );
+    assert_eq!(cart.total, 4_000_000);
+    assert_eq!(cart.payment_ids.len(), 2);
+    let auths = h.env.auths();
+    assert!(auths.iter().any(|(signer, _)| *signer == books));
+    assert!(auths.iter().any(|(signer, _)| *signer == prints));
+    let books_leg = cart.payment_ids.get(0).unwrap();
+    let prints_leg = cart.payment_ids.get(1).unwrap();
+    assert_eq!(
+        h.payments.get_payment(&prints_leg).order_reference,
+        cart.cart_id
+    );
+
+    // One transfer from the customer pays the whole cart
+    let payer = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&payer, &4_000_000);
+    TokenClient::new(&h.env, &h.token).transfer(&payer, &deposit_address, &4_000_000);
+    let status = h.payments.verify_cart(
+        &h.oracle,
+        &cart.cart_id,
+        &BytesN::<32>::random(&h.env),
+        &payer,
+        &4_000_000,
+        &(h.payments.get_oracle_nonce(&h.oracle) + 1),
+    );
+    assert_eq!(status, CartStatus::Paid);
+    assert_eq!(h.payments.get_active_deposit(&deposit_address), None);
+
+    // Each leg then settles or refunds on its own
+    let prints_charge = h.payments.get_payment(&prints_leg);
+    h.sweep_to_escrow(&prints_charge);
+    let settled = h.payments.settle_payment(&h.operator, &books_leg);
+    assert_eq!(settled.status, PaymentStatus::Settled);
+    assert_eq!(settled.merchant_id, books);
+    assert_eq!(h.balance(&books), 3_000_000);
+
+    assert_eq!(
+        h.payments.get_payment(&prints_leg).status,
+        PaymentStatus::Confirmed
+    );
+    let refund_id = h.refunds.create_refund(
+        &prints_leg,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Poster arrived torn")),
+        &payer,
+    );
+    h.refunds.approve_refund(&prints, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 1_000_000);
+}
+
+#[test]
+fn test_cart_needs_every_merchant_to_sign() {
+    let h = TestHarness::setup();
+    let books = h.onboard_merchant("Corner Books");
+    let prints = h.onboard_merchant("Poster Prints");
+    let deposit_address = Address::generate(&h.env);
+    let legs = Vec::from_array(
+        &h.env,
+        [
+            CartLeg {
+                merchant_id: books.clone(),
+                amount: 3_000_000,
+            },
+            CartLeg {
+                merchant_id: prints,
+                amount: 1_000_000,
+            },
+        ],
+    );
+    let usdc = Symbol::new(&h.env, "USDC");
+    let expires_at = h.env.ledger().timestamp() + 3600;
+
+    // One merchant signing cannot open a charge against the other; the host refuses the
+    // missing signature outright rather than returning a contract error
+    h.env.mock_auths(&[MockAuth {
+        address: &books,
+        invoke: &MockAuthInvoke {
+            contract: &h.payments.address,
+            fn_name: "create_cart",
+            args: (
+                usdc.clone(),
+                deposit_address.clone(),
+                legs.clone(),
+                expires_at,
+            )
+                .into_val(&h.env),
+            sub_invokes: &[],
+        },
+    }]);
+    let result = h
+        .payments
+        .try_create_cart(&usdc, &deposit_address, &legs, &expires_at);
+    assert_eq!(result, Err(Err(InvokeError::Abort)));
+    assert_eq!(h.payments.get_active_deposit(&deposit_address), None);
+
+    // With both signatures the same cart goes through
+    h.env.mock_all_auths();
+    let cart = h
+        .payments
+        .create_cart(&usdc, &deposit_address, &legs, &expires_at);
+    assert_eq!(
+        h.payments.get_active_deposit(&deposit_address),
+        Some(cart.cart_id)
+    );
+}
+
+#[test]
+fn test_indexes_drop_rejected_refunds_and_archived_payments() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+
+    let merchant_id = h.onboard_merchant("Tidy Tools");
+    let payment = h.charge("tidy", &merchant_id, 2_000_000);
+    let (payer, _status) = h.pay(&payment, 2_000_000);
+    h.sweep_to_escrow(&payment);
+    let reason = String::from_str(&h.env, "Changed mind");
+
+    let kept = h.refunds.create_refund(
+        &payment.payment_id,
+        &500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let rejected = h.refunds.create_refund(
+        &payment.payment_id,
+        &500_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    h.refunds.reject_refund(&merchant_id, &rejected, &reason);
+
+    // The rejected refund is still readable, just no longer indexed under the payment
+    let refunds = h.refunds.get_payment_refunds(&payment.payment_id);
+    assert_eq!(refunds.len(), 1);
+    assert_eq!(refunds.get(0).unwrap().refund_id, kept);
+    assert_eq!(
+        h.refunds.get_refund(&rejected).status,
+        RefundStatus::Rejected
+    );
+    let payment_ids = Vec::from_array(&h.env, [payment.payment_id.clone()]);
+    assert_eq!(h.refunds.compact_payment_refunds(&payment_ids), 0);
+
+    // Archiving takes a payment out of its status and merchant indexes
+    let cancelled = h.charge("tidy_cancelled", &merchant_id, 1_000_000);
+    h.payments
+        .cancel_pending_payment(&merchant_id, &cancelled.payment_id);
+    h.payments.set_archive_retention(&h.admin, &1);
+    h.env.ledger().set_timestamp(h.env.ledger().timestamp() + 2);
+    h.payments
+        .archive_payment(&h.operator, &cancelled.payment_id);
+    assert_eq!(
+        h.payments
+            .get_payments_by_status(&PaymentStatus::Cancelled, &0, &10)
+            .total,
+        0
+    );
+    assert_eq!(
+        h.payments
+            .get_merchant_payments(&merchant_id, &0, &10)
+            .total,
+        1
+    );
+
+    // Entries left behind by records removed some other way are swept on demand
+    let orphan = h.charge("tidy_orphan", &merchant_id, 1_000_000);
+    h.env.as_contract(&h.payments.address, || {
+        h.env
+            .storage()
+            .persistent()
+            .remove(&DataKey::Payment(orphan.payment_id.clone()));
+    });
+    assert_eq!(
+        h.payments
+            .get_payments_by_status(&PaymentStatus::Pending, &0, &10)
+            .total,
+        1
+    );
+    assert_eq!(h.payments.compact_indexes(&0), 0);
+    assert_eq!(h.payments.compact_indexes(&10), 1);
+    assert_eq!(
+        h.payments
+            .get_payments_by_status(&PaymentStatus::Pending, &0, &10)
+            .total,
+        0
+    );
+}
+
+#[test]
+fn test_archive_waits_for_outstanding_funds_and_claims() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.refunds
+        .set_feature(&h.admin, &Symbol::new(&h.env, "DISPUTES"), &true);
+    let arbiter = Address::generate(&h.env);
+    h.refunds
+        .grant_role(&h.admin, &Symbol::new(&h.env, "ARBITER"), &arbiter);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    h.payments.set_archive_retention(&h.admin, &60);
+    let merchant_id = h.onboard_merchant("Camera Store");
+    let reason = String::from_str(&h.env, "Lens scratched");
+    let settle = |payment_id: &str, paid: i128| {
+        let payment = h.charge(payment_id, &merchant_id, 1_000_000);
+        let (payer, _status) = h.pay(&payment, paid);
+        h.sweep_to_escrow(&payment);
+        (payment.payment_id, payer)
+    };
+
+    // An overpayment still held for the payer
+    let (overpaid, _payer) = settle("arch_overpaid", 1_200_000);
+    h.payments.settle_payment(&h.operator, &overpaid);
+
+    // A refund awaiting the merchant
+    let (refunded, payer) = settle("arch_refunded", 1_000_000);
+    let refund_id = h.refunds.create_refund(
+        &refunded,
+        &400_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    h.payments.settle_payment(&h.operator, &refunded);
+
+    // An open dispute
+    let (disputed, payer) = settle("arch_disputed", 1_000_000);
+    h.payments.settle_payment(&h.operator, &disputed);
+    let dispute = h
+        .refunds
+        .open_dispute(&payer, &disputed, &reason, &BytesN::<32>::random(&h.env));
+
+    h.env
+        .ledger()
+        .set_timestamp(h.env.ledger().timestamp() + 61);
+    for payment_id in [&overpaid, &refunded, &disputed] {
+        assert_eq!(
+            h.payments.try_archive_payment(&h.operator, payment_id),
+            Err(Ok(Error::PaymentNotArchivable))
+        );
+        assert_eq!(
+            h.payments.get_payment(payment_id).status,
+            PaymentStatus::Settled
+        );
+    }
+
+    // Each becomes archivable once what it owes is closed out
+    h.refunds.refund_overpayment(&overpaid);
+    h.refunds.reject_refund(&merchant_id, &refund_id, &reason);
+    h.refunds
+        .resolve_dispute(&arbiter, &dispute.dispute_id, &DisputeOutcome::Merchant);
+    for payment_id in [&overpaid, &refunded, &disputed] {
+        h.payments.archive_payment(&h.operator, payment_id);
+        assert_eq!(
+            h.payments.try_get_payment(payment_id),
+            Err(Ok(Error::PaymentNotFound))
+        );
+    }
+}
+
+#[test]
+fn test_collect_payment_pulls_from_allowance() {
+    let h = TestHarness::setup();
+    let merchant_id = h.onboard_merchant("Utility Co");
+    let payer = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&payer, &3_000_000);
+    let bill = |payment_id: &str, expected_payer: Option<Address>| {
+        h.payments.create_payment(
+            &String::from_str(&h.env, payment_id),
+            &merchant_id,
+            &1_000_000,
+            &Symbol::new(&h.env, "USDC"),
+            &Address::generate(&h.env),
+            &(h.env.ledger().timestamp() + 3600),
+            &String::from_str(&h.env, ""),
+            &None,
+            &None,
+            &expected_payer,
+            &None,
+        )
+    };
+
+    // Only charges naming their payer can be collected
+    let open = bill("open_bill", None);
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&merchant_id, &open.payment_id),
+        Err(Ok(Error::PayerNotPinned))
+    );
+
+    let march = bill("march_bill", Some(payer.clone()));
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&merchant_id, &march.payment_id),
+        Err(Ok(Error::MandateExceeded))
+    );
+    h.payments
+        .set_collection_mandate(&payer, &merchant_id, &3_000_000);
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&merchant_id, &march.payment_id),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+
+    let expiration_ledger = h.env.ledger().sequence() + 1_000;
+    TokenClient::new(&h.env, &h.token).approve(
+        &payer,
+        &h.payments.address,
+        &1_500_000,
+        &expiration_ledger,
+    );
+    let collected = h.payments.collect_payment(&merchant_id, &march.payment_id);
+    assert_eq!(collected.status, PaymentStatus::Confirmed);
+    assert_eq!(collected.payer_address, Some(payer.clone()));
+    assert_eq!(h.balance(&payer), 2_000_000);
+    assert_eq!(
+        h.payments.get_collection_mandate(&payer, &merchant_id),
+        2_000_000
+    );
+    assert_eq!(h.balance(&h.refunds.address), 1_000_000);
+    assert_eq!(
+        h.payments.get_merchant_pending_balance(
+            &merchant_id,
+            &merchant_id,
+            &Symbol::new(&h.env, "USDC")
+        ),
+        1_000_000
+    );
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&merchant_id, &march.payment_id),
+        Err(Ok(Error::PaymentAlreadyProcessed))
+    );
+
+    // What is left of the allowance does not cover another full charge
+    let april = bill("april_bill", Some(payer.clone()));
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&merchant_id, &april.payment_id),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+    assert_eq!(
+        h.payments.get_payment(&april.payment_id).status,
+        PaymentStatus::Pending
+    );
+}
+
+#[test]
+fn test_collect_payment_needs_the_payers_mandate() {
+    let h = TestHarness::setup();
+    let utility = h.onboard_merchant("Utility Co");
+    let intruder = h.onboard_merchant("Other Shop");
+    let payer = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&payer, &3_000_000);
+    TokenClient::new(&h.env, &h.token).approve(
+        &payer,
+        &h.payments.address,
+        &3_000_000,
+        &(h.env.ledger().sequence() + 1_000),
+    );
+    h.payments
+        .set_collection_mandate(&payer, &utility, &1_000_000);
+    let bill = |payment_id: &str, merchant_id: &Address| {
+        h.payments.create_payment(
+            &String::from_str(&h.env, payment_id),
+            merchant_id,
+            &1_000_000,
+            &Symbol::new(&h.env, "USDC"),
+            &Address::generate(&h.env),
+            &(h.env.ledger().timestamp() + 3600),
+            &String::from_str(&h.env, ""),
+            &None,
+            &None,
+            &Some(payer.clone()),
+            &None,
+        )
+    };
+
+    // The allowance covers any merchant, but only the one the payer mandated may pull on it
+    let pulled = bill("pulled_bill", &intruder);
+    assert_eq!(
+        h.payments
+            .try_collect_payment(&intruder, &pulled.payment_id),
+        Err(Ok(Error::MandateExceeded))
+    );
+    assert_eq!(h.balance(&payer), 3_000_000);
+
+    let owed = bill("owed_bill", &utility);
+    h.payments.collect_payment(&utility, &owed.payment_id);
+    assert_eq!(h.balance(&payer), 2_000_000);
+
+    // The mandate is used up as the merchant collects
+    let extra = bill("extra_bill", &utility);
+    assert_eq!(
+        h.payments.try_collect_payment(&utility, &extra.payment_id),
+        Err(Ok(Error::MandateExceeded))
+    );
+}
+
+#[test]
+fn test_authorize_then_capture_or_release() {
+    let h = TestHarness::setup();
+    let merchant_id = h.onboard_merchant("Hotel Stay");
+    let payer = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&payer, &3_000_000);
+
+    // Partial capture: the captured part lands on the deposit address, the rest goes back
+    let booking = h.charge("booking", &merchant_id, 1_000_000);
+    let authorization = h.payments.authorize_payment(&payer, &booking.payment_id);
+    assert_eq!(authorization.amount, 1_000_000);
+    assert_eq!(
+        h.payments.get_payment(&booking.payment_id).status,
+        PaymentStatus::Authorized
+    );
+    assert_eq!(h.balance(&payer), 2_000_000);
+    assert_eq!(h.balance(&h.payments.address), 1_000_000);
+    assert_eq!(
+        h.payments
+            .try_capture_payment(&merchant_id, &booking.payment_id, &1_500_000),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    let captured = h
+        .payments
+        .capture_payment(&merchant_id, &booking.payment_id, &800_000);
+    assert_eq!(captured.status, PaymentStatus::Confirmed);
+    assert_eq!(captured.amount, 800_000);
+    assert_eq!(captured.payer_address, Some(payer.clone()));
+    assert_eq!(h.balance(&booking.deposit_address), 800_000);
+    assert_eq!(h.balance(&payer), 2_200_000);
+    assert_eq!(h.balance(&h.payments.address), 0);
+
+    // Release returns the whole hold
+    let deposit = h.charge("deposit", &merchant_id, 500_000);
+    h.payments.authorize_payment(&payer, &deposit.payment_id);
+    assert_eq!(
+        h.payments
+            .release_authorization(&merchant_id, &deposit.payment_id),
+        500_000
+    );
+    assert_eq!(
+        h.payments.get_payment(&deposit.payment_id).status,
+        PaymentStatus::Cancelled
+    );
+    assert_eq!(h.balance(&payer), 2_200_000);
+
+    // Holds nobody captures lapse back to the payer
+    let minibar = h.charge("minibar", &merchant_id, 200_000);
+    let authorization = h.payments.authorize_payment(&payer, &minibar.payment_id);
+    assert_eq!(h.payments.expire_authorizations(&10), 0);
+    h.env
+        .ledger()
+        .set_timestamp(authorization.capture_deadline + 1);
+    assert_eq!(
+        h.payments
+            .try_capture_payment(&merchant_id, &minibar.payment_id, &200_000),
+        Err(Ok(Error::AuthorizationExpired))
+    );
+    assert_eq!(h.payments.expire_authorizations(&10), 1);
+    assert_eq!(
+        h.payments.get_payment(&minibar.payment_id).status,
+        PaymentStatus::Expired
+    );
+    assert_eq!(h.balance(&payer), 2_200_000);
+    assert_eq!(
+        h.payments.try_get_authorization(&minibar.payment_id),
+        Err(Ok(Error::PaymentNotAuthorized))
+    );
+}
+
+#[test]
+fn test_authorize_only_by_the_pinned_payer() {
+    let h = TestHarness::setup();
+    let merchant_id = h.onboard_merchant("Car Rental");
+    let renter = Address::generate(&h.env);
+    let stranger = Address::generate(&h.env);
+    StellarAssetClient::new(&h.env, &h.token).mint(&renter, &1_000_000);
+    StellarAssetClient::new(&h.env, &h.token).mint(&stranger, &1_000_000);
+    let booking = h.payments.create_payment(
+        &String::from_str(&h.env, "rental_hold"),
+        &merchant_id,
+        &1_000_000,
+        &Symbol::new(&h.env, "USDC"),
+        &Address::generate(&h.env),
+        &(h.env.ledger().timestamp() + 3600),
+        &String::from_str(&h.env, ""),
+        &None,
+        &None,
+        &Some(renter.clone()),
+        &None,
+    );
+
+    assert_eq!(
+        h.payments
+            .try_authorize_payment(&stranger, &booking.payment_id),
+        Err(Ok(Error::WrongPayer))
+    );
+    assert_eq!(h.balance(&stranger), 1_000_000);
+    let authorization = h.payments.authorize_payment(&renter, &booking.payment_id);
+    assert_eq!(authorization.payer, renter);
+}
+
+#[test]
+fn test_late_verifications_after_sweeps_are_refundable() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let merchant_id = h.onboard_merchant("Slow Oracle Books");
+
+    let first = h.charge("late_first", &merchant_id, 1_000_000);
+    let second = h.charge("late_second", &merchant_id, 2_000_000);
+    h.payments
+        .cancel_pending_payment(&merchant_id, &second.payment_id);
+    h.env.ledger().set_timestamp(first.expires_at + 1);
+    assert_eq!(h.payments.expire_pending_batch(&10), 1);
+
+    // The oracle reports the later charge first, and both only after the sweeps
+    let (second_payer, status) = h.pay(&second, 2_000_000);
+    assert_eq!(status, PaymentStatus::LateConfirmed);
+    let (first_payer, status) = h.pay(&first, 1_000_000);
+    assert_eq!(status, PaymentStatus::LateConfirmed);
+
+    // Neither is credited to the merchant; the funds go back through the overpayment refund
+    let late = h.payments.get_payment(&first.payment_id);
+    assert_eq!(late.overpaid_amount, 1_000_000);
+    assert_eq!(late.payer_address, Some(first_payer.clone()));
+    assert_eq!(
+        h.payments
+            .get_payments_by_status(&PaymentStatus::LateConfirmed, &0, &10)
+            .total,
+        2
+    );
+    assert_eq!(
+        h.payments
+            .try_capture_payment(&merchant_id, &first.payment_id, &1_000_000),
+        Err(Ok(Error::PaymentNotAuthorized))
+    );
+
+    h.sweep_to_escrow(&first);
+    h.sweep_to_escrow(&second);
+    assert_eq!(h.refunds.refund_overpayment(&first.payment_id), 1_000_000);
+    assert_eq!(h.refunds.refund_overpayment(&second.payment_id), 2_000_000);
+    assert_eq!(h.balance(&first_payer), 1_000_000);
+    assert_eq!(h.balance(&second_payer), 2_000_000);
+
+    // A replayed report for a charge already recorded late is still rejected
+    let result = h.payments.try_verify_payment(
+        &h.oracle,
+        &first.payment_id,
+        &BytesN::<32>::random(&h.env),
+        &first_payer,
+        &1_000_000,
+        &(h.payments.get_oracle_nonce(&h.oracle) + 1),
+    );
+    assert_eq!(result, Err(Ok(Error::PaymentAlreadyProcessed)));
+}
+
+#[test]
+fn test_blocked_addresses_cannot_verify_settle_or_be_refunded() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let officer = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_compliance(&h.env), &officer);
+    let operator = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &operator);
+    let reason = String::from_str(&h.env, "Sanctions match");
+
+    // Only the compliance role may block
+    let result = h
+        .payments
+        .try_block_address(&h.oracle, &Address::generate(&h.env), &reason);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    // A blocked merchant's charges cannot be confirmed
+    let merchant_id = h.onboard_merchant("Shoe Outlet");
+    h.payments.block_address(&officer, &merchant_id, &reason);
+    let payment = h.charge("screened", &merchant_id, 2_000_000);
+    let result = h.payments.try_verify_payment(
+        &h.oracle,
+        &payment.payment_id,
+        &BytesN::<32>::random(&h.env),
+        &Address::generate(&h.env),
+        &2_000_000,
+        &(h.payments.get_oracle_nonce(&h.oracle) + 1),
+    );
+    assert_eq!(result, Err(Ok(Error::AddressBlocked)));
+    h.payments.unblock_address(&officer, &merchant_id);
+    assert!(!h.payments.is_address_blocked(&merchant_id));
+    let result = h.payments.try_unblock_address(&officer, &merchant_id);
+    assert_eq!(result, Err(Ok(Error::AddressNotBlocked)));
+
+    // A payer blocked after paying holds up settlement and refunds
+    let (payer, _status) = h.pay(&payment, 2_000_000);
+    h.sweep_to_escrow(&payment);
+    let record = h.payments.block_address(&officer, &payer, &reason);
+    assert_eq!(h.payments.get_address_block(&payer), Some(record));
+    let result = h
+        .payments
+        .try_settle_payment(&operator, &payment.payment_id);
+    assert_eq!(result, Err(Ok(Error::AddressBlocked)));
+
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &2_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Order cancelled")),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    assert!(
+        !h.refunds
+            .can_process_refund(&h.operator, &refund_id)
+            .destination_ok
+    );
+    let result = h.refunds.try_process_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::AddressBlocked)));
+
+    // An explicit admin override lets that one refund through
+    let result = h
+        .refunds
+        .try_override_blocked_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    h.refunds.override_blocked_refund(&h.admin, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 2_000_000);
+
+    // Holds are screened too: a blocked payer cannot place one, nor a blocked merchant take one
+    let hold = h.charge("screened_hold", &merchant_id, 500_000);
+    let result = h.payments.try_authorize_payment(&payer, &hold.payment_id);
+    assert_eq!(result, Err(Ok(Error::AddressBlocked)));
+    h.payments.unblock_address(&officer, &payer);
+    h.payments.authorize_payment(&payer, &hold.payment_id);
+    h.payments.block_address(&officer, &merchant_id, &reason);
+    let result = h
+        .payments
+        .try_capture_payment(&merchant_id, &hold.payment_id, &500_000);
+    assert_eq!(result, Err(Ok(Error::AddressBlocked)));
+}
+
+#[test]
+fn test_refund_window_closes_unless_admin_allows() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.refunds.set_refund_window(&h.admin, &(30 * 24 * 3600));
+
+    let merchant_id = h.onboard_merchant("Final Sale Outlet");
+    h.refunds
+        .set_merchant_refund_window(&merchant_id, &(7 * 24 * 3600));
+    assert_eq!(
+        h.refunds.get_refund_window(&Some(merchant_id.clone())),
+        Some(7 * 24 * 3600)
+    );
+    assert_eq!(h.refunds.get_refund_window(&None), Some(30 * 24 * 3600));
+
+    let payment = h.charge("final_sale", &merchant_id, 3_000_000);
+    let (payer, _status) = h.pay(&payment, 3_000_000);
+    let reason = String::from_str(&h.env, "Changed mind");
+    h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+
+    // A week and a second after confirmation the merchant's window has closed
+    h.env
+        .ledger()
+        .set_timestamp(h.env.ledger().timestamp() + 7 * 24 * 3600 + 1);
+    let result = h.refunds.try_create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    assert_eq!(result, Err(Ok(Error::RefundWindowClosed)));
+
+    // An admin override admits exactly one late request
+    h.refunds.allow_late_refund(&h.admin, &payment.payment_id);
+    h.refunds.create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    let result = h.refunds.try_create_refund(
+        &payment.payment_id,
+        &1_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &payer,
+    );
+    assert_eq!(result, Err(Ok(Error::RefundWindowClosed)));
+}
+
+#[test]
+fn test_settlement_report_accumulates_per_day() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .set_fee_config(&h.admin, &100, &Address::generate(&h.env));
+    let operator = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &operator);
+    let merchant_id = h.onboard_merchant("Tea House");
+    let usdc = Symbol::new(&h.env, "USDC");
+    let day = h.env.ledger().timestamp() / REPORT_DAY_SECONDS;
+
+    let first = h.charge("report_1", &merchant_id, 4_000_000);
+    let (payer, _status) = h.pay(&first, 4_000_000);
+    let second = h.charge("report_2", &merchant_id, 1_000_000);
+    h.pay(&second, 1_000_000);
+
+    h.sweep_to_escrow(&first);
+    let refund_id = h.refunds.create_refund(
+        &first.payment_id,
+        &500_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Damaged")),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    h.sweep_to_escrow(&second);
+    h.payments.settle_payment(&operator, &second.payment_id);
+
+    let report = h
+        .payments
+        .get_settlement_report(&merchant_id, &merchant_id, &day);
+    assert_eq!(report.lines.len(), 1);
+    let line = report.lines.get(0).unwrap();
+    assert_eq!(line.currency, usdc);
+    assert_eq!(line.gross_volume, 5_000_000);
+    assert_eq!(line.payment_count, 2);
+    assert_eq!(line.refunds, 500_000);
+    assert_eq!(line.fees, 10_000);
+    assert_eq!(line.net_settled, 990_000);
+
+    // Activity on a later day opens a fresh report
+    h.env
+        .ledger()
+        .set_timestamp(h.env.ledger().timestamp() + REPORT_DAY_SECONDS);
+    h.payments.settle_payment(&operator, &first.payment_id);
+    let next = h
+        .payments
+        .get_settlement_report(&merchant_id, &merchant_id, &(day + 1))
+        .lines
+        .get(0)
+        .unwrap();
+    assert_eq!((next.gross_volume, next.fees), (0, 40_000));
+    assert_eq!(next.net_settled, 3_960_000);
+    assert_eq!(
+        h.payments
+            .get_settlement_report(&merchant_id, &merchant_id, &day)
+            .lines
+            .get(0)
+            .unwrap()
+            .net_settled,
+        990_000
+    );
+
+    let stranger = Address::generate(&h.env);
+    let result = h
+        .payments
+        .try_get_settlement_report(&stranger, &merchant_id, &day);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_escrow_accounting_reconciles_with_token_balance() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let operator = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &operator);
+    let merchant_id = h.onboard_merchant("Bike Shop");
+    let usdc = Symbol::new(&h.env, "USDC");
+
+    let payment = h.charge("escrow_books", &merchant_id, 4_000_000);
+    let (payer, _status) = h.pay(&payment, 4_000_000);
+    assert_eq!(h.payments.get_escrow_balance(&h.admin, &usdc), 4_000_000);
+    assert_eq!(
+        h.payments
+            .get_merchant_pending_balance(&merchant_id, &merchant_id, &usdc),
+        4_000_000
+    );
+
+    // Until the deposit address is swept, escrow holds less than it has booked
+    let line = h.payments.reconcile(&h.admin).get(0).unwrap();
+    assert_eq!(
+        (line.booked, line.held, line.balanced),
+        (4_000_000, 0, false)
+    );
+    h.sweep_to_escrow(&payment);
+    assert!(h.payments.reconcile(&h.admin).get(0).unwrap().balanced);
+
+    let refund_id = h.refunds.create_refund(
+        &payment.payment_id,
+        &1_500_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Wrong frame size")),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    let line = h.payments.reconcile(&h.admin).get(0).unwrap();
+    assert_eq!(
+        (line.booked, line.held, line.balanced),
+        (2_500_000, 2_500_000, true)
+    );
+
+    h.payments.settle_payment(&operator, &payment.payment_id);
+    assert_eq!(h.payments.get_escrow_balance(&h.admin, &usdc), 0);
+    assert_eq!(h.balance(&merchant_id), 2_500_000);
+    let line = h.payments.reconcile(&h.admin).get(0).unwrap();
+    assert_eq!((line.booked, line.held, line.balanced), (0, 0, true));
+    assert_eq!(
+        h.payments
+            .get_merchant_pending_balance(&merchant_id, &merchant_id, &usdc),
+        0
+    );
+
+    let result = h
+        .payments
+        .try_get_escrow_balance(&Address::generate(&h.env), &usdc);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_emergency_withdrawal_waits_out_timelock() {
+    let h = TestHarness::setup();
+    let recovery = Address::generate(&h.env);
+    // Funds sent to the escrow by mistake, outside any payment
+    StellarAssetClient::new(&h.env, &h.token).mint(&h.refunds.address, &700_000);
+
+    let withdrawal = h
+        .refunds
+        .propose_emergency_withdrawal(&h.admin, &h.token, &700_000, &recovery);
+    assert_eq!(
+        withdrawal.executable_at,
+        h.env.ledger().timestamp() + DEFAULT_EMERGENCY_TIMELOCK
+    );
+    let result = h
+        .refunds
+        .try_execute_emergency_withdrawal(&h.admin, &withdrawal.withdrawal_id);
+    assert_eq!(result, Err(Ok(Error::WithdrawalTimelocked)));
+
+    // A cancelled proposal can never execute
+    let cancelled = h
+        .refunds
+        .propose_emergency_withdrawal(&h.admin, &h.token, &700_000, &h.admin);
+    h.refunds
+        .cancel_emergency_withdrawal(&h.admin, &cancelled.withdrawal_id);
+
+    h.env.ledger().set_timestamp(withdrawal.executable_at);
+    let executed = h
+        .refunds
+        .execute_emergency_withdrawal(&h.admin, &withdrawal.withdrawal_id);
+    assert_eq!(executed.status, WithdrawalStatus::Executed);
+    assert_eq!(h.balance(&recovery), 700_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+    for withdrawal_id in [withdrawal.withdrawal_id, cancelled.withdrawal_id] {
+        let result = h
+            .refunds
+            .try_execute_emergency_withdrawal(&h.admin, &withdrawal_id);
+        assert_eq!(result, Err(Ok(Error::WithdrawalNotPending)));
+    }
+
+    let result = h
+        .refunds
+        .try_set_emergency_timelock(&h.admin, &(MIN_EMERGENCY_TIMELOCK - 1));
+    assert_eq!(result, Err(Ok(Error::InvalidInterval)));
+    let result = h
+        .refunds
+        .try_propose_emergency_withdrawal(&h.operator, &h.token, &1, &h.operator);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_high_value_actions_need_operator_approvals() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let approvers = [
+        Address::generate(&h.env),
+        Address::generate(&h.env),
+        Address::generate(&h.env),
+    ];
+    for approver in approvers.iter() {
+        h.payments
+            .grant_role(&h.admin, &role_settlement_operator(&h.env), approver);
+        h.refunds
+            .grant_role(&h.admin, &role_settlement_operator(&h.env), approver);
+    }
+    h.payments.set_approval_policy(&h.admin, &1_000_000, &2);
+    h.refunds.set_approval_policy(&h.admin, &1_000_000, &2);
+    let merchant_id = h.onboard_merchant("Jewellers");
+
+    // Below the threshold nothing changes
+    let small = h.charge("small_ring", &merchant_id, 1_000_000);
+    h.pay(&small, 1_000_000);
+    h.sweep_to_escrow(&small);
+    h.payments.settle_payment(&approvers[0], &small.payment_id);
+
+    let large = h.charge("large_ring", &merchant_id, 5_000_000);
+    let (payer, _status) = h.pay(&large, 5_000_000);
+    h.sweep_to_escrow(&large);
+    let refund_id = h.refunds.create_refund(
+        &large.payment_id,
+        &2_000_000,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&h.env, "Resized")),
+        &payer,
+    );
+    h.refunds.approve_refund(&merchant_id, &refund_id);
+
+    let result = h.refunds.try_process_refund(&h.operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::ApprovalRequired)));
+    h.refunds.approve_refund_payout(&approvers[0], &refund_id);
+    let result = h
+        .refunds
+        .try_approve_refund_payout(&approvers[0], &refund_id);
+    assert_eq!(result, Err(Ok(Error::AlreadyApproved)));
+    assert!(
+        !h.refunds
+            .can_process_refund(&h.operator, &refund_id)
+            .approvals_ok
+    );
+    let approval = h.refunds.approve_refund_payout(&approvers[1], &refund_id);
+    assert_eq!(approval.approvers.len(), 2);
+    h.refunds.process_refund(&h.operator, &refund_id);
+    assert_eq!(h.balance(&payer), 2_000_000);
+    // Approvals are used up by the action they cleared
+    assert_eq!(h.refunds.get_approval(&refund_id), None);
+
+    let result = h
+        .payments
+        .try_settle_payment(&approvers[2], &large.payment_id);
+    assert_eq!(result, Err(Ok(Error::ApprovalRequired)));
+    let result = h
+        .payments
+        .try_approve_settlement(&h.oracle, &large.payment_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    h.payments
+        .approve_settlement(&approvers[0], &large.payment_id);
+    h.payments
+        .approve_settlement(&approvers[1], &large.payment_id);
+    h.payments.settle_payment(&approvers[2], &large.payment_id);
+    assert_eq!(
+        h.payments.get_payment(&large.payment_id).status,
+        PaymentStatus::Settled
+    );
+}
+
+#[test]
+fn test_settle_batch_pays_merchants_from_escrow() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    let bakery = h.onboard_merchant("Bakery");
+    let florist = h.onboard_merchant("Florist");
+
+    let mut payment_ids = Vec::new(&h.env);
+    for (payment_id, merchant_id, amount) in [
+        ("bread", &bakery, 300_000),
+        ("cake", &bakery, 700_000),
+        ("roses", &florist, 2_000_000),
+    ] {
+        let payment = h.charge(payment_id, merchant_id, amount);
+        h.pay(&payment, amount);
+        h.sweep_to_escrow(&payment);
+        payment_ids.push_back(payment.payment_id);
+    }
+
+    // One payout per merchant empties escrow of everything settled
+    let batch = h.payments.settle_batch(&h.operator, &payment_ids);
+    assert_eq!(batch.lines.len(), 2);
+    assert_eq!(batch.lines.get(0).unwrap().net, 1_000_000);
+    assert_eq!(h.balance(&bakery), 1_000_000);
+    assert_eq!(h.balance(&florist), 2_000_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+
+    // Escrow that was never swept in cannot be paid out, so the batch does not settle
+    let unswept = h.charge("tulips", &florist, 500_000);
+    h.pay(&unswept, 500_000);
+    assert_eq!(
+        h.payments.try_settle_batch(
+            &h.operator,
+            &Vec::from_array(&h.env, [unswept.payment_id.clone()])
+        ),
+        Err(Ok(Error::InsufficientEscrow))
+    );
+    assert_eq!(
+        h.payments.get_payment(&unswept.payment_id).status,
+        PaymentStatus::Confirmed
+    );
+}
+
+#[test]
+fn test_settlement_pays_the_merchant_settlement_address() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    let merchant_id = h.onboard_merchant("Furniture Co");
+    let cold_wallet = Address::generate(&h.env);
+    h.merchants
+        .set_settlement_address(&merchant_id, &cold_wallet);
+
+    let payment = h.charge("sofa", &merchant_id, 8_000_000);
+    h.pay(&payment, 8_000_000);
+    h.sweep_to_escrow(&payment);
+    h.payments.settle_payment(&h.operator, &payment.payment_id);
+    assert_eq!(h.balance(&cold_wallet), 8_000_000);
+    assert_eq!(h.balance(&merchant_id), 0);
+}
+
+#[test]
+fn test_settlement_pays_fee_collector_and_partner() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    let merchant_id = h.onboard_merchant("Print Lab");
+    let partner = Address::generate(&h.env);
+    h.payments.register_partner(&h.admin, &partner, &5_000);
+
+    // Fees with nowhere to go keep the payment in escrow
+    h.payments.set_merchant_fee(&h.admin, &merchant_id, &100);
+    let payment = h.charge("canvas", &merchant_id, 2_000_000);
+    h.payments
+        .set_payment_partner(&merchant_id, &payment.payment_id, &partner, &2_500);
+    h.pay(&payment, 2_000_000);
+    h.sweep_to_escrow(&payment);
+    assert_eq!(
+        h.payments
+            .try_settle_payment(&h.operator, &payment.payment_id),
+        Err(Ok(Error::FeeCollectorNotSet))
+    );
+
+    // A 1% fee of 20_000, a quarter of it earned by the partner
+    let fee_collector = Address::generate(&h.env);
+    h.payments.set_fee_config(&h.admin, &100, &fee_collector);
+    let settled = h.payments.settle_payment(&h.operator, &payment.payment_id);
+    assert_eq!(settled.fee_amount, 20_000);
+    assert_eq!(h.balance(&partner), 5_000);
+    assert_eq!(h.balance(&fee_collector), 15_000);
+    assert_eq!(h.balance(&merchant_id), 1_980_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+}
+
+#[test]
+fn test_run_payouts_follows_merchant_payout_preferences() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let operator = Address::generate(&h.env);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &operator);
+    h.env.ledger().set_timestamp(10 * REPORT_DAY_SECONDS + 3600);
+
+    let instant = h.onboard_merchant("Corner Shop");
+    let daily = h.onboard_merchant("Night Market");
+    let threshold = h.onboard_merchant("Book Stall");
+    h.merchants.set_payout_preference(
+        &daily,
+        &PayoutPreference {
+            cadence: PayoutCadence::Daily,
+            min_amount: 0,
+        },
+    );
+    let result = h.merchants.try_set_payout_preference(
+        &threshold,
+        &PayoutPreference {
+            cadence: PayoutCadence::Threshold,
+            min_amount: 0,
+        },
+    );
+    assert_eq!(
+        result,
+        Err(Ok(merchant_registry::Error::InvalidPayoutPreference))
+    );
+    h.merchants.set_payout_preference(
+        &threshold,
+        &PayoutPreference {
+            cadence: PayoutCadence::Threshold,
+            min_amount: 15_000,
+        },
+    );
+
+    let now_charge = h.charge("payout_instant", &instant, 10_000);
+    h.pay(&now_charge, 10_000);
+    let daily_charge = h.charge("payout_daily", &daily, 10_000);
+    h.pay(&daily_charge, 10_000);
+    let small_charge = h.charge("payout_small", &threshold, 10_000);
+    h.pay(&small_charge, 10_000);
+    for charge in [&now_charge, &daily_charge, &small_charge] {
+        h.sweep_to_escrow(charge);
+    }
+
+    // Only the immediate merchant is paid today; the others wait on their schedules
+    let batch = h.payments.run_payouts(&operator);
+    assert_eq!(
+        batch.payment_ids,
+        Vec::from_array(&h.env, [now_charge.payment_id.clone()])
+    );
+    assert_eq!(
+        h.payments.get_payment(&daily_charge.payment_id).status,
+        PaymentStatus::Confirmed
+    );
+    assert_eq!(h.balance(&instant), 10_000);
+    assert_eq!((h.balance(&daily), h.balance(&threshold)), (0, 0));
+    assert_eq!(
+        h.payments.try_run_payouts(&operator),
+        Err(Ok(Error::EmptyBatch))
+    );
+
+    // Crossing the threshold releases the whole pending balance, and the next day the
+    // daily merchant's earlier charges follow
+    let top_up = h.charge("payout_top_up", &threshold, 6_000);
+    h.pay(&top_up, 6_000);
+    h.sweep_to_escrow(&top_up);
+    h.env
+        .ledger()
+        .set_timestamp(h.env.ledger().timestamp() + REPORT_DAY_SECONDS);
+    let batch = h.payments.run_payouts(&operator);
+    assert_eq!(batch.payment_ids.len(), 3);
+    assert_eq!(batch.total_gross, 26_000);
+    assert_eq!(h.balance(&daily), 10_000);
+    assert_eq!(h.balance(&threshold), 16_000);
+    assert_eq!(h.balance(&h.refunds.address), 0);
+    for payment_id in batch.payment_ids.iter() {
+        assert_eq!(
+            h.payments.get_payment(&payment_id).status,
+            PaymentStatus::Settled
+        );
+    }
+
+    let stranger = Address::generate(&h.env);
+    assert_eq!(
+        h.payments.try_run_payouts(&stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_category_policy_applies_to_merchants_in_category() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let merchant_id = h.onboard_merchant("Noodle Bar");
+    let restaurants = Symbol::new(&h.env, "5812");
+    h.merchants
+        .set_merchant_category(&h.admin, &merchant_id, &restaurants);
+    assert_eq!(h.merchants.get_merchant(&merchant_id).category, restaurants);
+
+    let invalid = CategoryPolicy {
+        max_amount: 50_000,
+        refund_window: Some(0),
+        fee_bps: None,
+    };
+    assert_eq!(
+        h.payments
+            .try_set_category_policy(&h.admin, &restaurants, &invalid),
+        Err(Ok(Error::InvalidCategoryPolicy))
+    );
+    let policy = CategoryPolicy {
+        max_amount: 50_000,
+        refund_window: Some(3600),
+        fee_bps: Some(250),
+    };
+    h.payments
+        .set_category_policy(&h.admin, &restaurants, &policy);
+    assert_eq!(h.payments.get_merchant_policy(&merchant_id), Some(policy));
+
+    // The category caps single charges, sets the refund window and prices the fee
+    let result = h.payments.try_create_payment(
+        &String::from_str(&h.env, "mcc_large"),
+        &merchant_id,
+        &60_000,
+        &Symbol::new(&h.env, "USDC"),
+        &Address::generate(&h.env),
+        &(h.env.ledger().timestamp() + 3600),
+        &String::from_str(&h.env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::LimitExceeded)));
+    h.charge("mcc_small", &merchant_id, 40_000);
+    assert_eq!(
+        h.refunds.get_refund_window(&Some(merchant_id.clone())),
+        Some(3600)
+    );
+    assert_eq!(h.payments.get_effective_fee(&merchant_id), 250);
+
+    // Merchant-specific settings still win over the category
+    h.payments.set_merchant_fee(&h.admin, &merchant_id, &100);
+    assert_eq!(h.payments.get_effective_fee(&merchant_id), 100);
+    h.refunds.set_merchant_refund_window(&merchant_id, &7200);
+    assert_eq!(
+        h.refunds.get_refund_window(&Some(merchant_id.clone())),
+        Some(7200)
+    );
+
+    h.payments.remove_category_policy(&h.admin, &restaurants);
+    assert_eq!(h.payments.get_merchant_policy(&merchant_id), None);
+}
+
+#[test]
+fn test_promo_codes_discount_price_or_rebate_fee() {
+    let h = TestHarness::setup();
+    h.refunds
+        .set_payment_processor(&h.admin, &h.payments.address);
+    let fee_collector = Address::generate(&h.env);
+    h.payments.set_fee_config(&h.admin, &200, &fee_collector);
+    h.payments
+        .grant_role(&h.admin, &role_settlement_operator(&h.env), &h.operator);
+    let merchant_id = h.onboard_merchant("Gift Shop");
+    let expires_at = h.env.ledger().timestamp() + 86_400;
+    let spring_sale: BytesN<32> = h
+        .env
+        .crypto()
+        .sha256(&Bytes::from_slice(&h.env, b"SPRING10"))
+        .into();
+    let half_fees: BytesN<32> = h
+        .env
+        .crypto()
+        .sha256(&Bytes::from_slice(&h.env, b"HALFFEES"))
+        .into();
+    let free = PromoCode {
+        code_hash: spring_sale.clone(),
+        kind: PromoKind::PriceDiscount,
+        discount_bps: 10_000,
+        max_uses: 1,
+        expires_at,
+    };
+    assert_eq!(
+        h.payments.try_set_promo_code(&h.admin, &free),
+        Err(Ok(Error::InvalidPromoCode))
+    );
+    h.payments.set_promo_code(
+        &h.admin,
+        &PromoCode {
+            code_hash: spring_sale.clone(),
+            kind: PromoKind::PriceDiscount,
+            discount_bps: 1_000,
+            max_uses: 1,
+            expires_at,
+        },
+    );
+    h.payments.set_promo_code(
+        &h.admin,
+        &PromoCode {
+            code_hash: half_fees.clone(),
+            kind: PromoKind::FeeRebate,
+            discount_bps: 5_000,
+            max_uses: 5,
+            expires_at,
+        },
+    );
+    let charge = |payment_id: &str, promo_code: &BytesN<32>| {
+        h.payments.try_create_payment(
+            &String::from_str(&h.env, payment_id),
+            &merchant_id,
+            &1_000_000,
+            &Symbol::new(&h.env, "USDC"),
+            &Address::generate(&h.env),
+            &0,
+            &String::from_str(&h.env, ""),
+            &None,
+            &None,
+            &None,
+            &Some(promo_code.clone()),
+        )
+    };
+
+    // A price discount lowers what the payer owes and uses up the code
+    let discounted = charge("sale_1", &spring_sale).unwrap().unwrap();
+    assert_eq!(discounted.amount, 900_000);
+    assert_eq!(
+        h.payments
+            .get_payment_promo(&discounted.payment_id)
+            .unwrap()
+            .discount_amount,
+        100_000
+    );
+    assert_eq!(h.payments.get_promo_remaining_uses(&spring_sale), 0);
+    assert_eq!(
+        charge("sale_2", &spring_sale),
+        Err(Ok(Error::PromoCodeExhausted))
+    );
+
+    // A fee rebate leaves the price alone and halves the fee at settlement
+    let rebated = charge("rebate_1", &half_fees).unwrap().unwrap();
+    assert_eq!(rebated.amount, 1_000_000);
+    assert_eq!(h.payments.get_promo_remaining_uses(&half_fees), 4);
+    h.pay(&rebated, 1_000_000);
+    h.sweep_to_escrow(&rebated);
+    let settled = h.payments.settle_payment(&h.operator, &rebated.payment_id);
+    assert_eq!(settled.fee_amount, 10_000);
+    assert_eq!(h.balance(&fee_collector), 10_000);
+    assert_eq!(h.balance(&merchant_id), 990_000);
+
+    // Codes stop applying once they expire or are withdrawn
+    h.env.ledger().set_timestamp(expires_at + 1);
+    assert_eq!(h.payments.get_promo_remaining_uses(&half_fees), 0);
+    assert_eq!(
+        charge("rebate_2", &half_fees),
+        Err(Ok(Error::PromoCodeExpired))
+    );
+    h.payments.remove_promo_code(&h.admin, &half_fees);
+    assert_eq!(
+        h.payments.try_get_promo_code(&half_fees),
+        Err(Ok(Error::PromoCodeNotFound))
+    );
+}
@@ -0,0 +1,138 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Symbol, Vec};
+
+use crate::Error;
+
+// Payer-initiated request-to-pay, converted into a PaymentCharge once the merchant accepts it
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentIntent {
+    pub intent_id: u64,
+    pub payer: Address,
+    pub merchant_id: Address,
+    pub amount: i128,
+    pub currency: Symbol,
+    pub memo: String,
+    pub status: IntentStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub payment_id: Option<String>, // charge created on acceptance
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IntentStatus {
+    Proposed,
+    Accepted,
+    Declined,
+    Expired,
+}
+
+#[contracttype]
+pub enum IntentDataKey {
+    Intent(u64),              // intent_id -> PaymentIntent
+    MerchantIntents(Address), // merchant_id -> Vec<intent_id>
+    IntentCounter,
+}
+
+pub struct Intents;
+
+impl Intents {
+    pub fn create(
+        env: &Env,
+        payer: Address,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        memo: String,
+        expires_at: u64,
+    ) -> Result<PaymentIntent, Error> {
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if expires_at <= env.ledger().timestamp() {
+            return Err(Error::IntentExpired);
+        }
+
+        let intent_id = Self::next_id(env);
+        let intent = PaymentIntent {
+            intent_id,
+            payer,
+            merchant_id: merchant_id.clone(),
+            amount,
+            currency,
+            memo,
+            status: IntentStatus::Proposed,
+            created_at: env.ledger().timestamp(),
+            expires_at,
+            payment_id: None,
+        };
+        Self::save(env, &intent);
+
+        let key = IntentDataKey::MerchantIntents(merchant_id);
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        ids.push_back(intent_id);
+        env.storage().persistent().set(&key, &ids);
+
+        Ok(intent)
+    }
+
+    pub fn get(env: &Env, intent_id: u64) -> Result<PaymentIntent, Error> {
+        env.storage()
+            .persistent()
+            .get(&IntentDataKey::Intent(intent_id))
+            .ok_or(Error::IntentNotFound)
+    }
+
+    pub fn get_by_merchant(env: &Env, merchant_id: &Address) -> Vec<PaymentIntent> {
+        let ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&IntentDataKey::MerchantIntents(merchant_id.clone()))
+            .unwrap_or(vec![env]);
+
+        let mut intents = vec![env];
+        for id in ids.iter() {
+            if let Ok(intent) = Self::get(env, id) {
+                intents.push_back(intent);
+            }
+        }
+        intents
+    }
+
+    /// Move a still-proposed intent to `status`, rejecting it once past its expiry
+    pub fn close(env: &Env, intent: &mut PaymentIntent, status: IntentStatus) -> Result<(), Error> {
+        if intent.status != IntentStatus::Proposed {
+            return Err(Error::IntentNotOpen);
+        }
+        let expired = env.ledger().timestamp() > intent.expires_at;
+        if expired != (status == IntentStatus::Expired) {
+            return Err(if expired {
+                Error::IntentExpired
+            } else {
+                Error::IntentNotOpen
+            });
+        }
+        intent.status = status;
+        Self::save(env, intent);
+        Ok(())
+    }
+
+    pub fn save(env: &Env, intent: &PaymentIntent) {
+        env.storage()
+            .persistent()
+            .set(&IntentDataKey::Intent(intent.intent_id), intent);
+    }
+
+    fn next_id(env: &Env) -> u64 {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&IntentDataKey::IntentCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&IntentDataKey::IntentCounter, &counter);
+        counter
+    }
+}
@@ -0,0 +1,181 @@
+use soroban_sdk::{contracttype, vec, Address, BytesN, Env, String, Symbol, Vec};
+
+use crate::ids::{IdBuilder, INVOICE_PREFIX};
+use crate::Error;
+
+pub const MAX_INVOICE_LINES: u32 = 20;
+pub const INVOICE_CHARGE_WINDOW: u64 = 24 * 3600; // minimum life of a charge paying an invoice
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvoiceLine {
+    pub description_hash: BytesN<32>, // hash of the line description kept off-chain
+    pub quantity: u32,
+    pub unit_price: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvoiceStatus {
+    Open, // awaiting payment, including while a charge for it is pending
+    Paid, // a charge raised for it was confirmed
+    Cancelled,
+}
+
+// Itemized bill a merchant issues ahead of payment; paying it raises a PaymentCharge
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Invoice {
+    pub invoice_id: u64,
+    pub merchant_id: Address,
+    pub currency: Symbol,
+    pub lines: Vec<InvoiceLine>,
+    pub total: i128,
+    pub due_at: u64, // overdue invoices can still be paid
+    pub status: InvoiceStatus,
+    pub created_at: u64,
+    pub payment_id: Option<String>, // latest charge raised to pay it
+    pub attempts: u32,              // charges raised so far
+}
+
+#[contracttype]
+pub enum InvoiceDataKey {
+    Invoice(u64),              // invoice_id -> Invoice
+    MerchantInvoices(Address), // merchant_id -> Vec<invoice_id>
+    PaymentInvoice(String),    // payment_id -> invoice_id it pays
+    InvoiceCounter,
+}
+
+pub struct Invoices;
+
+impl Invoices {
+    /// Check the line items and return the invoice total
+    pub fn validate(lines: &Vec<InvoiceLine>) -> Result<i128, Error> {
+        if lines.is_empty() || lines.len() > MAX_INVOICE_LINES {
+            return Err(Error::InvalidInvoice);
+        }
+        let mut total: i128 = 0;
+        for line in lines.iter() {
+            if line.quantity == 0 || line.unit_price <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            total = line
+                .unit_price
+                .checked_mul(line.quantity as i128)
+                .and_then(|amount| total.checked_add(amount))
+                .ok_or(Error::InvalidAmount)?;
+        }
+        Ok(total)
+    }
+
+    pub fn create(
+        env: &Env,
+        merchant_id: Address,
+        currency: Symbol,
+        lines: Vec<InvoiceLine>,
+        due_at: u64,
+    ) -> Result<Invoice, Error> {
+        let total = Self::validate(&lines)?;
+        if due_at <= env.ledger().timestamp() {
+            return Err(Error::InvalidInvoice);
+        }
+
+        let invoice = Invoice {
+            invoice_id: Self::next_id(env),
+            merchant_id: merchant_id.clone(),
+            currency,
+            lines,
+            total,
+            due_at,
+            status: InvoiceStatus::Open,
+            created_at: env.ledger().timestamp(),
+            payment_id: None,
+            attempts: 0,
+        };
+        Self::save(env, &invoice);
+
+        let key = InvoiceDataKey::MerchantInvoices(merchant_id);
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        ids.push_back(invoice.invoice_id);
+        env.storage().persistent().set(&key, &ids);
+
+        Ok(invoice)
+    }
+
+    pub fn get(env: &Env, invoice_id: u64) -> Result<Invoice, Error> {
+        env.storage()
+            .persistent()
+            .get(&InvoiceDataKey::Invoice(invoice_id))
+            .ok_or(Error::InvoiceNotFound)
+    }
+
+    pub fn get_by_merchant(env: &Env, merchant_id: &Address) -> Vec<Invoice> {
+        let ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&InvoiceDataKey::MerchantInvoices(merchant_id.clone()))
+            .unwrap_or(vec![env]);
+
+        let mut invoices = vec![env];
+        for id in ids.iter() {
+            if let Ok(invoice) = Self::get(env, id) {
+                invoices.push_back(invoice);
+            }
+        }
+        invoices
+    }
+
+    /// Id for the next charge raised against the invoice, e.g. "invoice_4_2" for its second
+    pub fn next_payment_id(env: &Env, invoice: &mut Invoice) -> String {
+        invoice.attempts += 1;
+        IdBuilder::new(INVOICE_PREFIX)
+            .push_u64(invoice.invoice_id)
+            .push_str("_")
+            .push_u64(invoice.attempts as u64)
+            .build(env)
+    }
+
+    /// Link a new charge to the invoice, superseding any earlier attempt
+    pub fn link_payment(env: &Env, invoice: &mut Invoice, payment_id: &String) {
+        invoice.payment_id = Some(payment_id.clone());
+        env.storage().persistent().set(
+            &InvoiceDataKey::PaymentInvoice(payment_id.clone()),
+            &invoice.invoice_id,
+        );
+        Self::save(env, invoice);
+    }
+
+    /// Mark the invoice a confirmed charge pays as Paid; returns it if there was one
+    pub fn mark_paid(env: &Env, payment_id: &String) -> Option<Invoice> {
+        let invoice_id: u64 = env
+            .storage()
+            .persistent()
+            .get(&InvoiceDataKey::PaymentInvoice(payment_id.clone()))?;
+        let mut invoice = Self::get(env, invoice_id).ok()?;
+        if invoice.status != InvoiceStatus::Open {
+            return None;
+        }
+        invoice.status = InvoiceStatus::Paid;
+        Self::save(env, &invoice);
+        Some(invoice)
+    }
+
+    pub fn save(env: &Env, invoice: &Invoice) {
+        env.storage()
+            .persistent()
+            .set(&InvoiceDataKey::Invoice(invoice.invoice_id), invoice);
+    }
+
+    fn next_id(env: &Env) -> u64 {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&InvoiceDataKey::InvoiceCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&InvoiceDataKey::InvoiceCounter, &counter);
+        counter
+    }
+}
@@ -0,0 +1,237 @@
+use soroban_sdk::{contracttype, token, vec, Address, BytesN, Env, Vec};
+
+use crate::Error;
+
+// Staked keepers run the housekeeping entry points for a bounty per unit of work
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeeperConfig {
+    pub stake_token: Address,
+    pub min_stake: i128,
+    pub bounty: i128,
+    pub slash_quorum: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Keeper {
+    pub keeper: Address,
+    pub stake: i128,
+    pub rewards: i128,
+    pub registered_at: u64,
+    pub open_slashes: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SlashProposal {
+    pub proposal_id: u64,
+    pub keeper: Address,
+    pub amount: i128,
+    pub evidence_hash: BytesN<32>,
+    pub approvals: Vec<Address>,
+    pub executed: bool,
+}
+
+#[contracttype]
+pub enum KeeperDataKey {
+    Config,
+    Keeper(Address),    // keeper -> Keeper
+    SlashProposal(u64), // proposal_id -> SlashProposal
+    SlashCounter,       // u64 counter for slash proposal IDs
+}
+
+pub struct Keepers;
+
+impl Keepers {
+    pub fn set_config(env: &Env, config: KeeperConfig) -> Result<(), Error> {
+        if config.min_stake <= 0 || config.bounty < 0 || config.slash_quorum == 0 {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage()
+            .persistent()
+            .set(&KeeperDataKey::Config, &config);
+        Ok(())
+    }
+
+    pub fn get_config(env: &Env) -> Result<KeeperConfig, Error> {
+        env.storage()
+            .persistent()
+            .get(&KeeperDataKey::Config)
+            .ok_or(Error::KeeperNotConfigured)
+    }
+
+    pub fn get(env: &Env, keeper: &Address) -> Option<Keeper> {
+        env.storage()
+            .persistent()
+            .get(&KeeperDataKey::Keeper(keeper.clone()))
+    }
+
+    pub fn require(env: &Env, keeper: &Address) -> Result<Keeper, Error> {
+        Self::get(env, keeper).ok_or(Error::KeeperNotRegistered)
+    }
+
+    /// Pull `stake` from the keeper into the contract and register them
+    pub fn register(env: &Env, keeper: &Address, stake: i128) -> Result<Keeper, Error> {
+        let config = Self::get_config(env)?;
+        if Self::get(env, keeper).is_some() {
+            return Err(Error::KeeperAlreadyRegistered);
+        }
+        if stake < config.min_stake {
+            return Err(Error::InsufficientStake);
+        }
+
+        token::Client::new(env, &config.stake_token).transfer(
+            keeper,
+            &env.current_contract_address(),
+            &stake,
+        );
+
+        let record = Keeper {
+            keeper: keeper.clone(),
+            stake,
+            rewards: 0,
+            registered_at: env.ledger().timestamp(),
+            open_slashes: 0,
+        };
+        Self::save(env, &record);
+        Ok(record)
+    }
+
+    /// Return the remaining stake and unclaimed rewards and remove the keeper
+    pub fn deregister(env: &Env, keeper: &Address) -> Result<i128, Error> {
+        let config = Self::get_config(env)?;
+        let record = Self::require(env, keeper)?;
+        // A keeper cannot exit ahead of a pending slash
+        if record.open_slashes > 0 {
+            return Err(Error::SlashPending);
+        }
+        let payout = record.stake + record.rewards;
+        if payout > 0 {
+            token::Client::new(env, &config.stake_token).transfer(
+                &env.current_contract_address(),
+                keeper,
+                &payout,
+            );
+        }
+        env.storage()
+            .persistent()
+            .remove(&KeeperDataKey::Keeper(keeper.clone()));
+        Ok(payout)
+    }
+
+    /// Credit the keeper one bounty per unit of work done
+    pub fn reward(env: &Env, keeper: &Address, units: u32) -> Result<i128, Error> {
+        let config = Self::get_config(env)?;
+        let mut record = Self::require(env, keeper)?;
+        let earned = config.bounty * units as i128;
+        record.rewards += earned;
+        Self::save(env, &record);
+        Ok(earned)
+    }
+
+    pub fn claim_rewards(env: &Env, keeper: &Address) -> Result<i128, Error> {
+        let config = Self::get_config(env)?;
+        let mut record = Self::require(env, keeper)?;
+        let rewards = record.rewards;
+        if rewards > 0 {
+            token::Client::new(env, &config.stake_token).transfer(
+                &env.current_contract_address(),
+                keeper,
+                &rewards,
+            );
+            record.rewards = 0;
+            Self::save(env, &record);
+        }
+        Ok(rewards)
+    }
+
+    pub fn propose_slash(
+        env: &Env,
+        admin: &Address,
+        keeper: &Address,
+        amount: i128,
+        evidence_hash: BytesN<32>,
+    ) -> Result<SlashProposal, Error> {
+        let mut record = Self::require(env, keeper)?;
+        if amount <= 0 || amount > record.stake {
+            return Err(Error::InvalidAmount);
+        }
+        record.open_slashes += 1;
+        Self::save(env, &record);
+
+        let proposal_id: u64 = env
+            .storage()
+            .persistent()
+            .get(&KeeperDataKey::SlashCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&KeeperDataKey::SlashCounter, &proposal_id);
+
+        let proposal = SlashProposal {
+            proposal_id,
+            keeper: keeper.clone(),
+            amount,
+            evidence_hash,
+            approvals: vec![env],
+            executed: false,
+        };
+        Self::approve_slash_internal(env, proposal, admin)
+    }
+
+    pub fn approve_slash(
+        env: &Env,
+        admin: &Address,
+        proposal_id: u64,
+    ) -> Result<SlashProposal, Error> {
+        let proposal = Self::get_slash_proposal(env, proposal_id)?;
+        if proposal.executed {
+            return Err(Error::SlashAlreadyExecuted);
+        }
+        if proposal.approvals.contains(admin) {
+            return Err(Error::SlashAlreadyApproved);
+        }
+        Self::approve_slash_internal(env, proposal, admin)
+    }
+
+    pub fn get_slash_proposal(env: &Env, proposal_id: u64) -> Result<SlashProposal, Error> {
+        env.storage()
+            .persistent()
+            .get(&KeeperDataKey::SlashProposal(proposal_id))
+            .ok_or(Error::SlashProposalNotFound)
+    }
+
+    // Record the approval, forfeiting the stake to the contract once quorum is reached
+    fn approve_slash_internal(
+        env: &Env,
+        mut proposal: SlashProposal,
+        admin: &Address,
+    ) -> Result<SlashProposal, Error> {
+        let config = Self::get_config(env)?;
+        proposal.approvals.push_back(admin.clone());
+
+        if proposal.approvals.len() >= config.slash_quorum {
+            let mut record = Self::require(env, &proposal.keeper)?;
+            // The stake may have shrunk since the proposal was opened
+            let slashed = proposal.amount.min(record.stake);
+            record.stake -= slashed;
+            record.open_slashes -= 1;
+            Self::save(env, &record);
+            proposal.executed = true;
+        }
+
+        env.storage().persistent().set(
+            &KeeperDataKey::SlashProposal(proposal.proposal_id),
+            &proposal,
+        );
+        Ok(proposal)
+    }
+
+    fn save(env: &Env, record: &Keeper) {
+        env.storage()
+            .persistent()
+            .set(&KeeperDataKey::Keeper(record.keeper.clone()), record);
+    }
+}
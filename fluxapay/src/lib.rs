@@ -1,12 +1,162 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, vec, Address, Env, String, Symbol, Vec,
-   Address, BytesN,
-    Env, String, Symbol,
+    contract, contracterror, contractimpl, contracttype, token, vec, Address, BytesN, Env, Map,
+    String, Symbol, Vec,
 };
 
 mod access_control;
-use access_control::{role_oracle, role_settlement_operator, AccessControl};
+mod anchor;
+mod api_version;
+mod approval;
+mod archive;
+mod attestation;
+mod audit;
+mod authorization;
+mod auto_settle;
+mod cart;
+mod category;
+mod clock;
+mod compliance;
+mod config;
+mod deposit_pool;
+mod dispute;
+mod emergency;
+mod escrow;
+mod events;
+mod expiry_stats;
+mod features;
+mod fees;
+mod ids;
+mod instalment;
+mod intent;
+mod invoice;
+mod keeper;
+mod limits;
+mod mass_refund;
+mod migration;
+mod partners;
+mod pausable;
+mod payment_link;
+pub mod privacy;
+mod promo;
+mod rate_limit;
+mod rates;
+mod refund_manager;
+mod refund_policy;
+mod remittance;
+mod settlement;
+mod settlement_report;
+mod spend_guard;
+mod statement;
+mod subscription;
+mod time_index;
+mod ttl;
+pub use access_control::RoleDefinition;
+use access_control::{
+    role_admin, role_compliance, role_oracle, role_settlement_operator, AccessControl,
+    AccessControlError,
+};
+use anchor::AnchorReferences;
+pub use anchor::{AnchorReference, MemoType, MAX_MEMO_TEXT_LEN};
+use api_version::ApiBehavior;
+use approval::Approvals;
+pub use approval::{Approval, ApprovalPolicy};
+use archive::Archive;
+pub use archive::{PaymentSummary, DEFAULT_ARCHIVE_RETENTION};
+use attestation::Attestations;
+use audit::AuditLog;
+pub use audit::{AuditEntity, AuditEntry, AuditRecord, AUDIT_JOURNAL_CAP, AUDIT_PAGE_SIZE};
+use authorization::Authorizations;
+pub use authorization::{Authorization, DEFAULT_CAPTURE_WINDOW};
+use auto_settle::AutoSettle;
+pub use auto_settle::{AutoSettleRule, QueuedSettlement, UnsettledBalance};
+use cart::Carts;
+pub use cart::{Cart, CartLeg, CartStatus};
+use category::CategoryPolicies;
+pub use category::CategoryPolicy;
+use clock::Clock;
+pub use compliance::BlockRecord;
+use compliance::Compliance;
+use config::Configs;
+pub use config::{
+    Config, DEFAULT_MAX_METADATA_SIZE, DEFAULT_MAX_PAYMENT_EXPIRY, DEFAULT_PAYMENT_EXPIRY,
+    DEFAULT_TOLERANCE_BPS,
+};
+use deposit_pool::DepositPool;
+pub use deposit_pool::{DepositAddressPool, RotationPolicy};
+pub use dispute::{Dispute, DisputeOutcome, DisputeStatus, Evidence};
+use emergency::Emergency;
+pub use emergency::{
+    EmergencyWithdrawal, WithdrawalStatus, DEFAULT_EMERGENCY_TIMELOCK, MIN_EMERGENCY_TIMELOCK,
+};
+use escrow::EscrowLedger;
+pub use escrow::EscrowReconciliation;
+pub use events::{MerchantEvent, PaymentEvent, RefundEvent, RoleEvent};
+use expiry_stats::ExpiryTracker;
+pub use expiry_stats::{ExpiryAlertConfig, ExpiryStats};
+use features::{feature_private_payments, feature_subscriptions, Features};
+use fees::Fees;
+pub use fees::{FeeConfig, FeeInvoice, FeeKind, FeeTier};
+use ids::{
+    validate_external_id, validate_id, IdBuilder, CART_PREFIX, INTENT_PREFIX, SUBSCRIPTION_PREFIX,
+};
+use instalment::InstalmentPlans;
+pub use instalment::{
+    InstalmentPlan, PlanStatus, ScheduledInstalment, INSTALMENT_GRACE_PERIOD,
+    INSTALMENT_ISSUE_LEAD, MAX_INSTALMENTS,
+};
+use intent::Intents;
+pub use intent::{IntentStatus, PaymentIntent};
+use invoice::Invoices;
+pub use invoice::{Invoice, InvoiceLine, InvoiceStatus, INVOICE_CHARGE_WINDOW, MAX_INVOICE_LINES};
+use keeper::Keepers;
+pub use keeper::{Keeper, KeeperConfig, SlashProposal};
+use limits::Limits;
+pub use limits::PayerLimits;
+pub use mass_refund::MassRefund;
+pub use merchant_registry::{CustodyMode, MerchantLimits, PayoutCadence, PayoutPreference};
+use merchant_registry::{Merchant, MerchantRegistryClient, DEFAULT_API_VERSION};
+use migration::Migrations;
+pub use migration::{MigrationCursor, MigrationProgress, SCHEMA_VERSION};
+use partners::Partners;
+pub use partners::{Partner, PartnerShare};
+use pausable::Pausable;
+pub use pausable::PauseScope;
+use payment_link::PaymentLinks;
+pub use payment_link::{PaymentLink, LINK_CHARGE_WINDOW};
+use promo::Promos;
+pub use promo::{PromoCode, PromoKind, PromoRedemption};
+pub use rate_limit::RateLimit;
+use rate_limit::RateLimits;
+use rates::Rates;
+pub use rates::{ExchangeRate, RATE_SCALE};
+pub use refund_manager::{RefundManager, RefundManagerClient};
+pub use remittance::RemittanceInfo;
+use settlement::Settlements;
+pub use settlement::{SettlementBatch, SettlementLine};
+use settlement_report::SettlementReports;
+pub use settlement_report::{ReportLine, SettlementReport, REPORT_DAY_SECONDS};
+use spend_guard::SpendGuard;
+pub use spend_guard::SpendLimit;
+use statement::Statements;
+pub use statement::{BalanceStatement, MerchantLedger};
+use subscription::Subscriptions;
+pub use subscription::{Subscription, SubscriptionStatus};
+use time_index::TimeIndex;
+pub use time_index::{RecordKind, TimeIndexEntry};
+
+/// Most IDs accepted by a single bulk read or batch operation
+pub const MAX_BATCH_SIZE: u32 = 50;
+
+/// How far past its original expiry a merchant may push a pending charge by default
+pub const DEFAULT_MAX_EXPIRY_EXTENSION: u64 = 7 * 24 * 3600;
+
+/// Bound on each value of the metadata a merchant can attach to a payment; the number of
+/// entries is capped by `Config::max_metadata_size`
+pub const MAX_METADATA_VALUE_LEN: u32 = 256;
+
+/// Oracle silence longer than this, while charges await verification, counts as degraded
+pub const ORACLE_HEALTH_WINDOW: u64 = 15 * 60;
 
 #[contract]
 pub struct PaymentProcessor;
@@ -19,12 +169,26 @@ pub struct PaymentCharge {
     pub amount: i128,
     pub currency: Symbol,
     pub deposit_address: Address,
+    pub order_reference: String, // merchant's own order or invoice number
+    pub metadata: Map<Symbol, String>, // merchant annotations, at most max_metadata_size
     pub status: PaymentStatus,
     pub payer_address: Option<Address>,
+    pub payer_commitment: Option<BytesN<32>>, // privacy mode: sha256(payer || salt)
     pub transaction_hash: Option<BytesN<32>>,
     pub created_at: u64,
     pub confirmed_at: Option<u64>,
     pub expires_at: u64,
+    pub fee_amount: i128,      // platform fee taken at settlement
+    pub refunded_amount: i128, // sum of refunds paid out of escrow
+    pub overpaid_amount: i128, // excess received, owed back to the payer
+    pub settled_at: Option<u64>,
+    pub custody_mode: CustodyMode, // merchant's mode when the charge was created
+    pub payout_currency: Option<Symbol>, // merchant's settlement currency, set at settlement
+    pub payout_amount: Option<i128>, // net amount converted into payout_currency
+    pub payout_rate: Option<ExchangeRate>, // rate, oracle and timestamp behind payout_amount
+    pub expected_payer: Option<Address>, // B2B pin: funds from anyone else are not confirmed
+    pub accept_overpayment: bool,  // confirm any excess as a tip to the merchant
+    pub tip_amount: i128,          // excess confirmed as a tip, included in amount
 }
 
 #[contracttype]
@@ -34,111 +198,656 @@ pub enum PaymentStatus {
     Confirmed,
     Expired,
     Failed,
+    Settled,
+    Cancelled,
+    Authorized,    // funds held by the processor until captured, released or expired
+    LateConfirmed, // paid after expiry or cancellation; what arrived is owed back to the payer
 }
 
-#[contracterror]
+/// A page of payments plus the total size of the underlying index
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentPage {
+    pub payments: Vec<PaymentCharge>,
+    pub total: u32,
+}
+
+/// Compact proof of a paid charge, for wallets to show in a purchase history
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Receipt {
+    pub payment_id: String,
+    pub merchant_id: Address,
+    pub merchant_name: String, // business name in the registry, empty if no longer listed
+    pub amount: i128,
+    pub currency: Symbol,
+    pub transaction_hash: Option<BytesN<32>>, // None for captured authorizations
+    pub confirmed_at: u64,
+}
+
+/// What a deployment is and what it supports, for wallets and integrators to discover
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContractInfo {
+    pub kind: Symbol,
+    pub version: String,
+    pub linked_contracts: Map<Symbol, Address>,
+    pub features: Vec<Symbol>,
+    pub currencies: Vec<Symbol>,
+    pub paused: bool,
+    pub paused_scopes: Vec<PauseScope>,
+}
+
+/// Live operating state for merchant dashboards to drive "payments delayed" banners from
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlatformStatus {
+    pub paused: bool,
+    pub paused_scopes: Vec<PauseScope>,
+    pub paused_currencies: Vec<Symbol>,
+    pub last_oracle_activity: Option<u64>,
+    pub oracle_healthy: bool, // false if charges are pending and oracles have gone quiet
+    pub pending_payments: u32,
+    pub settlement_backlog: u32, // confirmed charges not yet settled
+    pub queued_settlements: u32, // auto-settlements due but not yet run
+    pub delayed: bool,           // any of the above is holding payments up
+}
+
+/// A currency accepted for payments and the token contract that settles it
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SupportedToken {
+    pub currency: Symbol,
+    pub token: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Refund {
+    pub refund_id: String,
+    pub payment_id: String,
+    pub amount: i128,
+    pub reason: RefundReason,
+    pub detail: Option<String>, // free-text context for the reason
+    pub status: RefundStatus,
+    pub requester: Address,
+    pub created_at: u64,
+    pub processed_at: Option<u64>,
+    pub dispute_id: Option<String>, // set when raised by a dispute resolved for the payer
+    pub rejection_reason: Option<String>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RefundReason {
+    Duplicate,
+    Fraud, // needs an admin's approval rather than the merchant's
+    ProductNotReceived,
+    CustomerRequest,
+    Other,
+}
+
+/// Refunds requested for one reason, for analytics on refund causes
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RefundReasonStats {
+    pub count: u32,
+    pub amount: i128, // total requested
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RefundStatus {
+    Pending,
+    Approved, // merchant signed off, awaiting operator execution
+    Completed,
+    Rejected,
+}
+
+/// Dry run of `process_refund`: each check it would make, and whether all of them pass
+#[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProcessCheck {
+    pub can_process: bool,
+    pub not_paused: bool,
+    pub status_ok: bool, // Approved, or Pending where no merchant sign-off is needed
+    pub operator_ok: bool, // operator holds a refund role and the amount fits its limits
+    pub escrow_funded: bool, // escrow still holds the payment and enough of its token
+    pub destination_ok: bool, // payer is known and allowed to hold the token
+    pub approvals_ok: bool, // under the approval threshold, or enough operators approved
+}
+
+// Every failure the PaymentProcessor and RefundManager report. Codes are part of the public
+// interface: a variant keeps its code for good, new variants take the next free one, and a
+// retired code is never handed out again. Errors of internal modules and of the
+// MerchantRegistry are converted through the `From` impls below rather than ad hoc.
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
 pub enum Error {
     PaymentNotFound = 1,
     PaymentAlreadyExists = 2,
     InvalidAmount = 3,
-    Unauthorized = 4,
-    PaymentNotFound = 5,
-    AccessControlError = 6,
     PaymentExpired = 4,
     PaymentAlreadyProcessed = 5,
     Unauthorized = 6,
     InvalidPaymentId = 7,
+    AccessControlError = 8, // retired catch-all for role failures, now reported as 104-109
+    RefundNotFound = 9,
+    RefundAlreadyProcessed = 10,
+    DepositAddressAlreadyExists = 11,
+    DepositAddressNotFound = 12,
+    DepositPoolEmpty = 13,
+    MerchantNotVerified = 14,
+    PaymentNotPrivate = 15,
+    CommitmentMismatch = 16,
+    SubscriptionNotFound = 17,
+    SubscriptionNotDue = 18,
+    SubscriptionNotActive = 19,
+    InvalidInterval = 20,
+    TooManyRefunds = 21,
+    InvalidTimeRange = 22,
+    InvalidFee = 23,
+    PaymentNotConfirmed = 24,
+    FeatureDisabled = 25,
+    KeeperNotConfigured = 26,
+    KeeperNotRegistered = 27,
+    KeeperAlreadyRegistered = 28,
+    InsufficientStake = 29,
+    SlashProposalNotFound = 30,
+    SlashAlreadyApproved = 31,
+    SlashAlreadyExecuted = 32,
+    SlashPending = 33,
+    ContractPaused = 34,
+    DepositAddressInUse = 35,
+    SettlementNotRequired = 36,
+    DisputeNotFound = 37,
+    DisputeAlreadyOpen = 38,
+    DisputeNotOpen = 39,
+    SpendLimitExceeded = 40,
+    BatchTooLarge = 41,
+    EmptyBatch = 42,
+    SettlementBatchNotFound = 43,
+    UnsupportedCurrency = 44,
+    InvalidRate = 45,
+    StaleRate = 46,
+    RateNotFound = 47,
+    IntentNotFound = 48,
+    IntentNotOpen = 49,
+    IntentExpired = 50,
+    InsufficientEscrow = 51,
+    MassRefundNotFound = 52,
+    MassRefundAlreadyClaimed = 53,
+    InvalidProof = 54,
+    ClaimWindowClosed = 55,
+    ClaimWindowOpen = 56,
+    RefundNotApproved = 57,
+    RefundExceedsPayment = 58,
+    NoOverpayment = 59,
+    FeeInvoiceNotFound = 60,
+    IdempotencyConflict = 61,
+    CartNotFound = 62,
+    InvalidCart = 63,
+    InvalidMetadata = 64,
+    OracleKeyNotFound = 65,
+    CurrencyNotAccepted = 66,
+    InvalidNonce = 67,
+    InvalidAnchorReference = 68,
+    SettlementNotFound = 69,
+    InvalidRetention = 70,
+    PaymentNotArchivable = 71,
+    PartnerNotFound = 72,
+    InvoiceNotFound = 73,
+    InvoiceNotOpen = 74,
+    InvalidInvoice = 75,
+    PaymentNotAuthorized = 76,
+    AuthorizationExpired = 77,
+    LinkNotFound = 78,
+    LinkInactive = 79,
+    LimitExceeded = 80,
+    IdTooLong = 81,
+    InvalidIdCharacter = 82,
+    ReservedIdPrefix = 83,
+    AddressBlocked = 84,
+    AddressNotBlocked = 85,
+    PayerLimitExceeded = 86,
+    MigrationCursorAhead = 87,
+    UnsupportedRecordKind = 88,
+    AlreadyInitialized = 89,
+    RefundWindowClosed = 90,
+    EscrowNotLinked = 91,
+    WithdrawalNotFound = 92,
+    WithdrawalTimelocked = 93,
+    WithdrawalNotPending = 94,
+    ApprovalRequired = 95,
+    AlreadyApproved = 96,
+    ExtensionTooLong = 97,
+    WrongPayer = 98,
+    InstalmentPlanNotFound = 99,
+    InstalmentPlanNotActive = 100,
+    InstalmentNotDue = 101,
+    InvalidInstalmentPlan = 102,
+    InvalidCategoryPolicy = 103,
+    RoleAlreadyGranted = 104,
+    RoleNotGranted = 105,
+    CannotRenounceAdmin = 106,
+    InvalidAdmin = 107,
+    RoleNotDefined = 108,
+    InvalidRoleExpiry = 109,
+    MerchantNotFound = 110,
+    MerchantAlreadyExists = 111,
+    MerchantRegistryError = 112, // registry failures with no counterpart here
+    RateLimited = 113,
+    AdminApprovalRequired = 114,
+    InvalidExpiry = 115,
+    PayerNotPinned = 116,
+    InsufficientAllowance = 117,
+    PromoCodeNotFound = 118,
+    PromoCodeExpired = 119,
+    PromoCodeExhausted = 120,
+    InvalidPromoCode = 121,
+    FeeCollectorNotSet = 122,
+    MandateExceeded = 123,
+}
+
+impl From<AccessControlError> for Error {
+    fn from(error: AccessControlError) -> Self {
+        match error {
+            AccessControlError::Unauthorized => Error::Unauthorized,
+            AccessControlError::RoleAlreadyGranted => Error::RoleAlreadyGranted,
+            AccessControlError::RoleNotGranted => Error::RoleNotGranted,
+            AccessControlError::CannotRenounceAdmin => Error::CannotRenounceAdmin,
+            AccessControlError::InvalidAdmin => Error::InvalidAdmin,
+            AccessControlError::RoleNotDefined => Error::RoleNotDefined,
+            AccessControlError::InvalidExpiry => Error::InvalidRoleExpiry,
+        }
+    }
+}
+
+impl From<merchant_registry::Error> for Error {
+    fn from(error: merchant_registry::Error) -> Self {
+        match error {
+            merchant_registry::Error::MerchantNotFound => Error::MerchantNotFound,
+            merchant_registry::Error::MerchantAlreadyExists => Error::MerchantAlreadyExists,
+            merchant_registry::Error::Unauthorized => Error::Unauthorized,
+            merchant_registry::Error::NotVerified => Error::MerchantNotVerified,
+            merchant_registry::Error::AdminAlreadySet => Error::AlreadyInitialized,
+            _ => Error::MerchantRegistryError,
+        }
+    }
 }
 
 #[contracttype]
 pub enum DataKey {
-    Refund(String),
-    PaymentRefunds(String),
-    RefundCounter,
+    Payment(String),                 // payment_id -> PaymentCharge
+    PaymentCounter,                  // u64 counter for generating payment IDs
+    Refund(String),                  // refund_id -> Refund
+    PaymentRefunds(String),          // payment_id -> Vec<refund_id>
+    RefundCounter,                   // u64 counter for generating refund IDs
+    MerchantRegistry,                // MerchantRegistry contract address
+    MerchantPayments(Address),       // merchant_id -> Vec<payment_id>
+    PaymentsByStatus(PaymentStatus), // status -> Vec<payment_id>
+    AllowedToken(Symbol),            // currency -> token contract address
+    SupportedCurrencies,             // Vec<Symbol> of whitelisted currencies
+    RefundManager,                   // RefundManager contract allowed to book refund debits
+    IdempotencyKey(Address, String), // (merchant, idempotency key) -> payment_id
+    AcceptedCurrencies(Address),     // merchant_id -> Vec<Symbol>; absent accepts all
+    OracleNonce(Address),            // oracle -> last nonce accepted from it
+    LastOracleActivity,              // u64 timestamp of the latest accepted oracle submission
+    Initialized,                     // bool in instance storage, set by the one-time setup
+    OriginalExpiry(String),          // payment_id -> expires_at before its first extension
+    MaxExpiryExtension, // u64 seconds a charge may be extended past its original expiry
+    RefundReasonStats(RefundReason), // reason -> RefundReasonStats of refunds requested
+    TxHashIndex(BytesN<32>), // transaction hash -> payment_id it was verified against
+    PayerPayments(Address), // payer -> Vec<payment_id> confirmed from them
 }
 
 #[contractimpl]
-impl RefundManager {
-    pub fn initialize(env: Env, admin: Address) {
-        AccessControl::initialize(&env, admin);
+impl PaymentProcessor {
+    /// Set up the contract once: its admin, the MerchantRegistry used to gate payments, the
+    /// accepted tokens by currency and, optionally, the platform fee
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        merchant_registry: Address,
+        tokens: Map<Symbol, Address>,
+        fee_config: Option<FeeConfig>,
+    ) -> Result<(), Error> {
+        Self::init(&env, admin, merchant_registry, tokens, fee_config)
+    }
+
+    /// Get the MerchantRegistry contract address
+    pub fn get_merchant_registry(env: Env) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::MerchantRegistry)
     }
 
+    /// Grant `role` to `account` (holders of the role's admin role)
     pub fn grant_role(
         env: Env,
-        admin: Address,
+        caller: Address,
         role: Symbol,
         account: Address,
     ) -> Result<(), Error> {
-        AccessControl::grant_role(&env, admin, role, account).map_err(|_| Error::AccessControlError)
+        AccessControl::grant_role(&env, caller, role, account).map_err(Error::from)
     }
 
+    /// Revoke `role` from `account` (holders of the role's admin role)
     pub fn revoke_role(
         env: Env,
-        admin: Address,
+        caller: Address,
         role: Symbol,
         account: Address,
     ) -> Result<(), Error> {
-        AccessControl::revoke_role(&env, admin, role, account)
-            .map_err(|_| Error::AccessControlError)
+        AccessControl::revoke_role(&env, caller, role, account).map_err(Error::from)
     }
 
     pub fn has_role(env: Env, role: Symbol, account: Address) -> bool {
         AccessControl::has_role(&env, &role, &account)
     }
 
-    pub fn renounce_role(env: Env, account: Address, role: Symbol) -> Result<(), Error> {
-        AccessControl::renounce_role(&env, account, role).map_err(|_| Error::AccessControlError)
+    /// Grant a role that lapses at `expires_at`, for automatic key rotation (holders of the
+    /// role's admin role)
+    pub fn grant_role_until(
+        env: Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+        expires_at: u64,
+    ) -> Result<(), Error> {
+        AccessControl::grant_role_until(&env, caller, role, account, expires_at)
+            .map_err(Error::from)
+    }
+
+    pub fn get_role_expiry(env: Env, role: Symbol, account: Address) -> Option<u64> {
+        AccessControl::get_role_expiry(&env, &role, &account)
+    }
+
+    /// Clean up to `limit` lapsed role grants from storage
+    pub fn prune_expired_roles(env: Env, limit: u32) -> u32 {
+        AccessControl::prune_expired_roles(&env, limit)
+    }
+
+    /// Cap how much any holder of `role` may move per transaction and per day (admin only)
+    pub fn set_role_spend_limit(
+        env: Env,
+        admin: Address,
+        role: Symbol,
+        limit: SpendLimit,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        SpendGuard::set_role_limit(&env, role, limit)
     }
 
-    pub fn transfer_admin(
+    /// Cap a single operator, overriding the limit of its role (admin only)
+    pub fn set_account_spend_limit(
         env: Env,
-        current_admin: Address,
-        new_admin: Address,
+        admin: Address,
+        account: Address,
+        limit: SpendLimit,
     ) -> Result<(), Error> {
-        AccessControl::transfer_admin(&env, current_admin, new_admin)
-            .map_err(|_| Error::AccessControlError)
+        Self::require_admin(&env, &admin)?;
+        SpendGuard::set_account_limit(&env, account, limit)
+    }
+
+    /// Limit enforced on `account` acting under `role`, if any
+    pub fn get_spend_limit(env: Env, account: Address, role: Symbol) -> Option<SpendLimit> {
+        SpendGuard::effective_limit(&env, &account, &role)
+    }
+
+    /// Amount `account` has spent today (the account itself, readers or admins)
+    pub fn get_daily_spend(env: Env, caller: Address, account: Address) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, Some(&account))?;
+        Ok(SpendGuard::get_daily_spend(&env, &account))
     }
 
     pub fn get_admin(env: Env) -> Option<Address> {
         AccessControl::get_admin(&env)
     }
 
-    pub fn create_refund(
+    /// Declare a new role with its description hash and administering role (admin only)
+    pub fn define_role(
         env: Env,
-        payment_id: String,
-        refund_amount: i128,
+        admin: Address,
+        role: Symbol,
+        description_hash: BytesN<32>,
+        admin_role: Symbol,
+    ) -> Result<(), Error> {
+        AccessControl::define_role(&env, admin, role, description_hash, admin_role)
+            .map_err(Error::from)
+    }
+
+    /// Hand administration of `role` to `admin_role`, whose holders then grant and revoke
+    /// it (admin only)
+    pub fn set_role_admin(
+        env: Env,
+        admin: Address,
+        role: Symbol,
+        admin_role: Symbol,
+    ) -> Result<(), Error> {
+        AccessControl::set_role_admin(&env, admin, role, admin_role).map_err(Error::from)
+    }
+
+    pub fn get_role_definition(env: Env, role: Symbol) -> Option<RoleDefinition> {
+        AccessControl::get_role_definition(&env, &role)
+    }
+
+    /// List the accounts holding `role`, `limit` at a time from `offset`
+    pub fn get_role_members(env: Env, role: Symbol, offset: u32, limit: u32) -> Vec<Address> {
+        AccessControl::get_role_members(&env, &role, offset, limit)
+    }
+
+    /// List the roles held by `account`, `limit` at a time from `offset`
+    pub fn get_account_roles(env: Env, account: Address, offset: u32, limit: u32) -> Vec<Symbol> {
+        AccessControl::get_account_roles(&env, &account, offset, limit)
+    }
+
+    /// Replace the contract-wide configuration in one call (admin only)
+    pub fn set_config(env: Env, admin: Address, config: Config) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Configs::set(&env, &config)?;
+        env.events().publish(
+            (
+                Symbol::new(&env, "CONTRACT"),
+                Symbol::new(&env, "CONFIGURED"),
+            ),
+            (admin, config),
+        );
+        Ok(())
+    }
+
+    pub fn get_config(env: Env) -> Config {
+        Configs::get(&env)
+    }
+
+    /// Halt payment and refund flows in an emergency (admin only)
+    pub fn pause(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_paused(&env, true);
+        env.events().publish(
+            (Symbol::new(&env, "CONTRACT"), Symbol::new(&env, "PAUSED")),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Resume normal operation after a pause (admin only)
+    pub fn unpause(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_paused(&env, false);
+        env.events().publish(
+            (Symbol::new(&env, "CONTRACT"), Symbol::new(&env, "UNPAUSED")),
+            admin,
+        );
+        Ok(())
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        Pausable::is_paused(&env)
+    }
+
+    /// Halt a single flow, leaving the others running (admin only)
+    pub fn pause_scope(env: Env, admin: Address, scope: PauseScope) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_scope_paused(&env, scope.clone(), true);
+        env.events().publish(
+            (
+                Symbol::new(&env, "SCOPE"),
+                Symbol::new(&env, "PAUSED"),
+                scope,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Resume a single paused flow (admin only)
+    pub fn unpause_scope(env: Env, admin: Address, scope: PauseScope) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_scope_paused(&env, scope.clone(), false);
+        env.events().publish(
+            (
+                Symbol::new(&env, "SCOPE"),
+                Symbol::new(&env, "UNPAUSED"),
+                scope,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    pub fn is_scope_paused(env: Env, scope: PauseScope) -> bool {
+        Pausable::is_scope_paused(&env, scope)
+    }
+
+    /// Stop new payments in and settlements of one currency, e.g. during a token incident
+    /// (admin only)
+    pub fn pause_currency(env: Env, admin: Address, currency: Symbol) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_currency_paused(&env, &currency, true);
+        env.events().publish(
+            (
+                Symbol::new(&env, "CURRENCY"),
+                Symbol::new(&env, "PAUSED"),
+                currency,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Resume a paused currency (admin only)
+    pub fn unpause_currency(env: Env, admin: Address, currency: Symbol) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_currency_paused(&env, &currency, false);
+        env.events().publish(
+            (
+                Symbol::new(&env, "CURRENCY"),
+                Symbol::new(&env, "UNPAUSED"),
+                currency,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Screen `address` out of verification and settlement, as payer or merchant
+    /// (compliance role)
+    pub fn block_address(
+        env: Env,
+        officer: Address,
+        address: Address,
         reason: String,
-        requester: Address,
-    ) -> Result<String, Error> {
-        if refund_amount <= 0 {
-            return Err(Error::InvalidAmount);
+    ) -> Result<BlockRecord, Error> {
+        Self::require_compliance(&env, &officer)?;
+        let record = Compliance::block(&env, &address, reason, &officer);
+        AuditLog::append(&env, &officer, "ADDRESS_BLOCKED", address.to_string());
+        env.events().publish(
+            (
+                Symbol::new(&env, "COMPLIANCE"),
+                Symbol::new(&env, "BLOCKED"),
+            ),
+            (address, officer),
+        );
+        Ok(record)
+    }
+
+    /// Lift a block (compliance role)
+    pub fn unblock_address(env: Env, officer: Address, address: Address) -> Result<(), Error> {
+        Self::require_compliance(&env, &officer)?;
+        Compliance::unblock(&env, &address)?;
+        AuditLog::append(&env, &officer, "ADDRESS_UNBLOCKED", address.to_string());
+        env.events().publish(
+            (
+                Symbol::new(&env, "COMPLIANCE"),
+                Symbol::new(&env, "UNBLOCKED"),
+            ),
+            (address, officer),
+        );
+        Ok(())
+    }
+
+    pub fn is_address_blocked(env: Env, address: Address) -> bool {
+        Compliance::is_blocked(&env, &address)
+    }
+
+    pub fn get_address_block(env: Env, address: Address) -> Option<BlockRecord> {
+        Compliance::get_block(&env, &address)
+    }
+
+    /// Pauses, oracle liveness and settlement backlog in one read
+    pub fn get_platform_status(env: Env) -> PlatformStatus {
+        let paused = Pausable::is_paused(&env);
+        let paused_scopes = Pausable::paused_scopes(&env);
+        let paused_currencies = Pausable::paused_currencies(&env);
+        let pending_payments = Self::get_status_index(&env, &PaymentStatus::Pending).len();
+        let last_oracle_activity: Option<u64> =
+            env.storage().persistent().get(&DataKey::LastOracleActivity);
+        let oracle_healthy = pending_payments == 0
+            || last_oracle_activity.is_some_and(|last| {
+                env.ledger().timestamp() <= last.saturating_add(ORACLE_HEALTH_WINDOW)
+            });
+
+        PlatformStatus {
+            delayed: paused
+                || !paused_scopes.is_empty()
+                || !paused_currencies.is_empty()
+                || !oracle_healthy,
+            paused,
+            paused_scopes,
+            paused_currencies,
+            last_oracle_activity,
+            oracle_healthy,
+            pending_payments,
+            settlement_backlog: Self::get_status_index(&env, &PaymentStatus::Confirmed).len(),
+            queued_settlements: AutoSettle::get_queue(&env).len(),
         }
+    }
 
-        let counter = Self::get_next_refund_id(&env);
-        let refund_id = match counter {
-            1 => String::from_str(&env, "refund_1"),
-            2 => String::from_str(&env, "refund_2"),
-            3 => String::from_str(&env, "refund_3"),
-            4 => String::from_str(&env, "refund_4"),
-            5 => String::from_str(&env, "refund_5"),
-            6 => String::from_str(&env, "refund_6"),
-            7 => String::from_str(&env, "refund_7"),
-            8 => String::from_str(&env, "refund_8"),
-            9 => String::from_str(&env, "refund_9"),
-            10 => String::from_str(&env, "refund_10"),
-            _ => String::from_str(&env, "refund_n"),
-        };
+    /// Describe this deployment: kind, version, linked contracts, features and currencies
+    pub fn get_contract_info(env: Env) -> ContractInfo {
+        let mut linked_contracts: Map<Symbol, Address> = Map::new(&env);
+        if let Some(registry) = env.storage().persistent().get(&DataKey::MerchantRegistry) {
+            linked_contracts.set(Symbol::new(&env, "MERCHANT_REGISTRY"), registry);
+        }
+        if let Some(refund_manager) = env.storage().persistent().get(&DataKey::RefundManager) {
+            linked_contracts.set(Symbol::new(&env, "REFUND_MANAGER"), refund_manager);
+        }
 
-        let refund = Refund {
-            refund_id: refund_id.clone(),
-    Payment(String),     // payment_id -> PaymentCharge
-    PaymentCounter,      // u64 counter for generating payment IDs
-}
+        ContractInfo {
+            kind: Symbol::new(&env, "PAYMENT_PROCESSOR"),
+            version: String::from_str(&env, env!("CARGO_PKG_VERSION")),
+            linked_contracts,
+            features: Features::enabled(&env),
+            currencies: Self::get_supported_currencies(&env),
+            paused: Pausable::is_paused(&env),
+            paused_scopes: Pausable::paused_scopes(&env),
+        }
+    }
 
-#[contractimpl]
-impl PaymentProcessor {
-    /// Create a new payment
+    /// Create a new payment; retrying with the same `idempotency_key` returns the
+    /// charge created by the first call instead of failing. An `expires_at` of 0 takes the
+    /// configured default expiry
+    #[allow(clippy::too_many_arguments)]
     pub fn create_payment(
         env: Env,
         payment_id: String,
@@ -147,185 +856,3777 @@ impl PaymentProcessor {
         currency: Symbol,
         deposit_address: Address,
         expires_at: u64,
+        order_reference: String,
+        idempotency_key: Option<String>,
+        metadata: Option<Map<Symbol, String>>,
+        expected_payer: Option<Address>,
+        promo_code: Option<BytesN<32>>,
     ) -> Result<PaymentCharge, Error> {
-        // Validate input
-        if amount <= 0 {
-            return Err(Error::InvalidAmount);
+        merchant_id.require_auth();
+        validate_external_id(&payment_id)?;
+        let key = idempotency_key.map(|key| DataKey::IdempotencyKey(merchant_id.clone(), key));
+        if let Some(key) = &key {
+            if let Some(existing_id) = env.storage().persistent().get::<_, String>(key) {
+                let existing = Self::get_payment_internal(&env, &existing_id)?;
+                let redemption = Promos::get_redemption(&env, &existing_id);
+                let discount = redemption.as_ref().map_or(0, |r| r.discount_amount);
+                // A reused key must describe the same charge
+                if existing.amount + discount != amount
+                    || existing.currency != currency
+                    || existing.order_reference != order_reference
+                    || existing.expected_payer != expected_payer
+                    || redemption.map(|r| r.code_hash) != promo_code
+                {
+                    return Err(Error::IdempotencyConflict);
+                }
+                return Ok(existing);
+            }
         }
 
-        // Check if payment already exists
-        if env.storage().persistent().has(&DataKey::Payment(payment_id.clone())) {
-            return Err(Error::PaymentAlreadyExists);
+        let expires_at = Self::resolve_expiry(&env, expires_at)?;
+        let redemption = match &promo_code {
+            Some(code_hash) => Some(Promos::quote(&env, code_hash, amount)?),
+            None => None,
+        };
+        let mut payment = Self::create_payment_internal(
+            &env,
+            &merchant_id.clone(),
+            payment_id,
+            merchant_id,
+            amount - redemption.as_ref().map_or(0, |r| r.discount_amount),
+            currency,
+            deposit_address,
+            expires_at,
+            order_reference,
+            metadata.unwrap_or_else(|| Map::new(&env)),
+        )?;
+        if expected_payer.is_some() {
+            payment.expected_payer = expected_payer;
+            env.storage()
+                .persistent()
+                .set(&DataKey::Payment(payment.payment_id.clone()), &payment);
         }
-
-        // Validate payment_id is not empty
-        if payment_id.is_empty() {
-            return Err(Error::InvalidPaymentId);
+        if let Some(redemption) = redemption {
+            Promos::redeem(&env, &payment.payment_id, &redemption);
+            env.events().publish(
+                (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "PROMO")),
+                (
+                    payment.payment_id.clone(),
+                    redemption.code_hash,
+                    redemption.discount_amount,
+                ),
+            );
+        }
+        if let Some(key) = key {
+            env.storage().persistent().set(&key, &payment.payment_id);
         }
+        Ok(payment)
+    }
 
-        // Create payment struct
-        let payment = PaymentCharge {
-            payment_id: payment_id.clone(),
+    /// Create a new payment on the merchant's behalf (merchant or one of its delegates)
+    pub fn create_delegated_payment(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        deposit_address: Address,
+        expires_at: u64,
+    ) -> Result<PaymentCharge, Error> {
+        Self::require_merchant_or_delegate(&env, &merchant_id, &caller)?;
+        validate_external_id(&payment_id)?;
+        let expires_at = Self::resolve_expiry(&env, expires_at)?;
+        Self::create_payment_internal(
+            &env,
+            &caller,
+            payment_id,
             merchant_id,
             amount,
             currency,
             deposit_address,
-            status: PaymentStatus::Pending,
-            payer_address: None,
-            transaction_hash: None,
-            created_at: env.ledger().timestamp(),
-            confirmed_at: None,
             expires_at,
-        };
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )
+    }
 
-        // Store payment
+    /// Withdraw a pending charge before it is paid (merchant or one of its delegates)
+    pub fn cancel_pending_payment(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+    ) -> Result<(), Error> {
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+
+        Self::set_status(&env, &mut payment, PaymentStatus::Cancelled);
         env.storage()
             .persistent()
             .set(&DataKey::Payment(payment_id.clone()), &payment);
 
-        let mut payment_refunds = Self::get_payment_refunds_internal(&env, &payment_id);
-        payment_refunds.push_back(refund_id.clone());
-        env.storage()
-            .persistent()
-            .set(&DataKey::PaymentRefunds(payment_id), &payment_refunds);
-        // Emit payment created event
-        env.events().publish((Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "CREATED")), payment_id.clone());
+        AuditLog::append(&env, &caller, "CANCEL", payment_id);
+        events::payment(&env, "CANCELLED", &payment, Some(&caller));
 
-        Ok(payment)
+        Ok(())
     }
 
-    pub fn process_refund(env: Env, operator: Address, refund_id: String) -> Result<(), Error> {
-        let has_settlement =
-            AccessControl::has_role(&env, &role_settlement_operator(&env), &operator);
-        let has_oracle = AccessControl::has_role(&env, &role_oracle(&env), &operator);
-
-        if !has_settlement && !has_oracle {
-            return Err(Error::Unauthorized);
-        }
-
-        let mut refund = Self::get_refund_internal(&env, &refund_id)?;
-
-        if refund.status != RefundStatus::Pending {
-            return Err(Error::RefundAlreadyProcessed);
-        }
-
-        refund.status = RefundStatus::Completed;
-        refund.processed_at = Some(env.ledger().timestamp());
-
-    /// Verify payment after customer sends USDC
-    pub fn verify_payment(
+    /// Push back a pending charge's deadline for a slow payer, up to the max extension past
+    /// its original expiry (merchant only)
+    pub fn extend_payment_expiry(
         env: Env,
+        merchant_id: Address,
         payment_id: String,
-        transaction_hash: BytesN<32>,
-        payer_address: Address,
-        amount_received: i128,
-    ) -> Result<PaymentStatus, Error> {
-        // Get payment
+        new_expires_at: u64,
+    ) -> Result<PaymentCharge, Error> {
+        merchant_id.require_auth();
         let mut payment = Self::get_payment_internal(&env, &payment_id)?;
-
-        // Check if payment is still pending
+        if payment.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
         if payment.status != PaymentStatus::Pending {
             return Err(Error::PaymentAlreadyProcessed);
         }
-
-        // Check if payment has expired
-        if env.ledger().timestamp() > payment.expires_at {
+        if Clock::now(&env) > payment.expires_at {
             return Err(Error::PaymentExpired);
         }
-
-        // Verify amount matches (exact match for now)
-        if amount_received != payment.amount {
-            // Update status to failed
-            payment.status = PaymentStatus::Failed;
-            env.storage()
-                .persistent()
-                .set(&DataKey::Payment(payment_id.clone()), &payment);
-
-            // Emit payment failed event
-            env.events().publish((Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "FAILED")), payment_id.clone());
-
-            return Ok(PaymentStatus::Failed);
+        if new_expires_at <= payment.expires_at {
+            return Err(Error::InvalidTimeRange);
         }
 
-        // Update payment with verification details
-        payment.status = PaymentStatus::Confirmed;
-        payment.payer_address = Some(payer_address);
-        payment.transaction_hash = Some(transaction_hash);
-        payment.confirmed_at = Some(env.ledger().timestamp());
+        let original_key = DataKey::OriginalExpiry(payment_id.clone());
+        let original: u64 = env
+            .storage()
+            .persistent()
+            .get(&original_key)
+            .unwrap_or(payment.expires_at);
+        if new_expires_at - original > Self::get_max_expiry_extension(env.clone()) {
+            return Err(Error::ExtensionTooLong);
+        }
+        env.storage().persistent().set(&original_key, &original);
 
-        // Store updated payment
+        let previous = payment.expires_at;
+        payment.expires_at = new_expires_at;
         env.storage()
             .persistent()
             .set(&DataKey::Payment(payment_id.clone()), &payment);
 
-        // Emit payment verified event
-        env.events().publish((Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "VERIFIED")), payment_id.clone());
-
-        Ok(PaymentStatus::Confirmed)
-    }
-
-    pub fn get_refund(env: Env, refund_id: String) -> Result<Refund, Error> {
-        Self::get_refund_internal(&env, &refund_id)
+        env.events().publish(
+            (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "EXTENDED")),
+            (payment_id, previous, new_expires_at),
+        );
+        Ok(payment)
     }
 
-    pub fn get_payment_refunds(env: Env, payment_id: String) -> Result<Vec<Refund>, Error> {
-        let refund_ids = Self::get_payment_refunds_internal(&env, &payment_id);
-        let mut refunds = vec![&env];
-    /// Get payment details
-    pub fn get_payment(env: Env, payment_id: String) -> Result<PaymentCharge, Error> {
-        Self::get_payment_internal(&env, &payment_id)
+    /// Let a pending charge confirm overpayments, keeping the excess as a tip instead of
+    /// holding it for the payer; tips count toward the refundable amount (merchant only)
+    pub fn set_accept_overpayment(
+        env: Env,
+        merchant_id: Address,
+        payment_id: String,
+        accept: bool,
+    ) -> Result<PaymentCharge, Error> {
+        merchant_id.require_auth();
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        if payment.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+
+        payment.accept_overpayment = accept;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        env.events().publish(
+            (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "TIPS")),
+            (payment_id, accept),
+        );
+        Ok(payment)
     }
 
-    /// Cancel expired payment
-    pub fn cancel_payment(env: Env, payment_id: String) -> Result<(), Error> {
-        // Get payment
+    /// How far past its original expiry a merchant may extend a charge, in seconds (admin only)
+    pub fn set_max_expiry_extension(env: Env, admin: Address, extension: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        env.storage()
+            .persistent()
+            .set(&DataKey::MaxExpiryExtension, &extension);
+        Ok(())
+    }
+
+    pub fn get_max_expiry_extension(env: Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MaxExpiryExtension)
+            .unwrap_or(DEFAULT_MAX_EXPIRY_EXTENSION)
+    }
+
+    /// Throttle how many charges each merchant may create per window of ledgers (admin only)
+    pub fn set_rate_limit(
+        env: Env,
+        admin: Address,
+        max_payments: u32,
+        window_ledgers: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RateLimits::set(
+            &env,
+            RateLimit {
+                max_payments,
+                window_ledgers,
+            },
+        )
+    }
+
+    pub fn clear_rate_limit(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RateLimits::clear(&env);
+        Ok(())
+    }
+
+    pub fn get_rate_limit(env: Env) -> Option<RateLimit> {
+        RateLimits::get(&env)
+    }
+
+    /// Charges counted against the merchant in the current sliding window
+    pub fn get_payment_rate(env: Env, merchant_id: Address) -> u32 {
+        RateLimits::get_count(&env, &merchant_id)
+    }
+
+    /// Pull a pending charge's exact amount from its pinned payer under the allowance they gave
+    /// this contract and the mandate they gave the merchant, confirming it in the same call:
+    /// escrow charges go straight to the linked RefundManager, self-custody ones to the deposit
+    /// address (merchant or delegate)
+    pub fn collect_payment(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+    ) -> Result<PaymentCharge, Error> {
+        Pausable::require_not_paused(&env, PauseScope::Payments)?;
         let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+        if Clock::now(&env) > payment.expires_at {
+            return Err(Error::PaymentExpired);
+        }
+        // The merchant alone picks the pinned payer, so the payer's own mandate is what stops
+        // one merchant pulling under an allowance meant for another
+        let payer = payment
+            .expected_payer
+            .clone()
+            .ok_or(Error::PayerNotPinned)?;
+        Compliance::require_clear(&env, &[Some(&payment.merchant_id), Some(&payer)])?;
+        if Limits::get_mandate(&env, &payer, &payment.merchant_id) < payment.amount {
+            return Err(Error::MandateExceeded);
+        }
 
-        // Check if payment is pending
+        let destination = if payment.custody_mode == CustodyMode::Escrow {
+            env.storage()
+                .persistent()
+                .get(&DataKey::RefundManager)
+                .ok_or(Error::EscrowNotLinked)?
+        } else {
+            payment.deposit_address.clone()
+        };
+        let token = token::Client::new(&env, &Self::require_token(&env, &payment.currency)?);
+        let spender = env.current_contract_address();
+        if token.allowance(&payer, &spender) < payment.amount {
+            return Err(Error::InsufficientAllowance);
+        }
+        token.transfer_from(&spender, &payer, &destination, &payment.amount);
+        Limits::spend_mandate(&env, &payer, &payment.merchant_id, payment.amount)?;
+
+        Self::set_status(&env, &mut payment, PaymentStatus::Confirmed);
+        payment.payer_address = Some(payer);
+        payment.confirmed_at = Some(env.ledger().timestamp());
+        Self::book_confirmation(&env, &mut payment);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        AuditLog::append(&env, &caller, "COLLECT", payment_id);
+        events::payment(&env, "COLLECTED", &payment, Some(&caller));
+        Ok(payment)
+    }
+
+    /// Place a pending charge's amount on hold with the processor instead of paying it outright;
+    /// the merchant then captures or releases it within the capture window (payer)
+    pub fn authorize_payment(
+        env: Env,
+        payer: Address,
+        payment_id: String,
+    ) -> Result<Authorization, Error> {
+        Pausable::require_not_paused(&env, PauseScope::Payments)?;
+        payer.require_auth();
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
         if payment.status != PaymentStatus::Pending {
             return Err(Error::PaymentAlreadyProcessed);
         }
+        if Clock::now(&env) > payment.expires_at {
+            return Err(Error::PaymentExpired);
+        }
+        Compliance::require_clear(&env, &[Some(&payment.merchant_id), Some(&payer)])?;
+        if payment.expected_payer.is_some() && payment.expected_payer.as_ref() != Some(&payer) {
+            return Err(Error::WrongPayer);
+        }
 
-        // Check if payment has expired
-        if env.ledger().timestamp() <= payment.expires_at {
+        let token_address = Self::require_token(&env, &payment.currency)?;
+        token::Client::new(&env, &token_address).transfer(
+            &payer,
+            &env.current_contract_address(),
+            &payment.amount,
+        );
+        let authorization = Authorizations::open(&env, &payment_id, &payer, payment.amount);
+
+        payment.payer_address = Some(payer.clone());
+        Self::set_status(&env, &mut payment, PaymentStatus::Authorized);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        AuditLog::append(&env, &payer, "AUTHORIZE", payment_id);
+        events::payment(&env, "AUTHORIZED", &payment, Some(&payer));
+        Ok(authorization)
+    }
+
+    /// Capture all or part of an authorization: `amount` goes to the charge's deposit address as
+    /// a confirmed payment and the rest of the hold back to the payer (merchant or delegate)
+    pub fn capture_payment(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+        amount: i128,
+    ) -> Result<PaymentCharge, Error> {
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Authorized {
+            return Err(Error::PaymentNotAuthorized);
+        }
+        let authorization = Authorizations::get(&env, &payment_id)?;
+        if Clock::now(&env) > authorization.capture_deadline {
+            return Err(Error::AuthorizationExpired);
+        }
+        if amount <= 0 || amount > authorization.amount {
+            return Err(Error::InvalidAmount);
+        }
+        // Either side may have been blocked while the funds were on hold
+        Compliance::require_clear(
+            &env,
+            &[Some(&payment.merchant_id), Some(&authorization.payer)],
+        )?;
+
+        let token = token::Client::new(&env, &Self::require_token(&env, &payment.currency)?);
+        token.transfer(
+            &env.current_contract_address(),
+            &payment.deposit_address,
+            &amount,
+        );
+        if amount < authorization.amount {
+            token.transfer(
+                &env.current_contract_address(),
+                &authorization.payer,
+                &(authorization.amount - amount),
+            );
+        }
+        Authorizations::close(&env, &payment_id);
+
+        payment.amount = amount;
+        Self::set_status(&env, &mut payment, PaymentStatus::Confirmed);
+        payment.confirmed_at = Some(env.ledger().timestamp());
+        Self::book_confirmation(&env, &mut payment);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        AuditLog::append(&env, &caller, "CAPTURE", payment_id);
+        events::payment(&env, "CAPTURED", &payment, Some(&caller));
+        Ok(payment)
+    }
+
+    /// Return an uncaptured authorization to the payer and cancel the charge (merchant or delegate)
+    pub fn release_authorization(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+    ) -> Result<i128, Error> {
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Authorized {
+            return Err(Error::PaymentNotAuthorized);
+        }
+        let released = Self::return_authorization(&env, &mut payment, PaymentStatus::Cancelled)?;
+
+        AuditLog::append(&env, &caller, "RELEASE", payment_id);
+        events::payment(&env, "RELEASED", &payment, Some(&caller));
+        Ok(released)
+    }
+
+    /// Sweep the first `limit` authorizations, returning to their payers those left uncaptured
+    /// past the capture window; returns the number expired (anyone)
+    pub fn expire_authorizations(env: Env, limit: u32) -> u32 {
+        // Snapshot the index, since expiring authorizations removes them from it
+        let authorized = Self::get_status_index(&env, &PaymentStatus::Authorized);
+        let end = limit.min(authorized.len());
+        let now = Clock::now(&env);
+
+        let mut expired = 0;
+        for i in 0..end {
+            if let Some(payment_id) = authorized.get(i) {
+                if Self::try_expire_authorization(&env, &payment_id, now) {
+                    expired += 1;
+                }
+            }
+        }
+        expired
+    }
+
+    pub fn get_authorization(env: Env, payment_id: String) -> Result<Authorization, Error> {
+        Authorizations::get(&env, &payment_id)
+    }
+
+    /// How long merchants have to capture an authorization, in seconds (admin only)
+    pub fn set_capture_window(env: Env, admin: Address, window: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Authorizations::set_capture_window(&env, window)
+    }
+
+    pub fn get_capture_window(env: Env) -> u64 {
+        Authorizations::get_capture_window(&env)
+    }
+
+    /// Open a marketplace checkout: one deposit address, with a child charge per merchant leg
+    /// (signed by every merchant with a leg in it)
+    pub fn create_cart(
+        env: Env,
+        currency: Symbol,
+        deposit_address: Address,
+        legs: Vec<CartLeg>,
+        expires_at: u64,
+    ) -> Result<Cart, Error> {
+        let expires_at = Self::resolve_expiry(&env, expires_at)?;
+        let total = Carts::validate(&legs)?;
+        // Each merchant signs once, however many legs it has in the cart
+        let mut signers: Vec<Address> = vec![&env];
+        for leg in legs.iter() {
+            if !signers.contains(&leg.merchant_id) {
+                leg.merchant_id.require_auth();
+                signers.push_back(leg.merchant_id);
+            }
+        }
+        let counter = Carts::next_id(&env);
+        let cart_id = IdBuilder::new(CART_PREFIX).push_u64(counter).build(&env);
+        // The cart, not its legs, holds the deposit address while Pending
+        DepositPool::activate(&env, &deposit_address, &cart_id)?;
+
+        let mut payment_ids = vec![&env];
+        for (i, leg) in legs.iter().enumerate() {
+            let payment_id = Carts::leg_payment_id(&env, counter, i as u32);
+            let merchant = Self::validate_new_payment(
+                &env,
+                &payment_id,
+                &leg.merchant_id,
+                leg.amount,
+                &currency,
+            )?;
+            Self::insert_payment(
+                &env,
+                payment_id.clone(),
+                &merchant,
+                leg.amount,
+                currency.clone(),
+                deposit_address.clone(),
+                expires_at,
+                cart_id.clone(),
+                Map::new(&env),
+                None,
+            );
+            payment_ids.push_back(payment_id);
+        }
+
+        let cart = Cart {
+            cart_id: cart_id.clone(),
+            currency,
+            deposit_address,
+            payment_ids,
+            total,
+            status: CartStatus::Pending,
+            created_at: env.ledger().timestamp(),
+            expires_at,
+        };
+        Carts::save(&env, &cart);
+
+        env.events().publish(
+            (Symbol::new(&env, "CART"), Symbol::new(&env, "CREATED")),
+            (cart_id, total),
+        );
+
+        Ok(cart)
+    }
+
+    /// Verify the single transfer paying a cart, splitting it across the legs in proportion
+    /// to their amounts; each leg is then matched under its own merchant's rules (oracle only)
+    pub fn verify_cart(
+        env: Env,
+        oracle: Address,
+        cart_id: String,
+        transaction_hash: BytesN<32>,
+        payer_address: Address,
+        amount_received: i128,
+        nonce: u64,
+    ) -> Result<CartStatus, Error> {
+        Self::require_verifier(&env, &oracle, nonce)?;
+        let mut cart = Carts::get(&env, &cart_id)?;
+        if cart.status != CartStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+
+        let mut amounts = vec![&env];
+        for payment_id in cart.payment_ids.iter() {
+            amounts.push_back(Self::get_payment_internal(&env, &payment_id)?.amount);
+        }
+        let shares = Carts::allocate(&env, &amounts, cart.total, amount_received);
+
+        let mut confirmed = 0;
+        for (payment_id, share) in cart.payment_ids.iter().zip(shares.iter()) {
+            let status = Self::apply_verification(
+                env.clone(),
+                oracle.clone(),
+                payment_id,
+                transaction_hash.clone(),
+                Some(payer_address.clone()),
+                None,
+                share,
+            )?;
+            if status == PaymentStatus::Confirmed {
+                confirmed += 1;
+            }
+        }
+
+        cart.status = if confirmed == cart.payment_ids.len() {
+            CartStatus::Paid
+        } else if confirmed == 0 {
+            CartStatus::Failed
+        } else {
+            CartStatus::PartiallyPaid
+        };
+        DepositPool::release(&env, &cart.deposit_address, &cart_id);
+        Carts::save(&env, &cart);
+
+        env.events().publish(
+            (Symbol::new(&env, "CART"), Symbol::new(&env, "VERIFIED")),
+            (cart_id, cart.status.clone()),
+        );
+
+        Ok(cart.status)
+    }
+
+    /// Expire an unpaid cart and its legs once its deadline has passed
+    pub fn expire_cart(env: Env, cart_id: String) -> Result<(), Error> {
+        let mut cart = Carts::get(&env, &cart_id)?;
+        if cart.status != CartStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+        if Clock::now(&env) <= cart.expires_at {
             return Err(Error::Unauthorized); // Not expired yet
         }
 
-    fn get_next_refund_id(env: &Env) -> u64 {
-        let mut counter: u64 = env
-            .storage()
+        for payment_id in cart.payment_ids.iter() {
+            let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+            if payment.status == PaymentStatus::Pending {
+                Self::set_status(&env, &mut payment, PaymentStatus::Expired);
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::Payment(payment_id), &payment);
+            }
+        }
+        cart.status = CartStatus::Expired;
+        DepositPool::release(&env, &cart.deposit_address, &cart_id);
+        Carts::save(&env, &cart);
+
+        env.events().publish(
+            (Symbol::new(&env, "CART"), Symbol::new(&env, "EXPIRED")),
+            cart_id,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_cart(env: Env, cart_id: String) -> Result<Cart, Error> {
+        Carts::get(&env, &cart_id)
+    }
+
+    /// Accept payments in `currency`, settled through the `token` contract (admin only)
+    pub fn add_supported_token(
+        env: Env,
+        admin: Address,
+        currency: Symbol,
+        token: Address,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Self::store_supported_token(&env, currency, token);
+        Ok(())
+    }
+
+    /// Stop accepting new payments in `currency`; existing charges are unaffected (admin only)
+    pub fn remove_supported_token(env: Env, admin: Address, currency: Symbol) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+
+        let key = DataKey::AllowedToken(currency.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::UnsupportedCurrency);
+        }
+        env.storage().persistent().remove(&key);
+
+        let mut currencies = Self::get_supported_currencies(&env);
+        if let Some(i) = currencies.first_index_of(&currency) {
+            currencies.remove(i);
+            env.storage()
+                .persistent()
+                .set(&DataKey::SupportedCurrencies, &currencies);
+        }
+
+        env.events().publish(
+            (Symbol::new(&env, "TOKEN"), Symbol::new(&env, "REMOVED")),
+            currency,
+        );
+        Ok(())
+    }
+
+    pub fn list_supported_tokens(env: Env) -> Vec<SupportedToken> {
+        let mut tokens = vec![&env];
+        for currency in Self::get_supported_currencies(&env).iter() {
+            if let Some(token) = env
+                .storage()
+                .persistent()
+                .get(&DataKey::AllowedToken(currency.clone()))
+            {
+                tokens.push_back(SupportedToken { currency, token });
+            }
+        }
+        tokens
+    }
+
+    /// Token contract that settles `currency`, if it is accepted
+    pub fn get_supported_token(env: Env, currency: Symbol) -> Option<Address> {
+        env.storage()
             .persistent()
-            .get(&DataKey::RefundCounter)
-            .unwrap_or(0);
-        counter += 1;
+            .get(&DataKey::AllowedToken(currency))
+    }
+
+    /// Limit the merchant's charges to a subset of the supported currencies; an empty list
+    /// accepts every supported currency again (merchant only)
+    pub fn set_accepted_currencies(
+        env: Env,
+        merchant_id: Address,
+        currencies: Vec<Symbol>,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        let key = DataKey::AcceptedCurrencies(merchant_id.clone());
+        if currencies.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            for currency in currencies.iter() {
+                if !env
+                    .storage()
+                    .persistent()
+                    .has(&DataKey::AllowedToken(currency))
+                {
+                    return Err(Error::UnsupportedCurrency);
+                }
+            }
+            env.storage().persistent().set(&key, &currencies);
+        }
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MERCHANT"),
+                Symbol::new(&env, "CURRENCIES"),
+            ),
+            (merchant_id, currencies),
+        );
+        Ok(())
+    }
+
+    /// Currencies the merchant opted into; `None` when it accepts every supported currency
+    pub fn get_accepted_currencies(env: Env, merchant_id: Address) -> Option<Vec<Symbol>> {
         env.storage()
             .persistent()
-            .set(&DataKey::RefundCounter, &counter);
-        counter
+            .get(&DataKey::AcceptedCurrencies(merchant_id))
     }
-        // Update status to expired
-        payment.status = PaymentStatus::Expired;
 
-        // Store updated payment
+    /// Link the RefundManager whose escrow refunds are booked against merchants (admin only)
+    pub fn set_refund_manager(
+        env: Env,
+        admin: Address,
+        refund_manager: Address,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
         env.storage()
             .persistent()
-            .set(&DataKey::Payment(payment_id.clone()), &payment);
+            .set(&DataKey::RefundManager, &refund_manager);
+        Ok(())
+    }
 
-        // Emit payment cancelled event
-        env.events().publish((Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "CANCELLED")), payment_id.clone());
+    /// Debit a refund paid from escrow from the merchant's pending balance (linked RefundManager)
+    pub fn record_refund_debit(env: Env, payment_id: String, amount: i128) -> Result<(), Error> {
+        let refund_manager: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RefundManager)
+            .ok_or(Error::Unauthorized)?;
+        refund_manager.require_auth();
+
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        if payment.refunded_amount + amount > payment.amount {
+            return Err(Error::RefundExceedsPayment);
+        }
+        payment.refunded_amount += amount;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id), &payment);
 
+        Statements::debit_refund(&env, &payment.merchant_id, &payment.currency, amount);
+        EscrowLedger::debit_merchant(&env, &payment.merchant_id, &payment.currency, amount);
+        SettlementReports::record_refund(&env, &payment.merchant_id, &payment.currency, amount);
+        AutoSettle::remove(&env, &payment.merchant_id, &payment.currency, amount);
         Ok(())
     }
 
-    // Helper functions
-    fn get_payment_internal(env: &Env, payment_id: &String) -> Result<PaymentCharge, Error> {
+    /// Mark a payment's overpayment as returned to the payer (linked RefundManager)
+    pub fn clear_overpayment(env: Env, payment_id: String) -> Result<i128, Error> {
+        let refund_manager: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RefundManager)
+            .ok_or(Error::Unauthorized)?;
+        refund_manager.require_auth();
+
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        let overpaid = payment.overpaid_amount;
+        if overpaid == 0 {
+            return Err(Error::NoOverpayment);
+        }
+        payment.overpaid_amount = 0;
         env.storage()
             .persistent()
-            .get(&DataKey::Payment(payment_id.clone()))
-            .ok_or(Error::PaymentNotFound)
+            .set(&DataKey::Payment(payment_id), &payment);
+        EscrowLedger::debit(&env, &payment.currency, overpaid);
+        Ok(overpaid)
+    }
+
+    /// Replace a pending payment's metadata (merchant or one of its delegates)
+    pub fn set_payment_metadata(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+        metadata: Map<Symbol, String>,
+    ) -> Result<(), Error> {
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+        Self::validate_metadata(&env, &metadata)?;
+
+        payment.metadata = metadata;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id), &payment);
+        Ok(())
+    }
+
+    /// Create a new payment using the next address from the merchant's deposit pool
+    pub fn create_payment_from_pool(
+        env: Env,
+        payment_id: String,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        expires_at: u64,
+    ) -> Result<PaymentCharge, Error> {
+        merchant_id.require_auth();
+        // Validate before consuming an address from the pool
+        validate_external_id(&payment_id)?;
+        let expires_at = Self::resolve_expiry(&env, expires_at)?;
+        Self::validate_new_payment(&env, &payment_id, &merchant_id, amount, &currency)?;
+
+        let deposit_address = DepositPool::next_address(&env, &merchant_id)?;
+        Self::create_payment_internal(
+            &env,
+            &merchant_id.clone(),
+            payment_id,
+            merchant_id,
+            amount,
+            currency,
+            deposit_address,
+            expires_at,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )
+    }
+
+    /// Add a deposit address to the merchant's rotation pool
+    pub fn add_deposit_address(
+        env: Env,
+        merchant_id: Address,
+        deposit_address: Address,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        DepositPool::add_address(&env, &merchant_id, deposit_address)
     }
-}
 
+    /// Remove (decommission) a deposit address from the merchant's rotation pool
+    pub fn remove_deposit_address(
+        env: Env,
+        merchant_id: Address,
+        deposit_address: Address,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        DepositPool::remove_address(&env, &merchant_id, &deposit_address)
+    }
+
+    /// Set how the merchant's deposit pool hands out addresses
+    pub fn set_deposit_rotation(env: Env, merchant_id: Address, policy: RotationPolicy) {
+        merchant_id.require_auth();
+        DepositPool::set_policy(&env, &merchant_id, policy);
+    }
+
+    /// Get the merchant's deposit address pool
+    pub fn get_deposit_pool(env: Env, merchant_id: Address) -> DepositAddressPool {
+        DepositPool::get(&env, &merchant_id)
+    }
+
+    /// Pending charge currently using a deposit address, if any
+    pub fn get_active_deposit(env: Env, deposit_address: Address) -> Option<String> {
+        DepositPool::active_payment(&env, &deposit_address)
+    }
+
+    /// Verify payment after customer sends USDC (oracle only)
+    pub fn verify_payment(
+        env: Env,
+        oracle: Address,
+        payment_id: String,
+        transaction_hash: BytesN<32>,
+        payer_address: Address,
+        amount_received: i128,
+        nonce: u64,
+    ) -> Result<PaymentStatus, Error> {
+        Self::record_verification(
+            env,
+            oracle,
+            payment_id,
+            transaction_hash,
+            Some(payer_address),
+            None,
+            amount_received,
+            nonce,
+        )
+    }
+
+    /// Verify payment in privacy mode, storing only a commitment to the payer (oracle only)
+    pub fn verify_payment_private(
+        env: Env,
+        oracle: Address,
+        payment_id: String,
+        transaction_hash: BytesN<32>,
+        payer_commitment: BytesN<32>,
+        amount_received: i128,
+        nonce: u64,
+    ) -> Result<PaymentStatus, Error> {
+        Features::require(&env, &feature_private_payments(&env))?;
+        Self::record_verification(
+            env,
+            oracle,
+            payment_id,
+            transaction_hash,
+            None,
+            Some(payer_commitment),
+            amount_received,
+            nonce,
+        )
+    }
+
+    /// Reveal the payer of a privacy-mode payment so refunds and disputes can be raised
+    pub fn reveal_payer(
+        env: Env,
+        payment_id: String,
+        payer: Address,
+        salt: BytesN<32>,
+    ) -> Result<(), Error> {
+        payer.require_auth();
+
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+        let commitment = payment
+            .payer_commitment
+            .clone()
+            .ok_or(Error::PaymentNotPrivate)?;
+
+        if !privacy::matches_commitment(&env, &commitment, &payer, &salt) {
+            return Err(Error::CommitmentMismatch);
+        }
+
+        payment.payer_address = Some(payer.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        env.events().publish(
+            (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "REVEALED")),
+            (payment_id, payer),
+        );
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_verification(
+        env: Env,
+        oracle: Address,
+        payment_id: String,
+        transaction_hash: BytesN<32>,
+        payer_address: Option<Address>,
+        payer_commitment: Option<BytesN<32>>,
+        amount_received: i128,
+        nonce: u64,
+    ) -> Result<PaymentStatus, Error> {
+        Self::require_verifier(&env, &oracle, nonce)?;
+        let status = Self::apply_verification(
+            env.clone(),
+            oracle.clone(),
+            payment_id.clone(),
+            transaction_hash,
+            payer_address,
+            payer_commitment,
+            amount_received,
+        )?;
+        AuditLog::append(&env, &oracle, "VERIFY", payment_id);
+        Ok(status)
+    }
+
+    /// Register the ed25519 key an oracle signs attestations with (admin only)
+    pub fn register_oracle_key(
+        env: Env,
+        admin: Address,
+        oracle: Address,
+        public_key: BytesN<32>,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Attestations::register_key(&env, &public_key, &oracle);
+
+        env.events().publish(
+            (Symbol::new(&env, "ORACLE"), Symbol::new(&env, "KEY_ADDED")),
+            (oracle, public_key),
+        );
+        Ok(())
+    }
+
+    /// Stop accepting attestations signed with `public_key` (admin only)
+    pub fn remove_oracle_key(
+        env: Env,
+        admin: Address,
+        public_key: BytesN<32>,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Attestations::remove_key(&env, &public_key)?;
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "ORACLE"),
+                Symbol::new(&env, "KEY_REMOVED"),
+            ),
+            public_key,
+        );
+        Ok(())
+    }
+
+    pub fn get_oracle_key_owner(env: Env, public_key: BytesN<32>) -> Option<Address> {
+        Attestations::get_oracle(&env, &public_key)
+    }
+
+    /// Verify payment from an oracle's ed25519 signature over
+    /// (payment_id, transaction_hash, amount_received, payer_address, nonce); anyone may
+    /// relay it
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_payment_attested(
+        env: Env,
+        payment_id: String,
+        transaction_hash: BytesN<32>,
+        payer_address: Address,
+        amount_received: i128,
+        nonce: u64,
+        public_key: BytesN<32>,
+        signature: BytesN<64>,
+    ) -> Result<PaymentStatus, Error> {
+        Pausable::require_not_paused(&env, PauseScope::Payments)?;
+        let message = Attestations::message(
+            &env,
+            &payment_id,
+            &transaction_hash,
+            amount_received,
+            &payer_address,
+            nonce,
+        );
+        let oracle = Attestations::verify(&env, &public_key, &message, &signature)?;
+        // The key only speaks for the oracle while it still holds the role
+        AccessControl::require_role(&env, &role_oracle(&env), &oracle)?;
+        Self::use_oracle_nonce(&env, &oracle, nonce)?;
+
+        let status = Self::apply_verification(
+            env.clone(),
+            oracle.clone(),
+            payment_id.clone(),
+            transaction_hash,
+            Some(payer_address),
+            None,
+            amount_received,
+        )?;
+        AuditLog::append(&env, &oracle, "VERIFY", payment_id);
+        Ok(status)
+    }
+
+    fn require_verifier(env: &Env, oracle: &Address, nonce: u64) -> Result<(), Error> {
+        Pausable::require_not_paused(env, PauseScope::Payments)?;
+        oracle.require_auth();
+        AccessControl::require_role(env, &role_oracle(env), oracle)?;
+        Self::use_oracle_nonce(env, oracle, nonce)
+    }
+
+    // Oracle submissions carry a nonce above the last one accepted, so a captured
+    // confirmation cannot be replayed (e.g. after a payment id is recreated)
+    fn use_oracle_nonce(env: &Env, oracle: &Address, nonce: u64) -> Result<(), Error> {
+        let key = DataKey::OracleNonce(oracle.clone());
+        let last: u64 = env.storage().persistent().get(&key).unwrap_or(0);
+        if nonce <= last {
+            return Err(Error::InvalidNonce);
+        }
+        env.storage().persistent().set(&key, &nonce);
+        env.storage()
+            .persistent()
+            .set(&DataKey::LastOracleActivity, &env.ledger().timestamp());
+        Ok(())
+    }
+
+    /// Last nonce accepted from `oracle`; its next submission must use a higher one
+    pub fn get_oracle_nonce(env: Env, oracle: Address) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OracleNonce(oracle))
+            .unwrap_or(0)
+    }
+
+    // Confirm or fail a pending charge against the amount the oracle saw arrive
+    fn apply_verification(
+        env: Env,
+        oracle: Address,
+        payment_id: String,
+        transaction_hash: BytesN<32>,
+        payer_address: Option<Address>,
+        payer_commitment: Option<BytesN<32>>,
+        amount_received: i128,
+    ) -> Result<PaymentStatus, Error> {
+        // Get payment
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+
+        // A charge past expiry is expired now, even if no sweep has got to it yet
+        if payment.status == PaymentStatus::Pending && Clock::now(&env) > payment.expires_at {
+            Self::try_expire(&env, &payment_id);
+            payment = Self::get_payment_internal(&env, &payment_id)?;
+        }
+
+        // Funds that arrive after the charge closed are kept on record, owed back to the payer
+        if payment.status == PaymentStatus::Expired || payment.status == PaymentStatus::Cancelled {
+            Self::set_status(&env, &mut payment, PaymentStatus::LateConfirmed);
+            payment.payer_address = payer_address;
+            payment.payer_commitment = payer_commitment;
+            Self::index_tx_hash(&env, &transaction_hash, &payment_id);
+            payment.transaction_hash = Some(transaction_hash);
+            payment.confirmed_at = Some(env.ledger().timestamp());
+            payment.overpaid_amount = amount_received;
+            if payment.custody_mode == CustodyMode::Escrow {
+                EscrowLedger::credit(&env, &payment.currency, amount_received);
+            }
+            env.storage()
+                .persistent()
+                .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+            events::payment(&env, "LATE_CONFIRMED", &payment, Some(&oracle));
+            return Ok(PaymentStatus::LateConfirmed);
+        }
+
+        // Check if payment is still pending
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+        Compliance::require_clear(&env, &[Some(&payment.merchant_id), payer_address.as_ref()])?;
+        if payment.expected_payer.is_some() && payment.expected_payer != payer_address {
+            return Err(Error::WrongPayer);
+        }
+
+        // How strictly the amount must match depends on the merchant's pinned API version
+        let behavior = ApiBehavior::for_version(
+            &env,
+            Self::get_merchant(&env, &payment.merchant_id)
+                .map(|merchant| merchant.api_version)
+                .unwrap_or(DEFAULT_API_VERSION),
+        );
+        let tip = if payment.accept_overpayment {
+            (amount_received - payment.amount).max(0)
+        } else {
+            0
+        };
+        if tip == 0 && !behavior.accepts(payment.amount, amount_received) {
+            // Update status to failed
+            Self::set_status(&env, &mut payment, PaymentStatus::Failed);
+            env.storage()
+                .persistent()
+                .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+            // Emit payment failed event
+            events::payment(&env, "FAILED", &payment, Some(&oracle));
+
+            return Ok(PaymentStatus::Failed);
+        }
+
+        // Confirm tolerated shortfalls and tips for what actually arrived, and hold any other
+        // excess for the payer
+        if amount_received < payment.amount || tip > 0 {
+            payment.amount = amount_received;
+            payment.tip_amount = tip;
+        } else {
+            payment.overpaid_amount = amount_received - payment.amount;
+        }
+
+        // Update payment with verification details
+        Self::set_status(&env, &mut payment, PaymentStatus::Confirmed);
+        payment.payer_address = payer_address;
+        payment.payer_commitment = payer_commitment;
+        Self::index_tx_hash(&env, &transaction_hash, &payment_id);
+        payment.transaction_hash = Some(transaction_hash);
+        payment.confirmed_at = Some(env.ledger().timestamp());
+        Self::book_confirmation(&env, &mut payment);
+
+        // Store updated payment
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        if payment.overpaid_amount > 0 {
+            env.events().publish(
+                (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "OVERPAID")),
+                (payment_id.clone(), payment.overpaid_amount),
+            );
+        }
+        if payment.tip_amount > 0 {
+            env.events().publish(
+                (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "TIPPED")),
+                (payment_id.clone(), payment.tip_amount),
+            );
+        }
+
+        // Emit payment verified event with the verifying oracle
+        events::payment(&env, "VERIFIED", &payment, Some(&oracle));
+
+        Ok(PaymentStatus::Confirmed)
+    }
+
+    // Point the transaction hash at the payment it paid; a cart's transfer stays indexed under
+    // the first of its legs
+    fn index_tx_hash(env: &Env, transaction_hash: &BytesN<32>, payment_id: &String) {
+        let key = DataKey::TxHashIndex(transaction_hash.clone());
+        if !env.storage().persistent().has(&key) {
+            env.storage().persistent().set(&key, payment_id);
+        }
+        ttl::extend(env, &key);
+    }
+
+    /// Get payment details
+    pub fn get_payment(env: Env, payment_id: String) -> Result<PaymentCharge, Error> {
+        Self::get_payment_internal(&env, &payment_id)
+    }
+
+    /// Find the payment a Stellar transaction was verified against
+    pub fn get_payment_by_tx_hash(
+        env: Env,
+        transaction_hash: BytesN<32>,
+    ) -> Result<PaymentCharge, Error> {
+        let payment_id: String = env
+            .storage()
+            .persistent()
+            .get(&DataKey::TxHashIndex(transaction_hash))
+            .ok_or(Error::PaymentNotFound)?;
+        Self::get_payment_internal(&env, &payment_id)
+    }
+
+    /// List a merchant's payments in creation order, `limit` at a time from `start`
+    pub fn get_merchant_payments(
+        env: Env,
+        merchant_id: Address,
+        start: u32,
+        limit: u32,
+    ) -> PaymentPage {
+        let payment_ids: Vec<String> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::MerchantPayments(merchant_id))
+            .unwrap_or(vec![&env]);
+        Self::load_payment_page(&env, payment_ids, start, limit)
+    }
+
+    /// List the charges a payer has paid in confirmation order, `limit` at a time from
+    /// `offset`
+    pub fn get_payer_payments(env: Env, payer: Address, offset: u32, limit: u32) -> PaymentPage {
+        let payment_ids: Vec<String> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PayerPayments(payer))
+            .unwrap_or(vec![&env]);
+        Self::load_payment_page(&env, payment_ids, offset, limit)
+    }
+
+    /// Receipt for a confirmed or settled charge
+    pub fn get_receipt(env: Env, payment_id: String) -> Result<Receipt, Error> {
+        let payment = Self::get_payment_internal(&env, &payment_id)?;
+        let confirmed_at = match (&payment.status, payment.confirmed_at) {
+            (PaymentStatus::Confirmed | PaymentStatus::Settled, Some(confirmed_at)) => confirmed_at,
+            _ => return Err(Error::PaymentNotConfirmed),
+        };
+        Ok(Receipt {
+            merchant_name: Self::get_merchant(&env, &payment.merchant_id)
+                .map(|merchant| merchant.business_name)
+                .unwrap_or(String::from_str(&env, "")),
+            payment_id: payment.payment_id,
+            merchant_id: payment.merchant_id,
+            amount: payment.amount,
+            currency: payment.currency,
+            transaction_hash: payment.transaction_hash,
+            confirmed_at,
+        })
+    }
+
+    /// List payments currently in `status`, `limit` at a time from `offset`
+    pub fn get_payments_by_status(
+        env: Env,
+        status: PaymentStatus,
+        offset: u32,
+        limit: u32,
+    ) -> PaymentPage {
+        let payment_ids = Self::get_status_index(&env, &status);
+        Self::load_payment_page(&env, payment_ids, offset, limit)
+    }
+
+    /// Find payments created within [from, to] (support tooling), 20 per page
+    pub fn find_by_time_range(
+        env: Env,
+        kind: RecordKind,
+        from: u64,
+        to: u64,
+        page: u32,
+    ) -> Result<Vec<TimeIndexEntry>, Error> {
+        TimeIndex::find(&env, kind, from, to, page)
+    }
+
+    /// Keep a payment record live for at least `ledgers` more ledgers, capped at the network
+    /// maximum; returns the TTL requested (anyone)
+    pub fn bump_payment(env: Env, payment_id: String, ledgers: u32) -> Result<u32, Error> {
+        ttl::extend_by(&env, &DataKey::Payment(payment_id), ledgers).ok_or(Error::PaymentNotFound)
+    }
+
+    /// Export a charge as an ISO 20022-style structured remittance record
+    pub fn get_remittance_info(env: Env, payment_id: String) -> Result<RemittanceInfo, Error> {
+        Self::get_payment_internal(&env, &payment_id).map(RemittanceInfo::from_charge)
+    }
+
+    /// Replace a final-status payment older than the retention period with its summary,
+    /// dropping the full record; payments with funds or claims still outstanding are kept
+    /// (settlement operator only)
+    pub fn archive_payment(
+        env: Env,
+        operator: Address,
+        payment_id: String,
+    ) -> Result<PaymentSummary, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+        let payment = Self::get_payment_internal(&env, &payment_id)?;
+        if !Archive::is_archivable(&env, &payment) || Self::has_open_claims(&env, &payment_id) {
+            return Err(Error::PaymentNotArchivable);
+        }
+
+        let summary = Archive::summarize(&payment, Some(env.ledger().timestamp()));
+        Archive::store(&env, &summary);
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Payment(payment_id.clone()));
+        Self::remove_from_status_index(&env, &payment.status, &payment_id);
+        Self::remove_from_index(
+            &env,
+            &DataKey::MerchantPayments(payment.merchant_id.clone()),
+            &payment_id,
+        );
+        if let Some(payer) = &payment.payer_address {
+            Self::remove_from_index(&env, &DataKey::PayerPayments(payer.clone()), &payment_id);
+        }
+        AuditLog::append(&env, &operator, "ARCHIVE", payment_id);
+
+        events::payment(&env, "ARCHIVED", &payment, Some(&operator));
+        Ok(summary)
+    }
+
+    /// How long a final-status payment is kept in full before it may be archived (admin only)
+    pub fn set_archive_retention(env: Env, admin: Address, retention: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Archive::set_retention(&env, retention)
+    }
+
+    pub fn get_archive_retention(env: Env) -> u64 {
+        Archive::get_retention(&env)
+    }
+
+    /// Reconciliation summary of a payment, whether still stored in full or archived
+    pub fn get_payment_summary(env: Env, payment_id: String) -> Result<PaymentSummary, Error> {
+        if let Some(summary) = Archive::get(&env, &payment_id) {
+            return Ok(summary);
+        }
+        Self::get_payment_internal(&env, &payment_id)
+            .map(|payment| Archive::summarize(&payment, None))
+    }
+
+    /// Cancel expired payment
+    pub fn cancel_payment(env: Env, payment_id: String) -> Result<(), Error> {
+        // Get payment
+        let mut payment = Self::get_payment_internal(&env, &payment_id)?;
+
+        // Check if payment is pending
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+
+        // Check if payment has expired
+        if Clock::now(&env) <= payment.expires_at {
+            return Err(Error::Unauthorized); // Not expired yet
+        }
+
+        // Update status to expired
+        Self::set_status(&env, &mut payment, PaymentStatus::Expired);
+
+        // Store updated payment
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        // Anyone may cancel an expired charge, so the journal credits the contract
+        AuditLog::append(
+            &env,
+            &env.current_contract_address(),
+            "CANCEL",
+            payment_id.clone(),
+        );
+
+        // Emit payment cancelled event
+        events::payment(&env, "CANCELLED", &payment, None);
+
+        Ok(())
+    }
+
+    /// Expire every listed payment that is Pending and past its expiry; returns the number expired
+    pub fn expire_payments(env: Env, payment_ids: Vec<String>) -> u32 {
+        let mut expired = 0;
+        for payment_id in payment_ids.iter() {
+            if Self::try_expire(&env, &payment_id) {
+                expired += 1;
+            }
+        }
+        expired
+    }
+
+    /// Sweep the first `limit` entries of the Pending index, expiring those past expiry
+    pub fn expire_pending_batch(env: Env, limit: u32) -> u32 {
+        // Snapshot the index, since expiring payments removes them from it
+        let pending = Self::get_status_index(&env, &PaymentStatus::Pending);
+        let end = limit.min(pending.len());
+
+        let mut expired = 0;
+        for i in 0..end {
+            if let Some(payment_id) = pending.get(i) {
+                if Self::try_expire(&env, &payment_id) {
+                    expired += 1;
+                }
+            }
+        }
+        expired
+    }
+
+    /// Drop up to `limit` status index entries whose payment record no longer exists, such as
+    /// payments archived before archiving cleaned up after itself; returns the number removed
+    pub fn compact_indexes(env: Env, limit: u32) -> u32 {
+        let statuses = vec![
+            &env,
+            PaymentStatus::Pending,
+            PaymentStatus::Confirmed,
+            PaymentStatus::Expired,
+            PaymentStatus::Failed,
+            PaymentStatus::Settled,
+            PaymentStatus::Cancelled,
+            PaymentStatus::Authorized,
+            PaymentStatus::LateConfirmed,
+        ];
+        let mut removed = 0;
+        for status in statuses.iter() {
+            if removed >= limit {
+                break;
+            }
+            let index = Self::get_status_index(&env, &status);
+            let mut kept = vec![&env];
+            for payment_id in index.iter() {
+                if removed < limit
+                    && !env
+                        .storage()
+                        .persistent()
+                        .has(&DataKey::Payment(payment_id.clone()))
+                {
+                    removed += 1;
+                } else {
+                    kept.push_back(payment_id);
+                }
+            }
+            if kept.len() < index.len() {
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::PaymentsByStatus(status), &kept);
+            }
+        }
+        removed
+    }
+
+    /// Rewrite up to `limit` payments, in creation order from `start_key`, in the current
+    /// storage layout; returns the cursor to pass to the next call, or `None` once every
+    /// payment is at `SCHEMA_VERSION`. Safe to retry: records are never migrated twice
+    /// (admin only)
+    pub fn migrate_range(
+        env: Env,
+        admin: Address,
+        kind: RecordKind,
+        start_key: MigrationCursor,
+        limit: u32,
+    ) -> Result<Option<MigrationCursor>, Error> {
+        Self::require_admin(&env, &admin)?;
+        if kind != RecordKind::Payment {
+            return Err(Error::UnsupportedRecordKind);
+        }
+        let (payment_ids, next) = Migrations::claim_range(&env, kind, start_key, limit)?;
+        for payment_id in payment_ids.iter() {
+            // Archived payments leave only a summary behind, which has nothing to migrate
+            let key = DataKey::Payment(payment_id);
+            if let Some(payment) = env.storage().persistent().get::<_, PaymentCharge>(&key) {
+                env.storage().persistent().set(&key, &payment);
+                ttl::extend(&env, &key);
+            }
+        }
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MIGRATION"),
+                Symbol::new(&env, "PROGRESS"),
+            ),
+            (kind, payment_ids.len(), next.clone()),
+        );
+        Ok(next)
+    }
+
+    pub fn get_migration_progress(env: Env, kind: RecordKind) -> Option<MigrationProgress> {
+        Migrations::get_progress(&env, kind)
+    }
+
+    pub fn get_schema_version(env: Env, kind: RecordKind) -> u32 {
+        Migrations::schema_version(&env, kind)
+    }
+
+    /// Queue recovery of `amount` of `token` stuck in this contract, payable to `to` once the
+    /// emergency timelock has passed (admin only)
+    pub fn propose_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        token: Address,
+        amount: i128,
+        to: Address,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::propose(&env, &admin, token, amount, to)
+    }
+
+    /// Pay out a proposed emergency withdrawal whose timelock has passed (admin only)
+    pub fn execute_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::execute(&env, withdrawal_id)
+    }
+
+    /// Drop a proposed emergency withdrawal before it executes (admin only)
+    pub fn cancel_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::cancel(&env, withdrawal_id)
+    }
+
+    pub fn get_emergency_withdrawal(
+        env: Env,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Emergency::get(&env, withdrawal_id)
+    }
+
+    /// Seconds between proposing and executing an emergency withdrawal, at least
+    /// `MIN_EMERGENCY_TIMELOCK`; already proposed withdrawals keep their time (admin only)
+    pub fn set_emergency_timelock(env: Env, admin: Address, timelock: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::set_timelock(&env, timelock)
+    }
+
+    pub fn get_emergency_timelock(env: Env) -> u64 {
+        Emergency::get_timelock(&env)
+    }
+
+    /// Require `required` settlement operator approvals for settlements above `threshold`
+    /// (admin only)
+    pub fn set_approval_policy(
+        env: Env,
+        admin: Address,
+        threshold: i128,
+        required: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Approvals::set_policy(
+            &env,
+            ApprovalPolicy {
+                threshold,
+                required,
+            },
+        )
+    }
+
+    /// Drop the approval requirement (admin only)
+    pub fn clear_approval_policy(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Approvals::clear_policy(&env);
+        Ok(())
+    }
+
+    pub fn get_approval_policy(env: Env) -> Option<ApprovalPolicy> {
+        Approvals::get_policy(&env)
+    }
+
+    pub fn get_approval(env: Env, action_id: String) -> Option<Approval> {
+        Approvals::get(&env, &action_id)
+    }
+
+    /// Approve settling a confirmed payment that is above the approval threshold
+    /// (settlement operator only)
+    pub fn approve_settlement(
+        env: Env,
+        operator: Address,
+        payment_id: String,
+    ) -> Result<Approval, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+        let payment = Self::get_payment_internal(&env, &payment_id)?;
+        if payment.status != PaymentStatus::Confirmed {
+            return Err(Error::PaymentNotConfirmed);
+        }
+        Approvals::approve(&env, &payment_id, payment.amount, &operator)
+    }
+
+    /// Configure keeper staking, the per-unit bounty and the slashing quorum (admin only)
+    pub fn set_keeper_config(env: Env, admin: Address, config: KeeperConfig) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Keepers::set_config(&env, config)
+    }
+
+    /// Register as a keeper by staking at least the configured minimum
+    pub fn register_keeper(env: Env, keeper: Address, stake: i128) -> Result<Keeper, Error> {
+        keeper.require_auth();
+        let record = Keepers::register(&env, &keeper, stake)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "KEEPER"), Symbol::new(&env, "REGISTERED")),
+            (keeper, stake),
+        );
+
+        Ok(record)
+    }
+
+    /// Leave the keeper set, withdrawing the remaining stake and unclaimed rewards
+    pub fn deregister_keeper(env: Env, keeper: Address) -> Result<i128, Error> {
+        keeper.require_auth();
+        Keepers::deregister(&env, &keeper)
+    }
+
+    /// Expire stale pending payments as a keeper, earning a bounty per payment expired
+    pub fn keeper_sweep(env: Env, keeper: Address, limit: u32) -> Result<u32, Error> {
+        keeper.require_auth();
+        Keepers::require(&env, &keeper)?;
+
+        let expired = Self::expire_pending_batch(env.clone(), limit);
+        Keepers::reward(&env, &keeper, expired)?;
+        Ok(expired)
+    }
+
+    /// Withdraw accrued keeper bounties
+    pub fn claim_keeper_rewards(env: Env, keeper: Address) -> Result<i128, Error> {
+        keeper.require_auth();
+        Keepers::claim_rewards(&env, &keeper)
+    }
+
+    pub fn get_keeper(env: Env, keeper: Address) -> Option<Keeper> {
+        Keepers::get(&env, &keeper)
+    }
+
+    /// Open a proposal to slash a keeper's stake, counting as the first approval (admin only)
+    pub fn propose_slash(
+        env: Env,
+        admin: Address,
+        keeper: Address,
+        amount: i128,
+        evidence_hash: BytesN<32>,
+    ) -> Result<SlashProposal, Error> {
+        Self::require_admin(&env, &admin)?;
+        let proposal = Keepers::propose_slash(&env, &admin, &keeper, amount, evidence_hash)?;
+        Self::publish_slash(&env, &proposal);
+        Ok(proposal)
+    }
+
+    /// Approve a pending slash; it executes once the admin quorum is reached (admin only)
+    pub fn approve_slash(
+        env: Env,
+        admin: Address,
+        proposal_id: u64,
+    ) -> Result<SlashProposal, Error> {
+        Self::require_admin(&env, &admin)?;
+        let proposal = Keepers::approve_slash(&env, &admin, proposal_id)?;
+        Self::publish_slash(&env, &proposal);
+        Ok(proposal)
+    }
+
+    pub fn get_slash_proposal(env: Env, proposal_id: u64) -> Result<SlashProposal, Error> {
+        Keepers::get_slash_proposal(&env, proposal_id)
+    }
+
+    /// Set the platform fee in basis points and the fee collector (admin only)
+    pub fn set_fee_config(
+        env: Env,
+        admin: Address,
+        fee_bps: u32,
+        fee_collector: Address,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Fees::set_config(&env, fee_bps, fee_collector)
+    }
+
+    pub fn get_fee_config(env: Env) -> Option<FeeConfig> {
+        Fees::get_config(&env)
+    }
+
+    /// Override the platform fee for a single merchant (admin only)
+    pub fn set_merchant_fee(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        fee_bps: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Fees::set_merchant_fee(&env, &merchant, fee_bps)?;
+        AuditLog::record(&env, AuditEntity::Merchant(merchant), "FEE_SET", None, None);
+        Ok(())
+    }
+
+    /// Set volume-based discount tiers applied on top of the base fee (admin only)
+    pub fn set_fee_tiers(env: Env, admin: Address, tiers: Vec<FeeTier>) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Fees::set_tiers(&env, tiers)
+    }
+
+    pub fn get_fee_tiers(env: Env) -> Vec<FeeTier> {
+        Fees::get_tiers(&env)
+    }
+
+    /// Raise an EXPIRY ALERT event when a merchant's expiry rate reaches the threshold (admin only)
+    pub fn set_expiry_alert(
+        env: Env,
+        admin: Address,
+        config: ExpiryAlertConfig,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        ExpiryTracker::set_alert_config(&env, config)
+    }
+
+    /// Share of the merchant's charges that expired in the current window, in basis points
+    /// (the merchant, readers or admins)
+    pub fn get_expiry_rate(env: Env, caller: Address, merchant: Address) -> Result<u32, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(ExpiryTracker::rate_bps(&ExpiryTracker::get_stats(
+            &env, &merchant,
+        )))
+    }
+
+    pub fn get_expiry_stats(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+    ) -> Result<ExpiryStats, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(ExpiryTracker::get_stats(&env, &merchant))
+    }
+
+    /// Set the fee charged on payouts converted into another currency (admin only)
+    pub fn set_fx_fee(env: Env, admin: Address, fx_fee_bps: u32) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Fees::set_fx_fee(&env, fx_fee_bps)
+    }
+
+    /// Set the flat fee invoiced to a merchant for each dispute it loses (admin only)
+    pub fn set_dispute_fee(env: Env, admin: Address, fee: i128) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Fees::set_dispute_fee(&env, fee)
+    }
+
+    /// Invoice the dispute fee for a payment resolved in the payer's favour (linked RefundManager)
+    pub fn record_dispute_fee(env: Env, payment_id: String) -> Result<i128, Error> {
+        let refund_manager: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RefundManager)
+            .ok_or(Error::Unauthorized)?;
+        refund_manager.require_auth();
+
+        let payment = Self::get_payment_internal(&env, &payment_id)?;
+        let fee = Fees::get_dispute_fee(&env);
+        if fee > 0 {
+            Fees::accrue_owed(&env, &payment.merchant_id, &payment.currency, fee);
+            Fees::invoice(
+                &env,
+                &payment.merchant_id,
+                &payment.currency,
+                FeeKind::Dispute,
+                fee,
+            );
+        }
+        Ok(fee)
+    }
+
+    /// Fees charged to the merchant so far in the open statement period
+    pub fn get_open_fee_invoice(env: Env, merchant: Address, currency: Symbol) -> FeeInvoice {
+        Fees::get_open_invoice(&env, &merchant, &currency)
+    }
+
+    /// A closed fee invoice, numbered from 1 per merchant and currency
+    pub fn get_fee_invoice(
+        env: Env,
+        merchant: Address,
+        currency: Symbol,
+        invoice_number: u32,
+    ) -> Result<FeeInvoice, Error> {
+        Fees::get_invoice(&env, &merchant, &currency, invoice_number)
+    }
+
+    /// Register a referral partner, or update its share ceiling (admin only)
+    pub fn register_partner(
+        env: Env,
+        admin: Address,
+        partner: Address,
+        max_share_bps: u32,
+    ) -> Result<Partner, Error> {
+        Self::require_admin(&env, &admin)?;
+        let record = Partners::register(&env, &partner, max_share_bps)?;
+        env.events().publish(
+            (
+                Symbol::new(&env, "PARTNER"),
+                Symbol::new(&env, "REGISTERED"),
+            ),
+            (partner, max_share_bps),
+        );
+        Ok(record)
+    }
+
+    /// Stop a partner from being attributed on new charges (admin only)
+    pub fn deactivate_partner(env: Env, admin: Address, partner: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Partners::deactivate(&env, &partner)
+    }
+
+    pub fn get_partner(env: Env, partner: Address) -> Result<Partner, Error> {
+        Partners::get(&env, &partner)
+    }
+
+    /// Credit a referral partner with a share of this charge's processing fee, while it is
+    /// still pending (merchant or one of its delegates)
+    pub fn set_payment_partner(
+        env: Env,
+        caller: Address,
+        payment_id: String,
+        partner: Address,
+        share_bps: u32,
+    ) -> Result<PartnerShare, Error> {
+        let payment = Self::get_payment_internal(&env, &payment_id)?;
+        Self::require_merchant_or_delegate(&env, &payment.merchant_id, &caller)?;
+        if payment.status != PaymentStatus::Pending {
+            return Err(Error::PaymentAlreadyProcessed);
+        }
+        let share = Partners::attribute(&env, &payment_id, &partner, share_bps)?;
+        env.events().publish(
+            (Symbol::new(&env, "PAYMENT"), Symbol::new(&env, "PARTNER")),
+            (payment_id, partner, share_bps),
+        );
+        Ok(share)
+    }
+
+    pub fn get_payment_partner(env: Env, payment_id: String) -> Option<PartnerShare> {
+        Partners::get_attribution(&env, &payment_id)
+    }
+
+    /// Processing fee shares a partner has earned in one currency
+    pub fn get_partner_earnings(env: Env, partner: Address, currency: Symbol) -> i128 {
+        Partners::get_earned(&env, &partner, &currency)
+    }
+
+    /// Fee in basis points the merchant's next settlement would be charged
+    pub fn get_effective_fee(env: Env, merchant: Address) -> u32 {
+        Self::merchant_fee_bps(&env, &merchant)
+    }
+
+    /// Add or replace a promo code merchants may apply when creating charges (admin only)
+    pub fn set_promo_code(env: Env, admin: Address, promo: PromoCode) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Promos::set(&env, &promo)
+    }
+
+    pub fn remove_promo_code(env: Env, admin: Address, code_hash: BytesN<32>) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Promos::remove(&env, &code_hash)
+    }
+
+    pub fn get_promo_code(env: Env, code_hash: BytesN<32>) -> Result<PromoCode, Error> {
+        Promos::get(&env, &code_hash)
+    }
+
+    /// Charges a promo code may still be applied to
+    pub fn get_promo_remaining_uses(env: Env, code_hash: BytesN<32>) -> Result<u32, Error> {
+        Promos::remaining_uses(&env, &code_hash)
+    }
+
+    /// The promo code applied to a charge, if any
+    pub fn get_payment_promo(env: Env, payment_id: String) -> Option<PromoRedemption> {
+        Promos::get_redemption(&env, &payment_id)
+    }
+
+    /// Apply limits, a refund window and a fee to every merchant in a category (admin only)
+    pub fn set_category_policy(
+        env: Env,
+        admin: Address,
+        category: Symbol,
+        policy: CategoryPolicy,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        CategoryPolicies::set(&env, &category, &policy)?;
+        env.events().publish(
+            (
+                Symbol::new(&env, "CATEGORY"),
+                Symbol::new(&env, "POLICY_SET"),
+            ),
+            (category, policy),
+        );
+        Ok(())
+    }
+
+    /// Drop a category's policy, returning its merchants to the defaults (admin only)
+    pub fn remove_category_policy(env: Env, admin: Address, category: Symbol) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        CategoryPolicies::remove(&env, &category);
+        env.events().publish(
+            (
+                Symbol::new(&env, "CATEGORY"),
+                Symbol::new(&env, "POLICY_REMOVED"),
+            ),
+            category,
+        );
+        Ok(())
+    }
+
+    pub fn get_category_policy(env: Env, category: Symbol) -> Option<CategoryPolicy> {
+        CategoryPolicies::get(&env, &category)
+    }
+
+    /// The policy of the category the registry files the merchant under, if there is one
+    pub fn get_merchant_policy(env: Env, merchant_id: Address) -> Option<CategoryPolicy> {
+        Self::category_policy(&env, &merchant_id)
+    }
+
+    /// Settled volume in the merchant's current rolling window (the merchant, readers or admins)
+    pub fn get_merchant_volume(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+    ) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(Fees::get_volume(&env, &merchant))
+    }
+
+    /// Enable or disable a feature flag (admin only)
+    pub fn set_feature(env: Env, admin: Address, flag: Symbol, enabled: bool) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Features::set(&env, flag.clone(), enabled);
+
+        env.events()
+            .publish((Symbol::new(&env, "FEATURE"), flag), enabled);
+
+        Ok(())
+    }
+
+    pub fn is_feature_enabled(env: Env, flag: Symbol) -> bool {
+        Features::is_enabled(&env, &flag)
+    }
+
+    /// Post an observed exchange rate, scaled by `RATE_SCALE` (oracle only)
+    pub fn post_rate(
+        env: Env,
+        oracle: Address,
+        base: Symbol,
+        quote: Symbol,
+        rate: i128,
+        timestamp: u64,
+        nonce: u64,
+    ) -> Result<ExchangeRate, Error> {
+        oracle.require_auth();
+        AccessControl::require_role(&env, &role_oracle(&env), &oracle)?;
+        Self::use_oracle_nonce(&env, &oracle, nonce)?;
+
+        let posted = Rates::post(&env, oracle, base, quote, rate, timestamp)?;
+        env.events().publish(
+            (Symbol::new(&env, "RATE"), Symbol::new(&env, "POSTED")),
+            posted.clone(),
+        );
+        Ok(posted)
+    }
+
+    pub fn get_rate(env: Env, base: Symbol, quote: Symbol) -> Option<ExchangeRate> {
+        Rates::get(&env, &base, &quote)
+    }
+
+    /// Set how old a rate may be before settlements relying on it are rejected (admin only)
+    pub fn set_max_rate_age(env: Env, admin: Address, max_age: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Rates::set_max_age(&env, max_age)
+    }
+
+    /// Settle a confirmed payment, paying it out of escrow to the merchant's settlement address,
+    /// the fee collector and any referral partner (settlement operator only)
+    pub fn settle_payment(
+        env: Env,
+        operator: Address,
+        payment_id: String,
+    ) -> Result<PaymentCharge, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+
+        let payment = Self::settle_internal(&env, &operator, &payment_id)?;
+        let mut lines = vec![&env];
+        Self::add_settled(&env, &mut lines, &payment);
+        Self::pay_out_line(&env, &lines.get_unchecked(0))?;
+        Self::close_statement(
+            &env,
+            &payment.merchant_id,
+            &payment.currency,
+            payment.fee_amount,
+        );
+        Ok(payment)
+    }
+
+    /// Settle up to `MAX_BATCH_SIZE` confirmed payments at once, paying escrow out once per
+    /// merchant and currency; any ineligible payment rejects the whole batch
+    /// (settlement operator only)
+    pub fn settle_batch(
+        env: Env,
+        operator: Address,
+        payment_ids: Vec<String>,
+    ) -> Result<SettlementBatch, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+        if payment_ids.is_empty() {
+            return Err(Error::EmptyBatch);
+        }
+        if payment_ids.len() > MAX_BATCH_SIZE {
+            return Err(Error::BatchTooLarge);
+        }
+
+        let mut lines = vec![&env];
+        for payment_id in payment_ids.iter() {
+            let payment = Self::settle_internal(&env, &operator, &payment_id)?;
+            Self::add_settled(&env, &mut lines, &payment);
+        }
+        for line in lines.iter() {
+            Self::pay_out_line(&env, &line)?;
+            Self::close_statement(&env, &line.merchant_id, &line.currency, line.fees);
+        }
+
+        let batch = Settlements::record(&env, operator, payment_ids, lines);
+        env.events().publish(
+            (Symbol::new(&env, "SETTLEMENT"), Symbol::new(&env, "BATCH")),
+            batch.clone(),
+        );
+        Ok(batch)
+    }
+
+    /// Settle and pay out up to `MAX_BATCH_SIZE` confirmed payments whose merchants' payout
+    /// preferences are met, skipping any held back by a compliance block or missing approvals
+    /// (settlement operator only)
+    pub fn run_payouts(env: Env, operator: Address) -> Result<SettlementBatch, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+
+        // Judge each merchant and currency once, before settling drains its pending balance
+        let mut cadences: Map<(Address, Symbol), Option<PayoutCadence>> = Map::new(&env);
+        let today = SettlementReports::day_index(&env);
+        let mut payment_ids = vec![&env];
+        let mut lines = vec![&env];
+        for payment_id in Self::get_status_index(&env, &PaymentStatus::Confirmed).iter() {
+            if payment_ids.len() >= MAX_BATCH_SIZE {
+                break;
+            }
+            let payment = Self::get_payment_internal(&env, &payment_id)?;
+            let key = (payment.merchant_id.clone(), payment.currency.clone());
+            let cadence = match cadences.get(key.clone()) {
+                Some(cadence) => cadence,
+                None => {
+                    let cadence =
+                        Self::payout_cadence(&env, &payment.merchant_id, &payment.currency);
+                    cadences.set(key, cadence.clone());
+                    cadence
+                }
+            };
+            let ready = match cadence {
+                Some(PayoutCadence::Daily) => {
+                    payment.confirmed_at.unwrap_or(0) / REPORT_DAY_SECONDS < today
+                }
+                Some(_) => true,
+                None => false,
+            };
+            if !ready || !Self::payout_eligible(&env, &payment) {
+                continue;
+            }
+
+            let payment = Self::settle_internal(&env, &operator, &payment_id)?;
+            Self::add_settled(&env, &mut lines, &payment);
+            payment_ids.push_back(payment_id);
+        }
+        if payment_ids.is_empty() {
+            return Err(Error::EmptyBatch);
+        }
+        for line in lines.iter() {
+            Self::pay_out_line(&env, &line)?;
+            Self::close_statement(&env, &line.merchant_id, &line.currency, line.fees);
+        }
+
+        let batch = Settlements::record(&env, operator, payment_ids, lines);
+        env.events().publish(
+            (
+                Symbol::new(&env, "SETTLEMENT"),
+                Symbol::new(&env, "PAYOUTS"),
+            ),
+            batch.clone(),
+        );
+        Ok(batch)
+    }
+
+    pub fn get_settlement_batch(env: Env, batch_id: String) -> Result<SettlementBatch, Error> {
+        Settlements::get(&env, &batch_id)
+    }
+
+    /// Attach the off-ramp anchor's references to a settled payment or settlement batch,
+    /// replacing any earlier ones (settlement operator only)
+    pub fn set_anchor_reference(
+        env: Env,
+        operator: Address,
+        settlement_id: String,
+        external_transaction_id: String,
+        anchor_account: Address,
+        memo_type: MemoType,
+        memo: String,
+    ) -> Result<AnchorReference, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+        let is_settled_payment = Self::get_payment_internal(&env, &settlement_id)
+            .map(|payment| payment.status == PaymentStatus::Settled)
+            .unwrap_or(false);
+        if !is_settled_payment && Settlements::get(&env, &settlement_id).is_err() {
+            return Err(Error::SettlementNotFound);
+        }
+        if external_transaction_id.is_empty() {
+            return Err(Error::InvalidAnchorReference);
+        }
+        AnchorReferences::validate_memo(&memo_type, &memo)?;
+
+        let reference = AnchorReference {
+            settlement_id,
+            external_transaction_id,
+            anchor_account,
+            memo_type,
+            memo,
+            recorded_by: operator,
+            recorded_at: env.ledger().timestamp(),
+        };
+        AnchorReferences::record(&env, &reference);
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "SETTLEMENT"),
+                Symbol::new(&env, "ANCHOR_REF"),
+            ),
+            reference.clone(),
+        );
+        Ok(reference)
+    }
+
+    pub fn get_anchor_reference(env: Env, settlement_id: String) -> Option<AnchorReference> {
+        AnchorReferences::get(&env, &settlement_id)
+    }
+
+    /// Status, config and role history recorded by this contract for an entity, oldest first
+    pub fn get_audit_trail(env: Env, entity: AuditEntity, page: u32) -> Vec<AuditEntry> {
+        AuditLog::page(&env, &AuditLog::get_all(&env, &entity), page)
+    }
+
+    /// Contract-wide journal records from `from_seq` on, oldest first
+    pub fn get_audit_entries(env: Env, from_seq: u64, limit: u32) -> Vec<AuditRecord> {
+        AuditLog::entries(&env, from_seq, limit)
+    }
+
+    /// Queue a settlement whenever escrowed funds exceed `max_balance` or `max_age` (merchant)
+    pub fn set_auto_settle_rule(
+        env: Env,
+        merchant_id: Address,
+        rule: AutoSettleRule,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        AutoSettle::set_rule(&env, &merchant_id, rule)
+    }
+
+    pub fn get_auto_settle_rule(env: Env, merchant_id: Address) -> Option<AutoSettleRule> {
+        AutoSettle::get_rule(&env, &merchant_id)
+    }
+
+    /// Escrowed, confirmed funds awaiting settlement for a merchant in a currency
+    /// (the merchant, readers or admins)
+    pub fn get_unsettled_balance(
+        env: Env,
+        caller: Address,
+        merchant_id: Address,
+        currency: Symbol,
+    ) -> Result<UnsettledBalance, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant_id))?;
+        Ok(AutoSettle::get_unsettled(&env, &merchant_id, &currency))
+    }
+
+    /// Re-check a merchant's rule, catching balances that have aged past it (anyone)
+    pub fn check_auto_settlement(env: Env, merchant_id: Address, currency: Symbol) -> bool {
+        AutoSettle::evaluate(&env, &merchant_id, &currency)
+    }
+
+    /// Settlements queued by merchant rules, for operators to pick up (readers or admins)
+    pub fn get_settlement_queue(env: Env, caller: Address) -> Result<Vec<QueuedSettlement>, Error> {
+        Self::require_reader(&env, &caller, None)?;
+        Ok(AutoSettle::get_queue(&env))
+    }
+
+    /// Open statement period for a merchant in a currency (the merchant, readers or admins)
+    pub fn get_merchant_ledger(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+        currency: Symbol,
+    ) -> Result<MerchantLedger, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(Statements::get_ledger(&env, &merchant, &currency))
+    }
+
+    /// A merchant's per-currency totals for one UTC day, where `day_index` is a ledger
+    /// timestamp divided by `REPORT_DAY_SECONDS` (the merchant, readers or admins)
+    pub fn get_settlement_report(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+        day_index: u64,
+    ) -> Result<SettlementReport, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(SettlementReports::get(&env, &merchant, day_index))
+    }
+
+    /// Fees invoiced to a merchant (self-custody fees, dispute fees) and not yet paid
+    /// (the merchant, readers or admins)
+    pub fn get_fees_owed(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+        currency: Symbol,
+    ) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(Fees::get_owed(&env, &merchant, &currency))
+    }
+
+    /// Record a merchant's payment against its fee invoice, returning the balance left (admin only)
+    pub fn record_fee_payment(
+        env: Env,
+        admin: Address,
+        merchant: Address,
+        currency: Symbol,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        Self::require_admin(&env, &admin)?;
+        let remaining = Fees::settle_owed(&env, &merchant, &currency, amount)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "FEE"), Symbol::new(&env, "PAID")),
+            (merchant, currency, amount),
+        );
+
+        Ok(remaining)
+    }
+
+    /// Total platform fees collected in a currency (readers or admins)
+    pub fn get_collected_fees(env: Env, caller: Address, currency: Symbol) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, None)?;
+        Ok(Fees::get_collected(&env, &currency))
+    }
+
+    /// Funds booked into the RefundManager escrow in a currency, merchant balances and
+    /// payer overpayments together (readers or admins)
+    pub fn get_escrow_balance(env: Env, caller: Address, currency: Symbol) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, None)?;
+        Ok(EscrowLedger::get_total(&env, &currency))
+    }
+
+    /// Confirmed escrow funds not yet settled to the merchant or refunded
+    /// (the merchant, readers or admins)
+    pub fn get_merchant_pending_balance(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+        currency: Symbol,
+    ) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, Some(&merchant))?;
+        Ok(EscrowLedger::get_pending(&env, &merchant, &currency))
+    }
+
+    /// Compare booked escrow with the RefundManager's token balance, less what its mass-refund
+    /// pools hold, in each supported currency (readers or admins)
+    pub fn reconcile(env: Env, caller: Address) -> Result<Vec<EscrowReconciliation>, Error> {
+        Self::require_reader(&env, &caller, None)?;
+        let escrow: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RefundManager)
+            .ok_or(Error::EscrowNotLinked)?;
+
+        let refunds = RefundManagerClient::new(&env, &escrow);
+        let mut report = vec![&env];
+        for supported in Self::list_supported_tokens(env.clone()).iter() {
+            let held = token::Client::new(&env, &supported.token).balance(&escrow)
+                - refunds.get_mass_refund_liability(&supported.token);
+            report.push_back(EscrowLedger::reconcile(&env, &supported.currency, held));
+        }
+        Ok(report)
+    }
+
+    /// Create a recurring subscription billed to the payer every `interval` seconds
+    pub fn create_subscription(
+        env: Env,
+        payer: Address,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        interval: u64,
+        first_due_at: u64,
+    ) -> Result<Subscription, Error> {
+        Features::require(&env, &feature_subscriptions(&env))?;
+        payer.require_auth();
+        Self::require_verified_merchant(&env, &merchant_id)?;
+
+        let subscription = Subscriptions::create(
+            &env,
+            merchant_id,
+            payer,
+            amount,
+            currency,
+            interval,
+            first_due_at,
+        )?;
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "SUBSCRIPTION"),
+                Symbol::new(&env, "CREATED"),
+            ),
+            subscription.subscription_id,
+        );
+
+        Ok(subscription)
+    }
+
+    /// Charge a due subscription, creating the next payment (oracle or settlement operator only)
+    pub fn charge_subscription(
+        env: Env,
+        operator: Address,
+        subscription_id: u64,
+        deposit_address: Address,
+    ) -> Result<PaymentCharge, Error> {
+        Features::require(&env, &feature_subscriptions(&env))?;
+        operator.require_auth();
+        let has_settlement =
+            AccessControl::has_role(&env, &role_settlement_operator(&env), &operator);
+        let has_oracle = AccessControl::has_role(&env, &role_oracle(&env), &operator);
+        if !has_settlement && !has_oracle {
+            return Err(Error::Unauthorized);
+        }
+
+        let subscription = Subscriptions::advance(&env, subscription_id)?;
+        Limits::spend_payer(
+            &env,
+            &subscription.payer,
+            &subscription.merchant_id,
+            subscription.amount,
+        )?;
+        let payment_id = IdBuilder::new(SUBSCRIPTION_PREFIX)
+            .push_u64(subscription_id)
+            .push_str("_")
+            .push_u64(subscription.cycles_charged)
+            .build(&env);
+
+        Self::create_payment_internal(
+            &env,
+            &operator,
+            payment_id,
+            subscription.merchant_id,
+            subscription.amount,
+            subscription.currency,
+            deposit_address,
+            Clock::now(&env) + subscription.interval,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )
+    }
+
+    /// Cap what subscriptions may pull from the payer, per charge and per merchant per day
+    /// (payer)
+    pub fn set_payer_limits(env: Env, payer: Address, limits: PayerLimits) -> Result<(), Error> {
+        payer.require_auth();
+        Limits::set_payer_limits(&env, &payer, &limits)?;
+        env.events().publish(
+            (Symbol::new(&env, "PAYER"), Symbol::new(&env, "LIMITS_SET")),
+            (payer, limits.max_single_payment, limits.max_daily_total),
+        );
+        Ok(())
+    }
+
+    /// Drop the payer's self-imposed limits (payer)
+    pub fn clear_payer_limits(env: Env, payer: Address) {
+        payer.require_auth();
+        Limits::clear_payer_limits(&env, &payer);
+        env.events().publish(
+            (
+                Symbol::new(&env, "PAYER"),
+                Symbol::new(&env, "LIMITS_CLEARED"),
+            ),
+            payer,
+        );
+    }
+
+    pub fn get_payer_limits(env: Env, payer: Address) -> Option<PayerLimits> {
+        Limits::get_payer_limits(&env, &payer)
+    }
+
+    /// Let the merchant collect up to `amount` in total from the payer's allowance through
+    /// `collect_payment`; zero revokes it (payer)
+    pub fn set_collection_mandate(
+        env: Env,
+        payer: Address,
+        merchant_id: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        payer.require_auth();
+        Limits::set_mandate(&env, &payer, &merchant_id, amount)?;
+        env.events().publish(
+            (Symbol::new(&env, "PAYER"), Symbol::new(&env, "MANDATE_SET")),
+            (payer, merchant_id, amount),
+        );
+        Ok(())
+    }
+
+    /// What the merchant may still collect from the payer
+    pub fn get_collection_mandate(env: Env, payer: Address, merchant_id: Address) -> i128 {
+        Limits::get_mandate(&env, &payer, &merchant_id)
+    }
+
+    /// Amount pulled from the payer toward the merchant so far today
+    pub fn get_payer_daily_total(env: Env, payer: Address, merchant_id: Address) -> i128 {
+        Limits::get_payer_total(&env, &payer, &merchant_id)
+    }
+
+    /// Cancel a subscription (payer or merchant)
+    pub fn cancel_subscription(
+        env: Env,
+        caller: Address,
+        subscription_id: u64,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        let mut subscription = Subscriptions::get(&env, subscription_id)?;
+        if caller != subscription.payer && caller != subscription.merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        Subscriptions::cancel(&env, &mut subscription)?;
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "SUBSCRIPTION"),
+                Symbol::new(&env, "CANCELLED"),
+            ),
+            subscription_id,
+        );
+
+        Ok(())
+    }
+
+    /// Get subscription details
+    pub fn get_subscription(env: Env, subscription_id: u64) -> Result<Subscription, Error> {
+        Subscriptions::get(&env, subscription_id)
+    }
+
+    /// Get all subscriptions for a merchant
+    pub fn get_subscriptions_by_merchant(env: Env, merchant_id: Address) -> Vec<Subscription> {
+        Subscriptions::get_by_merchant(&env, &merchant_id)
+    }
+
+    /// Propose a payment to a merchant without an invoice, e.g. at a market stall (payer)
+    pub fn create_payment_intent(
+        env: Env,
+        payer: Address,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        memo: String,
+        expires_at: u64,
+    ) -> Result<PaymentIntent, Error> {
+        payer.require_auth();
+        Pausable::require_not_paused(&env, PauseScope::Payments)?;
+        Self::require_verified_merchant(&env, &merchant_id)?;
+        if !env
+            .storage()
+            .persistent()
+            .has(&DataKey::AllowedToken(currency.clone()))
+        {
+            return Err(Error::UnsupportedCurrency);
+        }
+
+        let intent = Intents::create(&env, payer, merchant_id, amount, currency, memo, expires_at)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "INTENT"), Symbol::new(&env, "CREATED")),
+            (intent.intent_id, intent.merchant_id.clone()),
+        );
+
+        Ok(intent)
+    }
+
+    /// Accept a payer's intent, turning it into a standard charge on `deposit_address` (merchant)
+    pub fn accept_payment_intent(
+        env: Env,
+        merchant_id: Address,
+        intent_id: u64,
+        deposit_address: Address,
+        expires_at: u64,
+    ) -> Result<PaymentCharge, Error> {
+        merchant_id.require_auth();
+        let expires_at = Self::resolve_expiry(&env, expires_at)?;
+
+        let mut intent = Intents::get(&env, intent_id)?;
+        if intent.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        Intents::close(&env, &mut intent, IntentStatus::Accepted)?;
+
+        let payment_id = IdBuilder::new(INTENT_PREFIX)
+            .push_u64(intent_id)
+            .build(&env);
+        let payment = Self::create_payment_internal(
+            &env,
+            &merchant_id.clone(),
+            payment_id.clone(),
+            merchant_id,
+            intent.amount,
+            intent.currency.clone(),
+            deposit_address,
+            expires_at,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )?;
+        intent.payment_id = Some(payment_id.clone());
+        Intents::save(&env, &intent);
+
+        env.events().publish(
+            (Symbol::new(&env, "INTENT"), Symbol::new(&env, "ACCEPTED")),
+            (intent_id, payment_id),
+        );
+
+        Ok(payment)
+    }
+
+    /// Turn down a payer's intent (merchant)
+    pub fn decline_payment_intent(
+        env: Env,
+        merchant_id: Address,
+        intent_id: u64,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut intent = Intents::get(&env, intent_id)?;
+        if intent.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        Intents::close(&env, &mut intent, IntentStatus::Declined)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "INTENT"), Symbol::new(&env, "DECLINED")),
+            intent_id,
+        );
+
+        Ok(())
+    }
+
+    /// Mark an intent nobody acted on before its expiry as Expired (anyone)
+    pub fn expire_payment_intent(env: Env, intent_id: u64) -> Result<(), Error> {
+        let mut intent = Intents::get(&env, intent_id)?;
+        Intents::close(&env, &mut intent, IntentStatus::Expired)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "INTENT"), Symbol::new(&env, "EXPIRED")),
+            intent_id,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_payment_intent(env: Env, intent_id: u64) -> Result<PaymentIntent, Error> {
+        Intents::get(&env, intent_id)
+    }
+
+    /// Every intent addressed at a merchant
+    pub fn get_merchant_intents(env: Env, merchant_id: Address) -> Vec<PaymentIntent> {
+        Intents::get_by_merchant(&env, &merchant_id)
+    }
+
+    /// Issue an itemized invoice payable in `currency` (merchant)
+    pub fn create_invoice(
+        env: Env,
+        merchant_id: Address,
+        currency: Symbol,
+        lines: Vec<InvoiceLine>,
+        due_at: u64,
+    ) -> Result<Invoice, Error> {
+        merchant_id.require_auth();
+        Self::require_verified_merchant(&env, &merchant_id)?;
+        if !env
+            .storage()
+            .persistent()
+            .has(&DataKey::AllowedToken(currency.clone()))
+        {
+            return Err(Error::UnsupportedCurrency);
+        }
+
+        let invoice = Invoices::create(&env, merchant_id, currency, lines, due_at)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "INVOICE"), Symbol::new(&env, "CREATED")),
+            (invoice.invoice_id, invoice.total),
+        );
+
+        Ok(invoice)
+    }
+
+    /// Raise the charge paying an open invoice on the next address from the merchant's deposit
+    /// pool; a charge still pending is returned as is (payer)
+    pub fn pay_invoice(env: Env, payer: Address, invoice_id: u64) -> Result<PaymentCharge, Error> {
+        payer.require_auth();
+        let mut invoice = Invoices::get(&env, invoice_id)?;
+        if invoice.status != InvoiceStatus::Open {
+            return Err(Error::InvoiceNotOpen);
+        }
+        if let Some(payment_id) = &invoice.payment_id {
+            if let Ok(payment) = Self::get_payment_internal(&env, payment_id) {
+                if payment.status == PaymentStatus::Pending {
+                    return Ok(payment);
+                }
+            }
+        }
+
+        let payment_id = Invoices::next_payment_id(&env, &mut invoice);
+        Self::validate_new_payment(
+            &env,
+            &payment_id,
+            &invoice.merchant_id,
+            invoice.total,
+            &invoice.currency,
+        )?;
+        let deposit_address = DepositPool::next_address(&env, &invoice.merchant_id)?;
+        let expires_at = invoice.due_at.max(Clock::now(&env) + INVOICE_CHARGE_WINDOW);
+        let payment = Self::create_payment_internal(
+            &env,
+            &payer,
+            payment_id.clone(),
+            invoice.merchant_id.clone(),
+            invoice.total,
+            invoice.currency.clone(),
+            deposit_address,
+            expires_at,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )?;
+        Invoices::link_payment(&env, &mut invoice, &payment_id);
+
+        env.events().publish(
+            (Symbol::new(&env, "INVOICE"), Symbol::new(&env, "BILLED")),
+            (invoice_id, payment_id, payer),
+        );
+
+        Ok(payment)
+    }
+
+    /// Withdraw an unpaid invoice, cancelling any charge still pending for it (merchant)
+    pub fn cancel_invoice(env: Env, merchant_id: Address, invoice_id: u64) -> Result<(), Error> {
+        merchant_id.require_auth();
+        let mut invoice = Invoices::get(&env, invoice_id)?;
+        if invoice.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        if invoice.status != InvoiceStatus::Open {
+            return Err(Error::InvoiceNotOpen);
+        }
+
+        if let Some(payment_id) = &invoice.payment_id {
+            if let Ok(mut payment) = Self::get_payment_internal(&env, payment_id) {
+                if payment.status == PaymentStatus::Pending {
+                    Self::set_status(&env, &mut payment, PaymentStatus::Cancelled);
+                    env.storage()
+                        .persistent()
+                        .set(&DataKey::Payment(payment_id.clone()), &payment);
+                    AuditLog::append(&env, &merchant_id, "CANCEL", payment_id.clone());
+                }
+            }
+        }
+        invoice.status = InvoiceStatus::Cancelled;
+        Invoices::save(&env, &invoice);
+
+        env.events().publish(
+            (Symbol::new(&env, "INVOICE"), Symbol::new(&env, "CANCELLED")),
+            invoice_id,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_invoice(env: Env, invoice_id: u64) -> Result<Invoice, Error> {
+        Invoices::get(&env, invoice_id)
+    }
+
+    /// Every invoice a merchant has issued
+    pub fn get_merchant_invoices(env: Env, merchant_id: Address) -> Vec<Invoice> {
+        Invoices::get_by_merchant(&env, &merchant_id)
+    }
+
+    /// Split `total_amount` into `instalment_count` charges due every `interval` seconds from
+    /// `first_due_at` (merchant and payer)
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_instalment_plan(
+        env: Env,
+        merchant_id: Address,
+        payer: Address,
+        total_amount: i128,
+        currency: Symbol,
+        instalment_count: u32,
+        interval: u64,
+        first_due_at: u64,
+    ) -> Result<InstalmentPlan, Error> {
+        merchant_id.require_auth();
+        payer.require_auth();
+        Self::require_verified_merchant(&env, &merchant_id)?;
+        Self::require_token(&env, &currency)?;
+
+        let plan = InstalmentPlans::create(
+            &env,
+            merchant_id,
+            payer,
+            currency,
+            total_amount,
+            instalment_count,
+            interval,
+            first_due_at,
+        )?;
+
+        env.events().publish(
+            (Symbol::new(&env, "PLAN"), Symbol::new(&env, "CREATED")),
+            (plan.plan_id, plan.total_amount, plan.instalment_count),
+        );
+
+        Ok(plan)
+    }
+
+    /// Raise the charge for a plan's next instalment once it is within INSTALMENT_ISSUE_LEAD
+    /// of due, on the next address from the merchant's deposit pool; a charge still pending
+    /// is returned as is (payer, oracle or settlement operator)
+    pub fn issue_instalment(
+        env: Env,
+        caller: Address,
+        plan_id: u64,
+    ) -> Result<PaymentCharge, Error> {
+        caller.require_auth();
+        let mut plan = InstalmentPlans::get(&env, plan_id)?;
+        if caller != plan.payer
+            && !AccessControl::has_role(&env, &role_settlement_operator(&env), &caller)
+            && !AccessControl::has_role(&env, &role_oracle(&env), &caller)
+        {
+            return Err(Error::Unauthorized);
+        }
+
+        let index = InstalmentPlans::next_due(&env, &plan)?;
+        if let Some(payment_id) = InstalmentPlans::get_charge(&env, plan_id, index) {
+            if let Ok(payment) = Self::get_payment_internal(&env, &payment_id) {
+                if payment.status == PaymentStatus::Pending {
+                    return Ok(payment);
+                }
+            }
+        }
+
+        let amount = InstalmentPlans::amount_of(&plan, index);
+        let payment_id = InstalmentPlans::next_payment_id(&env, &mut plan);
+        Self::validate_new_payment(&env, &payment_id, &plan.merchant_id, amount, &plan.currency)?;
+        let deposit_address = DepositPool::next_address(&env, &plan.merchant_id)?;
+        // The charge lapses when the plan would default, so an expired charge means a default
+        let expires_at = InstalmentPlans::due_at(&plan, index) + INSTALMENT_GRACE_PERIOD;
+        let payment = Self::create_payment_internal(
+            &env,
+            &caller,
+            payment_id.clone(),
+            plan.merchant_id.clone(),
+            amount,
+            plan.currency.clone(),
+            deposit_address,
+            expires_at,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )?;
+        InstalmentPlans::link_payment(&env, &plan, index, &payment_id);
+
+        env.events().publish(
+            (Symbol::new(&env, "PLAN"), Symbol::new(&env, "ISSUED")),
+            (plan_id, index, payment_id),
+        );
+
+        Ok(payment)
+    }
+
+    /// Get an instalment plan, its status judged against the current ledger time
+    pub fn get_plan(env: Env, plan_id: u64) -> Result<InstalmentPlan, Error> {
+        InstalmentPlans::get(&env, plan_id)
+    }
+
+    /// Every instalment of a plan with its amount, due date and charge
+    pub fn get_plan_schedule(env: Env, plan_id: u64) -> Result<Vec<ScheduledInstalment>, Error> {
+        let plan = InstalmentPlans::get(&env, plan_id)?;
+        Ok(InstalmentPlans::schedule(&env, &plan))
+    }
+
+    /// Publish a reusable payment link, fixed-price or open-amount; `max_uses` of 0 means
+    /// unlimited (merchant)
+    pub fn create_payment_link(
+        env: Env,
+        merchant_id: Address,
+        amount: Option<i128>,
+        currency: Symbol,
+        max_uses: u32,
+        expires_at: u64,
+    ) -> Result<PaymentLink, Error> {
+        merchant_id.require_auth();
+        Self::require_verified_merchant(&env, &merchant_id)?;
+        if !env
+            .storage()
+            .persistent()
+            .has(&DataKey::AllowedToken(currency.clone()))
+        {
+            return Err(Error::UnsupportedCurrency);
+        }
+
+        let link = PaymentLinks::create(&env, merchant_id, amount, currency, max_uses, expires_at)?;
+
+        env.events().publish(
+            (Symbol::new(&env, "LINK"), Symbol::new(&env, "CREATED")),
+            (link.link_id, link.merchant_id.clone()),
+        );
+
+        Ok(link)
+    }
+
+    /// Use a payment link, raising a charge for `amount` on the next address from the
+    /// merchant's deposit pool (payer)
+    pub fn pay_link(
+        env: Env,
+        link_id: u64,
+        payer: Address,
+        amount: i128,
+    ) -> Result<PaymentCharge, Error> {
+        payer.require_auth();
+        let mut link = PaymentLinks::get(&env, link_id)?;
+        let payment_id = PaymentLinks::use_link(&env, &mut link, amount)?;
+
+        Self::validate_new_payment(&env, &payment_id, &link.merchant_id, amount, &link.currency)?;
+        let deposit_address = DepositPool::next_address(&env, &link.merchant_id)?;
+        let payment = Self::create_payment_internal(
+            &env,
+            &payer,
+            payment_id.clone(),
+            link.merchant_id.clone(),
+            amount,
+            link.currency.clone(),
+            deposit_address,
+            Clock::now(&env) + LINK_CHARGE_WINDOW,
+            String::from_str(&env, ""),
+            Map::new(&env),
+        )?;
+
+        env.events().publish(
+            (Symbol::new(&env, "LINK"), Symbol::new(&env, "USED")),
+            (link_id, payment_id, payer),
+        );
+
+        Ok(payment)
+    }
+
+    /// Stop a payment link from raising further charges (merchant)
+    pub fn deactivate_link(env: Env, merchant_id: Address, link_id: u64) -> Result<(), Error> {
+        merchant_id.require_auth();
+        let mut link = PaymentLinks::get(&env, link_id)?;
+        if link.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        link.active = false;
+        PaymentLinks::save(&env, &link);
+
+        env.events().publish(
+            (Symbol::new(&env, "LINK"), Symbol::new(&env, "DEACTIVATED")),
+            link_id,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_payment_link(env: Env, link_id: u64) -> Result<PaymentLink, Error> {
+        PaymentLinks::get(&env, link_id)
+    }
+
+    /// Every payment link a merchant has published
+    pub fn get_merchant_links(env: Env, merchant_id: Address) -> Vec<PaymentLink> {
+        PaymentLinks::get_by_merchant(&env, &merchant_id)
+    }
+
+    // Helper functions
+    fn publish_slash(env: &Env, proposal: &SlashProposal) {
+        if proposal.executed {
+            env.events().publish(
+                (Symbol::new(env, "KEEPER"), Symbol::new(env, "SLASHED")),
+                (proposal.keeper.clone(), proposal.amount),
+            );
+        }
+    }
+
+    // Shared by `initialize` and the deploy-time constructor; contracts set up before the
+    // flag existed are recognised by their admin
+    fn init(
+        env: &Env,
+        admin: Address,
+        merchant_registry: Address,
+        tokens: Map<Symbol, Address>,
+        fee_config: Option<FeeConfig>,
+    ) -> Result<(), Error> {
+        if env.storage().instance().has(&DataKey::Initialized)
+            || AccessControl::get_admin(env).is_some()
+        {
+            return Err(Error::AlreadyInitialized);
+        }
+        if let Some(fee_config) = fee_config {
+            Fees::set_config(env, fee_config.fee_bps, fee_config.fee_collector)?;
+        }
+
+        AccessControl::initialize(env, admin);
+        env.storage()
+            .persistent()
+            .set(&DataKey::MerchantRegistry, &merchant_registry);
+        for (currency, token) in tokens.iter() {
+            Self::store_supported_token(env, currency, token);
+        }
+        env.storage().instance().set(&DataKey::Initialized, &true);
+        Ok(())
+    }
+
+    fn store_supported_token(env: &Env, currency: Symbol, token: Address) {
+        let key = DataKey::AllowedToken(currency.clone());
+        if !env.storage().persistent().has(&key) {
+            let mut currencies = Self::get_supported_currencies(env);
+            currencies.push_back(currency.clone());
+            env.storage()
+                .persistent()
+                .set(&DataKey::SupportedCurrencies, &currencies);
+        }
+        env.storage().persistent().set(&key, &token);
+
+        env.events().publish(
+            (Symbol::new(env, "TOKEN"), Symbol::new(env, "ADDED")),
+            (currency, token),
+        );
+    }
+
+    fn require_admin(env: &Env, admin: &Address) -> Result<(), Error> {
+        admin.require_auth();
+        AccessControl::require_role(env, &role_admin(env), admin).map_err(Error::from)
+    }
+
+    fn require_compliance(env: &Env, officer: &Address) -> Result<(), Error> {
+        officer.require_auth();
+        AccessControl::require_role(env, &role_compliance(env), officer).map_err(Error::from)
+    }
+
+    // Sensitive views are open to readers and admins, and to the account they describe
+    fn require_reader(env: &Env, caller: &Address, subject: Option<&Address>) -> Result<(), Error> {
+        caller.require_auth();
+        if subject == Some(caller) || AccessControl::can_read(env, caller) {
+            return Ok(());
+        }
+        Err(Error::Unauthorized)
+    }
+
+    fn validate_new_payment(
+        env: &Env,
+        payment_id: &String,
+        merchant_id: &Address,
+        amount: i128,
+        currency: &Symbol,
+    ) -> Result<Merchant, Error> {
+        Pausable::require_not_paused(env, PauseScope::Payments)?;
+        Pausable::require_currency_not_paused(env, currency)?;
+
+        // Validate input
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        if !env
+            .storage()
+            .persistent()
+            .has(&DataKey::AllowedToken(currency.clone()))
+        {
+            return Err(Error::UnsupportedCurrency);
+        }
+        if let Some(accepted) = env
+            .storage()
+            .persistent()
+            .get::<_, Vec<Symbol>>(&DataKey::AcceptedCurrencies(merchant_id.clone()))
+        {
+            if !accepted.contains(currency) {
+                return Err(Error::CurrencyNotAccepted);
+            }
+        }
+
+        // Validate payment_id is non-empty, bounded and URL-safe
+        validate_id(payment_id)?;
+
+        // Check if payment already exists, including as an archived summary
+        if env
+            .storage()
+            .persistent()
+            .has(&DataKey::Payment(payment_id.clone()))
+            || Archive::is_archived(env, payment_id)
+        {
+            return Err(Error::PaymentAlreadyExists);
+        }
+
+        // Only verified, active merchants may accept payments, within their category's and
+        // their own compliance caps
+        let merchant = Self::require_verified_merchant(env, merchant_id)?;
+        RateLimits::check(env, merchant_id)?;
+        if let Some(policy) = CategoryPolicies::get(env, &merchant.category) {
+            CategoryPolicies::check_amount(&policy, amount)?;
+        }
+        if let Some(registry) = env
+            .storage()
+            .persistent()
+            .get::<_, Address>(&DataKey::MerchantRegistry)
+        {
+            let limits =
+                MerchantRegistryClient::new(env, &registry).get_merchant_limits(merchant_id);
+            Limits::check(env, merchant_id, &limits, amount)?;
+        }
+        Ok(merchant)
+    }
+
+    // A new charge's deadline: 0 takes the configured default, anything else must lie ahead and
+    // within the configured maximum
+    fn resolve_expiry(env: &Env, expires_at: u64) -> Result<u64, Error> {
+        let config = Configs::get(env);
+        let now = Clock::now(env);
+        if expires_at == 0 {
+            return Ok(now + config.default_expiry);
+        }
+        if expires_at <= now || expires_at - now > config.max_expiry {
+            return Err(Error::InvalidExpiry);
+        }
+        Ok(expires_at)
+    }
+
+    fn validate_metadata(env: &Env, metadata: &Map<Symbol, String>) -> Result<(), Error> {
+        if metadata.len() > Configs::get(env).max_metadata_size {
+            return Err(Error::InvalidMetadata);
+        }
+        for value in metadata.values().iter() {
+            if value.len() > MAX_METADATA_VALUE_LEN {
+                return Err(Error::InvalidMetadata);
+            }
+        }
+        Ok(())
+    }
+
+    fn require_merchant_or_delegate(
+        env: &Env,
+        merchant_id: &Address,
+        caller: &Address,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+        if caller == merchant_id {
+            return Ok(());
+        }
+        let registry: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::MerchantRegistry)
+            .ok_or(Error::Unauthorized)?;
+        if !MerchantRegistryClient::new(env, &registry).is_delegate(merchant_id, caller) {
+            return Err(Error::Unauthorized);
+        }
+        Ok(())
+    }
+
+    fn require_verified_merchant(env: &Env, merchant_id: &Address) -> Result<Merchant, Error> {
+        let registry: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::MerchantRegistry)
+            .ok_or(Error::MerchantNotVerified)?;
+
+        match MerchantRegistryClient::new(env, &registry).try_get_merchant(merchant_id) {
+            Ok(Ok(merchant)) if merchant.verified && merchant.active => Ok(merchant),
+            Ok(Ok(_)) => Err(Error::MerchantNotVerified),
+            Err(Ok(error)) => Err(error.into()),
+            _ => Err(Error::MerchantRegistryError),
+        }
+    }
+
+    fn get_merchant(env: &Env, merchant_id: &Address) -> Option<Merchant> {
+        let registry: Address = env.storage().persistent().get(&DataKey::MerchantRegistry)?;
+        match MerchantRegistryClient::new(env, &registry).try_get_merchant(merchant_id) {
+            Ok(Ok(merchant)) => Some(merchant),
+            _ => None,
+        }
+    }
+
+    // The merchant's payout cadence, or None while a Threshold merchant's pending balance in
+    // `currency` is below its minimum
+    fn payout_cadence(
+        env: &Env,
+        merchant_id: &Address,
+        currency: &Symbol,
+    ) -> Option<PayoutCadence> {
+        let preference = match env
+            .storage()
+            .persistent()
+            .get::<_, Address>(&DataKey::MerchantRegistry)
+        {
+            Some(registry) => {
+                MerchantRegistryClient::new(env, &registry).get_payout_preference(merchant_id)
+            }
+            None => return Some(PayoutCadence::Immediate),
+        };
+        if preference.cadence == PayoutCadence::Threshold
+            && EscrowLedger::get_pending(env, merchant_id, currency) < preference.min_amount
+        {
+            return None;
+        }
+        Some(preference.cadence)
+    }
+
+    // Escrow charges not held back by a compliance block or missing approvals
+    fn payout_eligible(env: &Env, payment: &PaymentCharge) -> bool {
+        payment.custody_mode == CustodyMode::Escrow
+            && Compliance::require_clear(
+                env,
+                &[Some(&payment.merchant_id), payment.payer_address.as_ref()],
+            )
+            .is_ok()
+            && Approvals::is_satisfied(env, &payment.payment_id, payment.amount)
+    }
+
+    fn category_policy(env: &Env, merchant_id: &Address) -> Option<CategoryPolicy> {
+        let merchant = Self::get_merchant(env, merchant_id)?;
+        CategoryPolicies::get(env, &merchant.category)
+    }
+
+    // Fee charged to the merchant, honouring its category's fee where it has no override
+    fn merchant_fee_bps(env: &Env, merchant_id: &Address) -> u32 {
+        let category_fee_bps = Self::category_policy(env, merchant_id).and_then(|p| p.fee_bps);
+        Fees::effective_fee_bps(env, merchant_id, category_fee_bps)
+    }
+
+    // Where a merchant's payouts go, falling back to merchant_id if the registry is unreachable
+    fn get_settlement_address(env: &Env, merchant_id: &Address) -> Address {
+        Self::get_merchant(env, merchant_id)
+            .map(|merchant| merchant.settlement_address)
+            .unwrap_or_else(|| merchant_id.clone())
+    }
+
+    fn get_supported_currencies(env: &Env) -> Vec<Symbol> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::SupportedCurrencies)
+            .unwrap_or(vec![env])
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_payment_internal(
+        env: &Env,
+        actor: &Address,
+        payment_id: String,
+        merchant_id: Address,
+        amount: i128,
+        currency: Symbol,
+        deposit_address: Address,
+        expires_at: u64,
+        order_reference: String,
+        metadata: Map<Symbol, String>,
+    ) -> Result<PaymentCharge, Error> {
+        let merchant =
+            Self::validate_new_payment(env, &payment_id, &merchant_id, amount, &currency)?;
+        Self::validate_metadata(env, &metadata)?;
+        // Charges raised from subscriptions, invoices, plans and links derive their own expiry;
+        // one past the configured maximum is cut back to it rather than left unbillable
+        let longest = Clock::now(env) + Configs::get(env).max_expiry;
+        let expires_at = Self::resolve_expiry(env, expires_at.min(longest))?;
+
+        // Balance-check verification breaks if two Pending charges share an address
+        DepositPool::activate(env, &deposit_address, &payment_id)?;
+
+        let payment = Self::insert_payment(
+            env,
+            payment_id,
+            &merchant,
+            amount,
+            currency,
+            deposit_address,
+            expires_at,
+            order_reference,
+            metadata,
+            Some(actor),
+        );
+        AuditLog::append(env, actor, "CREATE", payment.payment_id.clone());
+        Ok(payment)
+    }
+
+    // Store and index a validated charge; the caller has claimed its deposit address
+    #[allow(clippy::too_many_arguments)]
+    fn insert_payment(
+        env: &Env,
+        payment_id: String,
+        merchant: &Merchant,
+        amount: i128,
+        currency: Symbol,
+        deposit_address: Address,
+        expires_at: u64,
+        order_reference: String,
+        metadata: Map<Symbol, String>,
+        actor: Option<&Address>,
+    ) -> PaymentCharge {
+        // Create payment struct
+        let payment = PaymentCharge {
+            payment_id: payment_id.clone(),
+            merchant_id: merchant.merchant_id.clone(),
+            amount,
+            currency,
+            deposit_address,
+            order_reference,
+            metadata,
+            status: PaymentStatus::Pending,
+            payer_address: None,
+            payer_commitment: None,
+            transaction_hash: None,
+            created_at: env.ledger().timestamp(),
+            confirmed_at: None,
+            expires_at,
+            fee_amount: 0,
+            refunded_amount: 0,
+            overpaid_amount: 0,
+            settled_at: None,
+            custody_mode: merchant.custody_mode.clone(),
+            payout_currency: None,
+            payout_amount: None,
+            payout_rate: None,
+            expected_payer: None,
+            accept_overpayment: false,
+            tip_amount: 0,
+        };
+
+        // Store payment
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+        ttl::extend(env, &DataKey::Payment(payment_id.clone()));
+
+        // Index payment under its status, merchant and creation time
+        Self::add_to_status_index(env, &payment.status, &payment_id);
+        Limits::record(env, &payment.merchant_id, amount);
+        RateLimits::record(env, &payment.merchant_id);
+        TimeIndex::record(env, RecordKind::Payment, payment_id.clone(), amount);
+        ExpiryTracker::record_created(env, &payment.merchant_id);
+        Self::record_status(env, &payment, &payment.status);
+
+        let merchant_key = DataKey::MerchantPayments(payment.merchant_id.clone());
+        let mut merchant_payments: Vec<String> = env
+            .storage()
+            .persistent()
+            .get(&merchant_key)
+            .unwrap_or(vec![env]);
+        merchant_payments.push_back(payment_id.clone());
+        env.storage()
+            .persistent()
+            .set(&merchant_key, &merchant_payments);
+
+        // Emit payment created event
+        events::payment(env, "CREATED", &payment, actor);
+
+        payment
+    }
+
+    fn get_payment_internal(env: &Env, payment_id: &String) -> Result<PaymentCharge, Error> {
+        let key = DataKey::Payment(payment_id.clone());
+        let payment = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .ok_or(Error::PaymentNotFound)?;
+        ttl::extend(env, &key);
+        Ok(payment)
+    }
+
+    // Expire a single payment if it is Pending and past expiry, skipping anything else
+    fn try_expire(env: &Env, payment_id: &String) -> bool {
+        let mut payment = match Self::get_payment_internal(env, payment_id) {
+            Ok(payment) => payment,
+            Err(_) => return false,
+        };
+        if payment.status != PaymentStatus::Pending || Clock::now(env) <= payment.expires_at {
+            return false;
+        }
+
+        Self::set_status(env, &mut payment, PaymentStatus::Expired);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        events::payment(env, "EXPIRED", &payment, None);
+        true
+    }
+
+    // Log a status change on both the payment's and the merchant's audit trail
+    fn record_status(env: &Env, payment: &PaymentCharge, status: &PaymentStatus) {
+        let detail = Symbol::new(
+            env,
+            match status {
+                PaymentStatus::Pending => "PENDING",
+                PaymentStatus::Confirmed => "CONFIRMED",
+                PaymentStatus::Expired => "EXPIRED",
+                PaymentStatus::Failed => "FAILED",
+                PaymentStatus::Settled => "SETTLED",
+                PaymentStatus::Cancelled => "CANCELLED",
+                PaymentStatus::Authorized => "AUTHORIZED",
+                PaymentStatus::LateConfirmed => "LATE_CONFIRMED",
+            },
+        );
+        AuditLog::record(
+            env,
+            AuditEntity::Payment(payment.payment_id.clone()),
+            "STATUS",
+            Some(detail.clone()),
+            None,
+        );
+        AuditLog::record(
+            env,
+            AuditEntity::Merchant(payment.merchant_id.clone()),
+            "STATUS",
+            Some(detail),
+            Some(payment.payment_id.clone()),
+        );
+    }
+
+    // Credit the merchant for a newly confirmed charge; callers persist the payment itself
+    fn book_confirmation(env: &Env, payment: &mut PaymentCharge) {
+        // Private payers stay out of the history, which is keyed by address
+        if let Some(payer) = &payment.payer_address {
+            let key = DataKey::PayerPayments(payer.clone());
+            let mut history: Vec<String> =
+                env.storage().persistent().get(&key).unwrap_or(vec![env]);
+            history.push_back(payment.payment_id.clone());
+            env.storage().persistent().set(&key, &history);
+        }
+        Statements::credit(env, &payment.merchant_id, &payment.currency, payment.amount);
+        SettlementReports::record_confirmation(
+            env,
+            &payment.merchant_id,
+            &payment.currency,
+            payment.amount,
+        );
+
+        // Self-custody funds never reach escrow, so the fee is invoiced at confirmation
+        if payment.custody_mode == CustodyMode::SelfCustody {
+            let fee_bps = Promos::fee_bps(
+                env,
+                &payment.payment_id,
+                Self::merchant_fee_bps(env, &payment.merchant_id),
+            );
+            payment.fee_amount = Fees::compute_fee(payment.amount, fee_bps);
+            Fees::accrue_owed(
+                env,
+                &payment.merchant_id,
+                &payment.currency,
+                payment.fee_amount,
+            );
+            Fees::invoice(
+                env,
+                &payment.merchant_id,
+                &payment.currency,
+                FeeKind::Processing,
+                payment.fee_amount,
+            );
+            Self::split_partner_fee(env, payment, payment.fee_amount);
+            Fees::record_volume(env, &payment.merchant_id, payment.amount);
+            SettlementReports::record_fee(
+                env,
+                &payment.merchant_id,
+                &payment.currency,
+                payment.fee_amount,
+            );
+        } else {
+            AutoSettle::add(env, &payment.merchant_id, &payment.currency, payment.amount);
+            EscrowLedger::credit_merchant(
+                env,
+                &payment.merchant_id,
+                &payment.currency,
+                payment.amount,
+            );
+            EscrowLedger::credit(env, &payment.currency, payment.overpaid_amount);
+        }
+    }
+
+    // Expire a single authorization if it is past its capture deadline, skipping anything else
+    fn try_expire_authorization(env: &Env, payment_id: &String, now: u64) -> bool {
+        let lapsed = Authorizations::get(env, payment_id)
+            .is_ok_and(|authorization| now > authorization.capture_deadline);
+        let mut payment = match Self::get_payment_internal(env, payment_id) {
+            Ok(payment) if lapsed && payment.status == PaymentStatus::Authorized => payment,
+            _ => return false,
+        };
+        if Self::return_authorization(env, &mut payment, PaymentStatus::Expired).is_err() {
+            return false;
+        }
+
+        events::payment(env, "EXPIRED", &payment, None);
+        events::payment(env, "RELEASED", &payment, None);
+        true
+    }
+
+    // Return an authorization's held funds to its payer and close the charge as `status`
+    fn return_authorization(
+        env: &Env,
+        payment: &mut PaymentCharge,
+        status: PaymentStatus,
+    ) -> Result<i128, Error> {
+        let authorization = Authorizations::get(env, &payment.payment_id)?;
+        let token_address = Self::require_token(env, &payment.currency)?;
+        token::Client::new(env, &token_address).transfer(
+            &env.current_contract_address(),
+            &authorization.payer,
+            &authorization.amount,
+        );
+        Authorizations::close(env, &payment.payment_id);
+        Self::set_status(env, payment, status);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment.payment_id.clone()), payment);
+        Ok(authorization.amount)
+    }
+
+    fn require_token(env: &Env, currency: &Symbol) -> Result<Address, Error> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AllowedToken(currency.clone()))
+            .ok_or(Error::UnsupportedCurrency)
+    }
+
+    // Move a payment between status indexes; callers persist the payment itself
+    fn set_status(env: &Env, payment: &mut PaymentCharge, status: PaymentStatus) {
+        if payment.status == PaymentStatus::Pending && status != PaymentStatus::Pending {
+            DepositPool::release(env, &payment.deposit_address, &payment.payment_id);
+        }
+        if status == PaymentStatus::Expired {
+            ExpiryTracker::record_expired(env, &payment.merchant_id);
+        }
+        if status == PaymentStatus::Confirmed {
+            if let Some(invoice) = Invoices::mark_paid(env, &payment.payment_id) {
+                env.events().publish(
+                    (Symbol::new(env, "INVOICE"), Symbol::new(env, "PAID")),
+                    (invoice.invoice_id, payment.payment_id.clone()),
+                );
+            }
+            if let Some(plan) = InstalmentPlans::mark_paid(env, &payment.payment_id) {
+                env.events().publish(
+                    (Symbol::new(env, "PLAN"), Symbol::new(env, "PAID")),
+                    (plan.plan_id, plan.paid_count, payment.payment_id.clone()),
+                );
+            }
+        }
+        Self::record_status(env, payment, &status);
+
+        Self::remove_from_status_index(env, &payment.status, &payment.payment_id);
+        Self::add_to_status_index(env, &status, &payment.payment_id);
+        payment.status = status;
+    }
+
+    fn remove_from_status_index(env: &Env, status: &PaymentStatus, payment_id: &String) {
+        let mut index = Self::get_status_index(env, status);
+        if let Some(i) = index.first_index_of(payment_id) {
+            index.remove(i);
+            env.storage()
+                .persistent()
+                .set(&DataKey::PaymentsByStatus(status.clone()), &index);
+        }
+    }
+
+    fn remove_from_index(env: &Env, key: &DataKey, payment_id: &String) {
+        let mut index: Vec<String> = env.storage().persistent().get(key).unwrap_or(vec![env]);
+        if let Some(i) = index.first_index_of(payment_id) {
+            index.remove(i);
+            env.storage().persistent().set(key, &index);
+        }
+    }
+
+    fn add_to_status_index(env: &Env, status: &PaymentStatus, payment_id: &String) {
+        let mut index = Self::get_status_index(env, status);
+        index.push_back(payment_id.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::PaymentsByStatus(status.clone()), &index);
+    }
+
+    fn get_status_index(env: &Env, status: &PaymentStatus) -> Vec<String> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PaymentsByStatus(status.clone()))
+            .unwrap_or(vec![env])
+    }
+
+    // Credit any referral partner on the charge with its share of the processing fee
+    fn split_partner_fee(env: &Env, payment: &PaymentCharge, processing_fee: i128) -> i128 {
+        let partner_fee =
+            Partners::split(env, &payment.payment_id, &payment.currency, processing_fee);
+        if partner_fee > 0 {
+            Fees::invoice_partner_share(env, &payment.merchant_id, &payment.currency, partner_fee);
+        }
+        partner_fee
+    }
+
+    // Mark a confirmed escrow payment Settled, paying its fee out to the collector and partner
+    fn settle_internal(
+        env: &Env,
+        operator: &Address,
+        payment_id: &String,
+    ) -> Result<PaymentCharge, Error> {
+        Pausable::require_not_paused(env, PauseScope::Settlements)?;
+        let mut payment = Self::get_payment_internal(env, payment_id)?;
+        if payment.status != PaymentStatus::Confirmed {
+            return Err(Error::PaymentNotConfirmed);
+        }
+        if payment.custody_mode == CustodyMode::SelfCustody {
+            return Err(Error::SettlementNotRequired);
+        }
+        Pausable::require_currency_not_paused(env, &payment.currency)?;
+        Compliance::require_clear(
+            env,
+            &[Some(&payment.merchant_id), payment.payer_address.as_ref()],
+        )?;
+        Approvals::consume(env, payment_id, payment.amount)?;
+        SpendGuard::spend(
+            env,
+            operator,
+            &role_settlement_operator(env),
+            payment.amount,
+        )?;
+
+        let fee_bps = Promos::fee_bps(
+            env,
+            &payment.payment_id,
+            Self::merchant_fee_bps(env, &payment.merchant_id),
+        );
+        let mut fee = Fees::compute_fee(payment.amount, fee_bps);
+        Fees::invoice(
+            env,
+            &payment.merchant_id,
+            &payment.currency,
+            FeeKind::Processing,
+            fee,
+        );
+        let partner_fee = Self::split_partner_fee(env, &payment, fee);
+        Fees::record_volume(env, &payment.merchant_id, payment.amount);
+
+        // Pay out in the merchant's settlement currency, falling back to the charge currency
+        let payout_currency = Self::get_merchant(env, &payment.merchant_id)
+            .and_then(|merchant| Rates::currency_symbol(env, &merchant.settlement_currency))
+            .unwrap_or_else(|| payment.currency.clone());
+        if payout_currency != payment.currency {
+            let fx_fee = Fees::compute_fee(payment.amount - fee, Fees::get_fx_fee(env));
+            Fees::invoice(
+                env,
+                &payment.merchant_id,
+                &payment.currency,
+                FeeKind::Fx,
+                fx_fee,
+            );
+            fee += fx_fee;
+        }
+        Fees::accrue(env, &payment.currency, fee - partner_fee);
+        let (payout_amount, payout_rate) = Rates::convert(
+            env,
+            payment.amount - fee,
+            &payment.currency,
+            &payout_currency,
+        )?;
+        if let Some(rate) = &payout_rate {
+            env.events().publish(
+                (Symbol::new(env, "PAYMENT"), Symbol::new(env, "CONVERTED")),
+                (payment_id.clone(), payout_amount, rate.clone()),
+            );
+        }
+        payment.payout_amount = Some(payout_amount);
+        payment.payout_currency = Some(payout_currency);
+        payment.payout_rate = payout_rate;
+
+        // Refunds already paid out of escrow were released when they were booked
+        AutoSettle::remove(
+            env,
+            &payment.merchant_id,
+            &payment.currency,
+            payment.amount - payment.refunded_amount,
+        );
+        EscrowLedger::debit_merchant(
+            env,
+            &payment.merchant_id,
+            &payment.currency,
+            payment.amount - payment.refunded_amount,
+        );
+
+        SettlementReports::record_fee(env, &payment.merchant_id, &payment.currency, fee);
+        SettlementReports::record_settlement(
+            env,
+            &payment.merchant_id,
+            &payment.currency,
+            payment.amount - fee,
+        );
+
+        payment.fee_amount = fee;
+        Self::pay_out_fees(env, &payment, partner_fee)?;
+        payment.settled_at = Some(Clock::now(env));
+        Self::set_status(env, &mut payment, PaymentStatus::Settled);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Payment(payment_id.clone()), &payment);
+
+        // Emitted per payment, so batch settlements are indexed the same way as single ones
+        events::payment(env, "SETTLED", &payment, Some(operator));
+        Ok(payment)
+    }
+
+    // Fold a settled payment into its merchant and currency's settlement line
+    fn add_settled(env: &Env, lines: &mut Vec<SettlementLine>, payment: &PaymentCharge) {
+        Settlements::add_to_lines(
+            lines,
+            &payment.merchant_id,
+            &Self::get_settlement_address(env, &payment.merchant_id),
+            &payment.currency,
+            payment.amount,
+            payment.fee_amount,
+            (payment.amount - payment.refunded_amount - payment.fee_amount).max(0),
+        );
+    }
+
+    // Pay a settlement line's net out of escrow to the merchant's settlement address, in the
+    // charge currency; any conversion into the payout currency happens at the off-ramp
+    fn pay_out_line(env: &Env, line: &SettlementLine) -> Result<(), Error> {
+        Self::pay_from_escrow(env, &line.currency, &line.settlement_address, line.net)
+    }
+
+    // Whether the linked RefundManager still has a dispute or an unprocessed refund open on the
+    // payment, either of which needs the full record to pay out
+    fn has_open_claims(env: &Env, payment_id: &String) -> bool {
+        let escrow: Address = match env.storage().persistent().get(&DataKey::RefundManager) {
+            Some(escrow) => escrow,
+            None => return false,
+        };
+        let refunds = RefundManagerClient::new(env, &escrow);
+        refunds.get_payment_dispute(payment_id).is_some()
+            || refunds
+                .get_payment_refunds(payment_id)
+                .iter()
+                .any(|refund| {
+                    matches!(
+                        refund.status,
+                        RefundStatus::Pending | RefundStatus::Approved
+                    )
+                })
+    }
+
+    // Pay a settled charge's referral share to its partner and the rest of its fee to the fee
+    // collector
+    fn pay_out_fees(env: &Env, payment: &PaymentCharge, partner_fee: i128) -> Result<(), Error> {
+        if !env.storage().persistent().has(&DataKey::RefundManager) {
+            return Ok(());
+        }
+        if let Some(share) = Partners::get_attribution(env, &payment.payment_id) {
+            Self::pay_from_escrow(env, &payment.currency, &share.partner, partner_fee)?;
+        }
+        let platform_fee = payment.fee_amount - partner_fee;
+        if platform_fee > 0 {
+            let collector = Fees::get_config(env)
+                .ok_or(Error::FeeCollectorNotSet)?
+                .fee_collector;
+            Self::pay_from_escrow(env, &payment.currency, &collector, platform_fee)?;
+        }
+        Ok(())
+    }
+
+    // Move funds out of the linked RefundManager's escrow; without one, settlement is kept on
+    // the books only
+    fn pay_from_escrow(
+        env: &Env,
+        currency: &Symbol,
+        to: &Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        let escrow: Address = match env.storage().persistent().get(&DataKey::RefundManager) {
+            Some(escrow) => escrow,
+            None => return Ok(()),
+        };
+        if amount <= 0 {
+            return Ok(());
+        }
+        let token = Self::require_token(env, currency)?;
+        match RefundManagerClient::new(env, &escrow).try_payout(&token, to, &amount) {
+            Ok(Ok(())) => Ok(()),
+            Err(Ok(error)) => Err(error),
+            _ => Err(Error::InsufficientEscrow),
+        }
+    }
+
+    // Close the merchant's statement period for this currency
+    fn close_statement(env: &Env, merchant: &Address, currency: &Symbol, fee: i128) {
+        let statement = Statements::close(env, merchant, currency, fee);
+        env.events()
+            .publish((Symbol::new(env, "STATEMENT"), merchant.clone()), statement);
+
+        let invoice = Fees::close_invoice(env, merchant, currency);
+        env.events().publish(
+            (Symbol::new(env, "FEE"), Symbol::new(env, "INVOICE")),
+            invoice,
+        );
+    }
+
+    fn load_payment_page(
+        env: &Env,
+        payment_ids: Vec<String>,
+        start: u32,
+        limit: u32,
+    ) -> PaymentPage {
+        let total = payment_ids.len();
+        let end = start.saturating_add(limit).min(total);
+
+        let mut payments = vec![env];
+        for i in start..end {
+            if let Some(payment_id) = payment_ids.get(i) {
+                if let Ok(payment) = Self::get_payment_internal(env, &payment_id) {
+                    payments.push_back(payment);
+                }
+            }
+        }
+
+        PaymentPage { payments, total }
+    }
+}
+
+// Deploy-time setup, compiled only into `constructor` builds: the contract is initialized in
+// the deployment transaction, leaving no window for anyone else to call `initialize` first
+#[cfg(feature = "constructor")]
+#[contractimpl]
+impl PaymentProcessor {
+    pub fn __constructor(
+        env: Env,
+        admin: Address,
+        merchant_registry: Address,
+        tokens: Map<Symbol, Address>,
+        fee_config: Option<FeeConfig>,
+    ) -> Result<(), Error> {
+        Self::init(&env, admin, merchant_registry, tokens, fee_config)
+    }
+}
+
+// Simulation hooks, compiled only into `sim-clock` builds for integration environments
+#[cfg(feature = "sim-clock")]
+#[contractimpl]
+impl PaymentProcessor {
+    /// Shift the clock used by expiry and settlement checks `offset` seconds ahead (admin only)
+    pub fn set_clock_offset(env: Env, admin: Address, offset: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Clock::set_offset(&env, offset);
+        Ok(())
+    }
+
+    pub fn get_clock_offset(env: Env) -> u64 {
+        Clock::get_offset(&env)
+    }
+}
+
+#[cfg(test)]
+mod budget_test;
+#[cfg(test)]
+mod integration_test;
 pub mod merchant_registry;
 #[cfg(test)]
 mod merchant_registry_test;
@@ -0,0 +1,173 @@
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::merchant_registry::MerchantLimits;
+use crate::Error;
+
+pub const DAY_SECONDS: u64 = 24 * 3600;
+pub const WEEK_SECONDS: u64 = 7 * DAY_SECONDS;
+
+// Charge volume created for a merchant since `window_start`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WindowVolume {
+    pub window_start: u64,
+    pub volume: i128,
+}
+
+// Caps a payer places on charges pulled from them under a subscription; 0 leaves one off
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayerLimits {
+    pub max_single_payment: i128,
+    pub max_daily_total: i128, // per merchant
+}
+
+#[contracttype]
+pub enum LimitDataKey {
+    Volume(Address, u64),          // (merchant, window length) -> WindowVolume
+    PayerLimits(Address),          // payer -> PayerLimits
+    PayerVolume(Address, Address), // (payer, merchant) -> WindowVolume over the day
+    Mandate(Address, Address),     // (payer, merchant) -> i128 the merchant may still collect
+}
+
+pub struct Limits;
+
+impl Limits {
+    /// Volume in the merchant's current window of `length` seconds, zero once it has lapsed
+    pub fn get_volume(env: &Env, merchant: &Address, length: u64) -> i128 {
+        Self::current(env, &LimitDataKey::Volume(merchant.clone(), length), length).volume
+    }
+
+    /// Fails if a new charge of `amount` would break any of the merchant's caps
+    pub fn check(
+        env: &Env,
+        merchant: &Address,
+        limits: &MerchantLimits,
+        amount: i128,
+    ) -> Result<(), Error> {
+        let over = |cap: i128, volume: i128| cap > 0 && volume + amount > cap;
+        if over(limits.max_single_payment, 0)
+            || over(
+                limits.max_daily_volume,
+                Self::get_volume(env, merchant, DAY_SECONDS),
+            )
+            || over(
+                limits.max_weekly_volume,
+                Self::get_volume(env, merchant, WEEK_SECONDS),
+            )
+        {
+            return Err(Error::LimitExceeded);
+        }
+        Ok(())
+    }
+
+    pub fn record(env: &Env, merchant: &Address, amount: i128) {
+        for length in [DAY_SECONDS, WEEK_SECONDS] {
+            let key = LimitDataKey::Volume(merchant.clone(), length);
+            let mut window = Self::current(env, &key, length);
+            window.volume += amount;
+            env.storage().persistent().set(&key, &window);
+        }
+    }
+
+    pub fn set_payer_limits(env: &Env, payer: &Address, limits: &PayerLimits) -> Result<(), Error> {
+        if limits.max_single_payment < 0 || limits.max_daily_total < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        env.storage()
+            .persistent()
+            .set(&LimitDataKey::PayerLimits(payer.clone()), limits);
+        Ok(())
+    }
+
+    pub fn clear_payer_limits(env: &Env, payer: &Address) {
+        env.storage()
+            .persistent()
+            .remove(&LimitDataKey::PayerLimits(payer.clone()));
+    }
+
+    pub fn get_payer_limits(env: &Env, payer: &Address) -> Option<PayerLimits> {
+        env.storage()
+            .persistent()
+            .get(&LimitDataKey::PayerLimits(payer.clone()))
+    }
+
+    /// Let `merchant` collect up to `amount` in total from `payer`; zero revokes the mandate
+    pub fn set_mandate(
+        env: &Env,
+        payer: &Address,
+        merchant: &Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        if amount < 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let key = LimitDataKey::Mandate(payer.clone(), merchant.clone());
+        if amount == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &amount);
+        }
+        Ok(())
+    }
+
+    pub fn get_mandate(env: &Env, payer: &Address, merchant: &Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&LimitDataKey::Mandate(payer.clone(), merchant.clone()))
+            .unwrap_or(0)
+    }
+
+    /// Draw `amount` from what `payer` lets `merchant` collect
+    pub fn spend_mandate(
+        env: &Env,
+        payer: &Address,
+        merchant: &Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        let remaining = Self::get_mandate(env, payer, merchant);
+        if amount > remaining {
+            return Err(Error::MandateExceeded);
+        }
+        Self::set_mandate(env, payer, merchant, remaining - amount)
+    }
+
+    /// Total pulled from `payer` toward `merchant` in the current day, zero once it has lapsed
+    pub fn get_payer_total(env: &Env, payer: &Address, merchant: &Address) -> i128 {
+        let key = LimitDataKey::PayerVolume(payer.clone(), merchant.clone());
+        Self::current(env, &key, DAY_SECONDS).volume
+    }
+
+    /// Count a pull of `amount` from `payer` toward `merchant`, unless it breaks the payer's caps
+    pub fn spend_payer(
+        env: &Env,
+        payer: &Address,
+        merchant: &Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        let limits = match Self::get_payer_limits(env, payer) {
+            Some(limits) => limits,
+            None => return Ok(()),
+        };
+        let key = LimitDataKey::PayerVolume(payer.clone(), merchant.clone());
+        let mut window = Self::current(env, &key, DAY_SECONDS);
+        let over = |cap: i128, volume: i128| cap > 0 && volume + amount > cap;
+        if over(limits.max_single_payment, 0) || over(limits.max_daily_total, window.volume) {
+            return Err(Error::PayerLimitExceeded);
+        }
+        window.volume += amount;
+        env.storage().persistent().set(&key, &window);
+        Ok(())
+    }
+
+    fn current(env: &Env, key: &LimitDataKey, length: u64) -> WindowVolume {
+        let now = env.ledger().timestamp();
+        match env.storage().persistent().get::<_, WindowVolume>(key) {
+            Some(window) if now < window.window_start + length => window,
+            _ => WindowVolume {
+                window_start: now,
+                volume: 0,
+            },
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use soroban_sdk::{contracttype, xdr::ToXdr, Address, Bytes, BytesN, Env, Symbol, Vec};
+
+use crate::Error;
+
+// Merchant-funded refund pools for mass-refund incidents: the merchant publishes a Merkle
+// root of (payer, amount) leaves and each payer pulls their own refund with a proof
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MassRefund {
+    pub pool_id: u64,
+    pub merchant_id: Address,
+    pub currency: Symbol,
+    pub token: Address,
+    pub merkle_root: BytesN<32>,
+    pub funded: i128,
+    pub claimed: i128,
+    pub claim_deadline: u64, // merchant may reclaim the remainder after this
+    pub created_at: u64,
+}
+
+#[contracttype]
+pub enum MassRefundDataKey {
+    Pool(u64),             // pool_id -> MassRefund
+    Claimed(u64, Address), // (pool_id, payer) -> bool
+    PoolCounter,           // u64 counter for pool IDs
+    Liability(Address),    // token -> i128 funded across pools, not yet claimed or reclaimed
+}
+
+pub struct MassRefunds;
+
+impl MassRefunds {
+    /// Leaf committed to in the tree: sha256(payer_xdr || amount as 16 big-endian bytes)
+    pub fn leaf(env: &Env, payer: &Address, amount: i128) -> BytesN<32> {
+        let mut preimage: Bytes = payer.clone().to_xdr(env);
+        preimage.append(&Bytes::from_array(env, &amount.to_be_bytes()));
+        env.crypto().sha256(&preimage).into()
+    }
+
+    /// Parent of two nodes, hashed in sorted order so proofs need no left/right flags
+    pub fn hash_pair(env: &Env, a: &BytesN<32>, b: &BytesN<32>) -> BytesN<32> {
+        let (first, second) = if a.to_array() <= b.to_array() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let mut preimage = Bytes::from(first.clone());
+        preimage.append(&Bytes::from(second.clone()));
+        env.crypto().sha256(&preimage).into()
+    }
+
+    pub fn verify(env: &Env, root: &BytesN<32>, leaf: BytesN<32>, proof: &Vec<BytesN<32>>) -> bool {
+        let mut node = leaf;
+        for sibling in proof.iter() {
+            node = Self::hash_pair(env, &node, &sibling);
+        }
+        node == *root
+    }
+
+    pub fn create(
+        env: &Env,
+        merchant_id: Address,
+        currency: Symbol,
+        token: Address,
+        merkle_root: BytesN<32>,
+        funded: i128,
+        claim_deadline: u64,
+    ) -> Result<MassRefund, Error> {
+        if funded <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if claim_deadline <= env.ledger().timestamp() {
+            return Err(Error::ClaimWindowClosed);
+        }
+
+        let pool_id: u64 = env
+            .storage()
+            .persistent()
+            .get(&MassRefundDataKey::PoolCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&MassRefundDataKey::PoolCounter, &pool_id);
+
+        let pool = MassRefund {
+            pool_id,
+            merchant_id,
+            currency,
+            token,
+            merkle_root,
+            funded,
+            claimed: 0,
+            claim_deadline,
+            created_at: env.ledger().timestamp(),
+        };
+        Self::save(env, &pool);
+        Self::add_liability(env, &pool.token, funded);
+        Ok(pool)
+    }
+
+    pub fn get(env: &Env, pool_id: u64) -> Result<MassRefund, Error> {
+        env.storage()
+            .persistent()
+            .get(&MassRefundDataKey::Pool(pool_id))
+            .ok_or(Error::MassRefundNotFound)
+    }
+
+    pub fn is_claimed(env: &Env, pool_id: u64, payer: &Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&MassRefundDataKey::Claimed(pool_id, payer.clone()))
+    }
+
+    /// Check a payer's proof and book their claim against the pool
+    pub fn claim(
+        env: &Env,
+        pool: &mut MassRefund,
+        payer: &Address,
+        proof: &Vec<BytesN<32>>,
+        amount: i128,
+    ) -> Result<(), Error> {
+        if env.ledger().timestamp() > pool.claim_deadline {
+            return Err(Error::ClaimWindowClosed);
+        }
+        if Self::is_claimed(env, pool.pool_id, payer) {
+            return Err(Error::MassRefundAlreadyClaimed);
+        }
+        let leaf = Self::leaf(env, payer, amount);
+        if !Self::verify(env, &pool.merkle_root, leaf, proof) {
+            return Err(Error::InvalidProof);
+        }
+        if pool.funded - pool.claimed < amount {
+            return Err(Error::InsufficientEscrow);
+        }
+
+        pool.claimed += amount;
+        Self::save(env, pool);
+        Self::add_liability(env, &pool.token, -amount);
+        env.storage().persistent().set(
+            &MassRefundDataKey::Claimed(pool.pool_id, payer.clone()),
+            &true,
+        );
+        Ok(())
+    }
+
+    /// Close the pool after the claim window, returning what was never claimed
+    pub fn reclaim(env: &Env, pool: &mut MassRefund) -> Result<i128, Error> {
+        if env.ledger().timestamp() <= pool.claim_deadline {
+            return Err(Error::ClaimWindowOpen);
+        }
+        let remainder = pool.funded - pool.claimed;
+        pool.funded = pool.claimed;
+        Self::save(env, pool);
+        Self::add_liability(env, &pool.token, -remainder);
+        Ok(remainder)
+    }
+
+    /// What open pools still owe payers in `token`; that much of the contract's balance is
+    /// not escrow
+    pub fn get_liability(env: &Env, token: &Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&MassRefundDataKey::Liability(token.clone()))
+            .unwrap_or(0)
+    }
+
+    fn add_liability(env: &Env, token: &Address, delta: i128) {
+        let liability = Self::get_liability(env, token) + delta;
+        env.storage()
+            .persistent()
+            .set(&MassRefundDataKey::Liability(token.clone()), &liability);
+    }
+
+    fn save(env: &Env, pool: &MassRefund) {
+        env.storage()
+            .persistent()
+            .set(&MassRefundDataKey::Pool(pool.pool_id), pool);
+    }
+}
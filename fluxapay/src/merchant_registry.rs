@@ -1,8 +1,38 @@
-use soroban_sdk::{contract, contracterror, contractimpl, contracttype, Address, Env, String};
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, vec, Address, BytesN, Env, String, Symbol,
+    Vec,
+};
+
+use crate::audit::{AuditLog, AuditRecord};
+use crate::events;
+use crate::ttl;
 
 #[contract]
 pub struct MerchantRegistry;
 
+/// Behaviour version new merchants are pinned to
+pub const DEFAULT_API_VERSION: u32 = 1;
+/// Newest behaviour version a merchant can opt into
+pub const LATEST_API_VERSION: u32 = 2;
+
+/// Webhook event mask bits, one per event topic relayers deliver
+pub const WEBHOOK_PAYMENT_EVENTS: u32 = 1 << 0;
+pub const WEBHOOK_REFUND_EVENTS: u32 = 1 << 1;
+pub const WEBHOOK_MERCHANT_EVENTS: u32 = 1 << 2;
+pub const WEBHOOK_ALL_EVENTS: u32 =
+    WEBHOOK_PAYMENT_EVENTS | WEBHOOK_REFUND_EVENTS | WEBHOOK_MERCHANT_EVENTS;
+pub const MAX_WEBHOOKS: u32 = 10;
+
+/// How a merchant's confirmed funds are handled
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CustodyMode {
+    /// Funds are held by FluxaPay and settled to the merchant, net of fees
+    Escrow,
+    /// Funds go straight to the merchant; FluxaPay only verifies and invoices fees
+    SelfCustody,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Merchant {
@@ -12,12 +42,62 @@ pub struct Merchant {
     pub verified: bool,
     pub active: bool,
     pub created_at: u64,
+    pub custody_mode: CustodyMode,
+    pub settlement_address: Address, // payout destination, defaults to merchant_id
+    pub api_version: u32,            // pinned behaviour version, see DEFAULT_API_VERSION
+    pub country: Symbol,             // ISO 3166 alpha-2, empty until KYC is submitted
+    pub kyc_document_hash: Option<BytesN<32>>, // hash of the off-chain KYC bundle
+    pub kyc_level: u32,              // 0 = unreviewed; higher tiers set by the admin
+    pub category: Symbol,            // MCC-style business category, empty until the admin sets it
+}
+
+/// Compliance caps on a merchant's charges, enforced by the PaymentProcessor; 0 means no cap
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantLimits {
+    pub max_single_payment: i128,
+    pub max_daily_volume: i128,  // over a rolling 24 hour window
+    pub max_weekly_volume: i128, // over a rolling 7 day window
+}
+
+/// How often `run_payouts` pays out a merchant's escrowed funds
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PayoutCadence {
+    /// Every confirmed charge on the next run
+    Immediate,
+    /// Charges confirmed before the current UTC day (T+1)
+    Daily,
+    /// Once the merchant's pending balance in a currency reaches `min_amount`
+    Threshold,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayoutPreference {
+    pub cadence: PayoutCadence,
+    pub min_amount: i128, // Threshold only; 0 for the other cadences
+}
+
+/// Where off-chain relayers should deliver a merchant's events; the endpoint URL itself
+/// stays off-chain and relayers match it by hash
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Webhook {
+    pub endpoint_hash: BytesN<32>,
+    pub event_mask: u32, // WEBHOOK_*_EVENTS bits
+    pub registered_at: u64,
 }
 
 #[contracttype]
 pub enum DataKey {
     Merchant(Address),
     Admin,
+    MerchantList,              // Vec<Address> in registration order
+    Delegates(Address),        // merchant_id -> Vec<Address> allowed to act for it
+    Limits(Address),           // merchant_id -> MerchantLimits
+    Webhooks(Address),         // merchant_id -> Vec<Webhook>
+    PayoutPreference(Address), // merchant_id -> PayoutPreference
 }
 
 #[contracterror]
@@ -29,6 +109,15 @@ pub enum Error {
     Unauthorized = 3,
     NotVerified = 4,
     AdminAlreadySet = 5,
+    InvalidApiVersion = 6,
+    KycNotSubmitted = 7,
+    DelegateAlreadyExists = 8,
+    DelegateNotFound = 9,
+    InvalidLimits = 10,
+    InvalidEventMask = 11,
+    WebhookNotFound = 12,
+    TooManyWebhooks = 13,
+    InvalidPayoutPreference = 14,
 }
 
 #[contractimpl]
@@ -66,12 +155,27 @@ impl MerchantRegistry {
             verified: false,
             active: true,
             created_at: env.ledger().timestamp(),
+            custody_mode: CustodyMode::Escrow,
+            settlement_address: merchant_id.clone(),
+            api_version: DEFAULT_API_VERSION,
+            country: Symbol::new(&env, ""),
+            kyc_document_hash: None,
+            category: Symbol::new(&env, ""),
+            kyc_level: 0,
         };
 
         env.storage()
             .persistent()
-            .set(&DataKey::Merchant(merchant_id), &merchant);
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+        ttl::extend(&env, &DataKey::Merchant(merchant_id.clone()));
+
+        let mut merchants = Self::get_merchant_list(&env);
+        merchants.push_back(merchant_id.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::MerchantList, &merchants);
 
+        events::merchant(&env, "REGISTERED", &merchant, &merchant_id);
         Ok(())
     }
 
@@ -99,18 +203,418 @@ impl MerchantRegistry {
 
         env.storage()
             .persistent()
-            .set(&DataKey::Merchant(merchant_id), &merchant);
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "UPDATED", &merchant, &merchant_id);
+        Ok(())
+    }
+
+    /// Switch between escrowed settlement and self-custody instant mode
+    pub fn set_custody_mode(
+        env: Env,
+        merchant_id: Address,
+        mode: CustodyMode,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.custody_mode = mode;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "UPDATED", &merchant, &merchant_id);
+        Ok(())
+    }
+
+    /// Route payouts to a wallet other than the merchant's auth key, e.g. a cold wallet
+    pub fn set_settlement_address(
+        env: Env,
+        merchant_id: Address,
+        new_address: Address,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.settlement_address = new_address;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "UPDATED", &merchant, &merchant_id);
+        Ok(())
+    }
+
+    /// Pin the merchant to a behaviour version, opting in to (or rolling back) upgrades
+    pub fn set_api_version(env: Env, merchant_id: Address, api_version: u32) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        if api_version == 0 || api_version > LATEST_API_VERSION {
+            return Err(Error::InvalidApiVersion);
+        }
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.api_version = api_version;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "UPDATED", &merchant, &merchant_id);
+        Ok(())
+    }
+
+    /// Submit KYC documents for review; a new submission resets the merchant's KYC level
+    pub fn submit_kyc(
+        env: Env,
+        merchant_id: Address,
+        country: Symbol,
+        document_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.country = country;
+        merchant.kyc_document_hash = Some(document_hash);
+        merchant.kyc_level = 0;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "KYC_SUBMITTED", &merchant, &merchant_id);
+        Ok(())
+    }
+
+    /// Record the KYC tier a merchant's submitted documents were reviewed at (admin only)
+    pub fn set_kyc_level(
+        env: Env,
+        admin: Address,
+        merchant_id: Address,
+        level: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        if merchant.kyc_document_hash.is_none() {
+            return Err(Error::KycNotSubmitted);
+        }
+        merchant.kyc_level = level;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "KYC_LEVEL", &merchant, &admin);
+        Ok(())
+    }
+
+    /// Assign the merchant's business category, which selects the PaymentProcessor's
+    /// category policy for its charges (admin only)
+    pub fn set_merchant_category(
+        env: Env,
+        admin: Address,
+        merchant_id: Address,
+        category: Symbol,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.category = category;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+
+        events::merchant(&env, "UPDATED", &merchant, &admin);
+        Ok(())
+    }
+
+    /// Cap a merchant's single charges and rolling daily and weekly volume (admin only)
+    pub fn set_merchant_limits(
+        env: Env,
+        admin: Address,
+        merchant_id: Address,
+        limits: MerchantLimits,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Self::get_merchant_internal(&env, &merchant_id)?;
+        if limits.max_single_payment < 0
+            || limits.max_daily_volume < 0
+            || limits.max_weekly_volume < 0
+        {
+            return Err(Error::InvalidLimits);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Limits(merchant_id.clone()), &limits);
+
+        env.events().publish(
+            (Symbol::new(&env, "MERCHANT"), Symbol::new(&env, "LIMITS")),
+            (merchant_id, limits, admin),
+        );
+
+        Ok(())
+    }
+
+    /// The merchant's caps, all 0 (uncapped) unless the admin has set them
+    pub fn get_merchant_limits(env: Env, merchant_id: Address) -> MerchantLimits {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Limits(merchant_id))
+            .unwrap_or(MerchantLimits {
+                max_single_payment: 0,
+                max_daily_volume: 0,
+                max_weekly_volume: 0,
+            })
+    }
+
+    /// Choose when the merchant's escrowed funds are paid out by scheduled payout runs
+    pub fn set_payout_preference(
+        env: Env,
+        merchant_id: Address,
+        preference: PayoutPreference,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        Self::get_merchant_internal(&env, &merchant_id)?;
+        let valid = match preference.cadence {
+            PayoutCadence::Threshold => preference.min_amount > 0,
+            _ => preference.min_amount == 0,
+        };
+        if !valid {
+            return Err(Error::InvalidPayoutPreference);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::PayoutPreference(merchant_id.clone()), &preference);
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MERCHANT"),
+                Symbol::new(&env, "PAYOUT_PREF"),
+            ),
+            (merchant_id, preference),
+        );
+
+        Ok(())
+    }
+
+    /// The merchant's payout preference, Immediate unless it has chosen otherwise
+    pub fn get_payout_preference(env: Env, merchant_id: Address) -> PayoutPreference {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PayoutPreference(merchant_id))
+            .unwrap_or(PayoutPreference {
+                cadence: PayoutCadence::Immediate,
+                min_amount: 0,
+            })
+    }
+
+    /// Let another address (e.g. a backend hot key) act for the merchant
+    pub fn add_delegate(env: Env, merchant_id: Address, delegate: Address) -> Result<(), Error> {
+        merchant_id.require_auth();
+        Self::get_merchant_internal(&env, &merchant_id)?;
+
+        let mut delegates = Self::get_delegates(env.clone(), merchant_id.clone());
+        if delegates.contains(&delegate) {
+            return Err(Error::DelegateAlreadyExists);
+        }
+        delegates.push_back(delegate);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Delegates(merchant_id), &delegates);
+
+        Ok(())
+    }
+
+    /// Revoke a delegate's right to act for the merchant
+    pub fn remove_delegate(env: Env, merchant_id: Address, delegate: Address) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut delegates = Self::get_delegates(env.clone(), merchant_id.clone());
+        let i = delegates
+            .first_index_of(&delegate)
+            .ok_or(Error::DelegateNotFound)?;
+        delegates.remove(i);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Delegates(merchant_id), &delegates);
+
+        Ok(())
+    }
+
+    pub fn get_delegates(env: Env, merchant_id: Address) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Delegates(merchant_id))
+            .unwrap_or(vec![&env])
+    }
+
+    pub fn is_delegate(env: Env, merchant_id: Address, account: Address) -> bool {
+        Self::get_delegates(env, merchant_id).contains(&account)
+    }
+
+    /// Ask relayers to deliver the events in `event_mask` to the endpoint hashing to
+    /// `endpoint_hash`; registering the same endpoint again replaces its mask
+    pub fn register_webhook(
+        env: Env,
+        merchant_id: Address,
+        endpoint_hash: BytesN<32>,
+        event_mask: u32,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        Self::get_merchant_internal(&env, &merchant_id)?;
+        if event_mask == 0 || event_mask & !WEBHOOK_ALL_EVENTS != 0 {
+            return Err(Error::InvalidEventMask);
+        }
+
+        let webhook = Webhook {
+            endpoint_hash: endpoint_hash.clone(),
+            event_mask,
+            registered_at: env.ledger().timestamp(),
+        };
+        let mut webhooks = Self::list_webhooks(env.clone(), merchant_id.clone());
+        match webhooks
+            .iter()
+            .position(|existing| existing.endpoint_hash == endpoint_hash)
+        {
+            Some(i) => webhooks.set(i as u32, webhook),
+            None if webhooks.len() >= MAX_WEBHOOKS => return Err(Error::TooManyWebhooks),
+            None => webhooks.push_back(webhook),
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Webhooks(merchant_id.clone()), &webhooks);
 
+        env.events().publish(
+            (
+                Symbol::new(&env, "WEBHOOK"),
+                Symbol::new(&env, "REGISTERED"),
+            ),
+            (merchant_id, endpoint_hash, event_mask),
+        );
         Ok(())
     }
 
+    /// Stop relaying the merchant's events to an endpoint
+    pub fn remove_webhook(
+        env: Env,
+        merchant_id: Address,
+        endpoint_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+
+        let mut webhooks = Self::list_webhooks(env.clone(), merchant_id.clone());
+        let i = webhooks
+            .iter()
+            .position(|webhook| webhook.endpoint_hash == endpoint_hash)
+            .ok_or(Error::WebhookNotFound)?;
+        webhooks.remove(i as u32);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Webhooks(merchant_id.clone()), &webhooks);
+
+        env.events().publish(
+            (Symbol::new(&env, "WEBHOOK"), Symbol::new(&env, "REMOVED")),
+            (merchant_id, endpoint_hash),
+        );
+        Ok(())
+    }
+
+    pub fn list_webhooks(env: Env, merchant_id: Address) -> Vec<Webhook> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Webhooks(merchant_id))
+            .unwrap_or(vec![&env])
+    }
+
     /// Get merchant info
     pub fn get_merchant(env: Env, merchant_id: Address) -> Result<Merchant, Error> {
         Self::get_merchant_internal(&env, &merchant_id)
     }
 
+    /// List merchants in registration order, `limit` at a time from `offset`
+    pub fn list_merchants(env: Env, offset: u32, limit: u32) -> Vec<Merchant> {
+        let merchants = Self::get_merchant_list(&env);
+        let end = offset.saturating_add(limit).min(merchants.len());
+
+        let mut page = vec![&env];
+        for i in offset..end {
+            if let Some(merchant_id) = merchants.get(i) {
+                if let Ok(merchant) = Self::get_merchant_internal(&env, &merchant_id) {
+                    page.push_back(merchant);
+                }
+            }
+        }
+        page
+    }
+
+    pub fn count_merchants(env: Env) -> u32 {
+        Self::get_merchant_list(&env).len()
+    }
+
+    /// List merchants matching both flags, `limit` at a time from `offset` into the matches
+    pub fn list_merchants_by_status(
+        env: Env,
+        verified: bool,
+        active: bool,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Merchant> {
+        let mut page = vec![&env];
+        let mut skipped = 0;
+        for merchant_id in Self::get_merchant_list(&env).iter() {
+            if page.len() >= limit {
+                break;
+            }
+            let merchant = match Self::get_merchant_internal(&env, &merchant_id) {
+                Ok(merchant) => merchant,
+                Err(_) => continue,
+            };
+            if merchant.verified != verified || merchant.active != active {
+                continue;
+            }
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            page.push_back(merchant);
+        }
+        page
+    }
+
     /// Verify merchant (admin only)
     pub fn verify_merchant(env: Env, admin: Address, merchant_id: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+
+        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
+        merchant.verified = true;
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant_id.clone()), &merchant);
+        AuditLog::append(&env, &admin, "MERCHANT_VERIFIED", merchant_id.to_string());
+
+        events::merchant(&env, "VERIFIED", &merchant, &admin);
+        Ok(())
+    }
+
+    /// Keep a merchant record live for at least `ledgers` more ledgers (anyone)
+    pub fn bump_merchant(env: Env, merchant_id: Address, ledgers: u32) -> Result<u32, Error> {
+        ttl::extend_by(&env, &DataKey::Merchant(merchant_id), ledgers)
+            .ok_or(Error::MerchantNotFound)
+    }
+
+    /// Journal records from `from_seq` on, oldest first
+    pub fn get_audit_entries(env: Env, from_seq: u64, limit: u32) -> Vec<AuditRecord> {
+        AuditLog::entries(&env, from_seq, limit)
+    }
+
+    // Helper functions
+    fn require_admin(env: &Env, admin: &Address) -> Result<(), Error> {
         admin.require_auth();
 
         let stored_admin: Address = env
@@ -119,25 +623,27 @@ impl MerchantRegistry {
             .get(&DataKey::Admin)
             .ok_or(Error::Unauthorized)?;
 
-        if admin != stored_admin {
+        if *admin != stored_admin {
             return Err(Error::Unauthorized);
         }
-
-        let mut merchant = Self::get_merchant_internal(&env, &merchant_id)?;
-        merchant.verified = true;
-
-        env.storage()
-            .persistent()
-            .set(&DataKey::Merchant(merchant_id), &merchant);
-
         Ok(())
     }
 
-    // Helper functions
     fn get_merchant_internal(env: &Env, merchant_id: &Address) -> Result<Merchant, Error> {
+        let key = DataKey::Merchant(merchant_id.clone());
+        let merchant = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .ok_or(Error::MerchantNotFound)?;
+        ttl::extend(env, &key);
+        Ok(merchant)
+    }
+
+    fn get_merchant_list(env: &Env) -> Vec<Address> {
         env.storage()
             .persistent()
-            .get(&DataKey::Merchant(merchant_id.clone()))
-            .ok_or(Error::MerchantNotFound)
+            .get(&DataKey::MerchantList)
+            .unwrap_or(vec![env])
     }
 }
@@ -1,7 +1,10 @@
 #![cfg(test)]
 
 use super::merchant_registry::*;
-use soroban_sdk::{testutils::Address as _, testutils::Ledger, Address, Env, String};
+use soroban_sdk::{
+    testutils::{Address as _, BytesN as _, Ledger},
+    Address, BytesN, Env, String, Symbol,
+};
 
 #[test]
 fn test_merchant_registration() {
@@ -108,3 +111,235 @@ fn test_unauthorized_verification() {
     // Attacker tries to verify the merchant
     client.verify_merchant(&attacker, &merchant_id);
 }
+
+#[test]
+fn test_set_custody_mode() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+
+    let merchant_id = Address::generate(&env);
+    client.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Self Custody Shop"),
+        &String::from_str(&env, "USD"),
+    );
+    assert_eq!(
+        client.get_merchant(&merchant_id).custody_mode,
+        CustodyMode::Escrow
+    );
+
+    client.set_custody_mode(&merchant_id, &CustodyMode::SelfCustody);
+    assert_eq!(
+        client.get_merchant(&merchant_id).custody_mode,
+        CustodyMode::SelfCustody
+    );
+}
+
+#[test]
+fn test_set_settlement_address() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+
+    let merchant_id = Address::generate(&env);
+    client.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Cold Wallet Shop"),
+        &String::from_str(&env, "USD"),
+    );
+    assert_eq!(
+        client.get_merchant(&merchant_id).settlement_address,
+        merchant_id
+    );
+
+    let cold_wallet = Address::generate(&env);
+    client.set_settlement_address(&merchant_id, &cold_wallet);
+    assert_eq!(
+        client.get_merchant(&merchant_id).settlement_address,
+        cold_wallet
+    );
+
+    assert_eq!(
+        client.try_set_settlement_address(&Address::generate(&env), &cold_wallet),
+        Err(Ok(Error::MerchantNotFound))
+    );
+}
+
+#[test]
+fn test_list_merchants() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    client.initialize(&admin);
+
+    let merchant_ids = [(); 5].map(|_| Address::generate(&env));
+    for merchant_id in merchant_ids.iter() {
+        client.register_merchant(
+            merchant_id,
+            &String::from_str(&env, "Merchant"),
+            &String::from_str(&env, "USDC"),
+        );
+    }
+    assert_eq!(client.count_merchants(), 5);
+
+    let page = client.list_merchants(&1, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().merchant_id, merchant_ids[1]);
+    assert_eq!(page.get(1).unwrap().merchant_id, merchant_ids[2]);
+    assert_eq!(client.list_merchants(&4, &10).len(), 1);
+    assert_eq!(client.list_merchants(&5, &10).len(), 0);
+
+    // Verify three merchants and deactivate one of them
+    for merchant_id in merchant_ids[..3].iter() {
+        client.verify_merchant(&admin, merchant_id);
+    }
+    client.update_merchant(&merchant_ids[0], &None, &None, &Some(false));
+
+    let verified_active = client.list_merchants_by_status(&true, &true, &0, &10);
+    assert_eq!(verified_active.len(), 2);
+    assert_eq!(verified_active.get(0).unwrap().merchant_id, merchant_ids[1]);
+    let page = client.list_merchants_by_status(&true, &true, &1, &10);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().merchant_id, merchant_ids[2]);
+    assert_eq!(
+        client
+            .list_merchants_by_status(&false, &true, &0, &10)
+            .len(),
+        2
+    );
+    assert_eq!(
+        client
+            .list_merchants_by_status(&true, &false, &0, &10)
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_kyc_submission_and_levels() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    client.initialize(&admin);
+
+    let merchant_id = Address::generate(&env);
+    client.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Lagos Textiles"),
+        &String::from_str(&env, "USDC"),
+    );
+    assert_eq!(client.get_merchant(&merchant_id).kyc_document_hash, None);
+    assert_eq!(
+        client.try_set_kyc_level(&admin, &merchant_id, &1),
+        Err(Ok(Error::KycNotSubmitted))
+    );
+
+    let document_hash = BytesN::<32>::random(&env);
+    client.submit_kyc(&merchant_id, &Symbol::new(&env, "NG"), &document_hash);
+    assert_eq!(
+        client.try_set_kyc_level(&Address::generate(&env), &merchant_id, &2),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.set_kyc_level(&admin, &merchant_id, &2);
+
+    let merchant = client.get_merchant(&merchant_id);
+    assert_eq!(merchant.country, Symbol::new(&env, "NG"));
+    assert_eq!(merchant.kyc_document_hash, Some(document_hash));
+    assert_eq!(merchant.kyc_level, 2);
+
+    // Fresh documents need a fresh review
+    client.submit_kyc(
+        &merchant_id,
+        &Symbol::new(&env, "NG"),
+        &BytesN::<32>::random(&env),
+    );
+    assert_eq!(client.get_merchant(&merchant_id).kyc_level, 0);
+}
+
+#[test]
+fn test_merchant_delegates() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+
+    let merchant_id = Address::generate(&env);
+    let delegate = Address::generate(&env);
+    assert_eq!(
+        client.try_add_delegate(&merchant_id, &delegate),
+        Err(Ok(Error::MerchantNotFound))
+    );
+    client.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Delegating Shop"),
+        &String::from_str(&env, "USDC"),
+    );
+
+    client.add_delegate(&merchant_id, &delegate);
+    assert!(client.is_delegate(&merchant_id, &delegate));
+    assert_eq!(
+        client.try_add_delegate(&merchant_id, &delegate),
+        Err(Ok(Error::DelegateAlreadyExists))
+    );
+
+    client.remove_delegate(&merchant_id, &delegate);
+    assert!(!client.is_delegate(&merchant_id, &delegate));
+    assert_eq!(client.get_delegates(&merchant_id).len(), 0);
+    assert_eq!(
+        client.try_remove_delegate(&merchant_id, &delegate),
+        Err(Ok(Error::DelegateNotFound))
+    );
+}
+
+#[test]
+fn test_merchant_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(MerchantRegistry, ());
+    let client = MerchantRegistryClient::new(&env, &contract_id);
+    let admin = Address::generate(&env);
+    client.initialize(&admin);
+
+    let merchant_id = Address::generate(&env);
+    client.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Capped Merchant"),
+        &String::from_str(&env, "USDC"),
+    );
+    assert_eq!(client.get_merchant_limits(&merchant_id).max_daily_volume, 0);
+
+    let limits = MerchantLimits {
+        max_single_payment: 1_000,
+        max_daily_volume: 10_000,
+        max_weekly_volume: 0,
+    };
+    let result = client.try_set_merchant_limits(&Address::generate(&env), &merchant_id, &limits);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = client.try_set_merchant_limits(
+        &admin,
+        &merchant_id,
+        &MerchantLimits {
+            max_single_payment: -1,
+            ..limits.clone()
+        },
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidLimits)));
+
+    client.set_merchant_limits(&admin, &merchant_id, &limits);
+    assert_eq!(client.get_merchant_limits(&merchant_id), limits);
+}
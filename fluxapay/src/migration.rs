@@ -0,0 +1,129 @@
+use soroban_sdk::{contracttype, vec, Env, String, Vec};
+
+use crate::time_index::{RecordKind, TimeIndex, BUCKET_SECONDS, MAX_BUCKETS_PER_QUERY};
+use crate::{Error, MAX_BATCH_SIZE};
+
+/// Storage layout version a completed migration leaves a kind's records in
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Position in the time index: the next entry to migrate is `offset` within hour `bucket`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MigrationCursor {
+    pub bucket: u64,
+    pub offset: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MigrationProgress {
+    pub kind: RecordKind,
+    pub target_version: u32,
+    pub next: MigrationCursor,
+    pub migrated: u64, // records rewritten so far in this run
+    pub started_at: u64,
+    pub completed_at: Option<u64>,
+}
+
+#[contracttype]
+pub enum MigrationDataKey {
+    SchemaVersion(RecordKind), // kind -> u32 layout its records are in, 0 before any migration
+    Progress(RecordKind),      // kind -> MigrationProgress of the latest run
+}
+
+pub struct Migrations;
+
+impl Migrations {
+    pub fn schema_version(env: &Env, kind: RecordKind) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&MigrationDataKey::SchemaVersion(kind))
+            .unwrap_or(0)
+    }
+
+    pub fn get_progress(env: &Env, kind: RecordKind) -> Option<MigrationProgress> {
+        env.storage()
+            .persistent()
+            .get(&MigrationDataKey::Progress(kind))
+    }
+
+    /// Claim the ids of up to `limit` records from `start_key` on, walking at most
+    /// `MAX_BUCKETS_PER_QUERY` hours of the time index, and save where the run got to.
+    /// A `start_key` behind the saved progress resumes from the progress instead, so a
+    /// retried call never hands out a record twice; one ahead of it would skip records
+    /// and is refused. Returns the claimed ids and the cursor to continue from, `None`
+    /// once the kind is fully migrated.
+    pub fn claim_range(
+        env: &Env,
+        kind: RecordKind,
+        start_key: MigrationCursor,
+        limit: u32,
+    ) -> Result<(Vec<String>, Option<MigrationCursor>), Error> {
+        if limit == 0 {
+            return Err(Error::EmptyBatch);
+        }
+        if limit > MAX_BATCH_SIZE {
+            return Err(Error::BatchTooLarge);
+        }
+        if Self::schema_version(env, kind) >= SCHEMA_VERSION {
+            return Ok((vec![env], None));
+        }
+
+        let now = env.ledger().timestamp();
+        let mut progress = match Self::get_progress(env, kind) {
+            Some(progress) if progress.target_version == SCHEMA_VERSION => {
+                if Self::is_before(&progress.next, &start_key) {
+                    return Err(Error::MigrationCursorAhead);
+                }
+                progress
+            }
+            _ => MigrationProgress {
+                kind,
+                target_version: SCHEMA_VERSION,
+                next: start_key,
+                migrated: 0,
+                started_at: now,
+                completed_at: None,
+            },
+        };
+
+        let last_bucket = now / BUCKET_SECONDS;
+        let mut cursor = progress.next.clone();
+        let mut ids = vec![env];
+        let mut buckets = 0;
+        let mut entries = TimeIndex::bucket(env, kind, cursor.bucket);
+        while ids.len() < limit && cursor.bucket <= last_bucket {
+            if cursor.offset < entries.len() {
+                ids.push_back(entries.get_unchecked(cursor.offset).id);
+                cursor.offset += 1;
+                continue;
+            }
+            buckets += 1;
+            cursor.bucket += 1;
+            cursor.offset = 0;
+            if buckets == MAX_BUCKETS_PER_QUERY {
+                break;
+            }
+            entries = TimeIndex::bucket(env, kind, cursor.bucket);
+        }
+
+        progress.migrated += ids.len() as u64;
+        progress.next = cursor.clone();
+        let done = cursor.bucket > last_bucket;
+        if done {
+            progress.completed_at = Some(now);
+            env.storage()
+                .persistent()
+                .set(&MigrationDataKey::SchemaVersion(kind), &SCHEMA_VERSION);
+        }
+        env.storage()
+            .persistent()
+            .set(&MigrationDataKey::Progress(kind), &progress);
+
+        Ok((ids, if done { None } else { Some(cursor) }))
+    }
+
+    fn is_before(a: &MigrationCursor, b: &MigrationCursor) -> bool {
+        (a.bucket, a.offset) < (b.bucket, b.offset)
+    }
+}
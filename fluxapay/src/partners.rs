@@ -0,0 +1,118 @@
+use soroban_sdk::{contracttype, Address, Env, String, Symbol};
+
+use crate::fees::{Fees, MAX_FEE_BPS};
+use crate::Error;
+
+// Referral partners (e.g. checkout plugins) that can be credited with a share of the
+// processing fee on the individual charges they bring in
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Partner {
+    pub partner: Address,
+    pub max_share_bps: u32, // ceiling on the share of the processing fee per charge
+    pub active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PartnerShare {
+    pub partner: Address,
+    pub share_bps: u32, // of the processing fee, not of the charge amount
+}
+
+#[contracttype]
+pub enum PartnerDataKey {
+    Partner(Address),        // partner -> Partner
+    Attribution(String),     // payment_id -> PartnerShare
+    Earned(Address, Symbol), // (partner, currency) -> i128 fee share accrued
+}
+
+pub struct Partners;
+
+impl Partners {
+    pub fn register(env: &Env, partner: &Address, max_share_bps: u32) -> Result<Partner, Error> {
+        if max_share_bps == 0 || max_share_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee);
+        }
+        let record = Partner {
+            partner: partner.clone(),
+            max_share_bps,
+            active: true,
+        };
+        env.storage()
+            .persistent()
+            .set(&PartnerDataKey::Partner(partner.clone()), &record);
+        Ok(record)
+    }
+
+    /// Stop new attributions; shares already earned are kept
+    pub fn deactivate(env: &Env, partner: &Address) -> Result<(), Error> {
+        let mut record = Self::get(env, partner)?;
+        record.active = false;
+        env.storage()
+            .persistent()
+            .set(&PartnerDataKey::Partner(partner.clone()), &record);
+        Ok(())
+    }
+
+    pub fn get(env: &Env, partner: &Address) -> Result<Partner, Error> {
+        env.storage()
+            .persistent()
+            .get(&PartnerDataKey::Partner(partner.clone()))
+            .ok_or(Error::PartnerNotFound)
+    }
+
+    /// Credit `partner` on one charge, checked against its registered ceiling
+    pub fn attribute(
+        env: &Env,
+        payment_id: &String,
+        partner: &Address,
+        share_bps: u32,
+    ) -> Result<PartnerShare, Error> {
+        let record = Self::get(env, partner)?;
+        if !record.active {
+            return Err(Error::PartnerNotFound);
+        }
+        if share_bps == 0 || share_bps > record.max_share_bps {
+            return Err(Error::InvalidFee);
+        }
+        let share = PartnerShare {
+            partner: partner.clone(),
+            share_bps,
+        };
+        env.storage()
+            .persistent()
+            .set(&PartnerDataKey::Attribution(payment_id.clone()), &share);
+        Ok(share)
+    }
+
+    pub fn get_attribution(env: &Env, payment_id: &String) -> Option<PartnerShare> {
+        env.storage()
+            .persistent()
+            .get(&PartnerDataKey::Attribution(payment_id.clone()))
+    }
+
+    /// Carve the partner's share out of a charge's processing fee, returning the amount split off
+    pub fn split(env: &Env, payment_id: &String, currency: &Symbol, processing_fee: i128) -> i128 {
+        let share = match Self::get_attribution(env, payment_id) {
+            Some(share) => share,
+            None => return 0,
+        };
+        let partner_fee = Fees::compute_fee(processing_fee, share.share_bps);
+        if partner_fee > 0 {
+            let key = PartnerDataKey::Earned(share.partner.clone(), currency.clone());
+            let earned: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            env.storage()
+                .persistent()
+                .set(&key, &(earned + partner_fee));
+        }
+        partner_fee
+    }
+
+    pub fn get_earned(env: &Env, partner: &Address, currency: &Symbol) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&PartnerDataKey::Earned(partner.clone(), currency.clone()))
+            .unwrap_or(0)
+    }
+}
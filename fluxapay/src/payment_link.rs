@@ -0,0 +1,132 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Symbol, Vec};
+
+use crate::ids::{IdBuilder, LINK_PREFIX};
+use crate::Error;
+
+pub const LINK_CHARGE_WINDOW: u64 = 3600; // life of each charge raised through a link
+
+// Reusable "pay me" link: every use raises a fresh PaymentCharge for the merchant
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentLink {
+    pub link_id: u64,
+    pub merchant_id: Address,
+    pub amount: Option<i128>, // fixed price, or None to let the payer choose
+    pub currency: Symbol,
+    pub max_uses: u32, // 0 for unlimited
+    pub uses: u32,
+    pub expires_at: u64,
+    pub active: bool,
+    pub created_at: u64,
+}
+
+#[contracttype]
+pub enum PaymentLinkDataKey {
+    Link(u64),              // link_id -> PaymentLink
+    MerchantLinks(Address), // merchant_id -> Vec<link_id>
+    LinkCounter,
+}
+
+pub struct PaymentLinks;
+
+impl PaymentLinks {
+    pub fn create(
+        env: &Env,
+        merchant_id: Address,
+        amount: Option<i128>,
+        currency: Symbol,
+        max_uses: u32,
+        expires_at: u64,
+    ) -> Result<PaymentLink, Error> {
+        if amount.is_some_and(|amount| amount <= 0) {
+            return Err(Error::InvalidAmount);
+        }
+        if expires_at <= env.ledger().timestamp() {
+            return Err(Error::LinkInactive);
+        }
+
+        let link = PaymentLink {
+            link_id: Self::next_id(env),
+            merchant_id: merchant_id.clone(),
+            amount,
+            currency,
+            max_uses,
+            uses: 0,
+            expires_at,
+            active: true,
+            created_at: env.ledger().timestamp(),
+        };
+        Self::save(env, &link);
+
+        let key = PaymentLinkDataKey::MerchantLinks(merchant_id);
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        ids.push_back(link.link_id);
+        env.storage().persistent().set(&key, &ids);
+
+        Ok(link)
+    }
+
+    pub fn get(env: &Env, link_id: u64) -> Result<PaymentLink, Error> {
+        env.storage()
+            .persistent()
+            .get(&PaymentLinkDataKey::Link(link_id))
+            .ok_or(Error::LinkNotFound)
+    }
+
+    pub fn get_by_merchant(env: &Env, merchant_id: &Address) -> Vec<PaymentLink> {
+        let ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&PaymentLinkDataKey::MerchantLinks(merchant_id.clone()))
+            .unwrap_or(vec![env]);
+
+        let mut links = vec![env];
+        for id in ids.iter() {
+            if let Ok(link) = Self::get(env, id) {
+                links.push_back(link);
+            }
+        }
+        links
+    }
+
+    /// Count one use of the link for `amount`, returning the id of the charge it raises,
+    /// e.g. "link_2_5" for its fifth use
+    pub fn use_link(env: &Env, link: &mut PaymentLink, amount: i128) -> Result<String, Error> {
+        let used_up = link.max_uses > 0 && link.uses >= link.max_uses;
+        if !link.active || used_up || env.ledger().timestamp() > link.expires_at {
+            return Err(Error::LinkInactive);
+        }
+        match link.amount {
+            Some(fixed) if fixed != amount => return Err(Error::InvalidAmount),
+            _ if amount <= 0 => return Err(Error::InvalidAmount),
+            _ => {}
+        }
+
+        link.uses += 1;
+        Self::save(env, link);
+        Ok(IdBuilder::new(LINK_PREFIX)
+            .push_u64(link.link_id)
+            .push_str("_")
+            .push_u64(link.uses as u64)
+            .build(env))
+    }
+
+    pub fn save(env: &Env, link: &PaymentLink) {
+        env.storage()
+            .persistent()
+            .set(&PaymentLinkDataKey::Link(link.link_id), link);
+    }
+
+    fn next_id(env: &Env) -> u64 {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&PaymentLinkDataKey::LinkCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&PaymentLinkDataKey::LinkCounter, &counter);
+        counter
+    }
+}
@@ -0,0 +1,18 @@
+use soroban_sdk::{xdr::ToXdr, Address, Bytes, BytesN, Env};
+
+// Payer privacy mode: charges store sha256(payer_xdr || salt) instead of the
+// payer address. The payer keeps the salt and reveals it to claim recourse.
+pub fn payer_commitment(env: &Env, payer: &Address, salt: &BytesN<32>) -> BytesN<32> {
+    let mut preimage: Bytes = payer.clone().to_xdr(env);
+    preimage.append(&Bytes::from(salt.clone()));
+    env.crypto().sha256(&preimage).into()
+}
+
+pub fn matches_commitment(
+    env: &Env,
+    commitment: &BytesN<32>,
+    payer: &Address,
+    salt: &BytesN<32>,
+) -> bool {
+    payer_commitment(env, payer, salt) == *commitment
+}
@@ -0,0 +1,148 @@
+use soroban_sdk::{contracttype, BytesN, Env, String};
+
+use crate::fees::{Fees, MAX_FEE_BPS};
+use crate::Error;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PromoKind {
+    FeeRebate,     // the merchant's platform fee on the charge is cut
+    PriceDiscount, // the payer is charged less
+}
+
+// A platform promotion redeemable on up to `max_uses` charges until `expires_at`; codes are
+// known here only by their sha256, so plain codes never reach the ledger
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PromoCode {
+    pub code_hash: BytesN<32>,
+    pub kind: PromoKind,
+    pub discount_bps: u32,
+    pub max_uses: u32,
+    pub expires_at: u64,
+}
+
+// A code as applied to one charge
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PromoRedemption {
+    pub code_hash: BytesN<32>,
+    pub kind: PromoKind,
+    pub discount_bps: u32,
+    pub discount_amount: i128, // taken off the price; 0 for fee rebates
+}
+
+#[contracttype]
+pub enum PromoDataKey {
+    Code(BytesN<32>),   // code_hash -> PromoCode
+    Uses(BytesN<32>),   // code_hash -> u32 charges it was applied to
+    Redemption(String), // payment_id -> PromoRedemption
+}
+
+pub struct Promos;
+
+impl Promos {
+    /// Add or replace a code; replacing keeps the uses already counted against it
+    pub fn set(env: &Env, promo: &PromoCode) -> Result<(), Error> {
+        if promo.discount_bps == 0 || promo.discount_bps > MAX_FEE_BPS || promo.max_uses == 0 {
+            return Err(Error::InvalidPromoCode);
+        }
+        // A charge discounted to nothing cannot be created, so such a code could never be used
+        if promo.kind == PromoKind::PriceDiscount && promo.discount_bps == MAX_FEE_BPS {
+            return Err(Error::InvalidPromoCode);
+        }
+        if promo.expires_at <= env.ledger().timestamp() {
+            return Err(Error::InvalidPromoCode);
+        }
+        env.storage()
+            .persistent()
+            .set(&PromoDataKey::Code(promo.code_hash.clone()), promo);
+        Ok(())
+    }
+
+    pub fn remove(env: &Env, code_hash: &BytesN<32>) -> Result<(), Error> {
+        let key = PromoDataKey::Code(code_hash.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::PromoCodeNotFound);
+        }
+        env.storage().persistent().remove(&key);
+        Ok(())
+    }
+
+    pub fn get(env: &Env, code_hash: &BytesN<32>) -> Result<PromoCode, Error> {
+        env.storage()
+            .persistent()
+            .get(&PromoDataKey::Code(code_hash.clone()))
+            .ok_or(Error::PromoCodeNotFound)
+    }
+
+    pub fn get_uses(env: &Env, code_hash: &BytesN<32>) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&PromoDataKey::Uses(code_hash.clone()))
+            .unwrap_or(0)
+    }
+
+    /// Charges the code may still be applied to; none once it has expired
+    pub fn remaining_uses(env: &Env, code_hash: &BytesN<32>) -> Result<u32, Error> {
+        let promo = Self::get(env, code_hash)?;
+        if env.ledger().timestamp() > promo.expires_at {
+            return Ok(0);
+        }
+        Ok(promo
+            .max_uses
+            .saturating_sub(Self::get_uses(env, code_hash)))
+    }
+
+    /// Check the code can be applied to a charge of `amount` and price what it takes off,
+    /// without using it up
+    pub fn quote(
+        env: &Env,
+        code_hash: &BytesN<32>,
+        amount: i128,
+    ) -> Result<PromoRedemption, Error> {
+        let promo = Self::get(env, code_hash)?;
+        if env.ledger().timestamp() > promo.expires_at {
+            return Err(Error::PromoCodeExpired);
+        }
+        if Self::get_uses(env, code_hash) >= promo.max_uses {
+            return Err(Error::PromoCodeExhausted);
+        }
+        let discount_amount = match promo.kind {
+            PromoKind::PriceDiscount => Fees::compute_fee(amount, promo.discount_bps),
+            PromoKind::FeeRebate => 0,
+        };
+        Ok(PromoRedemption {
+            code_hash: promo.code_hash,
+            kind: promo.kind,
+            discount_bps: promo.discount_bps,
+            discount_amount,
+        })
+    }
+
+    /// Count a use of the code against the charge it was applied to
+    pub fn redeem(env: &Env, payment_id: &String, redemption: &PromoRedemption) {
+        let uses_key = PromoDataKey::Uses(redemption.code_hash.clone());
+        let uses = Self::get_uses(env, &redemption.code_hash) + 1;
+        env.storage().persistent().set(&uses_key, &uses);
+        env.storage()
+            .persistent()
+            .set(&PromoDataKey::Redemption(payment_id.clone()), redemption);
+    }
+
+    pub fn get_redemption(env: &Env, payment_id: &String) -> Option<PromoRedemption> {
+        env.storage()
+            .persistent()
+            .get(&PromoDataKey::Redemption(payment_id.clone()))
+    }
+
+    /// The fee rate for a charge once any fee rebate applied to it is taken off
+    pub fn fee_bps(env: &Env, payment_id: &String, fee_bps: u32) -> u32 {
+        match Self::get_redemption(env, payment_id) {
+            Some(redemption) if redemption.kind == PromoKind::FeeRebate => {
+                fee_bps - Fees::compute_fee(fee_bps as i128, redemption.discount_bps) as u32
+            }
+            _ => fee_bps,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::Error;
+
+// At most `max_payments` charges per merchant over any `window_ledgers` consecutive ledgers
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    pub max_payments: u32,
+    pub window_ledgers: u32,
+}
+
+#[contracttype]
+pub enum RateLimitDataKey {
+    Limit,               // RateLimit; absent leaves payment creation unthrottled
+    Count(Address, u32), // (merchant, window index) -> u32 charges created, in temporary storage
+}
+
+pub struct RateLimits;
+
+impl RateLimits {
+    pub fn set(env: &Env, limit: RateLimit) -> Result<(), Error> {
+        if limit.max_payments == 0 || limit.window_ledgers == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        env.storage()
+            .persistent()
+            .set(&RateLimitDataKey::Limit, &limit);
+        Ok(())
+    }
+
+    pub fn clear(env: &Env) {
+        env.storage().persistent().remove(&RateLimitDataKey::Limit);
+    }
+
+    pub fn get(env: &Env) -> Option<RateLimit> {
+        env.storage().persistent().get(&RateLimitDataKey::Limit)
+    }
+
+    /// Charges the merchant created over the last window, weighting the previous fixed window
+    /// by how much of it the sliding window still overlaps
+    pub fn get_count(env: &Env, merchant: &Address) -> u32 {
+        Self::get(env).map_or(0, |limit| Self::count(env, merchant, &limit))
+    }
+
+    /// Fails once the merchant has used up the window's allowance
+    pub fn check(env: &Env, merchant: &Address) -> Result<(), Error> {
+        match Self::get(env) {
+            Some(limit) if Self::count(env, merchant, &limit) >= limit.max_payments => {
+                Err(Error::RateLimited)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a new charge; counters live two windows, long enough to weigh in as the previous
+    /// one, and then expire on their own
+    pub fn record(env: &Env, merchant: &Address) {
+        let limit = match Self::get(env) {
+            Some(limit) => limit,
+            None => return,
+        };
+        let window = env.ledger().sequence() / limit.window_ledgers;
+        let key = RateLimitDataKey::Count(merchant.clone(), window);
+        let count = Self::count_in(env, merchant, window) + 1;
+        let ttl = limit
+            .window_ledgers
+            .saturating_mul(2)
+            .min(env.storage().max_ttl());
+        env.storage().temporary().set(&key, &count);
+        env.storage().temporary().extend_ttl(&key, ttl, ttl);
+    }
+
+    fn count(env: &Env, merchant: &Address, limit: &RateLimit) -> u32 {
+        let ledger = env.ledger().sequence();
+        let window = ledger / limit.window_ledgers;
+        let elapsed = (ledger % limit.window_ledgers) as u64;
+        let previous = if window == 0 {
+            0
+        } else {
+            Self::count_in(env, merchant, window - 1) as u64
+        };
+        let overlap =
+            previous * (limit.window_ledgers as u64 - elapsed) / limit.window_ledgers as u64;
+        Self::count_in(env, merchant, window) + overlap as u32
+    }
+
+    fn count_in(env: &Env, merchant: &Address, window: u32) -> u32 {
+        env.storage()
+            .temporary()
+            .get(&RateLimitDataKey::Count(merchant.clone(), window))
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,122 @@
+use soroban_sdk::{contracttype, Address, Env, String, Symbol};
+
+use crate::Error;
+
+// Oracle-posted exchange rates used to convert payouts into the merchant's settlement currency
+pub const RATE_SCALE: i128 = 10_000_000; // 7 decimals, matching Stellar asset precision
+pub const DEFAULT_MAX_RATE_AGE: u64 = 3600;
+const MAX_CURRENCY_LEN: usize = 32;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExchangeRate {
+    pub base: Symbol,
+    pub quote: Symbol,
+    pub rate: i128, // units of quote per unit of base, scaled by RATE_SCALE
+    pub timestamp: u64,
+    pub oracle: Address,
+}
+
+#[contracttype]
+pub enum RateDataKey {
+    Rate(Symbol, Symbol), // (base, quote) -> ExchangeRate
+    MaxRateAge,           // u64 seconds before a posted rate is considered stale
+}
+
+pub struct Rates;
+
+impl Rates {
+    pub fn post(
+        env: &Env,
+        oracle: Address,
+        base: Symbol,
+        quote: Symbol,
+        rate: i128,
+        timestamp: u64,
+    ) -> Result<ExchangeRate, Error> {
+        if rate <= 0 || base == quote {
+            return Err(Error::InvalidRate);
+        }
+        // Reject observations from the future or older than the one already stored
+        if timestamp > env.ledger().timestamp() {
+            return Err(Error::InvalidRate);
+        }
+        if let Some(current) = Self::get(env, &base, &quote) {
+            if timestamp <= current.timestamp {
+                return Err(Error::StaleRate);
+            }
+        }
+
+        let posted = ExchangeRate {
+            base: base.clone(),
+            quote: quote.clone(),
+            rate,
+            timestamp,
+            oracle,
+        };
+        env.storage()
+            .persistent()
+            .set(&RateDataKey::Rate(base, quote), &posted);
+        Ok(posted)
+    }
+
+    pub fn get(env: &Env, base: &Symbol, quote: &Symbol) -> Option<ExchangeRate> {
+        env.storage()
+            .persistent()
+            .get(&RateDataKey::Rate(base.clone(), quote.clone()))
+    }
+
+    pub fn set_max_age(env: &Env, max_age: u64) -> Result<(), Error> {
+        if max_age == 0 {
+            return Err(Error::InvalidRate);
+        }
+        env.storage()
+            .persistent()
+            .set(&RateDataKey::MaxRateAge, &max_age);
+        Ok(())
+    }
+
+    pub fn get_max_age(env: &Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&RateDataKey::MaxRateAge)
+            .unwrap_or(DEFAULT_MAX_RATE_AGE)
+    }
+
+    /// Convert `amount` of `base` into `quote` using a fresh rate, returning the rate applied
+    /// so the result can be reproduced later (`None` when no conversion was needed)
+    pub fn convert(
+        env: &Env,
+        amount: i128,
+        base: &Symbol,
+        quote: &Symbol,
+    ) -> Result<(i128, Option<ExchangeRate>), Error> {
+        if base == quote {
+            return Ok((amount, None));
+        }
+        let rate = Self::get(env, base, quote).ok_or(Error::RateNotFound)?;
+        if env.ledger().timestamp() - rate.timestamp > Self::get_max_age(env) {
+            return Err(Error::StaleRate);
+        }
+        Ok((amount * rate.rate / RATE_SCALE, Some(rate)))
+    }
+
+    /// Symbol for a merchant's free-form settlement currency, if it is a valid code
+    pub fn currency_symbol(env: &Env, currency: &String) -> Option<Symbol> {
+        let len = currency.len() as usize;
+        if len == 0 || len > MAX_CURRENCY_LEN {
+            return None;
+        }
+        let mut buf = [0u8; MAX_CURRENCY_LEN];
+        currency.copy_into_slice(&mut buf[..len]);
+        if !buf[..len]
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        {
+            return None;
+        }
+        core::str::from_utf8(&buf[..len])
+            .ok()
+            .map(|code| Symbol::new(env, code))
+    }
+}
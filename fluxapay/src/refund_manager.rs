@@ -0,0 +1,1532 @@
+use soroban_sdk::{
+    contract, contractimpl, token, vec, Address, BytesN, Env, Map, String, Symbol, Vec,
+};
+
+use crate::access_control::{
+    role_admin, role_arbiter, role_oracle, role_settlement_operator, AccessControl, RoleDefinition,
+};
+use crate::approval::{Approval, ApprovalPolicy, Approvals};
+use crate::audit::{AuditEntity, AuditEntry, AuditLog, AuditRecord, AUDIT_PAGE_SIZE};
+use crate::compliance::Compliance;
+use crate::config::{Config, Configs};
+use crate::dispute::{Dispute, DisputeOutcome, DisputeStatus, Disputes, Evidence};
+use crate::emergency::{Emergency, EmergencyWithdrawal};
+use crate::events;
+use crate::features::{feature_disputes, Features};
+use crate::ids::{validate_id, IdBuilder, REFUND_PREFIX};
+use crate::mass_refund::{MassRefund, MassRefunds};
+use crate::merchant_registry::CustodyMode;
+use crate::migration::{MigrationCursor, MigrationProgress, Migrations};
+use crate::pausable::{Pausable, PauseScope};
+use crate::refund_policy::RefundPolicy;
+use crate::spend_guard::{SpendGuard, SpendLimit};
+use crate::time_index::{RecordKind, TimeIndex, TimeIndexEntry};
+use crate::ttl;
+use crate::{
+    ContractInfo, DataKey, Error, PaymentCharge, PaymentProcessorClient, PaymentStatus,
+    ProcessCheck, Refund, RefundReason, RefundReasonStats, RefundStatus, MAX_BATCH_SIZE,
+};
+
+// Refunds, disputes and mass refunds paid out of the escrow this contract holds. It keeps
+// its own records and reaches payments only through the linked PaymentProcessor's client
+#[contract]
+pub struct RefundManager;
+
+#[contractimpl]
+impl RefundManager {
+    /// Set up the contract once with its admin and, when already deployed, the
+    /// PaymentProcessor whose payments it refunds
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        payment_processor: Option<Address>,
+    ) -> Result<(), Error> {
+        if env.storage().instance().has(&DataKey::Initialized)
+            || AccessControl::get_admin(&env).is_some()
+        {
+            return Err(Error::AlreadyInitialized);
+        }
+        AccessControl::initialize(&env, admin);
+        if let Some(payment_processor) = payment_processor {
+            RefundPolicy::set_payment_processor(&env, &payment_processor);
+        }
+        env.storage().instance().set(&DataKey::Initialized, &true);
+        Ok(())
+    }
+
+    /// Grant `role` to `account` (holders of the role's admin role)
+    pub fn grant_role(
+        env: Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+    ) -> Result<(), Error> {
+        AccessControl::grant_role(&env, caller, role, account).map_err(Error::from)
+    }
+
+    /// Revoke `role` from `account` (holders of the role's admin role)
+    pub fn revoke_role(
+        env: Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+    ) -> Result<(), Error> {
+        AccessControl::revoke_role(&env, caller, role, account).map_err(Error::from)
+    }
+
+    pub fn has_role(env: Env, role: Symbol, account: Address) -> bool {
+        AccessControl::has_role(&env, &role, &account)
+    }
+
+    /// Grant a role that lapses at `expires_at`, for automatic key rotation (holders of the
+    /// role's admin role)
+    pub fn grant_role_until(
+        env: Env,
+        caller: Address,
+        role: Symbol,
+        account: Address,
+        expires_at: u64,
+    ) -> Result<(), Error> {
+        AccessControl::grant_role_until(&env, caller, role, account, expires_at)
+            .map_err(Error::from)
+    }
+
+    pub fn get_role_expiry(env: Env, role: Symbol, account: Address) -> Option<u64> {
+        AccessControl::get_role_expiry(&env, &role, &account)
+    }
+
+    /// Clean up to `limit` lapsed role grants from storage
+    pub fn prune_expired_roles(env: Env, limit: u32) -> u32 {
+        AccessControl::prune_expired_roles(&env, limit)
+    }
+
+    /// Cap how much any holder of `role` may move per transaction and per day (admin only)
+    pub fn set_role_spend_limit(
+        env: Env,
+        admin: Address,
+        role: Symbol,
+        limit: SpendLimit,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        SpendGuard::set_role_limit(&env, role, limit)
+    }
+
+    /// Cap a single operator, overriding the limit of its role (admin only)
+    pub fn set_account_spend_limit(
+        env: Env,
+        admin: Address,
+        account: Address,
+        limit: SpendLimit,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        SpendGuard::set_account_limit(&env, account, limit)
+    }
+
+    /// Limit enforced on `account` acting under `role`, if any
+    pub fn get_spend_limit(env: Env, account: Address, role: Symbol) -> Option<SpendLimit> {
+        SpendGuard::effective_limit(&env, &account, &role)
+    }
+
+    /// Amount `account` has spent today (the account itself, readers or admins)
+    pub fn get_daily_spend(env: Env, caller: Address, account: Address) -> Result<i128, Error> {
+        Self::require_reader(&env, &caller, Some(&account))?;
+        Ok(SpendGuard::get_daily_spend(&env, &account))
+    }
+
+    pub fn renounce_role(env: Env, account: Address, role: Symbol) -> Result<(), Error> {
+        AccessControl::renounce_role(&env, account, role).map_err(Error::from)
+    }
+
+    pub fn transfer_admin(
+        env: Env,
+        current_admin: Address,
+        new_admin: Address,
+    ) -> Result<(), Error> {
+        AccessControl::transfer_admin(&env, current_admin, new_admin).map_err(Error::from)
+    }
+
+    pub fn get_admin(env: Env) -> Option<Address> {
+        AccessControl::get_admin(&env)
+    }
+
+    /// Declare a new role with its description hash and administering role (admin only)
+    pub fn define_role(
+        env: Env,
+        admin: Address,
+        role: Symbol,
+        description_hash: BytesN<32>,
+        admin_role: Symbol,
+    ) -> Result<(), Error> {
+        AccessControl::define_role(&env, admin, role, description_hash, admin_role)
+            .map_err(Error::from)
+    }
+
+    /// Hand administration of `role` to `admin_role`, whose holders then grant and revoke
+    /// it (admin only)
+    pub fn set_role_admin(
+        env: Env,
+        admin: Address,
+        role: Symbol,
+        admin_role: Symbol,
+    ) -> Result<(), Error> {
+        AccessControl::set_role_admin(&env, admin, role, admin_role).map_err(Error::from)
+    }
+
+    pub fn get_role_definition(env: Env, role: Symbol) -> Option<RoleDefinition> {
+        AccessControl::get_role_definition(&env, &role)
+    }
+
+    /// List the accounts holding `role`, `limit` at a time from `offset`
+    pub fn get_role_members(env: Env, role: Symbol, offset: u32, limit: u32) -> Vec<Address> {
+        AccessControl::get_role_members(&env, &role, offset, limit)
+    }
+
+    /// List the roles held by `account`, `limit` at a time from `offset`
+    pub fn get_account_roles(env: Env, account: Address, offset: u32, limit: u32) -> Vec<Symbol> {
+        AccessControl::get_account_roles(&env, &account, offset, limit)
+    }
+
+    /// Replace the contract-wide configuration in one call (admin only)
+    pub fn set_config(env: Env, admin: Address, config: Config) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Configs::set(&env, &config)?;
+        env.events().publish(
+            (
+                Symbol::new(&env, "CONTRACT"),
+                Symbol::new(&env, "CONFIGURED"),
+            ),
+            (admin, config),
+        );
+        Ok(())
+    }
+
+    pub fn get_config(env: Env) -> Config {
+        Configs::get(&env)
+    }
+
+    /// Halt payment and refund flows in an emergency (admin only)
+    pub fn pause(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_paused(&env, true);
+        env.events().publish(
+            (Symbol::new(&env, "CONTRACT"), Symbol::new(&env, "PAUSED")),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Resume normal operation after a pause (admin only)
+    pub fn unpause(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_paused(&env, false);
+        env.events().publish(
+            (Symbol::new(&env, "CONTRACT"), Symbol::new(&env, "UNPAUSED")),
+            admin,
+        );
+        Ok(())
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        Pausable::is_paused(&env)
+    }
+
+    /// Halt a single flow, leaving the others running (admin only)
+    pub fn pause_scope(env: Env, admin: Address, scope: PauseScope) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_scope_paused(&env, scope.clone(), true);
+        env.events().publish(
+            (
+                Symbol::new(&env, "SCOPE"),
+                Symbol::new(&env, "PAUSED"),
+                scope,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    /// Resume a single paused flow (admin only)
+    pub fn unpause_scope(env: Env, admin: Address, scope: PauseScope) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Pausable::set_scope_paused(&env, scope.clone(), false);
+        env.events().publish(
+            (
+                Symbol::new(&env, "SCOPE"),
+                Symbol::new(&env, "UNPAUSED"),
+                scope,
+            ),
+            admin,
+        );
+        Ok(())
+    }
+
+    pub fn is_scope_paused(env: Env, scope: PauseScope) -> bool {
+        Pausable::is_scope_paused(&env, scope)
+    }
+
+    /// Describe this deployment: kind, version, linked contracts, features and currencies
+    pub fn get_contract_info(env: Env) -> ContractInfo {
+        let mut linked_contracts: Map<Symbol, Address> = Map::new(&env);
+        let mut currencies = vec![&env];
+        if let Some(processor) = RefundPolicy::get_payment_processor(&env) {
+            // Refunds accept whatever the linked processor accepts
+            for token in PaymentProcessorClient::new(&env, &processor)
+                .list_supported_tokens()
+                .iter()
+            {
+                currencies.push_back(token.currency);
+            }
+            linked_contracts.set(Symbol::new(&env, "PAYMENT_PROCESSOR"), processor);
+        }
+
+        ContractInfo {
+            kind: Symbol::new(&env, "REFUND_MANAGER"),
+            version: String::from_str(&env, env!("CARGO_PKG_VERSION")),
+            linked_contracts,
+            features: Features::enabled(&env),
+            currencies,
+            paused: Pausable::is_paused(&env),
+            paused_scopes: Pausable::paused_scopes(&env),
+        }
+    }
+
+    /// Link the PaymentProcessor used to resolve a payment's merchant (admin only)
+    pub fn set_payment_processor(
+        env: Env,
+        admin: Address,
+        payment_processor: Address,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RefundPolicy::set_payment_processor(&env, &payment_processor);
+        Ok(())
+    }
+
+    /// Set the global maximum number of refunds per payment (admin only)
+    pub fn set_max_refunds_per_payment(env: Env, admin: Address, cap: u32) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RefundPolicy::set_global_cap(&env, cap);
+        Ok(())
+    }
+
+    /// Override the maximum number of refunds per payment for one merchant (admin only)
+    pub fn set_merchant_max_refunds(
+        env: Env,
+        admin: Address,
+        merchant_id: Address,
+        cap: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RefundPolicy::set_merchant_cap(&env, merchant_id, cap);
+        Ok(())
+    }
+
+    /// Get the refund cap that applies to a merchant's payments, if any
+    pub fn get_max_refunds(env: Env, merchant_id: Option<Address>) -> Option<u32> {
+        RefundPolicy::effective_cap(&env, merchant_id.as_ref())
+    }
+
+    /// Set how many seconds after confirmation refunds may be requested, for merchants
+    /// without their own window (admin only)
+    pub fn set_refund_window(env: Env, admin: Address, window_secs: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        if window_secs == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        RefundPolicy::set_global_window(&env, window_secs);
+        Ok(())
+    }
+
+    /// Set the merchant's own refund request window, overriding the global one (merchant)
+    pub fn set_merchant_refund_window(
+        env: Env,
+        merchant_id: Address,
+        window_secs: u64,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        if window_secs == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        RefundPolicy::set_merchant_window(&env, merchant_id, window_secs);
+        Ok(())
+    }
+
+    /// Get the refund request window that applies to a merchant's payments, if any
+    pub fn get_refund_window(env: Env, merchant_id: Option<Address>) -> Option<u64> {
+        let category_window = merchant_id
+            .as_ref()
+            .and_then(|merchant_id| Self::category_refund_window(&env, merchant_id));
+        RefundPolicy::effective_window(&env, merchant_id.as_ref(), category_window)
+    }
+
+    /// Let one more refund be requested on the payment after its window has closed
+    /// (admin only)
+    pub fn allow_late_refund(env: Env, admin: Address, payment_id: String) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        RefundPolicy::allow_late_refund(&env, &payment_id);
+        Self::record_audit(
+            &env,
+            &payment_id,
+            None,
+            "LATE_REFUND_ALLOWED",
+            None,
+            payment_id.clone(),
+        );
+        Ok(())
+    }
+
+    pub fn create_refund(
+        env: Env,
+        payment_id: String,
+        refund_amount: i128,
+        reason: RefundReason,
+        detail: Option<String>,
+        requester: Address,
+    ) -> Result<String, Error> {
+        requester.require_auth();
+        // Pending refunds hold part of the payment and a refund slot, so outsiders may not file
+        if let Ok(payment) = Self::get_linked_payment(&env, &payment_id) {
+            Self::require_party(&payment, &requester)?;
+        }
+        Self::create_refund_internal(
+            &env,
+            payment_id,
+            refund_amount,
+            reason,
+            detail,
+            requester,
+            None,
+        )
+    }
+
+    /// Count and total of the refunds requested for `reason`
+    pub fn get_refund_reason_stats(env: Env, reason: RefundReason) -> RefundReasonStats {
+        env.storage()
+            .persistent()
+            .get(&DataKey::RefundReasonStats(reason))
+            .unwrap_or_default()
+    }
+
+    pub fn process_refund(env: Env, operator: Address, refund_id: String) -> Result<(), Error> {
+        operator.require_auth();
+        Pausable::require_not_paused(&env, PauseScope::Refunds)?;
+        let has_settlement =
+            AccessControl::has_role(&env, &role_settlement_operator(&env), &operator);
+        let has_oracle = AccessControl::has_role(&env, &role_oracle(&env), &operator);
+
+        if !has_settlement && !has_oracle {
+            return Err(Error::Unauthorized);
+        }
+
+        let refund = Self::get_refund_internal(&env, &refund_id)?;
+        // Once refunds can be tied to a merchant, the merchant must sign off first; fraud
+        // refunds always wait for an admin
+        if refund.status == RefundStatus::Pending
+            && (RefundPolicy::get_payment_processor(&env).is_some()
+                || refund.reason == RefundReason::Fraud)
+        {
+            return Err(Error::RefundNotApproved);
+        }
+        let role = if has_settlement {
+            role_settlement_operator(&env)
+        } else {
+            role_oracle(&env)
+        };
+        Approvals::consume(&env, &refund_id, refund.amount)?;
+        SpendGuard::spend(&env, &operator, &role, refund.amount)?;
+
+        Self::complete_refund(&env, &refund_id, &operator)?;
+        AuditLog::append(&env, &operator, "REFUND", refund_id);
+        Ok(())
+    }
+
+    /// Check whether `operator` could process the refund right now, without changing state
+    pub fn can_process_refund(
+        env: Env,
+        operator: Address,
+        refund_id: String,
+    ) -> Result<ProcessCheck, Error> {
+        let refund = Self::get_refund_internal(&env, &refund_id)?;
+        let processor = RefundPolicy::get_payment_processor(&env);
+
+        let not_paused = Pausable::require_not_paused(&env, PauseScope::Refunds).is_ok();
+        let status_ok = match refund.status {
+            RefundStatus::Approved => true,
+            RefundStatus::Pending => processor.is_none() && refund.reason != RefundReason::Fraud,
+            _ => false,
+        };
+        let role = if AccessControl::has_role(&env, &role_settlement_operator(&env), &operator) {
+            Some(role_settlement_operator(&env))
+        } else if AccessControl::has_role(&env, &role_oracle(&env), &operator) {
+            Some(role_oracle(&env))
+        } else {
+            None
+        };
+        let operator_ok = role
+            .map(|role| SpendGuard::check(&env, &operator, &role, refund.amount).is_ok())
+            .unwrap_or(false);
+        // Without a linked PaymentProcessor nothing is transferred
+        let (escrow_funded, destination_ok) = match processor {
+            Some(processor) => Self::check_escrow(&env, &processor, &refund),
+            None => (true, true),
+        };
+
+        let approvals_ok = Approvals::is_satisfied(&env, &refund_id, refund.amount);
+
+        Ok(ProcessCheck {
+            can_process: not_paused
+                && status_ok
+                && operator_ok
+                && escrow_funded
+                && destination_ok
+                && approvals_ok,
+            not_paused,
+            status_ok,
+            operator_ok,
+            escrow_funded,
+            destination_ok,
+            approvals_ok,
+        })
+    }
+
+    /// Sign off on a pending refund so an operator can execute it (merchant)
+    pub fn approve_refund(env: Env, merchant_id: Address, refund_id: String) -> Result<(), Error> {
+        merchant_id.require_auth();
+        let mut refund = Self::get_refund_internal(&env, &refund_id)?;
+        Self::require_refund_merchant(&env, &refund, &merchant_id)?;
+        if refund.reason == RefundReason::Fraud {
+            return Err(Error::AdminApprovalRequired);
+        }
+
+        refund.status = RefundStatus::Approved;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Refund(refund_id.clone()), &refund);
+
+        let payment = Self::get_linked_payment(&env, &refund.payment_id).ok();
+        events::refund(&env, "APPROVED", &refund, payment.as_ref(), &merchant_id);
+
+        Ok(())
+    }
+
+    /// Sign off on a pending refund claimed as fraud, in place of the merchant (admin only)
+    pub fn approve_fraud_refund(env: Env, admin: Address, refund_id: String) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        let mut refund = Self::get_refund_internal(&env, &refund_id)?;
+        if refund.status != RefundStatus::Pending {
+            return Err(Error::RefundAlreadyProcessed);
+        }
+        if refund.reason != RefundReason::Fraud {
+            return Err(Error::Unauthorized);
+        }
+
+        refund.status = RefundStatus::Approved;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Refund(refund_id.clone()), &refund);
+
+        let payment = Self::get_linked_payment(&env, &refund.payment_id).ok();
+        events::refund(&env, "APPROVED", &refund, payment.as_ref(), &admin);
+        AuditLog::append(&env, &admin, "FRAUD_REFUND_APPROVED", refund_id);
+
+        Ok(())
+    }
+
+    /// Turn down a pending refund with a reason for the requester (merchant)
+    pub fn reject_refund(
+        env: Env,
+        merchant_id: Address,
+        refund_id: String,
+        reason: String,
+    ) -> Result<(), Error> {
+        merchant_id.require_auth();
+        let mut refund = Self::get_refund_internal(&env, &refund_id)?;
+        Self::require_refund_merchant(&env, &refund, &merchant_id)?;
+
+        refund.status = RefundStatus::Rejected;
+        refund.processed_at = Some(env.ledger().timestamp());
+        refund.rejection_reason = Some(reason);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Refund(refund_id.clone()), &refund);
+        // A rejected refund no longer counts against the payment, so it leaves the index
+        Self::compact_payment_refunds_internal(&env, &refund.payment_id);
+        Self::record_audit(
+            &env,
+            &refund.payment_id,
+            None,
+            "REFUND_REJECTED",
+            None,
+            refund_id.clone(),
+        );
+
+        let payment = Self::get_linked_payment(&env, &refund.payment_id).ok();
+        events::refund(&env, "REJECTED", &refund, payment.as_ref(), &merchant_id);
+
+        Ok(())
+    }
+
+    /// Allow a refund to be paid out even though its payer is blocked (admin only)
+    pub fn override_blocked_refund(
+        env: Env,
+        admin: Address,
+        refund_id: String,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        let refund = Self::get_refund_internal(&env, &refund_id)?;
+        if refund.status != RefundStatus::Pending && refund.status != RefundStatus::Approved {
+            return Err(Error::RefundAlreadyProcessed);
+        }
+        Compliance::set_refund_override(&env, &refund_id);
+        Self::record_audit(
+            &env,
+            &refund.payment_id,
+            None,
+            "REFUND_BLOCK_OVERRIDE",
+            None,
+            refund_id.clone(),
+        );
+
+        let payment = Self::get_linked_payment(&env, &refund.payment_id).ok();
+        events::refund(&env, "BLOCK_OVERRIDE", &refund, payment.as_ref(), &admin);
+        Ok(())
+    }
+
+    pub fn get_refund(env: Env, refund_id: String) -> Result<Refund, Error> {
+        Self::get_refund_internal(&env, &refund_id)
+    }
+
+    /// Keep a refund record live for at least `ledgers` more ledgers, capped at the network
+    /// maximum; returns the TTL requested (anyone)
+    pub fn bump_refund(env: Env, refund_id: String, ledgers: u32) -> Result<u32, Error> {
+        ttl::extend_by(&env, &DataKey::Refund(refund_id), ledgers).ok_or(Error::RefundNotFound)
+    }
+
+    /// Rewrite up to `limit` refunds, in creation order from `start_key`, in the current
+    /// storage layout; returns the cursor to pass to the next call, or `None` once every
+    /// refund is at `SCHEMA_VERSION`. Safe to retry: records are never migrated twice
+    /// (admin only)
+    pub fn migrate_range(
+        env: Env,
+        admin: Address,
+        kind: RecordKind,
+        start_key: MigrationCursor,
+        limit: u32,
+    ) -> Result<Option<MigrationCursor>, Error> {
+        Self::require_admin(&env, &admin)?;
+        if kind != RecordKind::Refund {
+            return Err(Error::UnsupportedRecordKind);
+        }
+        let (refund_ids, next) = Migrations::claim_range(&env, kind, start_key, limit)?;
+        for refund_id in refund_ids.iter() {
+            let key = DataKey::Refund(refund_id);
+            if let Some(refund) = env.storage().persistent().get::<_, Refund>(&key) {
+                env.storage().persistent().set(&key, &refund);
+                ttl::extend(&env, &key);
+            }
+        }
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MIGRATION"),
+                Symbol::new(&env, "PROGRESS"),
+            ),
+            (kind, refund_ids.len(), next.clone()),
+        );
+        Ok(next)
+    }
+
+    pub fn get_migration_progress(env: Env, kind: RecordKind) -> Option<MigrationProgress> {
+        Migrations::get_progress(&env, kind)
+    }
+
+    pub fn get_schema_version(env: Env, kind: RecordKind) -> u32 {
+        Migrations::schema_version(&env, kind)
+    }
+
+    /// Queue recovery of `amount` of `token` stuck in this contract, payable to `to` once the
+    /// emergency timelock has passed (admin only)
+    pub fn propose_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        token: Address,
+        amount: i128,
+        to: Address,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::propose(&env, &admin, token, amount, to)
+    }
+
+    /// Pay out a proposed emergency withdrawal whose timelock has passed (admin only)
+    pub fn execute_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::execute(&env, withdrawal_id)
+    }
+
+    /// Drop a proposed emergency withdrawal before it executes (admin only)
+    pub fn cancel_emergency_withdrawal(
+        env: Env,
+        admin: Address,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::cancel(&env, withdrawal_id)
+    }
+
+    pub fn get_emergency_withdrawal(
+        env: Env,
+        withdrawal_id: u64,
+    ) -> Result<EmergencyWithdrawal, Error> {
+        Emergency::get(&env, withdrawal_id)
+    }
+
+    /// Seconds between proposing and executing an emergency withdrawal, at least
+    /// `MIN_EMERGENCY_TIMELOCK`; already proposed withdrawals keep their time (admin only)
+    pub fn set_emergency_timelock(env: Env, admin: Address, timelock: u64) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Emergency::set_timelock(&env, timelock)
+    }
+
+    pub fn get_emergency_timelock(env: Env) -> u64 {
+        Emergency::get_timelock(&env)
+    }
+
+    /// Require `required` settlement operator approvals for refund payouts above `threshold`
+    /// (admin only)
+    pub fn set_approval_policy(
+        env: Env,
+        admin: Address,
+        threshold: i128,
+        required: u32,
+    ) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Approvals::set_policy(
+            &env,
+            ApprovalPolicy {
+                threshold,
+                required,
+            },
+        )
+    }
+
+    /// Drop the approval requirement (admin only)
+    pub fn clear_approval_policy(env: Env, admin: Address) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Approvals::clear_policy(&env);
+        Ok(())
+    }
+
+    pub fn get_approval_policy(env: Env) -> Option<ApprovalPolicy> {
+        Approvals::get_policy(&env)
+    }
+
+    pub fn get_approval(env: Env, action_id: String) -> Option<Approval> {
+        Approvals::get(&env, &action_id)
+    }
+
+    /// Approve paying out a refund that is above the approval threshold
+    /// (settlement operator only)
+    pub fn approve_refund_payout(
+        env: Env,
+        operator: Address,
+        refund_id: String,
+    ) -> Result<Approval, Error> {
+        operator.require_auth();
+        AccessControl::require_role(&env, &role_settlement_operator(&env), &operator)?;
+        let refund = Self::get_refund_internal(&env, &refund_id)?;
+        if refund.status != RefundStatus::Pending && refund.status != RefundStatus::Approved {
+            return Err(Error::RefundAlreadyProcessed);
+        }
+        Approvals::approve(&env, &refund_id, refund.amount, &operator)
+    }
+
+    /// Drop rejected and no-longer-stored refunds from each listed payment's refund index;
+    /// returns the number of entries removed (anyone)
+    pub fn compact_payment_refunds(env: Env, payment_ids: Vec<String>) -> u32 {
+        let mut removed = 0;
+        for payment_id in payment_ids.iter() {
+            removed += Self::compact_payment_refunds_internal(&env, &payment_id);
+        }
+        removed
+    }
+
+    /// How much more can be refunded on a payment, net of refunds not yet rejected
+    pub fn get_refundable_amount(env: Env, payment_id: String) -> Result<i128, Error> {
+        let payment = Self::get_linked_payment(&env, &payment_id)?;
+        Ok(payment.amount - Self::outstanding_refunds(&env, &payment_id))
+    }
+
+    /// Enable or disable a feature flag (admin only)
+    pub fn set_feature(env: Env, admin: Address, flag: Symbol, enabled: bool) -> Result<(), Error> {
+        Self::require_admin(&env, &admin)?;
+        Features::set(&env, flag.clone(), enabled);
+
+        env.events()
+            .publish((Symbol::new(&env, "FEATURE"), flag), enabled);
+
+        Ok(())
+    }
+
+    pub fn is_feature_enabled(env: Env, flag: Symbol) -> bool {
+        Features::is_enabled(&env, &flag)
+    }
+
+    /// Open a dispute over a confirmed payment (payer or merchant)
+    pub fn open_dispute(
+        env: Env,
+        opener: Address,
+        payment_id: String,
+        reason: String,
+        evidence_hash: BytesN<32>,
+    ) -> Result<Dispute, Error> {
+        Features::require(&env, &feature_disputes(&env))?;
+        opener.require_auth();
+
+        let payment = Self::get_linked_payment(&env, &payment_id)?;
+        if payment.status != PaymentStatus::Confirmed && payment.status != PaymentStatus::Settled {
+            return Err(Error::PaymentNotConfirmed);
+        }
+        Self::require_party(&payment, &opener)?;
+
+        let dispute = Disputes::open(&env, payment_id, opener, reason, evidence_hash)?;
+        Self::record_audit(
+            &env,
+            &dispute.payment_id,
+            Some(&payment.merchant_id),
+            "DISPUTE_OPENED",
+            None,
+            dispute.dispute_id.clone(),
+        );
+
+        env.events().publish(
+            (Symbol::new(&env, "DISPUTE"), Symbol::new(&env, "OPENED")),
+            (dispute.dispute_id.clone(), dispute.payment_id.clone()),
+        );
+
+        Ok(dispute)
+    }
+
+    /// Attach further evidence to an open dispute (payer or merchant)
+    pub fn submit_evidence(
+        env: Env,
+        submitter: Address,
+        dispute_id: String,
+        evidence_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        Features::require(&env, &feature_disputes(&env))?;
+        submitter.require_auth();
+
+        let dispute = Disputes::get(&env, &dispute_id)?;
+        if dispute.status != DisputeStatus::Open {
+            return Err(Error::DisputeNotOpen);
+        }
+        let payment = Self::get_linked_payment(&env, &dispute.payment_id)?;
+        Self::require_party(&payment, &submitter)?;
+
+        Disputes::add_evidence(&env, &dispute_id, submitter.clone(), evidence_hash);
+        Self::record_audit(
+            &env,
+            &dispute.payment_id,
+            None,
+            "DISPUTE_EVIDENCE",
+            None,
+            dispute_id.clone(),
+        );
+
+        env.events().publish(
+            (Symbol::new(&env, "DISPUTE"), Symbol::new(&env, "EVIDENCE")),
+            (dispute_id, submitter),
+        );
+
+        Ok(())
+    }
+
+    /// Decide a dispute (arbiter only). A payer win refunds whatever of the payment is not
+    /// already refunded, processed immediately when the payment was escrow-funded; a merchant
+    /// win releases the funds.
+    pub fn resolve_dispute(
+        env: Env,
+        arbiter: Address,
+        dispute_id: String,
+        outcome: DisputeOutcome,
+    ) -> Result<Dispute, Error> {
+        Features::require(&env, &feature_disputes(&env))?;
+        arbiter.require_auth();
+        AccessControl::require_role(&env, &role_arbiter(&env), &arbiter)?;
+
+        let mut dispute = Disputes::get(&env, &dispute_id)?;
+        if dispute.status != DisputeStatus::Open {
+            return Err(Error::DisputeNotOpen);
+        }
+
+        let payment = Self::get_linked_payment(&env, &dispute.payment_id)?;
+        if outcome == DisputeOutcome::Payer {
+            let remaining = payment.amount - Self::outstanding_refunds(&env, &dispute.payment_id);
+            if remaining > 0 {
+                let refund_id = Self::create_refund_internal(
+                    &env,
+                    dispute.payment_id.clone(),
+                    remaining,
+                    RefundReason::Other,
+                    Some(dispute.reason.clone()),
+                    payment
+                        .payer_address
+                        .clone()
+                        .unwrap_or_else(|| dispute.opener.clone()),
+                    Some(dispute_id.clone()),
+                )?;
+                if payment.custody_mode == CustodyMode::Escrow {
+                    Self::complete_refund(&env, &refund_id, &arbiter)?;
+                }
+                dispute.refund_id = Some(refund_id);
+            }
+            if let Some(processor) = RefundPolicy::get_payment_processor(&env) {
+                PaymentProcessorClient::new(&env, &processor)
+                    .record_dispute_fee(&dispute.payment_id);
+            }
+        }
+        Disputes::resolve(&env, &mut dispute, &outcome);
+        let detail = match outcome {
+            DisputeOutcome::Payer => Symbol::new(&env, "PAYER"),
+            DisputeOutcome::Merchant => Symbol::new(&env, "MERCHANT"),
+        };
+        Self::record_audit(
+            &env,
+            &dispute.payment_id,
+            Some(&payment.merchant_id),
+            "DISPUTE_RESOLVED",
+            Some(detail),
+            dispute_id.clone(),
+        );
+
+        env.events().publish(
+            (Symbol::new(&env, "DISPUTE"), Symbol::new(&env, "RESOLVED")),
+            (dispute_id, outcome),
+        );
+
+        Ok(dispute)
+    }
+
+    pub fn get_dispute(env: Env, dispute_id: String) -> Result<Dispute, Error> {
+        Disputes::get(&env, &dispute_id)
+    }
+
+    /// Open dispute on a payment, if any
+    pub fn get_payment_dispute(env: Env, payment_id: String) -> Option<String> {
+        Disputes::get_for_payment(&env, &payment_id)
+    }
+
+    pub fn get_dispute_evidence(env: Env, dispute_id: String) -> Vec<Evidence> {
+        Disputes::get_evidence(&env, &dispute_id)
+    }
+
+    /// Pay settled funds out of escrow (linked PaymentProcessor)
+    pub fn payout(env: Env, token: Address, to: Address, amount: i128) -> Result<(), Error> {
+        let processor = RefundPolicy::get_payment_processor(&env).ok_or(Error::Unauthorized)?;
+        processor.require_auth();
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if Self::escrow_balance(&env, &token) < amount {
+            return Err(Error::InsufficientEscrow);
+        }
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+        Ok(())
+    }
+
+    /// Return the excess on an overpaid charge to its payer from escrow (anyone)
+    pub fn refund_overpayment(env: Env, payment_id: String) -> Result<i128, Error> {
+        Pausable::require_not_paused(&env, PauseScope::Refunds)?;
+        let payment = Self::get_linked_payment(&env, &payment_id)?;
+        if payment.overpaid_amount == 0 {
+            return Err(Error::NoOverpayment);
+        }
+        // Self-custody funds, excess included, went straight to the merchant
+        if payment.custody_mode == CustodyMode::SelfCustody {
+            return Err(Error::InsufficientEscrow);
+        }
+        let payer = payment.payer_address.ok_or(Error::PaymentNotConfirmed)?;
+
+        let processor = PaymentProcessorClient::new(
+            &env,
+            &RefundPolicy::get_payment_processor(&env).ok_or(Error::PaymentNotFound)?,
+        );
+        if processor.is_address_blocked(&payer) {
+            return Err(Error::AddressBlocked);
+        }
+        let token_address = processor
+            .get_supported_token(&payment.currency)
+            .ok_or(Error::UnsupportedCurrency)?;
+        if Self::escrow_balance(&env, &token_address) < payment.overpaid_amount {
+            return Err(Error::InsufficientEscrow);
+        }
+        let amount = processor.clear_overpayment(&payment_id);
+        token::Client::new(&env, &token_address).transfer(
+            &env.current_contract_address(),
+            &payer,
+            &amount,
+        );
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "OVERPAYMENT"),
+                Symbol::new(&env, "REFUNDED"),
+            ),
+            (payment_id, payer, amount),
+        );
+        Ok(amount)
+    }
+
+    /// Fund a mass-refund pool and publish the Merkle root of its (payer, amount) leaves;
+    /// payers then claim individually until `claim_deadline` (merchant)
+    pub fn fund_mass_refund(
+        env: Env,
+        merchant_id: Address,
+        currency: Symbol,
+        amount: i128,
+        merkle_root: BytesN<32>,
+        claim_deadline: u64,
+    ) -> Result<MassRefund, Error> {
+        merchant_id.require_auth();
+        Pausable::require_not_paused(&env, PauseScope::Refunds)?;
+
+        let processor =
+            RefundPolicy::get_payment_processor(&env).ok_or(Error::UnsupportedCurrency)?;
+        let token_address = PaymentProcessorClient::new(&env, &processor)
+            .get_supported_token(&currency)
+            .ok_or(Error::UnsupportedCurrency)?;
+
+        let pool = MassRefunds::create(
+            &env,
+            merchant_id.clone(),
+            currency,
+            token_address.clone(),
+            merkle_root,
+            amount,
+            claim_deadline,
+        )?;
+        token::Client::new(&env, &token_address).transfer(
+            &merchant_id,
+            &env.current_contract_address(),
+            &amount,
+        );
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MASS_REFUND"),
+                Symbol::new(&env, "FUNDED"),
+            ),
+            (pool.pool_id, merchant_id, amount),
+        );
+
+        Ok(pool)
+    }
+
+    /// Claim a payer's share of a mass refund with a Merkle proof of (payer, amount)
+    pub fn claim_mass_refund(
+        env: Env,
+        payer: Address,
+        pool_id: u64,
+        proof: Vec<BytesN<32>>,
+        amount: i128,
+    ) -> Result<(), Error> {
+        payer.require_auth();
+        Pausable::require_not_paused(&env, PauseScope::Refunds)?;
+
+        if let Some(processor) = RefundPolicy::get_payment_processor(&env) {
+            if PaymentProcessorClient::new(&env, &processor).is_address_blocked(&payer) {
+                return Err(Error::AddressBlocked);
+            }
+        }
+        let mut pool = MassRefunds::get(&env, pool_id)?;
+        MassRefunds::claim(&env, &mut pool, &payer, &proof, amount)?;
+        token::Client::new(&env, &pool.token).transfer(
+            &env.current_contract_address(),
+            &payer,
+            &amount,
+        );
+
+        env.events().publish(
+            (
+                Symbol::new(&env, "MASS_REFUND"),
+                Symbol::new(&env, "CLAIMED"),
+            ),
+            (pool_id, payer, amount),
+        );
+
+        Ok(())
+    }
+
+    /// Return the unclaimed remainder to the merchant once the claim window has closed
+    pub fn reclaim_mass_refund(
+        env: Env,
+        merchant_id: Address,
+        pool_id: u64,
+    ) -> Result<i128, Error> {
+        merchant_id.require_auth();
+
+        let mut pool = MassRefunds::get(&env, pool_id)?;
+        if pool.merchant_id != merchant_id {
+            return Err(Error::Unauthorized);
+        }
+        let remainder = MassRefunds::reclaim(&env, &mut pool)?;
+        if remainder > 0 {
+            token::Client::new(&env, &pool.token).transfer(
+                &env.current_contract_address(),
+                &merchant_id,
+                &remainder,
+            );
+        }
+
+        Ok(remainder)
+    }
+
+    pub fn get_mass_refund(env: Env, pool_id: u64) -> Result<MassRefund, Error> {
+        MassRefunds::get(&env, pool_id)
+    }
+
+    pub fn is_mass_refund_claimed(env: Env, pool_id: u64, payer: Address) -> bool {
+        MassRefunds::is_claimed(&env, pool_id, &payer)
+    }
+
+    /// Held in `token` for mass-refund claims, apart from payment escrow
+    pub fn get_mass_refund_liability(env: Env, token: Address) -> i128 {
+        MassRefunds::get_liability(&env, &token)
+    }
+
+    /// Everything known about a payment, merchant or account in one chronological list:
+    /// the linked PaymentProcessor's status, config and role history stitched together with
+    /// this contract's role changes, refunds and disputes
+    pub fn get_audit_trail(env: Env, entity: AuditEntity, page: u32) -> Vec<AuditEntry> {
+        let mut processor_trail = vec![&env];
+        if let Some(processor) = RefundPolicy::get_payment_processor(&env) {
+            let client = PaymentProcessorClient::new(&env, &processor);
+            let mut processor_page = 0;
+            loop {
+                let entries = client.get_audit_trail(&entity, &processor_page);
+                let len = entries.len();
+                processor_trail.append(&entries);
+                if len < AUDIT_PAGE_SIZE {
+                    break;
+                }
+                processor_page += 1;
+            }
+        }
+
+        let own_trail = AuditLog::get_all(&env, &entity);
+        AuditLog::page(
+            &env,
+            &AuditLog::merge(&env, &processor_trail, &own_trail),
+            page,
+        )
+    }
+
+    /// This contract's journal records from `from_seq` on, oldest first
+    pub fn get_audit_entries(env: Env, from_seq: u64, limit: u32) -> Vec<AuditRecord> {
+        AuditLog::entries(&env, from_seq, limit)
+    }
+
+    /// Find refunds created within [from, to] (support tooling), 20 per page
+    pub fn find_by_time_range(
+        env: Env,
+        kind: RecordKind,
+        from: u64,
+        to: u64,
+        page: u32,
+    ) -> Result<Vec<TimeIndexEntry>, Error> {
+        TimeIndex::find(&env, kind, from, to, page)
+    }
+
+    /// Load up to `MAX_BATCH_SIZE` refunds in one call, `None` where an ID is unknown
+    pub fn get_refunds(env: Env, refund_ids: Vec<String>) -> Result<Vec<Option<Refund>>, Error> {
+        if refund_ids.len() > MAX_BATCH_SIZE {
+            return Err(Error::BatchTooLarge);
+        }
+
+        let mut refunds = vec![&env];
+        for refund_id in refund_ids.iter() {
+            refunds.push_back(Self::get_refund_internal(&env, &refund_id).ok());
+        }
+        Ok(refunds)
+    }
+
+    pub fn get_payment_refunds(env: Env, payment_id: String) -> Result<Vec<Refund>, Error> {
+        let refund_ids = Self::get_payment_refunds_internal(&env, &payment_id);
+        let mut refunds = vec![&env];
+
+        for refund_id in refund_ids.iter() {
+            if let Ok(refund) = Self::get_refund_internal(&env, &refund_id) {
+                refunds.push_back(refund);
+            }
+        }
+
+        Ok(refunds)
+    }
+
+    // Look up the payment's merchant through the linked PaymentProcessor, if any
+    fn get_linked_payment(env: &Env, payment_id: &String) -> Result<PaymentCharge, Error> {
+        let processor = RefundPolicy::get_payment_processor(env).ok_or(Error::PaymentNotFound)?;
+        match PaymentProcessorClient::new(env, &processor).try_get_payment(payment_id) {
+            Ok(Ok(payment)) => Ok(payment),
+            _ => Err(Error::PaymentNotFound),
+        }
+    }
+
+    // Refund window set by the merchant's category policy on the linked PaymentProcessor
+    fn category_refund_window(env: &Env, merchant_id: &Address) -> Option<u64> {
+        let processor = RefundPolicy::get_payment_processor(env)?;
+        match PaymentProcessorClient::new(env, &processor).try_get_merchant_policy(merchant_id) {
+            Ok(Ok(policy)) => policy.and_then(|policy| policy.refund_window),
+            _ => None,
+        }
+    }
+
+    // Only the payment's payer or merchant may take part in its dispute
+    fn require_party(payment: &PaymentCharge, account: &Address) -> Result<(), Error> {
+        if &payment.merchant_id == account || payment.payer_address.as_ref() == Some(account) {
+            return Ok(());
+        }
+        Err(Error::Unauthorized)
+    }
+
+    fn complete_refund(env: &Env, refund_id: &String, actor: &Address) -> Result<(), Error> {
+        let mut refund = Self::get_refund_internal(env, refund_id)?;
+
+        if refund.status != RefundStatus::Pending && refund.status != RefundStatus::Approved {
+            return Err(Error::RefundAlreadyProcessed);
+        }
+        // Without a linked PaymentProcessor refunds are bookkeeping only
+        if let Some(processor) = RefundPolicy::get_payment_processor(env) {
+            Self::pay_from_escrow(env, &processor, &refund)?;
+        }
+
+        refund.status = RefundStatus::Completed;
+        refund.processed_at = Some(env.ledger().timestamp());
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Refund(refund_id.clone()), &refund);
+        Self::record_audit(
+            env,
+            &refund.payment_id,
+            None,
+            "REFUND_COMPLETED",
+            None,
+            refund_id.clone(),
+        );
+
+        let payment = Self::get_linked_payment(env, &refund.payment_id).ok();
+        events::refund(env, "COMPLETED", &refund, payment.as_ref(), actor);
+
+        Ok(())
+    }
+
+    // Count a new refund toward its reason's running totals
+    fn record_reason(env: &Env, reason: RefundReason, amount: i128) {
+        let key = DataKey::RefundReasonStats(reason);
+        let mut stats: RefundReasonStats = env.storage().persistent().get(&key).unwrap_or_default();
+        stats.count += 1;
+        stats.amount += amount;
+        env.storage().persistent().set(&key, &stats);
+    }
+
+    // Only the merchant behind the refunded payment may decide a pending refund
+    fn require_refund_merchant(
+        env: &Env,
+        refund: &Refund,
+        merchant_id: &Address,
+    ) -> Result<(), Error> {
+        if refund.status != RefundStatus::Pending {
+            return Err(Error::RefundAlreadyProcessed);
+        }
+        match Self::get_payment_merchant(env, &refund.payment_id) {
+            Some(merchant) if &merchant == merchant_id => Ok(()),
+            _ => Err(Error::Unauthorized),
+        }
+    }
+
+    // Return the refund to the payer out of this contract's escrow balance
+    fn pay_from_escrow(env: &Env, processor: &Address, refund: &Refund) -> Result<(), Error> {
+        let processor = PaymentProcessorClient::new(env, processor);
+        let payment = match processor.try_get_payment(&refund.payment_id) {
+            Ok(Ok(payment)) => payment,
+            _ => return Err(Error::PaymentNotFound),
+        };
+        // Settled funds have left escrow, and self-custody funds never entered it
+        if payment.status == PaymentStatus::Settled
+            || payment.custody_mode == CustodyMode::SelfCustody
+        {
+            return Err(Error::InsufficientEscrow);
+        }
+        let payer = payment.payer_address.ok_or(Error::PaymentNotConfirmed)?;
+        if processor.is_address_blocked(&payer)
+            && !Compliance::has_refund_override(env, &refund.refund_id)
+        {
+            return Err(Error::AddressBlocked);
+        }
+        let token_address = processor
+            .get_supported_token(&payment.currency)
+            .ok_or(Error::UnsupportedCurrency)?;
+
+        if Self::escrow_balance(env, &token_address) < refund.amount {
+            return Err(Error::InsufficientEscrow);
+        }
+        token::Client::new(env, &token_address).transfer(
+            &env.current_contract_address(),
+            &payer,
+            &refund.amount,
+        );
+        processor.record_refund_debit(&refund.payment_id, &refund.amount);
+        Ok(())
+    }
+
+    // Read-only counterpart of pay_from_escrow: (escrow can cover it, payer can receive it)
+    fn check_escrow(env: &Env, processor: &Address, refund: &Refund) -> (bool, bool) {
+        let processor = PaymentProcessorClient::new(env, processor);
+        let payment = match processor.try_get_payment(&refund.payment_id) {
+            Ok(Ok(payment)) => payment,
+            _ => return (false, false),
+        };
+        let token_address = processor.get_supported_token(&payment.currency);
+
+        let escrow_funded = payment.status != PaymentStatus::Settled
+            && payment.custody_mode != CustodyMode::SelfCustody
+            && token_address.as_ref().is_some_and(|token_address| {
+                Self::escrow_balance(env, token_address) >= refund.amount
+            });
+        // Asset issuers can freeze holders; tokens without that notion accept anyone
+        let destination_ok = match (payment.payer_address, token_address) {
+            (Some(payer), Some(token_address)) => {
+                !matches!(
+                    token::StellarAssetClient::new(env, &token_address).try_authorized(&payer),
+                    Ok(Ok(false))
+                ) && (!processor.is_address_blocked(&payer)
+                    || Compliance::has_refund_override(env, &refund.refund_id))
+            }
+            _ => false,
+        };
+        (escrow_funded, destination_ok)
+    }
+
+    // Token balance less what mass-refund pools owe their payers
+    fn escrow_balance(env: &Env, token: &Address) -> i128 {
+        token::Client::new(env, token).balance(&env.current_contract_address())
+            - MassRefunds::get_liability(env, token)
+    }
+
+    // Log a refund or dispute event on the payment's trail and, when known, the merchant's
+    fn record_audit(
+        env: &Env,
+        payment_id: &String,
+        merchant_id: Option<&Address>,
+        action: &str,
+        detail: Option<Symbol>,
+        reference: String,
+    ) {
+        AuditLog::record(
+            env,
+            AuditEntity::Payment(payment_id.clone()),
+            action,
+            detail.clone(),
+            Some(reference.clone()),
+        );
+        if let Some(merchant_id) = merchant_id {
+            AuditLog::record(
+                env,
+                AuditEntity::Merchant(merchant_id.clone()),
+                action,
+                detail,
+                Some(reference),
+            );
+        }
+    }
+
+    fn get_payment_merchant(env: &Env, payment_id: &String) -> Option<Address> {
+        Self::get_linked_payment(env, payment_id)
+            .ok()
+            .map(|payment| payment.merchant_id)
+    }
+
+    // Sum of the payment's refunds that are paid or may still be paid
+    fn outstanding_refunds(env: &Env, payment_id: &String) -> i128 {
+        let mut total = 0;
+        for refund_id in Self::get_payment_refunds_internal(env, payment_id).iter() {
+            if let Ok(refund) = Self::get_refund_internal(env, &refund_id) {
+                if refund.status != RefundStatus::Rejected {
+                    total += refund.amount;
+                }
+            }
+        }
+        total
+    }
+
+    fn require_admin(env: &Env, admin: &Address) -> Result<(), Error> {
+        admin.require_auth();
+        AccessControl::require_role(env, &role_admin(env), admin).map_err(Error::from)
+    }
+
+    // Sensitive views are open to readers and admins, and to the account they describe
+    fn require_reader(env: &Env, caller: &Address, subject: Option<&Address>) -> Result<(), Error> {
+        caller.require_auth();
+        if subject == Some(caller) || AccessControl::can_read(env, caller) {
+            return Ok(());
+        }
+        Err(Error::Unauthorized)
+    }
+
+    fn create_refund_internal(
+        env: &Env,
+        payment_id: String,
+        refund_amount: i128,
+        reason: RefundReason,
+        detail: Option<String>,
+        requester: Address,
+        dispute_id: Option<String>,
+    ) -> Result<String, Error> {
+        Pausable::require_not_paused(env, PauseScope::Refunds)?;
+        validate_id(&payment_id)?;
+        if refund_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        // Partial refunds may add up to, but never beyond, the amount paid
+        let payment = Self::get_linked_payment(env, &payment_id).ok();
+        if let Some(payment) = &payment {
+            if Self::outstanding_refunds(env, &payment_id) + refund_amount > payment.amount {
+                return Err(Error::RefundExceedsPayment);
+            }
+        }
+
+        // Refunds outside a dispute must be requested within the merchant's window
+        if let Some(payment) = &payment {
+            let window = RefundPolicy::effective_window(
+                env,
+                Some(&payment.merchant_id),
+                Self::category_refund_window(env, &payment.merchant_id),
+            );
+            if let (Some(window), Some(confirmed_at), None) =
+                (window, payment.confirmed_at, &dispute_id)
+            {
+                if env.ledger().timestamp() > confirmed_at.saturating_add(window)
+                    && !RefundPolicy::take_late_refund(env, &payment_id)
+                {
+                    return Err(Error::RefundWindowClosed);
+                }
+            }
+        }
+
+        // Enforce the per-merchant (or global) cap on refunds per payment; a dispute's refund
+        // is owed however many came before it
+        let merchant_id = payment.as_ref().map(|payment| payment.merchant_id.clone());
+        if let (Some(cap), None) = (
+            RefundPolicy::effective_cap(env, merchant_id.as_ref()),
+            &dispute_id,
+        ) {
+            if Self::get_payment_refunds_internal(env, &payment_id).len() >= cap {
+                return Err(Error::TooManyRefunds);
+            }
+        }
+
+        let counter = Self::get_next_refund_id(env);
+        let refund_id = IdBuilder::new(REFUND_PREFIX).push_u64(counter).build(env);
+
+        let refund = Refund {
+            refund_id: refund_id.clone(),
+            payment_id: payment_id.clone(),
+            amount: refund_amount,
+            reason: reason.clone(),
+            detail,
+            status: RefundStatus::Pending,
+            requester,
+            created_at: env.ledger().timestamp(),
+            processed_at: None,
+            dispute_id,
+            rejection_reason: None,
+        };
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Refund(refund_id.clone()), &refund);
+        ttl::extend(env, &DataKey::Refund(refund_id.clone()));
+
+        let mut payment_refunds = Self::get_payment_refunds_internal(env, &payment_id);
+        payment_refunds.push_back(refund_id.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::PaymentRefunds(payment_id), &payment_refunds);
+
+        TimeIndex::record(env, RecordKind::Refund, refund_id.clone(), refund_amount);
+        Self::record_reason(env, reason, refund_amount);
+        AuditLog::append(env, &refund.requester, "REFUND_CREATED", refund_id.clone());
+        Self::record_audit(
+            env,
+            &refund.payment_id,
+            merchant_id.as_ref(),
+            "REFUND_CREATED",
+            None,
+            refund_id.clone(),
+        );
+        events::refund(env, "CREATED", &refund, payment.as_ref(), &refund.requester);
+
+        Ok(refund_id)
+    }
+
+    fn get_refund_internal(env: &Env, refund_id: &String) -> Result<Refund, Error> {
+        let key = DataKey::Refund(refund_id.clone());
+        let refund = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .ok_or(Error::RefundNotFound)?;
+        ttl::extend(env, &key);
+        Ok(refund)
+    }
+
+    fn get_payment_refunds_internal(env: &Env, payment_id: &String) -> Vec<String> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PaymentRefunds(payment_id.clone()))
+            .unwrap_or(vec![env])
+    }
+
+    // Keep only refunds that are still stored and not rejected; returns the number dropped
+    fn compact_payment_refunds_internal(env: &Env, payment_id: &String) -> u32 {
+        let key = DataKey::PaymentRefunds(payment_id.clone());
+        let index = Self::get_payment_refunds_internal(env, payment_id);
+        let mut kept = vec![env];
+        for refund_id in index.iter() {
+            let live = env
+                .storage()
+                .persistent()
+                .get::<_, Refund>(&DataKey::Refund(refund_id.clone()))
+                .is_some_and(|refund| refund.status != RefundStatus::Rejected);
+            if live {
+                kept.push_back(refund_id);
+            }
+        }
+
+        let removed = index.len() - kept.len();
+        if kept.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else if removed > 0 {
+            env.storage().persistent().set(&key, &kept);
+        }
+        removed
+    }
+
+    fn get_next_refund_id(env: &Env) -> u64 {
+        let mut counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RefundCounter)
+            .unwrap_or(0);
+        counter += 1;
+        env.storage()
+            .persistent()
+            .set(&DataKey::RefundCounter, &counter);
+        counter
+    }
+}
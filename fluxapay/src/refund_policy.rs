@@ -0,0 +1,113 @@
+use soroban_sdk::{contracttype, Address, Env, String};
+
+use crate::config::Configs;
+
+// Caps on how many refunds may be raised against a single payment, and how long after
+// confirmation they may be requested; the global window lives in `Config`
+#[contracttype]
+pub enum RefundPolicyDataKey {
+    MaxRefundsPerPayment,          // u32 global cap
+    MerchantMaxRefunds(Address),   // merchant_id -> u32 override
+    PaymentProcessor,              // linked PaymentProcessor contract address
+    MerchantRefundWindow(Address), // merchant_id -> u64 seconds override
+    LateRefundAllowed(String),     // payment_id -> bool, admin lets one refund past the window
+}
+
+pub struct RefundPolicy;
+
+impl RefundPolicy {
+    pub fn set_global_cap(env: &Env, cap: u32) {
+        env.storage()
+            .persistent()
+            .set(&RefundPolicyDataKey::MaxRefundsPerPayment, &cap);
+    }
+
+    pub fn set_merchant_cap(env: &Env, merchant_id: Address, cap: u32) {
+        env.storage()
+            .persistent()
+            .set(&RefundPolicyDataKey::MerchantMaxRefunds(merchant_id), &cap);
+    }
+
+    pub fn get_global_cap(env: &Env) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&RefundPolicyDataKey::MaxRefundsPerPayment)
+    }
+
+    pub fn get_merchant_cap(env: &Env, merchant_id: &Address) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&RefundPolicyDataKey::MerchantMaxRefunds(
+                merchant_id.clone(),
+            ))
+    }
+
+    /// The merchant override wins over the global cap; `None` means unlimited
+    pub fn effective_cap(env: &Env, merchant_id: Option<&Address>) -> Option<u32> {
+        merchant_id
+            .and_then(|merchant_id| Self::get_merchant_cap(env, merchant_id))
+            .or_else(|| Self::get_global_cap(env))
+    }
+
+    pub fn set_global_window(env: &Env, window: u64) {
+        Configs::update(env, |config| config.refund_window = window);
+    }
+
+    pub fn set_merchant_window(env: &Env, merchant_id: Address, window: u64) {
+        env.storage().persistent().set(
+            &RefundPolicyDataKey::MerchantRefundWindow(merchant_id),
+            &window,
+        );
+    }
+
+    /// The merchant's own window wins over its category's, which wins over the global one;
+    /// `None` means no deadline
+    pub fn effective_window(
+        env: &Env,
+        merchant_id: Option<&Address>,
+        category_window: Option<u64>,
+    ) -> Option<u64> {
+        merchant_id
+            .and_then(|merchant_id| {
+                env.storage()
+                    .persistent()
+                    .get(&RefundPolicyDataKey::MerchantRefundWindow(
+                        merchant_id.clone(),
+                    ))
+            })
+            .or(category_window)
+            .or(match Configs::get(env).refund_window {
+                0 => None,
+                window => Some(window),
+            })
+    }
+
+    pub fn allow_late_refund(env: &Env, payment_id: &String) {
+        env.storage().persistent().set(
+            &RefundPolicyDataKey::LateRefundAllowed(payment_id.clone()),
+            &true,
+        );
+    }
+
+    /// Use up the payment's late-refund override, returning whether there was one
+    pub fn take_late_refund(env: &Env, payment_id: &String) -> bool {
+        let key = RefundPolicyDataKey::LateRefundAllowed(payment_id.clone());
+        let allowed = env.storage().persistent().has(&key);
+        if allowed {
+            env.storage().persistent().remove(&key);
+        }
+        allowed
+    }
+
+    pub fn set_payment_processor(env: &Env, payment_processor: &Address) {
+        env.storage()
+            .persistent()
+            .set(&RefundPolicyDataKey::PaymentProcessor, payment_processor);
+    }
+
+    pub fn get_payment_processor(env: &Env) -> Option<Address> {
+        env.storage()
+            .persistent()
+            .get(&RefundPolicyDataKey::PaymentProcessor)
+    }
+}
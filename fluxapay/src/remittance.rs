@@ -0,0 +1,35 @@
+use soroban_sdk::{contracttype, Address, BytesN, String, Symbol};
+
+use crate::{PaymentCharge, PaymentStatus};
+
+// Structured remittance record modelled on ISO 20022 RmtInf/Strd, so banks and
+// ERPs can map on-chain charges into their reconciliation formats.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RemittanceInfo {
+    pub creditor_reference: String, // CdtrRefInf/Ref: the charge's payment_id
+    pub creditor: Address,          // Cdtr: merchant
+    pub debtor: Option<Address>,    // Dbtr: payer, once known
+    pub amount: i128,               // RmtdAmt
+    pub currency: Symbol,           // RmtdAmt/@Ccy
+    pub due_date: u64,              // DueDt: charge expiry
+    pub value_date: Option<u64>,    // settlement confirmation time
+    pub end_to_end_id: Option<BytesN<32>>, // on-chain transaction hash
+    pub status: PaymentStatus,
+}
+
+impl RemittanceInfo {
+    pub fn from_charge(payment: PaymentCharge) -> Self {
+        RemittanceInfo {
+            creditor_reference: payment.payment_id,
+            creditor: payment.merchant_id,
+            debtor: payment.payer_address,
+            amount: payment.amount,
+            currency: payment.currency,
+            due_date: payment.expires_at,
+            value_date: payment.confirmed_at,
+            end_to_end_id: payment.transaction_hash,
+            status: payment.status,
+        }
+    }
+}
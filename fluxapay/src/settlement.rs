@@ -0,0 +1,118 @@
+use soroban_sdk::{contracttype, vec, Address, Env, String, Symbol, Vec};
+
+use crate::ids::{IdBuilder, BATCH_PREFIX};
+use crate::Error;
+
+// Operator-run settlement batches, aggregated per merchant and currency for reconciliation
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementLine {
+    pub merchant_id: Address,
+    pub settlement_address: Address, // where the line's net was paid
+    pub currency: Symbol,
+    pub gross: i128,
+    pub fees: i128,
+    pub net: i128, // paid out of escrow: gross less fees and refunds already paid from it
+    pub payment_count: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementBatch {
+    pub batch_id: String,
+    pub operator: Address,
+    pub payment_ids: Vec<String>,
+    pub lines: Vec<SettlementLine>,
+    pub total_gross: i128,
+    pub total_fees: i128,
+    pub total_net: i128,
+    pub settled_at: u64,
+}
+
+#[contracttype]
+pub enum SettlementDataKey {
+    Batch(String), // batch_id -> SettlementBatch
+    BatchCounter,  // u64 counter for batch IDs
+}
+
+pub struct Settlements;
+
+impl Settlements {
+    /// Fold one settled payment into the line for its merchant and currency
+    pub fn add_to_lines(
+        lines: &mut Vec<SettlementLine>,
+        merchant_id: &Address,
+        settlement_address: &Address,
+        currency: &Symbol,
+        amount: i128,
+        fee: i128,
+        net: i128,
+    ) {
+        for i in 0..lines.len() {
+            let mut line = lines.get_unchecked(i);
+            if &line.merchant_id == merchant_id && &line.currency == currency {
+                line.gross += amount;
+                line.fees += fee;
+                line.net += net;
+                line.payment_count += 1;
+                lines.set(i, line);
+                return;
+            }
+        }
+        lines.push_back(SettlementLine {
+            merchant_id: merchant_id.clone(),
+            settlement_address: settlement_address.clone(),
+            currency: currency.clone(),
+            gross: amount,
+            fees: fee,
+            net,
+            payment_count: 1,
+        });
+    }
+
+    pub fn record(
+        env: &Env,
+        operator: Address,
+        payment_ids: Vec<String>,
+        lines: Vec<SettlementLine>,
+    ) -> SettlementBatch {
+        let counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&SettlementDataKey::BatchCounter)
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&SettlementDataKey::BatchCounter, &counter);
+
+        let mut batch = SettlementBatch {
+            batch_id: IdBuilder::new(BATCH_PREFIX).push_u64(counter).build(env),
+            operator,
+            payment_ids,
+            lines: vec![env],
+            total_gross: 0,
+            total_fees: 0,
+            total_net: 0,
+            settled_at: env.ledger().timestamp(),
+        };
+        for line in lines.iter() {
+            batch.total_gross += line.gross;
+            batch.total_fees += line.fees;
+            batch.total_net += line.net;
+        }
+        batch.lines = lines;
+
+        env.storage()
+            .persistent()
+            .set(&SettlementDataKey::Batch(batch.batch_id.clone()), &batch);
+        batch
+    }
+
+    pub fn get(env: &Env, batch_id: &String) -> Result<SettlementBatch, Error> {
+        env.storage()
+            .persistent()
+            .get(&SettlementDataKey::Batch(batch_id.clone()))
+            .ok_or(Error::SettlementBatchNotFound)
+    }
+}
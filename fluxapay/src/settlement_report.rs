@@ -0,0 +1,97 @@
+use soroban_sdk::{contracttype, vec, Address, Env, Symbol, Vec};
+
+pub const REPORT_DAY_SECONDS: u64 = 24 * 3600;
+
+// One currency's totals within a merchant's day, in that currency's units
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReportLine {
+    pub currency: Symbol,
+    pub gross_volume: i128, // confirmed charges
+    pub fees: i128,         // invoiced at confirmation (self-custody) or taken at settlement
+    pub refunds: i128,      // paid out of escrow
+    pub net_settled: i128,  // paid out to the merchant at settlement, before conversion
+    pub payment_count: u32, // confirmed charges
+}
+
+// What happened to a merchant's money on one UTC day, accumulated as payments move
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementReport {
+    pub merchant_id: Address,
+    pub day_index: u64, // ledger timestamp / REPORT_DAY_SECONDS
+    pub lines: Vec<ReportLine>,
+}
+
+#[contracttype]
+pub enum ReportDataKey {
+    Daily(Address, u64), // (merchant, day_index) -> SettlementReport
+}
+
+pub struct SettlementReports;
+
+impl SettlementReports {
+    pub fn day_index(env: &Env) -> u64 {
+        env.ledger().timestamp() / REPORT_DAY_SECONDS
+    }
+
+    pub fn get(env: &Env, merchant: &Address, day_index: u64) -> SettlementReport {
+        env.storage()
+            .persistent()
+            .get(&ReportDataKey::Daily(merchant.clone(), day_index))
+            .unwrap_or(SettlementReport {
+                merchant_id: merchant.clone(),
+                day_index,
+                lines: vec![env],
+            })
+    }
+
+    /// Count a newly confirmed charge toward today's gross volume
+    pub fn record_confirmation(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        Self::update(env, merchant, currency, |line| {
+            line.gross_volume += amount;
+            line.payment_count += 1;
+        });
+    }
+
+    pub fn record_fee(env: &Env, merchant: &Address, currency: &Symbol, fee: i128) {
+        Self::update(env, merchant, currency, |line| line.fees += fee);
+    }
+
+    pub fn record_refund(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        Self::update(env, merchant, currency, |line| line.refunds += amount);
+    }
+
+    pub fn record_settlement(env: &Env, merchant: &Address, currency: &Symbol, net: i128) {
+        Self::update(env, merchant, currency, |line| line.net_settled += net);
+    }
+
+    // Apply `change` to today's line for the currency, opening it if needed
+    fn update(env: &Env, merchant: &Address, currency: &Symbol, change: impl Fn(&mut ReportLine)) {
+        let day_index = Self::day_index(env);
+        let mut report = Self::get(env, merchant, day_index);
+        let position = report
+            .lines
+            .iter()
+            .position(|line| &line.currency == currency);
+        let mut line = match position {
+            Some(i) => report.lines.get_unchecked(i as u32),
+            None => ReportLine {
+                currency: currency.clone(),
+                gross_volume: 0,
+                fees: 0,
+                refunds: 0,
+                net_settled: 0,
+                payment_count: 0,
+            },
+        };
+        change(&mut line);
+        match position {
+            Some(i) => report.lines.set(i as u32, line),
+            None => report.lines.push_back(line),
+        }
+        env.storage()
+            .persistent()
+            .set(&ReportDataKey::Daily(merchant.clone(), day_index), &report);
+    }
+}
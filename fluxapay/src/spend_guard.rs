@@ -0,0 +1,94 @@
+use soroban_sdk::{contracttype, Address, Env, Symbol};
+
+use crate::Error;
+
+pub const DAY_SECONDS: u64 = 24 * 3600;
+
+// Mandate limits on value an operator may move, per transaction and per day
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpendLimit {
+    pub per_transaction: i128,
+    pub per_day: i128,
+}
+
+#[contracttype]
+pub enum SpendGuardDataKey {
+    RoleLimit(Symbol),        // role -> SpendLimit
+    AccountLimit(Address),    // account -> SpendLimit, overrides its role's limit
+    DailySpend(Address, u64), // (account, day index) -> i128 moved that day
+}
+
+pub struct SpendGuard;
+
+impl SpendGuard {
+    pub fn set_role_limit(env: &Env, role: Symbol, limit: SpendLimit) -> Result<(), Error> {
+        Self::validate(&limit)?;
+        env.storage()
+            .persistent()
+            .set(&SpendGuardDataKey::RoleLimit(role), &limit);
+        Ok(())
+    }
+
+    pub fn set_account_limit(env: &Env, account: Address, limit: SpendLimit) -> Result<(), Error> {
+        Self::validate(&limit)?;
+        env.storage()
+            .persistent()
+            .set(&SpendGuardDataKey::AccountLimit(account), &limit);
+        Ok(())
+    }
+
+    /// Limit that applies to `account` acting under `role`; `None` means unlimited
+    pub fn effective_limit(env: &Env, account: &Address, role: &Symbol) -> Option<SpendLimit> {
+        env.storage()
+            .persistent()
+            .get(&SpendGuardDataKey::AccountLimit(account.clone()))
+            .or_else(|| {
+                env.storage()
+                    .persistent()
+                    .get(&SpendGuardDataKey::RoleLimit(role.clone()))
+            })
+    }
+
+    pub fn get_daily_spend(env: &Env, account: &Address) -> i128 {
+        let day = env.ledger().timestamp() / DAY_SECONDS;
+        env.storage()
+            .persistent()
+            .get(&SpendGuardDataKey::DailySpend(account.clone(), day))
+            .unwrap_or(0)
+    }
+
+    /// Whether `amount` fits the operator's mandate, without counting it
+    pub fn check(env: &Env, account: &Address, role: &Symbol, amount: i128) -> Result<(), Error> {
+        if let Some(limit) = Self::effective_limit(env, account, role) {
+            let spent = Self::get_daily_spend(env, account);
+            if amount > limit.per_transaction || spent + amount > limit.per_day {
+                return Err(Error::SpendLimitExceeded);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject `amount` if it breaks the operator's mandate, otherwise count it against today
+    pub fn spend(env: &Env, account: &Address, role: &Symbol, amount: i128) -> Result<(), Error> {
+        Self::check(env, account, role, amount)?;
+        if Self::effective_limit(env, account, role).is_none() {
+            return Ok(());
+        }
+
+        let spent = Self::get_daily_spend(env, account);
+        let day = env.ledger().timestamp() / DAY_SECONDS;
+        env.storage().persistent().set(
+            &SpendGuardDataKey::DailySpend(account.clone(), day),
+            &(spent + amount),
+        );
+        Ok(())
+    }
+
+    fn validate(limit: &SpendLimit) -> Result<(), Error> {
+        if limit.per_transaction <= 0 || limit.per_day < limit.per_transaction {
+            return Err(Error::InvalidAmount);
+        }
+        Ok(())
+    }
+}
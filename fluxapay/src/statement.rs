@@ -0,0 +1,108 @@
+use soroban_sdk::{contracttype, Address, Env, Symbol};
+
+// Running per-merchant, per-currency ledger, closed into a statement at each settlement
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantLedger {
+    pub balance: i128,
+    pub period_start: u64,
+    pub credits: i128,
+    pub refund_debits: i128,
+    pub reserve_debits: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BalanceStatement {
+    pub merchant_id: Address,
+    pub currency: Symbol,
+    pub period_start: u64,
+    pub period_end: u64,
+    pub opening_balance: i128,
+    pub credits: i128,
+    pub refund_debits: i128,
+    pub fee_debits: i128,
+    pub reserve_debits: i128,
+    pub closing_balance: i128,
+}
+
+#[contracttype]
+pub enum StatementDataKey {
+    Ledger(Address, Symbol), // (merchant, currency) -> MerchantLedger
+}
+
+pub struct Statements;
+
+impl Statements {
+    pub fn get_ledger(env: &Env, merchant: &Address, currency: &Symbol) -> MerchantLedger {
+        env.storage()
+            .persistent()
+            .get(&StatementDataKey::Ledger(
+                merchant.clone(),
+                currency.clone(),
+            ))
+            .unwrap_or(MerchantLedger {
+                balance: 0,
+                period_start: env.ledger().timestamp(),
+                credits: 0,
+                refund_debits: 0,
+                reserve_debits: 0,
+            })
+    }
+
+    /// Book a confirmed payment into the merchant's open period
+    pub fn credit(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        let mut ledger = Self::get_ledger(env, merchant, currency);
+        ledger.credits += amount;
+        Self::set_ledger(env, merchant, currency, &ledger);
+    }
+
+    /// Book a refund paid out of escrow against the merchant's open period
+    pub fn debit_refund(env: &Env, merchant: &Address, currency: &Symbol, amount: i128) {
+        let mut ledger = Self::get_ledger(env, merchant, currency);
+        ledger.refund_debits += amount;
+        Self::set_ledger(env, merchant, currency, &ledger);
+    }
+
+    /// Close the open period, debiting `fee`, and start a new one
+    pub fn close(env: &Env, merchant: &Address, currency: &Symbol, fee: i128) -> BalanceStatement {
+        let ledger = Self::get_ledger(env, merchant, currency);
+        let now = env.ledger().timestamp();
+        let closing_balance =
+            ledger.balance + ledger.credits - ledger.refund_debits - fee - ledger.reserve_debits;
+
+        let statement = BalanceStatement {
+            merchant_id: merchant.clone(),
+            currency: currency.clone(),
+            period_start: ledger.period_start,
+            period_end: now,
+            opening_balance: ledger.balance,
+            credits: ledger.credits,
+            refund_debits: ledger.refund_debits,
+            fee_debits: fee,
+            reserve_debits: ledger.reserve_debits,
+            closing_balance,
+        };
+
+        Self::set_ledger(
+            env,
+            merchant,
+            currency,
+            &MerchantLedger {
+                balance: closing_balance,
+                period_start: now,
+                credits: 0,
+                refund_debits: 0,
+                reserve_debits: 0,
+            },
+        );
+        statement
+    }
+
+    fn set_ledger(env: &Env, merchant: &Address, currency: &Symbol, ledger: &MerchantLedger) {
+        env.storage().persistent().set(
+            &StatementDataKey::Ledger(merchant.clone(), currency.clone()),
+            ledger,
+        );
+    }
+}
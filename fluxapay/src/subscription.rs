@@ -0,0 +1,146 @@
+use soroban_sdk::{contracttype, vec, Address, Env, Symbol, Vec};
+
+use crate::Error;
+
+// Recurring billing on top of PaymentCharge
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Subscription {
+    pub subscription_id: u64,
+    pub merchant_id: Address,
+    pub payer: Address,
+    pub amount: i128,
+    pub currency: Symbol,
+    pub interval: u64, // seconds between charges
+    pub next_due_at: u64,
+    pub cycles_charged: u64,
+    pub status: SubscriptionStatus,
+    pub created_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SubscriptionStatus {
+    Active,
+    Cancelled,
+}
+
+#[contracttype]
+pub enum SubscriptionDataKey {
+    Subscription(u64),              // subscription_id -> Subscription
+    MerchantSubscriptions(Address), // merchant_id -> Vec<subscription_id>
+    SubscriptionCounter,
+}
+
+pub struct Subscriptions;
+
+impl Subscriptions {
+    pub fn create(
+        env: &Env,
+        merchant_id: Address,
+        payer: Address,
+        amount: i128,
+        currency: Symbol,
+        interval: u64,
+        first_due_at: u64,
+    ) -> Result<Subscription, Error> {
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if interval == 0 {
+            return Err(Error::InvalidInterval);
+        }
+
+        let subscription_id = Self::next_id(env);
+        let subscription = Subscription {
+            subscription_id,
+            merchant_id: merchant_id.clone(),
+            payer,
+            amount,
+            currency,
+            interval,
+            next_due_at: first_due_at,
+            cycles_charged: 0,
+            status: SubscriptionStatus::Active,
+            created_at: env.ledger().timestamp(),
+        };
+        Self::save(env, &subscription);
+
+        let key = SubscriptionDataKey::MerchantSubscriptions(merchant_id);
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        ids.push_back(subscription_id);
+        env.storage().persistent().set(&key, &ids);
+
+        Ok(subscription)
+    }
+
+    pub fn get(env: &Env, subscription_id: u64) -> Result<Subscription, Error> {
+        env.storage()
+            .persistent()
+            .get(&SubscriptionDataKey::Subscription(subscription_id))
+            .ok_or(Error::SubscriptionNotFound)
+    }
+
+    pub fn get_by_merchant(env: &Env, merchant_id: &Address) -> Vec<Subscription> {
+        let ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&SubscriptionDataKey::MerchantSubscriptions(
+                merchant_id.clone(),
+            ))
+            .unwrap_or(vec![env]);
+
+        let mut subscriptions = vec![env];
+        for id in ids.iter() {
+            if let Ok(subscription) = Self::get(env, id) {
+                subscriptions.push_back(subscription);
+            }
+        }
+        subscriptions
+    }
+
+    /// Advance a due subscription by one billing cycle
+    pub fn advance(env: &Env, subscription_id: u64) -> Result<Subscription, Error> {
+        let mut subscription = Self::get(env, subscription_id)?;
+        if subscription.status != SubscriptionStatus::Active {
+            return Err(Error::SubscriptionNotActive);
+        }
+        if env.ledger().timestamp() < subscription.next_due_at {
+            return Err(Error::SubscriptionNotDue);
+        }
+
+        subscription.cycles_charged += 1;
+        subscription.next_due_at += subscription.interval;
+        Self::save(env, &subscription);
+        Ok(subscription)
+    }
+
+    pub fn cancel(env: &Env, subscription: &mut Subscription) -> Result<(), Error> {
+        if subscription.status != SubscriptionStatus::Active {
+            return Err(Error::SubscriptionNotActive);
+        }
+        subscription.status = SubscriptionStatus::Cancelled;
+        Self::save(env, subscription);
+        Ok(())
+    }
+
+    fn save(env: &Env, subscription: &Subscription) {
+        env.storage().persistent().set(
+            &SubscriptionDataKey::Subscription(subscription.subscription_id),
+            subscription,
+        );
+    }
+
+    fn next_id(env: &Env) -> u64 {
+        let mut counter: u64 = env
+            .storage()
+            .persistent()
+            .get(&SubscriptionDataKey::SubscriptionCounter)
+            .unwrap_or(0);
+        counter += 1;
+        env.storage()
+            .persistent()
+            .set(&SubscriptionDataKey::SubscriptionCounter, &counter);
+        counter
+    }
+}
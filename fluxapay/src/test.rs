@@ -1,41 +1,73 @@
 #![cfg(test)]
 
 use super::*;
-use access_control::{role_admin, role_merchant, role_oracle, role_settlement_operator};
-use soroban_sdk::{testutils::{Address as _, BytesN as _, Ledger}, Address, BytesN, Env, String, Symbol};
+use access_control::{
+    role_admin, role_merchant, role_oracle, role_reader, role_settlement_operator,
+};
+use merchant_registry::{
+    MerchantRegistry, MerchantRegistryClient, MAX_WEBHOOKS, WEBHOOK_ALL_EVENTS,
+    WEBHOOK_PAYMENT_EVENTS, WEBHOOK_REFUND_EVENTS,
+};
+use soroban_sdk::{
+    testutils::{Address as _, BytesN as _, Ledger, MockAuth, MockAuthInvoke},
+    Address, BytesN, Env, IntoVal, Map, String, Symbol, Vec,
+};
 
 fn setup_contract(env: &Env) -> (Address, RefundManagerClient<'_>) {
+    env.mock_all_auths();
     let contract_id = env.register(RefundManager, ());
     let client = RefundManagerClient::new(env, &contract_id);
     let admin = Address::generate(env);
-    client.initialize(&admin);
+    client.initialize(&admin, &None);
     (admin, client)
 }
 
-#[test]
-fn test_create_payment() {
-    let env = Env::default();
-    let (_admin, client) = setup_contract(&env);
-
-    let payment_id = String::from_str(&env, "payment_123");
-    let refund_amount = 1000i128;
-    let reason = String::from_str(&env, "Customer requested refund");
-    let requester = Address::generate(&env);
+fn setup_payment_processor(env: &Env) -> (Address, PaymentProcessorClient<'_>) {
+    env.mock_all_auths();
+    let admin = Address::generate(env);
 
-    let refund_id = client.create_refund(&payment_id, &refund_amount, &reason, &requester);
-    let refund = client.get_refund(&refund_id);
+    let registry_id = env.register(MerchantRegistry, ());
+    MerchantRegistryClient::new(env, &registry_id).initialize(&admin);
 
-    assert_eq!(refund.payment_id, payment_id);
-    assert_eq!(refund.amount, refund_amount);
-    assert_eq!(refund.reason, reason);
-    assert_eq!(refund.status, RefundStatus::Pending);
-    assert_eq!(refund.requester, requester);
-    assert!(refund.processed_at.is_none());
     let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
+    let client = PaymentProcessorClient::new(env, &contract_id);
+    client.initialize(
+        &admin,
+        &registry_id,
+        &Map::from_array(env, [(Symbol::new(env, "USDC"), Address::generate(env))]),
+        &None,
+    );
+
+    let oracle = Address::generate(env);
+    client.grant_role(&admin, &role_oracle(env), &oracle);
+    (oracle, client)
+}
+
+// Register a merchant in the processor's MerchantRegistry and verify it
+fn register_merchant(env: &Env, client: &PaymentProcessorClient) -> Address {
+    let registry = MerchantRegistryClient::new(env, &client.get_merchant_registry().unwrap());
+    let merchant_id = Address::generate(env);
+    registry.register_merchant(
+        &merchant_id,
+        &String::from_str(env, "Merchant"),
+        &String::from_str(env, "USDC"),
+    );
+    registry.verify_merchant(&client.get_admin().unwrap(), &merchant_id);
+    merchant_id
+}
+
+// Nonce the oracle's next submission must carry
+fn next_nonce(client: &PaymentProcessorClient, oracle: &Address) -> u64 {
+    client.get_oracle_nonce(oracle) + 1
+}
+
+#[test]
+fn test_create_payment() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
 
     let payment_id = String::from_str(&env, "payment_123");
-    let merchant_id = Address::generate(&env);
+    let merchant_id = register_merchant(&env, &client);
     let amount = 1000000000i128; // 1000 USDC (6 decimals)
     let currency = Symbol::new(&env, "USDC");
     let deposit_address = Address::generate(&env);
@@ -49,6 +81,11 @@ fn test_create_payment() {
         &currency,
         &deposit_address,
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
     );
 
     // Verify payment details
@@ -67,12 +104,10 @@ fn test_create_payment() {
 #[test]
 fn test_verify_payment_success() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
+    let (oracle, client) = setup_payment_processor(&env);
 
     let payment_id = String::from_str(&env, "payment_123");
-    let merchant_id = Address::generate(&env);
+    let merchant_id = register_merchant(&env, &client);
     let amount = 1000000000i128; // 1000 USDC (6 decimals)
     let currency = Symbol::new(&env, "USDC");
     let deposit_address = Address::generate(&env);
@@ -86,6 +121,11 @@ fn test_verify_payment_success() {
         &currency,
         &deposit_address,
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
     );
 
     // Verify payment
@@ -93,20 +133,13 @@ fn test_verify_payment_success() {
     let transaction_hash = BytesN::<32>::random(&env);
     let amount_received = amount; // Exact match
 
-    let refund_id = client.create_refund(&payment_id, &refund_amount, &reason, &requester);
-
-    let operator = Address::generate(&env);
-    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
-    client.process_refund(&operator, &refund_id);
-
-    let refund = client.get_refund(&refund_id);
-    assert_eq!(refund.status, RefundStatus::Completed);
-    assert!(refund.processed_at.is_some());
     let status = client.verify_payment(
+        &oracle,
         &payment_id,
         &transaction_hash,
         &payer_address,
         &amount_received,
+        &next_nonce(&client, &oracle),
     );
 
     assert_eq!(status, PaymentStatus::Confirmed);
@@ -115,19 +148,24 @@ fn test_verify_payment_success() {
     let payment = client.get_payment(&payment_id);
     assert_eq!(payment.status, PaymentStatus::Confirmed);
     assert_eq!(payment.payer_address, Some(payer_address));
-    assert_eq!(payment.transaction_hash, Some(transaction_hash));
+    assert_eq!(payment.transaction_hash, Some(transaction_hash.clone()));
     assert!(payment.confirmed_at.is_some());
+
+    // Support can find the charge from the Stellar transaction alone
+    assert_eq!(client.get_payment_by_tx_hash(&transaction_hash), payment);
+    assert_eq!(
+        client.try_get_payment_by_tx_hash(&BytesN::random(&env)),
+        Err(Ok(Error::PaymentNotFound))
+    );
 }
 
 #[test]
 fn test_verify_payment_wrong_amount() {
     let env = Env::default();
-    let (_admin, client) = setup_contract(&env);
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
+    let (oracle, client) = setup_payment_processor(&env);
 
     let payment_id = String::from_str(&env, "payment_123");
-    let merchant_id = Address::generate(&env);
+    let merchant_id = register_merchant(&env, &client);
     let amount = 1000000000i128;
     let currency = Symbol::new(&env, "USDC");
     let deposit_address = Address::generate(&env);
@@ -141,6 +179,11 @@ fn test_verify_payment_wrong_amount() {
         &currency,
         &deposit_address,
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
     );
 
     // Try to verify with wrong amount
@@ -149,10 +192,12 @@ fn test_verify_payment_wrong_amount() {
     let amount_received = amount - 1000000i128; // Slightly less
 
     let status = client.verify_payment(
+        &oracle,
         &payment_id,
         &transaction_hash,
         &payer_address,
         &amount_received,
+        &next_nonce(&client, &oracle),
     );
 
     assert_eq!(status, PaymentStatus::Failed);
@@ -162,20 +207,55 @@ fn test_verify_payment_wrong_amount() {
     assert_eq!(payment.status, PaymentStatus::Failed);
 }
 
+#[test]
+fn test_verify_payment_requires_oracle_role() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let amount = 1000000000i128;
+    client.create_payment(
+        &payment_id,
+        &register_merchant(&env, &client),
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    // An account without the ORACLE role cannot confirm payments
+    let impostor = Address::generate(&env);
+    let result = client.try_verify_payment(
+        &impostor,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &impostor),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    let payment = client.get_payment(&payment_id);
+    assert_eq!(payment.status, PaymentStatus::Pending);
+}
+
 #[test]
 fn test_get_payment() {
     let env = Env::default();
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
+    let (_oracle, client) = setup_payment_processor(&env);
 
     let payment_id = String::from_str(&env, "payment_456");
-    let merchant_id = Address::generate(&env);
+    let merchant_id = register_merchant(&env, &client);
     let amount = 500000000i128;
     let currency = Symbol::new(&env, "USDC");
     let deposit_address = Address::generate(&env);
     let expires_at = env.ledger().timestamp() + 7200;
 
-    let refund_id1 = client.create_refund(
     // Create payment
     let created_payment = client.create_payment(
         &payment_id,
@@ -184,6 +264,11 @@ fn test_get_payment() {
         &currency,
         &deposit_address,
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
     );
 
     // Get payment details
@@ -193,7 +278,10 @@ fn test_get_payment() {
     assert_eq!(retrieved_payment.merchant_id, created_payment.merchant_id);
     assert_eq!(retrieved_payment.amount, created_payment.amount);
     assert_eq!(retrieved_payment.currency, created_payment.currency);
-    assert_eq!(retrieved_payment.deposit_address, created_payment.deposit_address);
+    assert_eq!(
+        retrieved_payment.deposit_address,
+        created_payment.deposit_address
+    );
     assert_eq!(retrieved_payment.status, created_payment.status);
     assert_eq!(retrieved_payment.expires_at, created_payment.expires_at);
 }
@@ -201,11 +289,10 @@ fn test_get_payment() {
 #[test]
 fn test_cancel_expired_payment() {
     let env = Env::default();
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
+    let (_oracle, client) = setup_payment_processor(&env);
 
     let payment_id = String::from_str(&env, "payment_expired");
-    let merchant_id = Address::generate(&env);
+    let merchant_id = register_merchant(&env, &client);
     let amount = 1000000000i128;
     let currency = Symbol::new(&env, "USDC");
     let deposit_address = Address::generate(&env);
@@ -219,22 +306,13 @@ fn test_cancel_expired_payment() {
         &currency,
         &deposit_address,
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
     );
 
-    let refunds = client.get_payment_refunds(&payment_id);
-    assert_eq!(refunds.len(), 2);
-
-    let mut found1 = false;
-    let mut found2 = false;
-    for refund in refunds.iter() {
-        if refund.refund_id == refund_id1 {
-            found1 = true;
-        }
-        if refund.refund_id == refund_id2 {
-            found2 = true;
-        }
-    }
-    assert!(found1 && found2);
     // Fast-forward time past expiration
     env.ledger().set_timestamp(expires_at + 1);
 
@@ -247,207 +325,768 @@ fn test_cancel_expired_payment() {
 }
 
 #[test]
-fn test_payment_already_exists() {
+fn test_merchant_extends_pending_expiry() {
     let env = Env::default();
-    let (_admin, _client) = setup_contract(&env);
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
-
-    let payment_id = String::from_str(&env, "duplicate_payment");
-    let merchant_id = Address::generate(&env);
-    let amount = 1000000000i128;
-    let currency = Symbol::new(&env, "USDC");
-    let deposit_address = Address::generate(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "slow_transfer");
     let expires_at = env.ledger().timestamp() + 3600;
-
-    // Create payment first time
     client.create_payment(
         &payment_id,
         &merchant_id,
-        &amount,
-        &currency,
-        &deposit_address,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
         &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let result = client.try_extend_payment_expiry(
+        &Address::generate(&env),
+        &payment_id,
+        &(expires_at + 3600),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = client.try_extend_payment_expiry(&merchant_id, &payment_id, &expires_at);
+    assert_eq!(result, Err(Ok(Error::InvalidTimeRange)));
+
+    let extended = client.extend_payment_expiry(&merchant_id, &payment_id, &(expires_at + 3600));
+    assert_eq!(extended.expires_at, expires_at + 3600);
+    // The cap counts from the original expiry, so repeated extensions cannot creep past it
+    let result = client.try_extend_payment_expiry(
+        &merchant_id,
+        &payment_id,
+        &(expires_at + DEFAULT_MAX_EXPIRY_EXTENSION + 1),
+    );
+    assert_eq!(result, Err(Ok(Error::ExtensionTooLong)));
+    client.extend_payment_expiry(
+        &merchant_id,
+        &payment_id,
+        &(expires_at + DEFAULT_MAX_EXPIRY_EXTENSION),
     );
 
-    // Try to create the same payment again (this will panic in Soroban tests)
-    // In a real environment, this would return an error
+    // Past the original deadline, the charge is still payable
+    env.ledger().set_timestamp(expires_at + 1);
+    let result = client.try_cancel_payment(&payment_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    assert_eq!(
+        client.get_payment(&payment_id).status,
+        PaymentStatus::Pending
+    );
 }
 
 #[test]
-fn test_verify_expired_payment() {
+fn test_pinned_payer_rejects_other_senders() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let contract_id = env.register(PaymentProcessor, ());
-    let client = PaymentProcessorClient::new(&env, &contract_id);
-
-    let payment_id = String::from_str(&env, "expired_payment");
-    let merchant_id = Address::generate(&env);
-    let amount = 1000000000i128;
-    let currency = Symbol::new(&env, "USDC");
-    let deposit_address = Address::generate(&env);
-    let expires_at = env.ledger().timestamp() + 3600;
-
-    let refund_id = client.create_refund(
-    // Create payment
-    client.create_payment(
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "b2b_invoice");
+    let buyer = Address::generate(&env);
+    let payment = client.create_payment(
         &payment_id,
         &merchant_id,
-        &amount,
-        &currency,
-        &deposit_address,
-        &expires_at,
+        &5_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, "PO-881"),
+        &None,
+        &None,
+        &Some(buyer.clone()),
+        &None,
     );
+    assert_eq!(payment.expected_payer, Some(buyer.clone()));
 
-    let operator = Address::generate(&env);
-    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
-    client.process_refund(&operator, &refund_id);
-}
+    let result = client.try_verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::random(&env),
+        &Address::generate(&env),
+        &5_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(result, Err(Ok(Error::WrongPayer)));
+    assert_eq!(
+        client.get_payment(&payment_id).status,
+        PaymentStatus::Pending
+    );
 
-#[test]
-fn test_get_nonexistent_refund() {
-    let _env = Env::default();
-    let (_admin, _client) = setup_contract(&_env);
+    let status = client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::random(&env),
+        &buyer,
+        &5_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
 }
 
 #[test]
-fn test_initialize_contract() {
+fn test_accepted_overpayment_is_confirmed_as_tip() {
     let env = Env::default();
-    let contract_id = env.register(RefundManager, ());
-    let client = RefundManagerClient::new(&env, &contract_id);
-    let admin = Address::generate(&env);
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let create = |id: &str| {
+        client.create_payment(
+            &String::from_str(&env, id),
+            &merchant_id,
+            &5_000,
+            &Symbol::new(&env, "USDC"),
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
 
-    client.initialize(&admin);
+    // At the default API version an overpaid charge fails unless tips are accepted
+    let strict = create("no_tips");
+    let status = client.verify_payment(
+        &oracle,
+        &strict.payment_id,
+        &BytesN::random(&env),
+        &Address::generate(&env),
+        &6_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Failed);
 
-    let stored_admin = client.get_admin();
-    assert_eq!(stored_admin, Some(admin.clone()));
-    assert!(client.has_role(&role_admin(&env), &admin));
+    let tipped = create("tips_welcome");
+    let result =
+        client.try_set_accept_overpayment(&Address::generate(&env), &tipped.payment_id, &true);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    assert!(
+        client
+            .set_accept_overpayment(&merchant_id, &tipped.payment_id, &true)
+            .accept_overpayment
+    );
+    let status = client.verify_payment(
+        &oracle,
+        &tipped.payment_id,
+        &BytesN::random(&env),
+        &Address::generate(&env),
+        &6_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+    let confirmed = client.get_payment(&tipped.payment_id);
+    assert_eq!(confirmed.amount, 6_000);
+    assert_eq!(confirmed.tip_amount, 1_000);
+    assert_eq!(confirmed.overpaid_amount, 0);
+    assert_eq!(
+        client.try_set_accept_overpayment(&merchant_id, &tipped.payment_id, &false),
+        Err(Ok(Error::PaymentAlreadyProcessed))
+    );
 }
 
 #[test]
-fn test_grant_role() {
+fn test_rate_limit_throttles_payment_creation_per_merchant() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let account = Address::generate(&env);
-    let role = role_oracle(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let other_merchant = register_merchant(&env, &client);
+    let create = |id: &str, merchant_id: &Address| {
+        client.try_create_payment(
+            &String::from_str(&env, id),
+            merchant_id,
+            &1_000,
+            &Symbol::new(&env, "USDC"),
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
 
-    client.grant_role(&admin, &role, &account);
-    assert!(client.has_role(&role, &account));
-}
+    assert_eq!(
+        client.try_set_rate_limit(&admin, &0, &100),
+        Err(Ok(Error::InvalidInterval))
+    );
+    client.set_rate_limit(&admin, &2, &100);
+    env.ledger().set_sequence_number(100);
+    assert!(create("rl_1", &merchant_id).is_ok());
+    assert!(create("rl_2", &merchant_id).is_ok());
+    assert_eq!(client.get_payment_rate(&merchant_id), 2);
+    assert_eq!(create("rl_3", &merchant_id), Err(Ok(Error::RateLimited)));
+    assert!(create("rl_other", &other_merchant).is_ok());
 
-#[test]
-fn test_grant_role_unauthorized() {
-    let _env = Env::default();
-    let (_admin, _client) = setup_contract(&_env);
-    let _unauthorized = Address::generate(&_env);
+    // Halfway into the next window, half of the previous window still counts
+    env.ledger().set_sequence_number(250);
+    assert_eq!(client.get_payment_rate(&merchant_id), 1);
+    assert!(create("rl_4", &merchant_id).is_ok());
+    assert_eq!(create("rl_5", &merchant_id), Err(Ok(Error::RateLimited)));
+
+    client.clear_rate_limit(&admin);
+    assert!(create("rl_5", &merchant_id).is_ok());
 }
 
 #[test]
-fn test_revoke_role() {
+fn test_config_drives_fees_pause_and_metadata() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let account = Address::generate(&env);
-    let role = role_merchant(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
 
-    client.grant_role(&admin, &role, &account);
-    assert!(client.has_role(&role, &account));
+    let mut config = client.get_config();
+    assert_eq!(config.default_expiry, DEFAULT_PAYMENT_EXPIRY);
+    assert_eq!(config.tolerance_bps, DEFAULT_TOLERANCE_BPS);
+    assert_eq!(config.max_metadata_size, DEFAULT_MAX_METADATA_SIZE);
+    assert!(!config.paused);
 
-    client.revoke_role(&admin, &role, &account);
-    assert!(!client.has_role(&role, &account));
+    config.fee_bps = 20_000;
+    assert_eq!(
+        client.try_set_config(&admin, &config),
+        Err(Ok(Error::InvalidFee))
+    );
+    assert_eq!(
+        client.try_set_config(&Address::generate(&env), &config),
+        Err(Ok(Error::Unauthorized))
+    );
+    config.fee_bps = 150;
+    config.max_metadata_size = 1;
+    client.set_config(&admin, &config);
+    assert_eq!(client.get_effective_fee(&merchant_id), 150);
+
+    // The older setters write through to the same configuration
+    client.pause(&admin);
+    assert!(client.get_config().paused);
+    client.unpause(&admin);
+
+    let mut metadata = Map::new(&env);
+    metadata.set(Symbol::new(&env, "a"), String::from_str(&env, "1"));
+    metadata.set(Symbol::new(&env, "b"), String::from_str(&env, "2"));
+    let result = client.try_create_payment(
+        &String::from_str(&env, "too_much_metadata"),
+        &merchant_id,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &Some(metadata),
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidMetadata)));
 }
 
 #[test]
-fn test_has_role() {
+fn test_payment_expiry_bounds() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let account = Address::generate(&env);
-    let role = role_oracle(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    env.ledger().set_timestamp(10_000);
+    let create = |id: &str, expires_at: u64| {
+        client.try_create_payment(
+            &String::from_str(&env, id),
+            &merchant_id,
+            &1_000,
+            &Symbol::new(&env, "USDC"),
+            &Address::generate(&env),
+            &expires_at,
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
 
-    assert!(!client.has_role(&role, &account));
+    assert_eq!(create("past", 10_000), Err(Ok(Error::InvalidExpiry)));
+    assert_eq!(
+        create("too_far", 10_000 + DEFAULT_MAX_PAYMENT_EXPIRY + 1),
+        Err(Ok(Error::InvalidExpiry))
+    );
+    let longest = create("longest", 10_000 + DEFAULT_MAX_PAYMENT_EXPIRY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(longest.expires_at, 10_000 + DEFAULT_MAX_PAYMENT_EXPIRY);
 
-    client.grant_role(&admin, &role, &account);
-    assert!(client.has_role(&role, &account));
+    // Zero takes the configured default
+    let defaulted = create("defaulted", 0).unwrap().unwrap();
+    assert_eq!(defaulted.expires_at, 10_000 + DEFAULT_PAYMENT_EXPIRY);
+
+    let admin = client.get_admin().unwrap();
+    let mut config = client.get_config();
+    config.default_expiry = 7200;
+    config.max_expiry = 3600;
+    assert_eq!(
+        client.try_set_config(&admin, &config),
+        Err(Ok(Error::InvalidExpiry))
+    );
+    config.default_expiry = 600;
+    client.set_config(&admin, &config);
+    assert_eq!(create("short", 0).unwrap().unwrap().expires_at, 10_600);
+    assert_eq!(create("long", 20_000), Err(Ok(Error::InvalidExpiry)));
 }
 
 #[test]
-fn test_renounce_role() {
+fn test_derived_charge_expiry_bounds() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-    let account = Address::generate(&env);
-    let role = role_merchant(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    for _ in 0..2 {
+        client.add_deposit_address(&merchant_id, &Address::generate(&env));
+    }
+    let payer = Address::generate(&env);
+    let mut config = client.get_config();
+    config.default_expiry = 600;
+    config.max_expiry = 3600;
+    client.set_config(&admin, &config);
 
-    client.grant_role(&admin, &role, &account);
-    assert!(client.has_role(&role, &account));
+    // A link charge lives for the link window
+    let link_expiry = env.ledger().timestamp() + 30 * 86_400;
+    let link = client.create_payment_link(&merchant_id, &None, &usdc, &0, &link_expiry);
+    let charge = client.pay_link(&link.link_id, &payer, &1_000);
+    assert_eq!(
+        charge.expires_at,
+        env.ledger().timestamp() + LINK_CHARGE_WINDOW
+    );
 
-    client.renounce_role(&account, &role);
-    assert!(!client.has_role(&role, &account));
+    // An invoice charge that would outlive the configured maximum is cut back to it
+    let lines = Vec::from_array(
+        &env,
+        [InvoiceLine {
+            description_hash: BytesN::<32>::random(&env),
+            quantity: 1,
+            unit_price: 5_000,
+        }],
+    );
+    let invoice = client.create_invoice(&merchant_id, &usdc, &lines, &(link_expiry - 1));
+    assert_eq!(
+        client.pay_invoice(&payer, &invoice.invoice_id).expires_at,
+        env.ledger().timestamp() + 3600
+    );
 }
 
 #[test]
-fn test_transfer_admin() {
+fn test_payment_already_exists() {
     let env = Env::default();
-    let (current_admin, client) = setup_contract(&env);
-    let new_admin = Address::generate(&env);
+    let (_oracle, client) = setup_payment_processor(&env);
 
-    client.transfer_admin(&current_admin, &new_admin);
+    let payment_id = String::from_str(&env, "duplicate_payment");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 1000000000i128;
+    let currency = Symbol::new(&env, "USDC");
+    let deposit_address = Address::generate(&env);
+    let expires_at = env.ledger().timestamp() + 3600;
 
-    assert!(client.has_role(&role_admin(&env), &new_admin));
-    assert!(!client.has_role(&role_admin(&env), &current_admin));
+    // Create payment first time
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
 
-    let stored_admin = client.get_admin();
-    assert_eq!(stored_admin, Some(new_admin));
+    // Try to create the same payment again
+    let result = client.try_create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::PaymentAlreadyExists)));
 }
 
 #[test]
-fn test_process_refund_with_oracle_role() {
+fn test_verify_expired_payment() {
     let env = Env::default();
-    let (admin, client) = setup_contract(&env);
-
-    let payment_id = String::from_str(&env, "payment_123");
-    let refund_amount = 500i128;
-    let reason = String::from_str(&env, "Product defect");
-    let requester = Address::generate(&env);
+    let (oracle, client) = setup_payment_processor(&env);
 
-    let refund_id = client.create_refund(&payment_id, &refund_amount, &reason, &requester);
+    let payment_id = String::from_str(&env, "expired_payment");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 1000000000i128;
+    let currency = Symbol::new(&env, "USDC");
+    let deposit_address = Address::generate(&env);
+    let expires_at = env.ledger().timestamp() + 3600;
 
-    let oracle = Address::generate(&env);
-    client.grant_role(&admin, &role_oracle(&env), &oracle);
-    client.process_refund(&oracle, &refund_id);
+    // Create payment
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    // Fast-forward time past expiration
+    env.ledger().set_timestamp(expires_at + 1);
+
+    // Verifying an expired payment records it as late rather than confirming it
+    let payer_address = Address::generate(&env);
+    let transaction_hash = BytesN::<32>::random(&env);
+    let status = client.verify_payment(
+        &oracle,
+        &payment_id,
+        &transaction_hash,
+        &payer_address,
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::LateConfirmed);
+    let payment = client.get_payment(&payment_id);
+    assert_eq!(payment.overpaid_amount, amount);
+    assert_eq!(payment.payer_address, Some(payer_address));
+}
+
+#[test]
+fn test_invalid_payment_amount() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let payment_id = String::from_str(&env, "invalid_amount");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 0i128; // Invalid amount
+    let currency = Symbol::new(&env, "USDC");
+    let deposit_address = Address::generate(&env);
+    let expires_at = env.ledger().timestamp() + 3600;
+
+    let result = client.try_create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_create_refund() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let refund_amount = 1000i128;
+    let detail = String::from_str(&env, "Customer requested refund");
+    let requester = Address::generate(&env);
+
+    let refund_id = client.create_refund(
+        &payment_id,
+        &refund_amount,
+        &RefundReason::CustomerRequest,
+        &Some(detail.clone()),
+        &requester,
+    );
+    let refund = client.get_refund(&refund_id);
+
+    assert_eq!(refund.payment_id, payment_id);
+    assert_eq!(refund.amount, refund_amount);
+    assert_eq!(refund.reason, RefundReason::CustomerRequest);
+    assert_eq!(refund.detail, Some(detail));
+    assert_eq!(refund.status, RefundStatus::Pending);
+    assert_eq!(refund.requester, requester);
+    assert!(refund.processed_at.is_none());
+    assert!(refund.dispute_id.is_none());
+}
+
+#[test]
+fn test_process_refund() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let refund_amount = 1000i128;
+    let reason = String::from_str(&env, "Customer requested refund");
+    let requester = Address::generate(&env);
+
+    let refund_id = client.create_refund(
+        &payment_id,
+        &refund_amount,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.process_refund(&operator, &refund_id);
 
     let refund = client.get_refund(&refund_id);
     assert_eq!(refund.status, RefundStatus::Completed);
+    assert!(refund.processed_at.is_some());
 }
 
 #[test]
-fn test_process_refund_unauthorized() {
+fn test_get_payment_refunds() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_456");
+    let requester = Address::generate(&env);
+
+    let refund_id1 = client.create_refund(
+        &payment_id,
+        &500i128,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&env, "Reason 1")),
+        &requester,
+    );
+    let refund_id2 = client.create_refund(
+        &payment_id,
+        &300i128,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&env, "Reason 2")),
+        &requester,
+    );
+
+    let refunds = client.get_payment_refunds(&payment_id);
+    assert_eq!(refunds.len(), 2);
+
+    let mut found1 = false;
+    let mut found2 = false;
+    for refund in refunds.iter() {
+        if refund.refund_id == refund_id1 {
+            found1 = true;
+        }
+        if refund.refund_id == refund_id2 {
+            found2 = true;
+        }
+    }
+    assert!(found1 && found2);
+}
+
+#[test]
+fn test_get_refunds_bulk() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let reason = String::from_str(&env, "Customer requested refund");
+    let first = client.create_refund(
+        &String::from_str(&env, "bulk_pay_1"),
+        &100i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &Address::generate(&env),
+    );
+    let second = client.create_refund(
+        &String::from_str(&env, "bulk_pay_2"),
+        &200i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &Address::generate(&env),
+    );
+
+    let mut ids = Vec::new(&env);
+    ids.push_back(second.clone());
+    ids.push_back(String::from_str(&env, "missing_refund"));
+    ids.push_back(first.clone());
+
+    // Results line up with the requested IDs
+    let refunds = client.get_refunds(&ids);
+    assert_eq!(refunds.len(), 3);
+    assert_eq!(refunds.get(0).unwrap().unwrap().amount, 200);
+    assert!(refunds.get(1).unwrap().is_none());
+    assert_eq!(refunds.get(2).unwrap().unwrap().refund_id, first);
+
+    let mut too_many = Vec::new(&env);
+    for _ in 0..=MAX_BATCH_SIZE {
+        too_many.push_back(first.clone());
+    }
+    assert_eq!(
+        client.try_get_refunds(&too_many),
+        Err(Ok(Error::BatchTooLarge))
+    );
+}
+
+#[test]
+fn test_invalid_refund_amount() {
     let env = Env::default();
     let (_admin, client) = setup_contract(&env);
 
+    let result = client.try_create_refund(
+        &String::from_str(&env, "payment_123"),
+        &0i128,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&env, "Invalid")),
+        &Address::generate(&env),
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_process_already_processed_refund() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+
     let payment_id = String::from_str(&env, "payment_123");
-    let refund_amount = 500i128;
-    let reason = String::from_str(&env, "Product defect");
+    let refund_amount = 1000i128;
+    let reason = String::from_str(&env, "Customer requested refund");
     let requester = Address::generate(&env);
 
-    let _refund_id = client.create_refund(&payment_id, &refund_amount, &reason, &requester);
+    let refund_id = client.create_refund(
+        &payment_id,
+        &refund_amount,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.process_refund(&operator, &refund_id);
+
+    let result = client.try_process_refund(&operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::RefundAlreadyProcessed)));
 }
 
 #[test]
-fn test_multiple_roles() {
+fn test_get_nonexistent_refund() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let result = client.try_get_refund(&String::from_str(&env, "missing"));
+    assert_eq!(result, Err(Ok(Error::RefundNotFound)));
+}
+
+#[test]
+fn test_initialize_contract() {
+    let env = Env::default();
+    let contract_id = env.register(RefundManager, ());
+    let client = RefundManagerClient::new(&env, &contract_id);
+    let admin = Address::generate(&env);
+
+    client.initialize(&admin, &None);
+
+    let stored_admin = client.get_admin();
+    assert_eq!(stored_admin, Some(admin.clone()));
+    assert!(client.has_role(&role_admin(&env), &admin));
+}
+
+#[test]
+fn test_initialize_only_once() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let registry_id = env.register(MerchantRegistry, ());
+    let client = PaymentProcessorClient::new(&env, &env.register(PaymentProcessor, ()));
+
+    let usdc = Symbol::new(&env, "USDC");
+    let token = Address::generate(&env);
+    let fee_config = FeeConfig {
+        fee_bps: 150,
+        fee_collector: Address::generate(&env),
+    };
+    client.initialize(
+        &admin,
+        &registry_id,
+        &Map::from_array(&env, [(usdc.clone(), token.clone())]),
+        &Some(fee_config.clone()),
+    );
+    assert_eq!(client.get_admin(), Some(admin.clone()));
+    assert_eq!(client.get_merchant_registry(), Some(registry_id.clone()));
+    assert_eq!(client.get_supported_token(&usdc), Some(token));
+    assert_eq!(client.get_fee_config(), Some(fee_config));
+
+    // A second call cannot take over the admin or registry
+    let attacker = Address::generate(&env);
+    let result = client.try_initialize(&attacker, &attacker, &Map::new(&env), &None);
+    assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
+    assert!(!client.has_role(&role_admin(&env), &attacker));
+
+    let (_admin, refunds) = setup_contract(&env);
+    let result = refunds.try_initialize(&attacker, &Some(attacker.clone()));
+    assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
+
+    // A RefundManager deployed after the processor can be linked to it from the start
+    let linked = RefundManagerClient::new(&env, &env.register(RefundManager, ()));
+    linked.initialize(&admin, &Some(client.address.clone()));
+    let info = linked.get_contract_info();
+    assert_eq!(
+        info.linked_contracts
+            .get(Symbol::new(&env, "PAYMENT_PROCESSOR")),
+        Some(client.address.clone())
+    );
+    assert_eq!(info.currencies, Vec::from_array(&env, [usdc]));
+}
+
+#[cfg(feature = "constructor")]
+#[test]
+fn test_constructor_initializes_at_deploy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let registry_id = env.register(MerchantRegistry, ());
+    let usdc = Symbol::new(&env, "USDC");
+    let tokens = Map::from_array(&env, [(usdc.clone(), Address::generate(&env))]);
+    let contract_id = env.register(
+        PaymentProcessor,
+        (&admin, &registry_id, tokens, None::<FeeConfig>),
+    );
+    let client = PaymentProcessorClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_admin(), Some(admin.clone()));
+    assert!(client.get_supported_token(&usdc).is_some());
+    let result = client.try_initialize(&admin, &registry_id, &Map::new(&env), &None);
+    assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
+}
+
+#[test]
+fn test_grant_role() {
     let env = Env::default();
     let (admin, client) = setup_contract(&env);
     let account = Address::generate(&env);
+    let role = role_oracle(&env);
 
-    client.grant_role(&admin, &role_merchant(&env), &account);
-    client.grant_role(&admin, &role_oracle(&env), &account);
-    client.grant_role(&admin, &role_settlement_operator(&env), &account);
+    client.grant_role(&admin, &role, &account);
+    assert!(client.has_role(&role, &account));
+}
 
-    assert!(client.has_role(&role_merchant(&env), &account));
-    assert!(client.has_role(&role_oracle(&env), &account));
-    assert!(client.has_role(&role_settlement_operator(&env), &account));
+#[test]
+fn test_grant_role_unauthorized() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+    let unauthorized = Address::generate(&env);
+    let account = Address::generate(&env);
+
+    let result = client.try_grant_role(&unauthorized, &role_oracle(&env), &account);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
 }
 
 #[test]
-fn test_role_already_granted() {
+fn test_revoke_role() {
     let env = Env::default();
     let (admin, client) = setup_contract(&env);
     let account = Address::generate(&env);
@@ -455,28 +1094,3613 @@ fn test_role_already_granted() {
 
     client.grant_role(&admin, &role, &account);
     assert!(client.has_role(&role, &account));
-    // Fast-forward time past expiration
-    env.ledger().set_timestamp(expires_at + 1);
 
-    // Try to verify expired payment (this will panic in Soroban tests)
-    let payer_address = Address::generate(&env);
-    let transaction_hash = BytesN::<32>::random(&env);
-    // client.verify_payment(&payment_id, &transaction_hash, &payer_address, &amount);
+    client.revoke_role(&admin, &role, &account);
+    assert!(!client.has_role(&role, &account));
 }
 
 #[test]
-fn test_invalid_payment_amount() {
+fn test_has_role() {
     let env = Env::default();
-    let contract_id = env.register(PaymentProcessor, ());
-    let _client = PaymentProcessorClient::new(&env, &contract_id);
+    let (admin, client) = setup_contract(&env);
+    let account = Address::generate(&env);
+    let role = role_oracle(&env);
+
+    assert!(!client.has_role(&role, &account));
+
+    client.grant_role(&admin, &role, &account);
+    assert!(client.has_role(&role, &account));
+}
+
+#[test]
+fn test_renounce_role() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    let account = Address::generate(&env);
+    let role = role_merchant(&env);
+
+    client.grant_role(&admin, &role, &account);
+    assert!(client.has_role(&role, &account));
+
+    client.renounce_role(&account, &role);
+    assert!(!client.has_role(&role, &account));
+}
+
+#[test]
+fn test_transfer_admin() {
+    let env = Env::default();
+    let (current_admin, client) = setup_contract(&env);
+    let new_admin = Address::generate(&env);
 
-    let _payment_id = String::from_str(&env, "invalid_amount");
-    let _merchant_id = Address::generate(&env);
-    let _amount = 0i128; // Invalid amount
-    let _currency = Symbol::new(&env, "USDC");
-    let _deposit_address = Address::generate(&env);
-    let _expires_at = env.ledger().timestamp() + 3600;
+    client.transfer_admin(&current_admin, &new_admin);
+
+    assert!(client.has_role(&role_admin(&env), &new_admin));
+    assert!(!client.has_role(&role_admin(&env), &current_admin));
+
+    let stored_admin = client.get_admin();
+    assert_eq!(stored_admin, Some(new_admin));
+}
 
-    // Try to create payment with invalid amount (this will panic in Soroban tests)
-    // _client.create_payment(&_payment_id, &_merchant_id, &_amount, &_currency, &_deposit_address, &_expires_at);
+#[test]
+fn test_privileged_calls_require_auth() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    let impostor = Address::generate(&env);
+    let account = Address::generate(&env);
+    let role = role_settlement_operator(&env);
+
+    // Passing the admin's address is not enough without the admin's signature
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &impostor,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "grant_role",
+                args: (&admin, &role, &account).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_grant_role(&admin, &role, &account);
+    assert!(result.is_err());
+    assert!(!client.has_role(&role, &account));
+
+    client
+        .mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "grant_role",
+                args: (&admin, &role, &account).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .grant_role(&admin, &role, &account);
+    assert!(client.has_role(&role, &account));
+
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &impostor,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "transfer_admin",
+                args: (&admin, &impostor).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_transfer_admin(&admin, &impostor);
+    assert!(result.is_err());
+    assert_eq!(client.get_admin(), Some(admin.clone()));
+
+    // Refunds cannot be requested in, or executed by, someone else's name
+    let payment_id = String::from_str(&env, "payment_123");
+    let reason = String::from_str(&env, "Customer requested refund");
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &impostor,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "create_refund",
+                args: (&payment_id, 1000i128, &reason, &account).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_create_refund(
+            &payment_id,
+            &1000i128,
+            &RefundReason::CustomerRequest,
+            &Some(reason.clone()),
+            &account,
+        );
+    assert!(result.is_err());
+
+    env.mock_all_auths();
+    let refund_id = client.create_refund(
+        &payment_id,
+        &1000i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &account,
+    );
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &impostor,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "process_refund",
+                args: (&account, &refund_id).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_process_refund(&account, &refund_id);
+    assert!(result.is_err());
+    assert_eq!(client.get_refund(&refund_id).status, RefundStatus::Pending);
+}
+
+#[test]
+fn test_process_refund_with_oracle_role() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let refund_amount = 500i128;
+    let reason = String::from_str(&env, "Product defect");
+    let requester = Address::generate(&env);
+
+    let refund_id = client.create_refund(
+        &payment_id,
+        &refund_amount,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let oracle = Address::generate(&env);
+    client.grant_role(&admin, &role_oracle(&env), &oracle);
+    client.process_refund(&oracle, &refund_id);
+
+    let refund = client.get_refund(&refund_id);
+    assert_eq!(refund.status, RefundStatus::Completed);
+}
+
+#[test]
+fn test_process_refund_unauthorized() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let refund_amount = 500i128;
+    let reason = String::from_str(&env, "Product defect");
+    let requester = Address::generate(&env);
+
+    let refund_id = client.create_refund(
+        &payment_id,
+        &refund_amount,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let unauthorized = Address::generate(&env);
+    let result = client.try_process_refund(&unauthorized, &refund_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_multiple_roles() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    let account = Address::generate(&env);
+
+    client.grant_role(&admin, &role_merchant(&env), &account);
+    client.grant_role(&admin, &role_oracle(&env), &account);
+    client.grant_role(&admin, &role_settlement_operator(&env), &account);
+
+    assert!(client.has_role(&role_merchant(&env), &account));
+    assert!(client.has_role(&role_oracle(&env), &account));
+    assert!(client.has_role(&role_settlement_operator(&env), &account));
+}
+
+#[test]
+fn test_role_already_granted() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    let account = Address::generate(&env);
+    let role = role_merchant(&env);
+
+    client.grant_role(&admin, &role, &account);
+    assert!(client.has_role(&role, &account));
+
+    let result = client.try_grant_role(&admin, &role, &account);
+    assert_eq!(result, Err(Ok(Error::RoleAlreadyGranted)));
+}
+
+#[test]
+fn test_deposit_pool_round_robin() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let address_a = Address::generate(&env);
+    let address_b = Address::generate(&env);
+    client.add_deposit_address(&merchant_id, &address_a);
+    client.add_deposit_address(&merchant_id, &address_b);
+
+    let currency = Symbol::new(&env, "USDC");
+    let expires_at = env.ledger().timestamp() + 3600;
+    let mut deposit_addresses = Vec::new(&env);
+    for payment_id in ["pool_1", "pool_2"] {
+        let payment = client.create_payment_from_pool(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            &currency,
+            &expires_at,
+        );
+        deposit_addresses.push_back(payment.deposit_address);
+    }
+    assert_eq!(deposit_addresses.get(0), Some(address_a.clone()));
+    assert_eq!(deposit_addresses.get(1), Some(address_b));
+
+    // Both addresses are attached to Pending charges, so none can be handed out
+    let pool_3 = String::from_str(&env, "pool_3");
+    let result = client.try_create_payment_from_pool(
+        &pool_3,
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &expires_at,
+    );
+    assert_eq!(result, Err(Ok(Error::DepositAddressInUse)));
+
+    // Confirming the first charge frees its address for the next rotation
+    client.verify_payment(
+        &oracle,
+        &String::from_str(&env, "pool_1"),
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &1000i128,
+        &next_nonce(&client, &oracle),
+    );
+    assert!(client.get_active_deposit(&address_a).is_none());
+    let payment =
+        client.create_payment_from_pool(&pool_3, &merchant_id, &1000i128, &currency, &expires_at);
+    assert_eq!(payment.deposit_address, address_a.clone());
+
+    // Decommissioned addresses are no longer handed out
+    client.remove_deposit_address(&merchant_id, &address_a);
+    let pool = client.get_deposit_pool(&merchant_id);
+    assert_eq!(pool.addresses.len(), 1);
+    assert!(!pool.addresses.contains(&address_a));
+}
+
+#[test]
+fn test_deposit_pool_per_payment() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let address = Address::generate(&env);
+    client.add_deposit_address(&merchant_id, &address);
+    client.set_deposit_rotation(&merchant_id, &RotationPolicy::PerPayment);
+
+    let currency = Symbol::new(&env, "USDC");
+    let expires_at = env.ledger().timestamp() + 3600;
+    let payment = client.create_payment_from_pool(
+        &String::from_str(&env, "single_use"),
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &expires_at,
+    );
+    assert_eq!(payment.deposit_address, address);
+
+    // Each address is used once, so the pool is now exhausted
+    let result = client.try_create_payment_from_pool(
+        &String::from_str(&env, "single_use_2"),
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &expires_at,
+    );
+    assert_eq!(result, Err(Ok(Error::DepositPoolEmpty)));
+}
+
+#[test]
+fn test_create_payment_requires_verified_merchant() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+
+    let currency = Symbol::new(&env, "USDC");
+    let expires_at = env.ledger().timestamp() + 3600;
+
+    // Unregistered merchant
+    let unknown = Address::generate(&env);
+    let result = client.try_create_payment(
+        &String::from_str(&env, "unregistered"),
+        &unknown,
+        &1000i128,
+        &currency,
+        &Address::generate(&env),
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::MerchantNotFound)));
+
+    // Registered but unverified merchant
+    let unverified = Address::generate(&env);
+    registry.register_merchant(
+        &unverified,
+        &String::from_str(&env, "Pending KYC"),
+        &String::from_str(&env, "USD"),
+    );
+    let result = client.try_create_payment(
+        &String::from_str(&env, "unverified"),
+        &unverified,
+        &1000i128,
+        &currency,
+        &Address::generate(&env),
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::MerchantNotVerified)));
+
+    // Verified merchant that has been deactivated
+    let inactive = register_merchant(&env, &client);
+    registry.update_merchant(&inactive, &None, &None, &Some(false));
+    let result = client.try_create_payment(
+        &String::from_str(&env, "inactive"),
+        &inactive,
+        &1000i128,
+        &currency,
+        &Address::generate(&env),
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::MerchantNotVerified)));
+}
+
+#[test]
+fn test_private_payment_reveal() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+
+    let payment_id = String::from_str(&env, "private_payment");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 1000000000i128;
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let payer = Address::generate(&env);
+    let salt = BytesN::<32>::random(&env);
+    let commitment = privacy::payer_commitment(&env, &payer, &salt);
+
+    let admin = client.get_admin().unwrap();
+    client.set_feature(&admin, &Symbol::new(&env, "PRIVATE_PAYMENTS"), &true);
+    let status = client.verify_payment_private(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &commitment,
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+
+    // The payer address is not stored on the charge
+    let payment = client.get_payment(&payment_id);
+    assert!(payment.payer_address.is_none());
+    assert_eq!(payment.payer_commitment, Some(commitment));
+
+    // A wrong preimage is rejected
+    let result = client.try_reveal_payer(&payment_id, &payer, &BytesN::<32>::random(&env));
+    assert_eq!(result, Err(Ok(Error::CommitmentMismatch)));
+
+    client.reveal_payer(&payment_id, &payer, &salt);
+    assert_eq!(client.get_payment(&payment_id).payer_address, Some(payer));
+}
+
+#[test]
+fn test_subscription_lifecycle() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let payer = Address::generate(&env);
+    let interval = 30 * 24 * 3600;
+    let first_due_at = env.ledger().timestamp() + 100;
+    let admin = client.get_admin().unwrap();
+    client.set_feature(&admin, &Symbol::new(&env, "SUBSCRIPTIONS"), &true);
+
+    let subscription = client.create_subscription(
+        &payer,
+        &merchant_id,
+        &9_990_000i128,
+        &Symbol::new(&env, "USDC"),
+        &interval,
+        &first_due_at,
+    );
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+
+    // Not yet due
+    let result =
+        client.try_charge_subscription(&oracle, &subscription.subscription_id, &merchant_id);
+    assert_eq!(result, Err(Ok(Error::SubscriptionNotDue)));
+
+    env.ledger().set_timestamp(first_due_at);
+    let payment = client.charge_subscription(&oracle, &subscription.subscription_id, &merchant_id);
+    assert_eq!(payment.payment_id, String::from_str(&env, "sub_1_1"));
+    assert_eq!(payment.amount, 9_990_000i128);
+
+    let updated = client.get_subscription(&subscription.subscription_id);
+    assert_eq!(updated.next_due_at, first_due_at + interval);
+    assert_eq!(client.get_subscriptions_by_merchant(&merchant_id).len(), 1);
+
+    client.cancel_subscription(&payer, &subscription.subscription_id);
+    env.ledger().set_timestamp(first_due_at + interval);
+    let result =
+        client.try_charge_subscription(&oracle, &subscription.subscription_id, &merchant_id);
+    assert_eq!(result, Err(Ok(Error::SubscriptionNotActive)));
+}
+
+#[test]
+fn test_subscription_interval_beyond_max_expiry() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payer = Address::generate(&env);
+    let admin = client.get_admin().unwrap();
+    client.set_feature(&admin, &Symbol::new(&env, "SUBSCRIPTIONS"), &true);
+
+    // A 31-day cycle outlasts the 30-day charge limit; each charge lapses at the limit instead
+    let interval = 31 * 24 * 3600;
+    let first_due_at = env.ledger().timestamp() + 100;
+    let subscription = client.create_subscription(
+        &payer,
+        &merchant_id,
+        &5_000_000i128,
+        &Symbol::new(&env, "USDC"),
+        &interval,
+        &first_due_at,
+    );
+    env.ledger().set_timestamp(first_due_at);
+    let payment = client.charge_subscription(&oracle, &subscription.subscription_id, &merchant_id);
+    assert_eq!(
+        payment.expires_at,
+        first_due_at + DEFAULT_MAX_PAYMENT_EXPIRY
+    );
+    assert_eq!(
+        client
+            .get_subscription(&subscription.subscription_id)
+            .next_due_at,
+        first_due_at + interval
+    );
+}
+
+#[test]
+fn test_get_remittance_info() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+
+    let payment_id = String::from_str(&env, "order_2024_001");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 250_000_000i128;
+    let expires_at = env.ledger().timestamp() + 3600;
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let info = client.get_remittance_info(&payment_id);
+    assert_eq!(info.creditor_reference, payment_id);
+    assert_eq!(info.creditor, merchant_id);
+    assert_eq!(info.amount, amount);
+    assert_eq!(info.due_date, expires_at);
+    assert!(info.debtor.is_none());
+
+    let payer = Address::generate(&env);
+    let transaction_hash = BytesN::<32>::random(&env);
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &transaction_hash,
+        &payer,
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+
+    let info = client.get_remittance_info(&payment_id);
+    assert_eq!(info.debtor, Some(payer));
+    assert_eq!(info.end_to_end_id, Some(transaction_hash));
+    assert_eq!(info.status, PaymentStatus::Confirmed);
+}
+
+#[test]
+fn test_get_merchant_payments_pagination() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let other_merchant = register_merchant(&env, &client);
+    let currency = Symbol::new(&env, "USDC");
+    let expires_at = env.ledger().timestamp() + 3600;
+    for payment_id in ["page_1", "page_2", "page_3"] {
+        client.create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            &currency,
+            &Address::generate(&env),
+            &expires_at,
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    }
+    client.create_payment(
+        &String::from_str(&env, "other"),
+        &other_merchant,
+        &1000i128,
+        &currency,
+        &Address::generate(&env),
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let page = client.get_merchant_payments(&merchant_id, &0, &2);
+    assert_eq!(page.total, 3);
+    assert_eq!(page.payments.len(), 2);
+    assert_eq!(
+        page.payments.get(0).unwrap().payment_id,
+        String::from_str(&env, "page_1")
+    );
+
+    let page = client.get_merchant_payments(&merchant_id, &2, &2);
+    assert_eq!(page.payments.len(), 1);
+    assert_eq!(
+        page.payments.get(0).unwrap().payment_id,
+        String::from_str(&env, "page_3")
+    );
+
+    let page = client.get_merchant_payments(&merchant_id, &5, &2);
+    assert_eq!(page.payments.len(), 0);
+}
+
+#[test]
+fn test_max_refunds_per_payment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (admin, client) = setup_contract(&env);
+
+    client.set_max_refunds_per_payment(&admin, &2);
+    assert_eq!(client.get_max_refunds(&None), Some(2));
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let reason = String::from_str(&env, "Partial refund");
+    let requester = Address::generate(&env);
+    client.create_refund(
+        &payment_id,
+        &100i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+    client.create_refund(
+        &payment_id,
+        &100i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let result = client.try_create_refund(
+        &payment_id,
+        &100i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+    assert_eq!(result, Err(Ok(Error::TooManyRefunds)));
+
+    // Other payments have their own allowance
+    client.create_refund(
+        &String::from_str(&env, "payment_456"),
+        &100i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+}
+
+#[test]
+fn test_get_payments_by_status() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let currency = Symbol::new(&env, "USDC");
+    let amount = 1000i128;
+    let expires_at = env.ledger().timestamp() + 3600;
+    for payment_id in ["status_1", "status_2", "status_3"] {
+        client.create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &amount,
+            &currency,
+            &Address::generate(&env),
+            &expires_at,
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    }
+    assert_eq!(
+        client
+            .get_payments_by_status(&PaymentStatus::Pending, &0, &10)
+            .total,
+        3
+    );
+
+    client.verify_payment(
+        &oracle,
+        &String::from_str(&env, "status_1"),
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    client.verify_payment(
+        &oracle,
+        &String::from_str(&env, "status_2"),
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &(amount - 1),
+        &next_nonce(&client, &oracle),
+    );
+    env.ledger().set_timestamp(expires_at + 1);
+    client.cancel_payment(&String::from_str(&env, "status_3"));
+
+    for status in [
+        PaymentStatus::Confirmed,
+        PaymentStatus::Failed,
+        PaymentStatus::Expired,
+    ] {
+        let page = client.get_payments_by_status(&status, &0, &10);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.payments.get(0).unwrap().status, status);
+    }
+    assert_eq!(
+        client
+            .get_payments_by_status(&PaymentStatus::Pending, &0, &10)
+            .total,
+        0
+    );
+}
+
+#[test]
+fn test_define_role() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    let auditor = Symbol::new(&env, "AUDITOR");
+    let account = Address::generate(&env);
+
+    // Built-in roles are defined at initialization
+    let oracle_definition = client.get_role_definition(&role_oracle(&env)).unwrap();
+    assert_eq!(oracle_definition.admin_role, role_admin(&env));
+
+    // Undeclared roles cannot be granted
+    let result = client.try_grant_role(&admin, &auditor, &account);
+    assert_eq!(result, Err(Ok(Error::RoleNotDefined)));
+
+    let description_hash = BytesN::<32>::random(&env);
+    client.define_role(&admin, &auditor, &description_hash, &role_admin(&env));
+    let definition = client.get_role_definition(&auditor).unwrap();
+    assert_eq!(definition.description_hash, description_hash);
+
+    client.grant_role(&admin, &auditor, &account);
+    assert!(client.has_role(&auditor, &account));
+
+    // Only the admin can declare roles
+    let result = client.try_define_role(
+        &account,
+        &Symbol::new(&env, "ARBITER"),
+        &description_hash,
+        &role_admin(&env),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_role_admin_hierarchy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (admin, client) = setup_contract(&env);
+    let ops_lead = Symbol::new(&env, "OPS_LEAD");
+    client.define_role(
+        &admin,
+        &ops_lead,
+        &BytesN::<32>::random(&env),
+        &role_admin(&env),
+    );
+    let lead = Address::generate(&env);
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &ops_lead, &lead);
+
+    // Until the hierarchy says otherwise, only admins manage operators
+    let result = client.try_grant_role(&lead, &role_settlement_operator(&env), &operator);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    // Only the top-level admin re-points a role's admin role, and only at a defined role
+    let result = client.try_set_role_admin(&lead, &role_settlement_operator(&env), &ops_lead);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = client.try_set_role_admin(
+        &admin,
+        &role_settlement_operator(&env),
+        &Symbol::new(&env, "UNKNOWN"),
+    );
+    assert_eq!(result, Err(Ok(Error::RoleNotDefined)));
+    client.set_role_admin(&admin, &role_settlement_operator(&env), &ops_lead);
+    assert_eq!(
+        client
+            .get_role_definition(&role_settlement_operator(&env))
+            .unwrap()
+            .admin_role,
+        ops_lead
+    );
+
+    client.grant_role(&lead, &role_settlement_operator(&env), &operator);
+    assert!(client.has_role(&role_settlement_operator(&env), &operator));
+
+    // The top-level admin no longer administers the delegated role, nor the lead other roles
+    let result = client.try_revoke_role(&admin, &role_settlement_operator(&env), &operator);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    let result = client.try_grant_role(&lead, &role_oracle(&env), &operator);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    client.revoke_role(&lead, &role_settlement_operator(&env), &operator);
+    assert!(!client.has_role(&role_settlement_operator(&env), &operator));
+}
+
+#[test]
+fn test_batch_expiry_sweep() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let currency = Symbol::new(&env, "USDC");
+    let now = env.ledger().timestamp();
+    for (payment_id, ttl) in [("sweep_1", 60), ("sweep_2", 60), ("sweep_3", 7200)] {
+        client.create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            &currency,
+            &Address::generate(&env),
+            &(now + ttl),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    }
+    env.ledger().set_timestamp(now + 120);
+
+    // Explicit list: unknown and unexpired payments are skipped
+    let mut payment_ids = Vec::new(&env);
+    payment_ids.push_back(String::from_str(&env, "sweep_1"));
+    payment_ids.push_back(String::from_str(&env, "sweep_3"));
+    payment_ids.push_back(String::from_str(&env, "missing"));
+    assert_eq!(client.expire_payments(&payment_ids), 1);
+
+    // Index-driven sweep picks up the remaining expired payment
+    assert_eq!(client.expire_pending_batch(&10), 1);
+    assert_eq!(
+        client
+            .get_payment(&String::from_str(&env, "sweep_2"))
+            .status,
+        PaymentStatus::Expired
+    );
+    assert_eq!(
+        client
+            .get_payment(&String::from_str(&env, "sweep_3"))
+            .status,
+        PaymentStatus::Pending
+    );
+}
+
+#[test]
+fn test_settle_payment_with_platform_fee() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+    let fee_collector = Address::generate(&env);
+    client.set_fee_config(&admin, &150, &fee_collector); // 1.5%
+    let result = client.try_set_fee_config(&admin, &10_001, &fee_collector);
+    assert_eq!(result, Err(Ok(Error::InvalidFee)));
+
+    let payment_id = String::from_str(&env, "fee_payment");
+    let merchant_id = register_merchant(&env, &client);
+    let currency = Symbol::new(&env, "USDC");
+    let amount = 100_000_000i128;
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    // Only confirmed payments can be settled
+    let result = client.try_settle_payment(&operator, &payment_id);
+    assert_eq!(result, Err(Ok(Error::PaymentNotConfirmed)));
+
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    let settled = client.settle_payment(&operator, &payment_id);
+    assert_eq!(settled.status, PaymentStatus::Settled);
+    assert_eq!(settled.fee_amount, 1_500_000);
+    assert!(settled.settled_at.is_some());
+    assert_eq!(client.get_collected_fees(&admin, &currency), 1_500_000);
+}
+
+#[test]
+fn test_feature_flags_gate_entry_points() {
+    let env = Env::default();
+    let (_, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let flag = Symbol::new(&env, "SUBSCRIPTIONS");
+
+    // Flags default to off
+    assert!(!client.is_feature_enabled(&flag));
+    let merchant_id = register_merchant(&env, &client);
+    let payer = Address::generate(&env);
+    let result = client.try_create_subscription(
+        &payer,
+        &merchant_id,
+        &1_000i128,
+        &Symbol::new(&env, "USDC"),
+        &3600,
+        &(env.ledger().timestamp() + 3600),
+    );
+    assert_eq!(result, Err(Ok(Error::FeatureDisabled)));
+
+    // Only the admin may toggle flags
+    let result = client.try_set_feature(&Address::generate(&env), &flag, &true);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    client.set_feature(&admin, &flag, &true);
+    assert!(client.is_feature_enabled(&flag));
+    client.create_subscription(
+        &payer,
+        &merchant_id,
+        &1_000i128,
+        &Symbol::new(&env, "USDC"),
+        &3600,
+        &(env.ledger().timestamp() + 3600),
+    );
+
+    client.set_feature(&admin, &flag, &false);
+    assert!(!client.is_feature_enabled(&flag));
+}
+
+#[test]
+fn test_merchant_fee_override_and_volume_tiers() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+    client.set_fee_config(&admin, &200, &Address::generate(&env)); // 2%
+    let merchant_id = register_merchant(&env, &client);
+    assert_eq!(client.get_effective_fee(&merchant_id), 200);
+
+    client.set_merchant_fee(&admin, &merchant_id, &150);
+    assert_eq!(client.get_effective_fee(&merchant_id), 150);
+    let result = client.try_set_merchant_fee(&admin, &merchant_id, &10_001);
+    assert_eq!(result, Err(Ok(Error::InvalidFee)));
+
+    let mut tiers = Vec::new(&env);
+    tiers.push_back(FeeTier {
+        min_volume: 100_000_000,
+        discount_bps: 50,
+    });
+    client.set_fee_tiers(&admin, &tiers);
+
+    let currency = Symbol::new(&env, "USDC");
+    let amount = 100_000_000i128;
+    for (i, expected_fee) in [(1u64, 1_500_000i128), (2, 1_000_000)] {
+        let payment_id = IdBuilder::new("tier_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &amount,
+            &currency,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &amount,
+            &next_nonce(&client, &oracle),
+        );
+        let settled = client.settle_payment(&operator, &payment_id);
+        assert_eq!(settled.fee_amount, expected_fee);
+    }
+    assert_eq!(
+        client.get_merchant_volume(&merchant_id, &merchant_id),
+        2 * amount
+    );
+
+    // The discount lapses with the rolling window
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + fees::VOLUME_WINDOW_SECONDS);
+    assert_eq!(client.get_merchant_volume(&merchant_id, &merchant_id), 0);
+    assert_eq!(client.get_effective_fee(&merchant_id), 150);
+}
+
+#[test]
+fn test_settlement_closes_balance_statement() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.set_fee_config(&admin, &100, &Address::generate(&env)); // 1%
+
+    let merchant_id = register_merchant(&env, &client);
+    let currency = Symbol::new(&env, "USDC");
+    let amount = 50_000_000i128;
+    for i in 1..=2u64 {
+        let payment_id = IdBuilder::new("stmt_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &amount,
+            &currency,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &amount,
+            &next_nonce(&client, &oracle),
+        );
+    }
+    assert_eq!(
+        client
+            .get_merchant_ledger(&merchant_id, &merchant_id, &currency)
+            .credits,
+        2 * amount
+    );
+
+    env.ledger().set_timestamp(1_000);
+    client.settle_payment(&operator, &IdBuilder::new("stmt_").push_u64(1).build(&env));
+
+    // The open period is reset and carries the closing balance forward
+    let ledger = client.get_merchant_ledger(&merchant_id, &merchant_id, &currency);
+    assert_eq!(ledger.balance, 2 * amount - 500_000);
+    assert_eq!(ledger.credits, 0);
+    assert_eq!(ledger.period_start, 1_000);
+}
+
+#[test]
+fn test_pause_blocks_payment_flows() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+
+    let payment_id = String::from_str(&env, "paused_payment");
+    let merchant_id = register_merchant(&env, &client);
+    let amount = 1_000i128;
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let result = client.try_pause(&Address::generate(&env));
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+    client.pause(&admin);
+    assert!(client.is_paused());
+
+    let result = client.try_create_payment(
+        &String::from_str(&env, "another_payment"),
+        &merchant_id,
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::ContractPaused)));
+    let result = client.try_verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(result, Err(Ok(Error::ContractPaused)));
+
+    // Getters stay available while paused
+    assert_eq!(
+        client.get_payment(&payment_id).status,
+        PaymentStatus::Pending
+    );
+
+    client.unpause(&admin);
+    let status = client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+}
+
+#[test]
+fn test_pause_blocks_refund_flows() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    env.mock_all_auths();
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let reason = String::from_str(&env, "Customer requested refund");
+    let requester = Address::generate(&env);
+    let refund_id = client.create_refund(
+        &payment_id,
+        &1000i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.pause(&admin);
+
+    let result = client.try_create_refund(
+        &payment_id,
+        &1000i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+    assert_eq!(result, Err(Ok(Error::ContractPaused)));
+    let result = client.try_process_refund(&operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.get_refund(&refund_id).status, RefundStatus::Pending);
+
+    client.unpause(&admin);
+    client.process_refund(&operator, &refund_id);
+    assert_eq!(
+        client.get_refund(&refund_id).status,
+        RefundStatus::Completed
+    );
+}
+
+#[test]
+fn test_deposit_address_reuse_rejected() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+
+    let merchant_id = register_merchant(&env, &client);
+    let deposit_address = Address::generate(&env);
+    let currency = Symbol::new(&env, "USDC");
+    let expires_at = env.ledger().timestamp() + 3600;
+    let first = String::from_str(&env, "first_charge");
+    client.create_payment(
+        &first,
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(client.get_active_deposit(&deposit_address), Some(first));
+
+    let second = String::from_str(&env, "second_charge");
+    let result = client.try_create_payment(
+        &second,
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &deposit_address,
+        &expires_at,
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::DepositAddressInUse)));
+
+    // Once the first charge expires the address can be reused
+    env.ledger().set_timestamp(expires_at + 1);
+    client.expire_pending_batch(&10);
+    client.create_payment(
+        &second,
+        &merchant_id,
+        &1000i128,
+        &currency,
+        &deposit_address,
+        &(expires_at + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(client.get_active_deposit(&deposit_address), Some(second));
+}
+
+#[test]
+fn test_role_members_and_account_roles() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+
+    let oracle_a = Address::generate(&env);
+    let oracle_b = Address::generate(&env);
+    client.grant_role(&admin, &role_oracle(&env), &oracle_a);
+    client.grant_role(&admin, &role_oracle(&env), &oracle_b);
+    client.grant_role(&admin, &role_settlement_operator(&env), &oracle_a);
+
+    let members = client.get_role_members(&role_oracle(&env), &0, &10);
+    assert_eq!(members.len(), 2);
+    assert_eq!(members.get(0), Some(oracle_a.clone()));
+    assert_eq!(
+        client.get_role_members(&role_oracle(&env), &1, &10).len(),
+        1
+    );
+    assert_eq!(
+        client.get_role_members(&role_oracle(&env), &5, &10).len(),
+        0
+    );
+    assert_eq!(client.get_account_roles(&oracle_a, &0, &10).len(), 2);
+    assert_eq!(
+        client.get_account_roles(&admin, &0, &10).get(0),
+        Some(role_admin(&env))
+    );
+
+    // Revoking and renouncing keep both indexes in sync
+    client.revoke_role(&admin, &role_oracle(&env), &oracle_b);
+    client.renounce_role(&oracle_a, &role_settlement_operator(&env));
+    let members = client.get_role_members(&role_oracle(&env), &0, &10);
+    assert_eq!(members.len(), 1);
+    assert!(!members.contains(&oracle_b));
+    let roles = client.get_account_roles(&oracle_a, &0, &10);
+    assert_eq!(roles.len(), 1);
+    assert_eq!(roles.get(0), Some(role_oracle(&env)));
+
+    // Transferring admin moves the ADMIN membership
+    let new_admin = Address::generate(&env);
+    client.transfer_admin(&admin, &new_admin);
+    let admins = client.get_role_members(&role_admin(&env), &0, &10);
+    assert_eq!(admins.len(), 1);
+    assert_eq!(admins.get(0), Some(new_admin));
+}
+
+#[test]
+fn test_self_custody_merchant_invoiced_fees() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.set_fee_config(&admin, &100, &Address::generate(&env)); // 1%
+
+    let merchant_id = register_merchant(&env, &client);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    registry.set_custody_mode(&merchant_id, &CustodyMode::SelfCustody);
+
+    let payment_id = String::from_str(&env, "self_custody");
+    let currency = Symbol::new(&env, "USDC");
+    let amount = 20_000_000i128;
+    let payment = client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &currency,
+        &merchant_id,
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(payment.custody_mode, CustodyMode::SelfCustody);
+
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(client.get_payment(&payment_id).fee_amount, 200_000);
+    assert_eq!(
+        client.get_fees_owed(&merchant_id, &merchant_id, &currency),
+        200_000
+    );
+
+    // Nothing is held in escrow, so there is nothing to settle
+    let result = client.try_settle_payment(&operator, &payment_id);
+    assert_eq!(result, Err(Ok(Error::SettlementNotRequired)));
+
+    let result = client.try_record_fee_payment(&admin, &merchant_id, &currency, &200_001);
+    assert_eq!(result, Err(Ok(Error::InvalidAmount)));
+    assert_eq!(
+        client.record_fee_payment(&admin, &merchant_id, &currency, &150_000),
+        50_000
+    );
+    assert_eq!(client.get_collected_fees(&admin, &currency), 150_000);
+}
+
+#[test]
+fn test_time_limited_role_grant() {
+    let env = Env::default();
+    let (admin, client) = setup_contract(&env);
+    env.ledger().set_timestamp(1_000);
+
+    let operator = Address::generate(&env);
+    let role = role_settlement_operator(&env);
+    let result = client.try_grant_role_until(&admin, &role, &operator, &1_000);
+    assert_eq!(result, Err(Ok(Error::InvalidRoleExpiry)));
+
+    client.grant_role_until(&admin, &role, &operator, &2_000);
+    assert!(client.has_role(&role, &operator));
+    assert_eq!(client.get_role_expiry(&role, &operator), Some(2_000));
+
+    // The grant lapses on its own and the operator loses access
+    env.ledger().set_timestamp(2_000);
+    assert!(!client.has_role(&role, &operator));
+    let refund_id = client.create_refund(
+        &String::from_str(&env, "payment_123"),
+        &1000i128,
+        &RefundReason::CustomerRequest,
+        &Some(String::from_str(&env, "Customer requested refund")),
+        &Address::generate(&env),
+    );
+    let result = client.try_process_refund(&operator, &refund_id);
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    // Pruning removes the lapsed grant from storage and the member index
+    assert_eq!(client.prune_expired_roles(&10), 1);
+    assert!(client.get_role_expiry(&role, &operator).is_none());
+    assert_eq!(client.get_role_members(&role, &0, &10).len(), 0);
+    assert_eq!(client.prune_expired_roles(&10), 0);
+}
+
+#[test]
+fn test_refund_operator_spend_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let (admin, client) = setup_contract(&env);
+    let operator = Address::generate(&env);
+    let role = role_settlement_operator(&env);
+    client.grant_role(&admin, &role, &operator);
+
+    let limit = SpendLimit {
+        per_transaction: 1_000,
+        per_day: 1_500,
+    };
+    client.set_role_spend_limit(&admin, &role, &limit);
+    assert_eq!(client.get_spend_limit(&operator, &role), Some(limit));
+
+    let reason = String::from_str(&env, "Customer requested refund");
+    let refund = |payment: &str, amount: i128| {
+        client.create_refund(
+            &String::from_str(&env, payment),
+            &amount,
+            &RefundReason::CustomerRequest,
+            &Some(reason.clone()),
+            &Address::generate(&env),
+        )
+    };
+
+    // Over the per-transaction cap
+    let large = refund("limit_pay_1", 1_200);
+    assert_eq!(
+        client.try_process_refund(&operator, &large),
+        Err(Ok(Error::SpendLimitExceeded))
+    );
+
+    let first = refund("limit_pay_2", 1_000);
+    client.process_refund(&operator, &first);
+    assert_eq!(client.get_daily_spend(&operator, &operator), 1_000);
+
+    // Over the daily cap
+    let second = refund("limit_pay_3", 600);
+    assert_eq!(
+        client.try_process_refund(&operator, &second),
+        Err(Ok(Error::SpendLimitExceeded))
+    );
+
+    // The counter resets the next day
+    env.ledger().set_timestamp(spend_guard::DAY_SECONDS);
+    assert_eq!(client.get_daily_spend(&operator, &operator), 0);
+    client.process_refund(&operator, &second);
+
+    // An account limit overrides the role limit
+    client.set_account_spend_limit(
+        &admin,
+        &operator,
+        &SpendLimit {
+            per_transaction: 2_000,
+            per_day: 5_000,
+        },
+    );
+    client.process_refund(&operator, &large);
+    assert_eq!(client.get_daily_spend(&operator, &operator), 1_800);
+}
+
+#[test]
+fn test_settlement_spend_limit() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "limited_settlement");
+    let amount = 5_000i128;
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &amount,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &amount,
+        &next_nonce(&client, &oracle),
+    );
+
+    // Limits must be positive with the daily cap covering one transaction
+    assert_eq!(
+        client.try_set_account_spend_limit(
+            &admin,
+            &operator,
+            &SpendLimit {
+                per_transaction: 1_000,
+                per_day: 500,
+            },
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    client.set_account_spend_limit(
+        &admin,
+        &operator,
+        &SpendLimit {
+            per_transaction: 1_000,
+            per_day: 10_000,
+        },
+    );
+    assert_eq!(
+        client.try_settle_payment(&operator, &payment_id),
+        Err(Ok(Error::SpendLimitExceeded))
+    );
+
+    client.set_account_spend_limit(
+        &admin,
+        &operator,
+        &SpendLimit {
+            per_transaction: 5_000,
+            per_day: 10_000,
+        },
+    );
+    client.settle_payment(&operator, &payment_id);
+    assert_eq!(client.get_daily_spend(&operator, &operator), amount);
+}
+
+#[test]
+fn test_settle_batch_aggregates_per_merchant() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.set_fee_config(&admin, &100, &Address::generate(&env)); // 1%
+
+    let currency = Symbol::new(&env, "USDC");
+    let merchant_a = register_merchant(&env, &client);
+    let merchant_b = register_merchant(&env, &client);
+    let mut payment_ids = Vec::new(&env);
+    for (i, merchant_id, amount) in [
+        (1u64, &merchant_a, 10_000i128),
+        (2, &merchant_a, 20_000),
+        (3, &merchant_b, 50_000),
+    ] {
+        let payment_id = IdBuilder::new("bulk_pay_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            merchant_id,
+            &amount,
+            &currency,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &amount,
+            &next_nonce(&client, &oracle),
+        );
+        payment_ids.push_back(payment_id);
+    }
+
+    assert_eq!(
+        client.try_settle_batch(&operator, &Vec::new(&env)),
+        Err(Ok(Error::EmptyBatch))
+    );
+
+    // Merchant B pays out to a cold wallet rather than its auth key
+    let cold_wallet = Address::generate(&env);
+    MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap())
+        .set_settlement_address(&merchant_b, &cold_wallet);
+
+    let batch = client.settle_batch(&operator, &payment_ids);
+    assert_eq!(batch.lines.len(), 2);
+    let line_a = batch.lines.get(0).unwrap();
+    assert_eq!(line_a.merchant_id, merchant_a);
+    assert_eq!(line_a.settlement_address, merchant_a);
+    assert_eq!(batch.lines.get(1).unwrap().settlement_address, cold_wallet);
+    assert_eq!(line_a.gross, 30_000);
+    assert_eq!(line_a.fees, 300);
+    assert_eq!(line_a.net, 29_700);
+    assert_eq!(line_a.payment_count, 2);
+    assert_eq!(batch.total_gross, 80_000);
+    assert_eq!(batch.total_fees, 800);
+    assert_eq!(batch.total_net, 79_200);
+    assert_eq!(client.get_settlement_batch(&batch.batch_id), batch);
+
+    for payment_id in payment_ids.iter() {
+        assert_eq!(
+            client.get_payment(&payment_id).status,
+            PaymentStatus::Settled
+        );
+    }
+
+    // A batch containing an already settled payment is rejected as a whole
+    assert_eq!(
+        client.try_settle_batch(&operator, &payment_ids),
+        Err(Ok(Error::PaymentNotConfirmed))
+    );
+}
+
+#[test]
+fn test_expiry_rate_and_alert() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    client.set_expiry_alert(
+        &admin,
+        &ExpiryAlertConfig {
+            threshold_bps: 5_000,
+            min_created: 4,
+        },
+    );
+
+    let merchant_id = register_merchant(&env, &client);
+    let now = env.ledger().timestamp();
+    for (i, ttl) in [(1u64, 60u64), (2, 60), (3, 7200), (4, 7200)] {
+        client.create_payment(
+            &IdBuilder::new("exp_rate_").push_u64(i).build(&env),
+            &merchant_id,
+            &1000i128,
+            &Symbol::new(&env, "USDC"),
+            &Address::generate(&env),
+            &(now + ttl),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    }
+    assert_eq!(client.get_expiry_rate(&admin, &merchant_id), 0);
+
+    env.ledger().set_timestamp(now + 120);
+    client.cancel_payment(&IdBuilder::new("exp_rate_").push_u64(1).build(&env));
+    assert_eq!(client.get_expiry_rate(&admin, &merchant_id), 2_500);
+    assert!(!client.get_expiry_stats(&admin, &merchant_id).alerted);
+
+    // Crossing the threshold raises the alert once for the window
+    assert_eq!(client.expire_pending_batch(&10), 1);
+    let stats = client.get_expiry_stats(&admin, &merchant_id);
+    assert_eq!(stats.created, 4);
+    assert_eq!(stats.expired, 2);
+    assert!(stats.alerted);
+    assert_eq!(client.get_expiry_rate(&admin, &merchant_id), 5_000);
+
+    // Counts start over in the next window
+    env.ledger()
+        .set_timestamp(now + expiry_stats::EXPIRY_WINDOW_SECONDS + 1);
+    assert_eq!(client.get_expiry_rate(&admin, &merchant_id), 0);
+}
+
+#[test]
+fn test_supported_token_whitelist() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+
+    let eurc = Symbol::new(&env, "EURC");
+    let create = |payment_id: &str| {
+        client.try_create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            &eurc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    assert_eq!(
+        create("eurc_payment_1").unwrap_err(),
+        Ok(Error::UnsupportedCurrency)
+    );
+
+    let eurc_token = Address::generate(&env);
+    client.add_supported_token(&admin, &eurc, &eurc_token);
+    let tokens = client.list_supported_tokens();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(
+        tokens.get(1).unwrap(),
+        SupportedToken {
+            currency: eurc.clone(),
+            token: eurc_token,
+        }
+    );
+    assert!(create("eurc_payment_2").is_ok());
+
+    client.remove_supported_token(&admin, &eurc);
+    assert_eq!(client.list_supported_tokens().len(), 1);
+    assert_eq!(
+        create("eurc_payment_3").unwrap_err(),
+        Ok(Error::UnsupportedCurrency)
+    );
+    assert_eq!(
+        client.try_remove_supported_token(&admin, &eurc),
+        Err(Ok(Error::UnsupportedCurrency))
+    );
+}
+
+#[test]
+fn test_merchant_accepted_currencies() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let eurc = Symbol::new(&env, "EURC");
+
+    // Only currencies on the platform list can be opted into
+    assert_eq!(
+        client.try_set_accepted_currencies(&merchant_id, &Vec::from_array(&env, [eurc.clone()])),
+        Err(Ok(Error::UnsupportedCurrency))
+    );
+    client.add_supported_token(&admin, &eurc, &Address::generate(&env));
+
+    let create = |payment_id: &str, currency: &Symbol| {
+        client.try_create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            currency,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    assert_eq!(client.get_accepted_currencies(&merchant_id), None);
+    assert!(create("any_currency", &eurc).is_ok());
+
+    client.set_accepted_currencies(&merchant_id, &Vec::from_array(&env, [usdc.clone()]));
+    assert_eq!(
+        create("eurc_rejected", &eurc).unwrap_err(),
+        Ok(Error::CurrencyNotAccepted)
+    );
+    assert!(create("usdc_accepted", &usdc).is_ok());
+
+    // An empty list lifts the restriction
+    client.set_accepted_currencies(&merchant_id, &Vec::new(&env));
+    assert_eq!(client.get_accepted_currencies(&merchant_id), None);
+    assert!(create("eurc_accepted", &eurc).is_ok());
+}
+
+#[test]
+fn test_settlement_converts_to_merchant_currency() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    let merchant_id = Address::generate(&env);
+    registry.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Euro Shop"),
+        &String::from_str(&env, "EUR"),
+    );
+    registry.verify_merchant(&admin, &merchant_id);
+
+    let usdc = Symbol::new(&env, "USDC");
+    let eur = Symbol::new(&env, "EUR");
+    let amount = 10_000i128;
+    for i in 1..=2u64 {
+        let payment_id = IdBuilder::new("fx_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &amount,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 7200),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &amount,
+            &next_nonce(&client, &oracle),
+        );
+    }
+    let first = IdBuilder::new("fx_").push_u64(1).build(&env);
+    let second = IdBuilder::new("fx_").push_u64(2).build(&env);
+    assert_eq!(
+        client.try_settle_payment(&operator, &first),
+        Err(Ok(Error::RateNotFound))
+    );
+
+    env.ledger().set_timestamp(1_000);
+    assert_eq!(
+        client.try_post_rate(
+            &operator,
+            &usdc,
+            &eur,
+            &9_000_000,
+            &1_000,
+            &next_nonce(&client, &operator),
+        ),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.post_rate(
+        &oracle,
+        &usdc,
+        &eur,
+        &9_000_000,
+        &1_000,
+        &next_nonce(&client, &oracle),
+    ); // 0.9 EUR per USDC
+    assert_eq!(
+        client.try_post_rate(
+            &oracle,
+            &usdc,
+            &eur,
+            &9_100_000,
+            &900,
+            &next_nonce(&client, &oracle),
+        ),
+        Err(Ok(Error::StaleRate))
+    );
+
+    let settled = client.settle_payment(&operator, &first);
+    assert_eq!(settled.payout_currency, Some(eur.clone()));
+    assert_eq!(settled.payout_amount, Some(9_000));
+    let applied = settled.payout_rate.unwrap();
+    assert_eq!((applied.rate, applied.timestamp), (9_000_000, 1_000));
+    assert_eq!(applied.oracle, oracle);
+
+    // Rates older than the allowed age block settlement until the oracle posts again
+    client.set_max_rate_age(&admin, &600);
+    env.ledger().set_timestamp(1_700);
+    assert_eq!(
+        client.try_settle_payment(&operator, &second),
+        Err(Ok(Error::StaleRate))
+    );
+    client.post_rate(
+        &oracle,
+        &usdc,
+        &eur,
+        &9_200_000,
+        &1_650,
+        &next_nonce(&client, &oracle),
+    );
+    let settled = client.settle_payment(&operator, &second);
+    assert_eq!(settled.payout_amount, Some(9_200));
+    assert_eq!(settled.payout_rate.unwrap().rate, 9_200_000);
+
+    // Each payout keeps the rate it was converted at, not the latest posted one
+    let applied = client.get_payment(&first).payout_rate.unwrap();
+    assert_eq!((applied.rate, applied.timestamp), (9_000_000, 1_000));
+}
+
+#[test]
+fn test_payment_intent_lifecycle() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payer = Address::generate(&env);
+    let usdc = Symbol::new(&env, "USDC");
+    let memo = String::from_str(&env, "2kg tomatoes");
+
+    let intent = client.create_payment_intent(&payer, &merchant_id, &1_500, &usdc, &memo, &600);
+    assert_eq!(intent.status, IntentStatus::Proposed);
+    assert_eq!(client.get_merchant_intents(&merchant_id).len(), 1);
+
+    // Only the addressed merchant can act on it
+    assert_eq!(
+        client.try_accept_payment_intent(
+            &Address::generate(&env),
+            &intent.intent_id,
+            &Address::generate(&env),
+            &3600,
+        ),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    let payment = client.accept_payment_intent(
+        &merchant_id,
+        &intent.intent_id,
+        &Address::generate(&env),
+        &3600,
+    );
+    assert_eq!(payment.amount, 1_500);
+    assert_eq!(payment.status, PaymentStatus::Pending);
+    let accepted = client.get_payment_intent(&intent.intent_id);
+    assert_eq!(accepted.status, IntentStatus::Accepted);
+    assert_eq!(accepted.payment_id, Some(payment.payment_id.clone()));
+    assert_eq!(
+        client.try_decline_payment_intent(&merchant_id, &intent.intent_id),
+        Err(Ok(Error::IntentNotOpen))
+    );
+
+    // The resulting charge follows the normal verification flow
+    let status = client.verify_payment(
+        &oracle,
+        &payment.payment_id,
+        &BytesN::<32>::random(&env),
+        &payer,
+        &1_500,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+
+    let declined = client.create_payment_intent(&payer, &merchant_id, &900, &usdc, &memo, &600);
+    client.decline_payment_intent(&merchant_id, &declined.intent_id);
+    assert_eq!(
+        client.get_payment_intent(&declined.intent_id).status,
+        IntentStatus::Declined
+    );
+
+    let stale = client.create_payment_intent(&payer, &merchant_id, &700, &usdc, &memo, &600);
+    assert_eq!(
+        client.try_expire_payment_intent(&stale.intent_id),
+        Err(Ok(Error::IntentNotOpen))
+    );
+    env.ledger().set_timestamp(601);
+    assert_eq!(
+        client.try_accept_payment_intent(
+            &merchant_id,
+            &stale.intent_id,
+            &Address::generate(&env),
+            &3600,
+        ),
+        Err(Ok(Error::IntentExpired))
+    );
+    client.expire_payment_intent(&stale.intent_id);
+    assert_eq!(
+        client.get_payment_intent(&stale.intent_id).status,
+        IntentStatus::Expired
+    );
+}
+
+#[test]
+fn test_auto_settle_rule_queues_idle_balances() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    let usdc = Symbol::new(&env, "USDC");
+    let merchant_id = register_merchant(&env, &client);
+    client.set_auto_settle_rule(
+        &merchant_id,
+        &AutoSettleRule {
+            max_balance: 25_000,
+            max_age: 86_400,
+        },
+    );
+
+    let mut payment_ids = Vec::new(&env);
+    for (i, amount) in [(1u64, 10_000i128), (2, 20_000)] {
+        let payment_id = IdBuilder::new("idle_pay_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &amount,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &amount,
+            &next_nonce(&client, &oracle),
+        );
+        payment_ids.push_back(payment_id);
+        // The first payment alone stays under the balance threshold
+        if i == 1 {
+            assert_eq!(client.get_settlement_queue(&admin).len(), 0);
+        }
+    }
+
+    let queue = client.get_settlement_queue(&admin);
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue.get(0).unwrap().merchant_id, merchant_id);
+    assert_eq!(queue.get(0).unwrap().amount, 30_000);
+
+    // Settling drains the balance and clears the queue
+    client.settle_batch(&operator, &payment_ids);
+    assert_eq!(
+        client
+            .get_unsettled_balance(&merchant_id, &merchant_id, &usdc)
+            .amount,
+        0
+    );
+    assert_eq!(client.get_settlement_queue(&admin).len(), 0);
+
+    // A small balance is queued once it outlives the merchant's max age
+    let payment_id = String::from_str(&env, "idle_pay_3");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &5_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &5_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert!(!client.check_auto_settlement(&merchant_id, &usdc));
+    env.ledger().set_timestamp(86_401);
+    assert!(client.check_auto_settlement(&merchant_id, &usdc));
+    assert_eq!(
+        client.get_settlement_queue(&admin).get(0).unwrap().amount,
+        5_000
+    );
+}
+
+#[test]
+fn test_pause_scope_halts_settlements_only() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    let usdc = Symbol::new(&env, "USDC");
+    let merchant_id = register_merchant(&env, &client);
+
+    let payment_id = String::from_str(&env, "scoped_payment");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    assert_eq!(
+        client.try_pause_scope(&Address::generate(&env), &PauseScope::Settlements),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.pause_scope(&admin, &PauseScope::Settlements);
+    assert!(client.is_scope_paused(&PauseScope::Settlements));
+    assert!(!client.is_paused());
+    assert_eq!(
+        client.get_contract_info().paused_scopes,
+        Vec::from_array(&env, [PauseScope::Settlements])
+    );
+
+    // In-flight checkouts still complete
+    let status = client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &1_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+    assert_eq!(
+        client.try_settle_payment(&operator, &payment_id),
+        Err(Ok(Error::ContractPaused))
+    );
+
+    client.unpause_scope(&admin, &PauseScope::Settlements);
+    let settled = client.settle_payment(&operator, &payment_id);
+    assert_eq!(settled.status, PaymentStatus::Settled);
+
+    // Pausing payments leaves settlement running but blocks new charges
+    client.pause_scope(&admin, &PauseScope::Payments);
+    assert_eq!(
+        client.try_create_payment(
+            &String::from_str(&env, "blocked_payment"),
+            &merchant_id,
+            &1_000,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        ),
+        Err(Ok(Error::ContractPaused))
+    );
+}
+
+#[test]
+fn test_fee_invoice_itemizes_processing_and_fx() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.set_fee_config(&admin, &100, &Address::generate(&env)); // 1%
+    client.set_fx_fee(&admin, &50); // 0.5%
+
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    let merchant_id = Address::generate(&env);
+    registry.register_merchant(
+        &merchant_id,
+        &String::from_str(&env, "Euro Imports"),
+        &String::from_str(&env, "EUR"),
+    );
+    registry.verify_merchant(&admin, &merchant_id);
+
+    let usdc = Symbol::new(&env, "USDC");
+    let eur = Symbol::new(&env, "EUR");
+    client.post_rate(
+        &oracle,
+        &usdc,
+        &eur,
+        &9_000_000,
+        &0,
+        &next_nonce(&client, &oracle),
+    );
+
+    let mut payment_ids = Vec::new(&env);
+    for i in 1..=2u64 {
+        let payment_id = IdBuilder::new("inv_").push_u64(i).build(&env);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &10_000,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        client.verify_payment(
+            &oracle,
+            &payment_id,
+            &BytesN::<32>::random(&env),
+            &Address::generate(&env),
+            &10_000,
+            &next_nonce(&client, &oracle),
+        );
+        payment_ids.push_back(payment_id);
+    }
+
+    let batch = client.settle_batch(&operator, &payment_ids);
+    // 100 processing plus 49 FX on the 9_900 converted, per payment
+    assert_eq!(batch.total_fees, 298);
+
+    let invoice = client.get_fee_invoice(&merchant_id, &usdc, &1);
+    assert_eq!(invoice.charge_count, 2);
+    assert_eq!(invoice.processing_fees, 200);
+    assert_eq!(invoice.fx_fees, 98);
+    assert_eq!(invoice.dispute_fees, 0);
+    assert_eq!(invoice.total_fees, 298);
+    assert_eq!(client.get_collected_fees(&admin, &usdc), 298);
+
+    // Settlement closed the period, so the next invoice starts empty
+    let open = client.get_open_fee_invoice(&merchant_id, &usdc);
+    assert_eq!(open.invoice_number, 2);
+    assert_eq!(open.total_fees, 0);
+    assert_eq!(
+        client.try_get_fee_invoice(&merchant_id, &usdc, &2),
+        Err(Ok(Error::FeeInvoiceNotFound))
+    );
+}
+
+#[test]
+fn test_delegate_creates_and_cancels_payments() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    let delegate = Address::generate(&env);
+    let usdc = Symbol::new(&env, "USDC");
+    let payment_id = String::from_str(&env, "delegated_payment");
+
+    let result = client.try_create_delegated_payment(
+        &delegate,
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+
+    registry.add_delegate(&merchant_id, &delegate);
+    client.create_delegated_payment(
+        &delegate,
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+    );
+    assert_eq!(
+        client.try_cancel_pending_payment(&Address::generate(&env), &payment_id),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.cancel_pending_payment(&delegate, &payment_id);
+    assert_eq!(
+        client.get_payment(&payment_id).status,
+        PaymentStatus::Cancelled
+    );
+    assert_eq!(
+        client.try_cancel_pending_payment(&merchant_id, &payment_id),
+        Err(Ok(Error::PaymentAlreadyProcessed))
+    );
+
+    // Removed delegates lose access
+    registry.remove_delegate(&merchant_id, &delegate);
+    let result = client.try_create_delegated_payment(
+        &delegate,
+        &String::from_str(&env, "another_payment"),
+        &merchant_id,
+        &1_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+    );
+    assert_eq!(result, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_webhook_registry() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    let endpoint = BytesN::<32>::random(&env);
+
+    let result = registry.try_register_webhook(&merchant_id, &endpoint, &0);
+    assert_eq!(result, Err(Ok(merchant_registry::Error::InvalidEventMask)));
+    let result = registry.try_register_webhook(&merchant_id, &endpoint, &(1 << 7));
+    assert_eq!(result, Err(Ok(merchant_registry::Error::InvalidEventMask)));
+
+    registry.register_webhook(&merchant_id, &endpoint, &WEBHOOK_PAYMENT_EVENTS);
+    // Registering the same endpoint again replaces its mask rather than adding a second hook
+    let mask = WEBHOOK_PAYMENT_EVENTS | WEBHOOK_REFUND_EVENTS;
+    registry.register_webhook(&merchant_id, &endpoint, &mask);
+    let webhooks = registry.list_webhooks(&merchant_id);
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks.get(0).unwrap().event_mask, mask);
+
+    for _ in 1..MAX_WEBHOOKS {
+        registry.register_webhook(&merchant_id, &BytesN::random(&env), &WEBHOOK_ALL_EVENTS);
+    }
+    let result =
+        registry.try_register_webhook(&merchant_id, &BytesN::random(&env), &WEBHOOK_ALL_EVENTS);
+    assert_eq!(result, Err(Ok(merchant_registry::Error::TooManyWebhooks)));
+
+    registry.remove_webhook(&merchant_id, &endpoint);
+    assert_eq!(registry.list_webhooks(&merchant_id).len(), MAX_WEBHOOKS - 1);
+    let result = registry.try_remove_webhook(&merchant_id, &endpoint);
+    assert_eq!(result, Err(Ok(merchant_registry::Error::WebhookNotFound)));
+}
+
+#[test]
+fn test_create_payment_idempotency_key() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let order_reference = String::from_str(&env, "ORDER-1042");
+    let key = Some(String::from_str(&env, "retry-7f3a"));
+
+    let first = client.create_payment(
+        &String::from_str(&env, "idem_1"),
+        &merchant_id,
+        &2_500,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &order_reference,
+        &key,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(first.order_reference, order_reference);
+
+    // A retry, even under a fresh payment id, returns the original charge
+    let retry = client.create_payment(
+        &String::from_str(&env, "idem_2"),
+        &merchant_id,
+        &2_500,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &order_reference,
+        &key,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(retry, first);
+    assert_eq!(
+        client.try_get_payment(&String::from_str(&env, "idem_2")),
+        Err(Ok(Error::PaymentNotFound))
+    );
+
+    // Reusing the key for a different charge is rejected
+    let result = client.try_create_payment(
+        &String::from_str(&env, "idem_3"),
+        &merchant_id,
+        &9_999,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &order_reference,
+        &key,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::IdempotencyConflict)));
+}
+
+#[test]
+fn test_cart_allocation_and_expiry() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let usdc = Symbol::new(&env, "USDC");
+    let legs = Vec::from_array(
+        &env,
+        [
+            CartLeg {
+                merchant_id: register_merchant(&env, &client),
+                amount: 2_000,
+            },
+            CartLeg {
+                merchant_id: register_merchant(&env, &client),
+                amount: 1_000,
+            },
+        ],
+    );
+    assert_eq!(
+        client.try_create_cart(&usdc, &Address::generate(&env), &Vec::new(&env), &3600),
+        Err(Ok(Error::InvalidCart))
+    );
+
+    // A short transfer underpays every leg, so exact-match merchants fail them all
+    let cart = client.create_cart(&usdc, &Address::generate(&env), &legs, &3600);
+    let status = client.verify_cart(
+        &oracle,
+        &cart.cart_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &2_400,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(status, CartStatus::Failed);
+    for payment_id in cart.payment_ids.iter() {
+        assert_eq!(
+            client.get_payment(&payment_id).status,
+            PaymentStatus::Failed
+        );
+    }
+
+    // Unpaid carts expire together with their legs
+    let cart = client.create_cart(&usdc, &Address::generate(&env), &legs, &3600);
+    assert_eq!(
+        client.try_expire_cart(&cart.cart_id),
+        Err(Ok(Error::Unauthorized))
+    );
+    env.ledger().set_timestamp(3601);
+    client.expire_cart(&cart.cart_id);
+    assert_eq!(client.get_cart(&cart.cart_id).status, CartStatus::Expired);
+    assert_eq!(
+        client.get_payment(&cart.payment_ids.get(1).unwrap()).status,
+        PaymentStatus::Expired
+    );
+}
+
+#[test]
+fn test_payment_metadata() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "tagged_payment");
+
+    let mut metadata = Map::new(&env);
+    metadata.set(
+        Symbol::new(&env, "invoice"),
+        String::from_str(&env, "INV-2291"),
+    );
+    let payment = client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &Some(metadata.clone()),
+        &None,
+        &None,
+    );
+    assert_eq!(payment.metadata, metadata);
+
+    metadata.set(
+        Symbol::new(&env, "webhook"),
+        String::from_str(&env, "corr-88"),
+    );
+    client.set_payment_metadata(&merchant_id, &payment_id, &metadata);
+    assert_eq!(client.get_payment(&payment_id).metadata.len(), 2);
+
+    // Oversized maps are rejected
+    let mut oversized = Map::new(&env);
+    for key in ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k"] {
+        oversized.set(Symbol::new(&env, key), String::from_str(&env, "v"));
+    }
+    assert!(oversized.len() > DEFAULT_MAX_METADATA_SIZE);
+    assert_eq!(
+        client.try_set_payment_metadata(&merchant_id, &payment_id, &oversized),
+        Err(Ok(Error::InvalidMetadata))
+    );
+    assert_eq!(
+        client.try_set_payment_metadata(&Address::generate(&env), &payment_id, &metadata),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    // Metadata is frozen once the payment leaves Pending
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &1_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(
+        client.try_set_payment_metadata(&merchant_id, &payment_id, &metadata),
+        Err(Ok(Error::PaymentAlreadyProcessed))
+    );
+}
+
+#[test]
+fn test_audit_journal_records_calls_in_order() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    let delegate = Address::generate(&env);
+    registry.add_delegate(&merchant_id, &delegate);
+    let usdc = Symbol::new(&env, "USDC");
+
+    let paid = String::from_str(&env, "journal_paid");
+    client.create_payment(
+        &paid,
+        &merchant_id,
+        &1_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    client.verify_payment(
+        &oracle,
+        &paid,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &1_000,
+        &next_nonce(&client, &oracle),
+    );
+    let withdrawn = String::from_str(&env, "journal_withdrawn");
+    client.create_delegated_payment(
+        &delegate,
+        &withdrawn,
+        &merchant_id,
+        &500,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+    );
+    client.cancel_pending_payment(&delegate, &withdrawn);
+
+    // Seq 0 is the oracle's role grant from setup
+    let entries = client.get_audit_entries(&0, &10);
+    assert_eq!(entries.len(), 5);
+    let first = entries.get(0).unwrap();
+    assert_eq!(first.action, Symbol::new(&env, "ROLE_GRANTED"));
+    assert_eq!(first.subject_id, oracle.to_string());
+    let expected = [
+        (merchant_id.clone(), "CREATE", paid.clone()),
+        (oracle.clone(), "VERIFY", paid),
+        (delegate.clone(), "CREATE", withdrawn.clone()),
+        (delegate, "CANCEL", withdrawn),
+    ];
+    for (seq, (actor, action, subject)) in (1u64..).zip(expected) {
+        let entry = entries.get(seq as u32).unwrap();
+        assert_eq!(entry.seq, seq);
+        assert_eq!(entry.actor, actor);
+        assert_eq!(entry.action, Symbol::new(&env, action));
+        assert_eq!(entry.subject_id, subject);
+    }
+
+    // Paging picks up from the requested sequence number
+    let page = client.get_audit_entries(&3, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().seq, 3);
+    assert_eq!(client.get_audit_entries(&5, &10).len(), 0);
+
+    // The registry journals merchant verification
+    let journal = registry.get_audit_entries(&0, &10);
+    assert_eq!(journal.len(), 1);
+    assert_eq!(
+        journal.get(0).unwrap().action,
+        Symbol::new(&env, "MERCHANT_VERIFIED")
+    );
+}
+
+#[test]
+fn test_sensitive_views_require_reader_role() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let stranger = Address::generate(&env);
+
+    assert_eq!(
+        client.try_get_collected_fees(&stranger, &usdc),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_get_fees_owed(&stranger, &merchant_id, &usdc),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_get_settlement_queue(&stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    // Merchants see their own figures but not platform-wide ones
+    assert_eq!(client.get_fees_owed(&merchant_id, &merchant_id, &usdc), 0);
+    assert_eq!(
+        client.try_get_collected_fees(&merchant_id, &usdc),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    client.grant_role(&admin, &role_reader(&env), &stranger);
+    assert_eq!(client.get_collected_fees(&stranger, &usdc), 0);
+    assert_eq!(client.get_expiry_rate(&stranger, &merchant_id), 0);
+
+    // Customer-facing views stay public
+    assert!(client
+        .try_get_payment(&String::from_str(&env, "missing"))
+        .is_err());
+    assert_eq!(client.list_supported_tokens().len(), 1);
+}
+
+// Sign an attestation the way an off-chain watcher would
+fn sign_attestation(
+    env: &Env,
+    signer: &ed25519_dalek::SigningKey,
+    payment_id: &String,
+    transaction_hash: &BytesN<32>,
+    amount_received: i128,
+    payer: &Address,
+    nonce: u64,
+) -> BytesN<64> {
+    use ed25519_dalek::Signer;
+
+    let message = attestation::Attestations::message(
+        env,
+        payment_id,
+        transaction_hash,
+        amount_received,
+        payer,
+        nonce,
+    );
+    let mut buf = [0u8; 256];
+    let len = message.len() as usize;
+    message.copy_into_slice(&mut buf[..len]);
+    BytesN::from_array(env, &signer.sign(&buf[..len]).to_bytes())
+}
+
+#[test]
+fn test_verify_payment_with_signed_attestation() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "attested_payment");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    let signer = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    let public_key = BytesN::from_array(&env, &signer.verifying_key().to_bytes());
+    let transaction_hash = BytesN::<32>::random(&env);
+    let payer = Address::generate(&env);
+    let nonce = next_nonce(&client, &oracle);
+    let signature = sign_attestation(
+        &env,
+        &signer,
+        &payment_id,
+        &transaction_hash,
+        1_000,
+        &payer,
+        nonce,
+    );
+
+    // Unregistered keys are rejected before the signature is checked
+    assert_eq!(
+        client.try_verify_payment_attested(
+            &payment_id,
+            &transaction_hash,
+            &payer,
+            &1_000,
+            &nonce,
+            &public_key,
+            &signature,
+        ),
+        Err(Ok(Error::OracleKeyNotFound))
+    );
+
+    client.register_oracle_key(&admin, &oracle, &public_key);
+    assert_eq!(
+        client.get_oracle_key_owner(&public_key),
+        Some(oracle.clone())
+    );
+
+    // A signature over a different amount does not verify
+    assert!(client
+        .try_verify_payment_attested(
+            &payment_id,
+            &transaction_hash,
+            &payer,
+            &2_000,
+            &nonce,
+            &public_key,
+            &signature,
+        )
+        .is_err());
+
+    let status = client.verify_payment_attested(
+        &payment_id,
+        &transaction_hash,
+        &payer,
+        &1_000,
+        &nonce,
+        &public_key,
+        &signature,
+    );
+    assert_eq!(status, PaymentStatus::Confirmed);
+    assert_eq!(client.get_oracle_nonce(&oracle), nonce);
+    let payment = client.get_payment(&payment_id);
+    assert_eq!(payment.payer_address, Some(payer));
+    assert_eq!(payment.transaction_hash, Some(transaction_hash));
+
+    client.remove_oracle_key(&admin, &public_key);
+    assert_eq!(client.get_oracle_key_owner(&public_key), None);
+}
+
+#[cfg(feature = "sim-clock")]
+#[test]
+fn test_clock_offset_drives_expiry() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "offset_payment");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    assert_eq!(client.expire_pending_batch(&10), 0);
+
+    // Jump past expiry without touching the ledger timestamp
+    client.set_clock_offset(&admin, &3601);
+    assert_eq!(client.get_clock_offset(), 3601);
+    assert_eq!(client.expire_pending_batch(&10), 1);
+    assert_eq!(
+        client.get_payment(&payment_id).status,
+        PaymentStatus::Expired
+    );
+}
+
+#[test]
+fn test_oracle_nonces_reject_replays() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let create = |payment_id: &str| {
+        let payment_id = String::from_str(&env, payment_id);
+        client.create_payment(
+            &payment_id,
+            &merchant_id,
+            &1_000,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+        payment_id
+    };
+    let first = create("nonce_first");
+    let second = create("nonce_second");
+    let transaction_hash = BytesN::<32>::random(&env);
+    let payer = Address::generate(&env);
+
+    client.verify_payment(&oracle, &first, &transaction_hash, &payer, &1_000, &5);
+    assert_eq!(client.get_oracle_nonce(&oracle), 5);
+
+    // Duplicate and stale nonces are refused, whatever the payment
+    for nonce in [5, 4] {
+        assert_eq!(
+            client.try_verify_payment(&oracle, &second, &transaction_hash, &payer, &1_000, &nonce),
+            Err(Ok(Error::InvalidNonce))
+        );
+    }
+
+    // Rate posts share the oracle's sequence
+    let eur = Symbol::new(&env, "EUR");
+    assert_eq!(
+        client.try_post_rate(&oracle, &usdc, &eur, &9_000_000, &0, &5),
+        Err(Ok(Error::InvalidNonce))
+    );
+    client.post_rate(&oracle, &usdc, &eur, &9_000_000, &0, &6);
+    assert_eq!(
+        client.verify_payment(&oracle, &second, &transaction_hash, &payer, &1_000, &7),
+        PaymentStatus::Confirmed
+    );
+}
+
+#[test]
+fn test_anchor_reference_on_settlement() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    let merchant_id = register_merchant(&env, &client);
+
+    let payment_id = String::from_str(&env, "offramp_payment");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &10_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &10_000,
+        &next_nonce(&client, &oracle),
+    );
+
+    let anchor_account = Address::generate(&env);
+    let external_id = String::from_str(&env, "anchor-tx-8841");
+    let memo = String::from_str(&env, "FLX-offramp");
+    let set = |memo_type: MemoType, memo: &String| {
+        client.try_set_anchor_reference(
+            &operator,
+            &payment_id,
+            &external_id,
+            &anchor_account,
+            &memo_type,
+            memo,
+        )
+    };
+
+    // References attach only once the payment is settled
+    assert_eq!(
+        set(MemoType::Text, &memo),
+        Err(Ok(Error::SettlementNotFound))
+    );
+    client.settle_payment(&operator, &payment_id);
+
+    assert_eq!(
+        set(MemoType::None, &memo),
+        Err(Ok(Error::InvalidAnchorReference))
+    );
+    assert_eq!(
+        set(
+            MemoType::Text,
+            &String::from_str(&env, "a text memo well past 28 bytes")
+        ),
+        Err(Ok(Error::InvalidAnchorReference))
+    );
+    assert_eq!(
+        client.try_set_anchor_reference(
+            &Address::generate(&env),
+            &payment_id,
+            &external_id,
+            &anchor_account,
+            &MemoType::Text,
+            &memo,
+        ),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    let reference = set(MemoType::Text, &memo).unwrap().unwrap();
+    assert_eq!(reference.recorded_by, operator);
+    assert_eq!(
+        client.get_anchor_reference(&payment_id),
+        Some(reference.clone())
+    );
+    assert_eq!(reference.external_transaction_id, external_id);
+    assert_eq!(reference.anchor_account, anchor_account);
+    assert_eq!(reference.memo, memo);
+}
+
+#[test]
+fn test_bump_payment_extends_ttl() {
+    use soroban_sdk::testutils::storage::Persistent as _;
+
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let payment_id = String::from_str(&env, "long_lived");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &1_000,
+        &Symbol::new(&env, "USDC"),
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+    let payment_ttl = || {
+        env.as_contract(&client.address, || {
+            env.storage()
+                .persistent()
+                .get_ttl(&DataKey::Payment(payment_id.clone()))
+        })
+    };
+    // New payments start with the standard bump
+    assert!(payment_ttl() >= ttl::BUMP_THRESHOLD);
+
+    let requested = client.bump_payment(&payment_id, &(ttl::BUMP_AMOUNT * 4));
+    assert_eq!(requested, ttl::BUMP_AMOUNT * 4);
+    assert!(payment_ttl() >= requested);
+
+    // Requests beyond the network maximum are capped
+    assert!(client.bump_payment(&payment_id, &u32::MAX) < u32::MAX);
+    assert_eq!(
+        client.try_bump_payment(&String::from_str(&env, "missing"), &100),
+        Err(Ok(Error::PaymentNotFound))
+    );
+
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    assert_eq!(registry.bump_merchant(&merchant_id, &1_000), 1_000);
+}
+
+#[test]
+fn test_archive_settled_payment_keeps_summary() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let payment_id = String::from_str(&env, "archived_payment");
+    let create = || {
+        client.try_create_payment(
+            &payment_id,
+            &merchant_id,
+            &10_000,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    assert!(create().is_ok());
+
+    // Only final statuses can be archived
+    assert_eq!(
+        client.try_archive_payment(&operator, &payment_id),
+        Err(Ok(Error::PaymentNotArchivable))
+    );
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &10_000,
+        &next_nonce(&client, &oracle),
+    );
+    let settled = client.settle_payment(&operator, &payment_id);
+
+    // ...and only once the retention period has passed
+    client.set_archive_retention(&admin, &86_400);
+    assert_eq!(
+        client.try_archive_payment(&operator, &payment_id),
+        Err(Ok(Error::PaymentNotArchivable))
+    );
+    env.ledger()
+        .set_timestamp(settled.settled_at.unwrap() + 86_400);
+    assert_eq!(
+        client.try_archive_payment(&Address::generate(&env), &payment_id),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    let summary = client.archive_payment(&operator, &payment_id);
+    assert_eq!(summary.status, PaymentStatus::Settled);
+    assert_eq!(summary.amount, 10_000);
+    assert_eq!(summary.merchant_id, merchant_id);
+    assert_eq!(summary.settled_at, settled.settled_at);
+    assert_eq!(summary.archived_at, Some(env.ledger().timestamp()));
+
+    assert_eq!(
+        client.try_get_payment(&payment_id),
+        Err(Ok(Error::PaymentNotFound))
+    );
+    assert_eq!(client.get_payment_summary(&payment_id), summary);
+    // Archived ids stay taken
+    assert_eq!(create().unwrap_err(), Ok(Error::PaymentAlreadyExists));
+}
+
+#[test]
+fn test_partner_share_of_processing_fee() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let operator = Address::generate(&env);
+    client.grant_role(&admin, &role_settlement_operator(&env), &operator);
+    client.set_fee_config(&admin, &100, &Address::generate(&env)); // 1%
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+
+    let partner = Address::generate(&env);
+    let payment_id = String::from_str(&env, "referred_payment");
+    client.create_payment(
+        &payment_id,
+        &merchant_id,
+        &100_000,
+        &usdc,
+        &Address::generate(&env),
+        &(env.ledger().timestamp() + 3600),
+        &String::from_str(&env, ""),
+        &None,
+        &None,
+        &None,
+        &None,
+    );
+
+    // Partners must be registered, and stay under their ceiling
+    assert_eq!(
+        client.try_set_payment_partner(&merchant_id, &payment_id, &partner, &2_000),
+        Err(Ok(Error::PartnerNotFound))
+    );
+    client.register_partner(&admin, &partner, &2_500);
+    assert_eq!(
+        client.try_set_payment_partner(&merchant_id, &payment_id, &partner, &3_000),
+        Err(Ok(Error::InvalidFee))
+    );
+    client.set_payment_partner(&merchant_id, &payment_id, &partner, &2_000);
+    assert_eq!(
+        client.get_payment_partner(&payment_id),
+        Some(PartnerShare {
+            partner: partner.clone(),
+            share_bps: 2_000,
+        })
+    );
+
+    client.verify_payment(
+        &oracle,
+        &payment_id,
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &100_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(
+        client.try_set_payment_partner(&merchant_id, &payment_id, &partner, &2_000),
+        Err(Ok(Error::PaymentAlreadyProcessed))
+    );
+    let settled = client.settle_payment(&operator, &payment_id);
+
+    // The merchant pays the full 1_000 fee; 20% of it goes to the partner
+    assert_eq!(settled.fee_amount, 1_000);
+    assert_eq!(client.get_partner_earnings(&partner, &usdc), 200);
+    assert_eq!(client.get_collected_fees(&admin, &usdc), 800);
+    let invoice = client.get_fee_invoice(&merchant_id, &usdc, &1);
+    assert_eq!(invoice.processing_fees, 1_000);
+    assert_eq!(invoice.partner_fees, 200);
+    assert_eq!(invoice.total_fees, 1_000);
+
+    // Deactivated partners keep what they earned
+    client.deactivate_partner(&admin, &partner);
+    assert!(!client.get_partner(&partner).active);
+    assert_eq!(client.get_partner_earnings(&partner, &usdc), 200);
+}
+
+#[test]
+fn test_invoice_paid_through_charge() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    client.add_deposit_address(&merchant_id, &Address::generate(&env));
+    client.add_deposit_address(&merchant_id, &Address::generate(&env));
+
+    let line = |quantity: u32, unit_price: i128| InvoiceLine {
+        description_hash: BytesN::<32>::random(&env),
+        quantity,
+        unit_price,
+    };
+    let due_at = env.ledger().timestamp() + 7 * 86_400;
+    assert_eq!(
+        client.try_create_invoice(&merchant_id, &usdc, &Vec::new(&env), &due_at),
+        Err(Ok(Error::InvalidInvoice))
+    );
+    let lines = Vec::from_array(&env, [line(3, 2_500), line(1, 10_000)]);
+    let invoice = client.create_invoice(&merchant_id, &usdc, &lines, &due_at);
+    assert_eq!(invoice.total, 17_500);
+    assert_eq!(invoice.status, InvoiceStatus::Open);
+
+    // Paying raises a charge for the total; asking again returns the same pending charge
+    let payer = Address::generate(&env);
+    let charge = client.pay_invoice(&payer, &invoice.invoice_id);
+    assert_eq!(charge.amount, 17_500);
+    assert_eq!(charge.merchant_id, merchant_id);
+    assert_eq!(charge.expires_at, due_at);
+    assert_eq!(client.pay_invoice(&payer, &invoice.invoice_id), charge);
+
+    client.verify_payment(
+        &oracle,
+        &charge.payment_id,
+        &BytesN::<32>::random(&env),
+        &payer,
+        &17_500,
+        &next_nonce(&client, &oracle),
+    );
+    let paid = client.get_invoice(&invoice.invoice_id);
+    assert_eq!(paid.status, InvoiceStatus::Paid);
+    assert_eq!(paid.payment_id, Some(charge.payment_id));
+    assert_eq!(
+        client.try_cancel_invoice(&merchant_id, &invoice.invoice_id),
+        Err(Ok(Error::InvoiceNotOpen))
+    );
+
+    // Cancelling an invoice also withdraws the charge raised for it
+    let unpaid = client.create_invoice(&merchant_id, &usdc, &lines, &due_at);
+    let pending = client.pay_invoice(&payer, &unpaid.invoice_id);
+    client.cancel_invoice(&merchant_id, &unpaid.invoice_id);
+    assert_eq!(
+        client.get_payment(&pending.payment_id).status,
+        PaymentStatus::Cancelled
+    );
+    assert_eq!(
+        client.try_pay_invoice(&payer, &unpaid.invoice_id),
+        Err(Ok(Error::InvoiceNotOpen))
+    );
+    assert_eq!(client.get_merchant_invoices(&merchant_id).len(), 2);
+}
+
+#[test]
+fn test_instalment_plan_raises_charges_as_they_come_due() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    client.add_deposit_address(&merchant_id, &Address::generate(&env));
+    client.add_deposit_address(&merchant_id, &Address::generate(&env));
+    env.ledger().set_timestamp(1_000_000);
+
+    let payer = Address::generate(&env);
+    let interval = 30 * 86_400;
+    let first_due_at = env.ledger().timestamp() + 10 * 86_400;
+    assert_eq!(
+        client.try_create_instalment_plan(
+            &merchant_id,
+            &payer,
+            &10_001,
+            &usdc,
+            &(MAX_INSTALMENTS + 1),
+            &interval,
+            &first_due_at,
+        ),
+        Err(Ok(Error::InvalidInstalmentPlan))
+    );
+    let plan = client.create_instalment_plan(
+        &merchant_id,
+        &payer,
+        &10_001,
+        &usdc,
+        &3,
+        &interval,
+        &first_due_at,
+    );
+    assert_eq!(plan.status, PlanStatus::OnTrack);
+
+    // The first instalment carries the rounding remainder; nothing is charged up front
+    let schedule = client.get_plan_schedule(&plan.plan_id);
+    assert_eq!(schedule.len(), 3);
+    assert_eq!(schedule.get(0).unwrap().amount, 3_335);
+    assert_eq!(schedule.get(1).unwrap().amount, 3_333);
+    assert_eq!(schedule.get(2).unwrap().due_at, first_due_at + 2 * interval);
+    assert!(schedule
+        .iter()
+        .all(|instalment| instalment.payment_id.is_none()));
+    assert_eq!(
+        client.try_issue_instalment(&payer, &plan.plan_id),
+        Err(Ok(Error::InstalmentNotDue))
+    );
+
+    // Close to due the charge can be raised; asking again returns the same pending charge
+    env.ledger().set_timestamp(first_due_at - 86_400);
+    let first = client.issue_instalment(&payer, &plan.plan_id);
+    assert_eq!(first.amount, 3_335);
+    assert_eq!(first.expires_at, first_due_at + INSTALMENT_GRACE_PERIOD);
+    assert_eq!(client.issue_instalment(&oracle, &plan.plan_id), first);
+    client.verify_payment(
+        &oracle,
+        &first.payment_id,
+        &BytesN::<32>::random(&env),
+        &payer,
+        &3_335,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(client.get_plan(&plan.plan_id).paid_count, 1);
+
+    // Past due the plan runs late until the instalment is paid
+    env.ledger().set_timestamp(first_due_at + interval + 1);
+    assert_eq!(client.get_plan(&plan.plan_id).status, PlanStatus::Late);
+    let second = client.issue_instalment(&payer, &plan.plan_id);
+    client.verify_payment(
+        &oracle,
+        &second.payment_id,
+        &BytesN::<32>::random(&env),
+        &payer,
+        &3_333,
+        &next_nonce(&client, &oracle),
+    );
+    let plan_after = client.get_plan(&plan.plan_id);
+    assert_eq!(plan_after.paid_count, 2);
+    assert_eq!(plan_after.status, PlanStatus::OnTrack);
+
+    // Unpaid past the grace period, the plan defaults and no further charges are raised
+    env.ledger()
+        .set_timestamp(first_due_at + 2 * interval + INSTALMENT_GRACE_PERIOD + 1);
+    assert_eq!(client.get_plan(&plan.plan_id).status, PlanStatus::Defaulted);
+    assert_eq!(
+        client.try_issue_instalment(&payer, &plan.plan_id),
+        Err(Ok(Error::InstalmentPlanNotActive))
+    );
+    let schedule = client.get_plan_schedule(&plan.plan_id);
+    assert_eq!(schedule.get(0).unwrap().payment_id, Some(first.payment_id));
+    assert!(schedule.get(1).unwrap().paid);
+    assert!(!schedule.get(2).unwrap().paid);
+    assert_eq!(schedule.get(2).unwrap().payment_id, None);
+
+    // Only the payer or an operator may raise charges
+    let single = client.create_instalment_plan(
+        &merchant_id,
+        &payer,
+        &5_000,
+        &usdc,
+        &1,
+        &interval,
+        &env.ledger().timestamp(),
+    );
+    assert_eq!(
+        client.try_issue_instalment(&Address::generate(&env), &single.plan_id),
+        Err(Ok(Error::Unauthorized))
+    );
+    let charge = client.issue_instalment(&payer, &single.plan_id);
+    client.verify_payment(
+        &oracle,
+        &charge.payment_id,
+        &BytesN::<32>::random(&env),
+        &payer,
+        &5_000,
+        &next_nonce(&client, &oracle),
+    );
+    assert_eq!(
+        client.get_plan(&single.plan_id).status,
+        PlanStatus::Completed
+    );
+}
+
+#[test]
+fn test_platform_status_reports_pauses_and_oracle_health() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+
+    let status = client.get_platform_status();
+    assert!(!status.delayed);
+    assert!(status.oracle_healthy);
+    assert_eq!(status.last_oracle_activity, None);
+
+    let create = |id: &str| {
+        client.try_create_payment(
+            &String::from_str(&env, id),
+            &merchant_id,
+            &10_000,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 7200),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    create("status_1").unwrap().unwrap();
+    create("status_2").unwrap().unwrap();
+    client.verify_payment(
+        &oracle,
+        &String::from_str(&env, "status_1"),
+        &BytesN::<32>::random(&env),
+        &Address::generate(&env),
+        &10_000,
+        &next_nonce(&client, &oracle),
+    );
+    let status = client.get_platform_status();
+    assert_eq!(status.pending_payments, 1);
+    assert_eq!(status.settlement_backlog, 1);
+    assert_eq!(status.last_oracle_activity, Some(env.ledger().timestamp()));
+    assert!(!status.delayed);
+
+    // A charge still waiting while the oracle has gone quiet shows as degraded
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + ORACLE_HEALTH_WINDOW + 1);
+    let status = client.get_platform_status();
+    assert!(!status.oracle_healthy);
+    assert!(status.delayed);
+
+    // Pausing a currency blocks new charges in it and is surfaced too
+    client.pause_currency(&admin, &usdc);
+    assert_eq!(create("status_3"), Err(Ok(Error::ContractPaused)));
+    assert_eq!(
+        client.get_platform_status().paused_currencies,
+        Vec::from_array(&env, [usdc.clone()])
+    );
+    client.unpause_currency(&admin, &usdc);
+    assert!(client.get_platform_status().paused_currencies.is_empty());
+    assert!(create("status_3").is_ok());
+}
+
+#[test]
+fn test_payment_link_raises_charge_per_use() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    for _ in 0..3 {
+        client.add_deposit_address(&merchant_id, &Address::generate(&env));
+    }
+    let expires_at = env.ledger().timestamp() + 30 * 86_400;
+    let payer = Address::generate(&env);
+
+    // Fixed-price link, good for two uses
+    let link = client.create_payment_link(&merchant_id, &Some(25_000), &usdc, &2, &expires_at);
+    assert_eq!(
+        client.try_pay_link(&link.link_id, &payer, &20_000),
+        Err(Ok(Error::InvalidAmount))
+    );
+    let first = client.pay_link(&link.link_id, &payer, &25_000);
+    let second = client.pay_link(&link.link_id, &payer, &25_000);
+    assert_ne!(first.payment_id, second.payment_id);
+    assert_ne!(first.deposit_address, second.deposit_address);
+    assert_eq!(first.merchant_id, merchant_id);
+    assert_eq!(first.status, PaymentStatus::Pending);
+    assert_eq!(client.get_payment_link(&link.link_id).uses, 2);
+    assert_eq!(
+        client.try_pay_link(&link.link_id, &payer, &25_000),
+        Err(Ok(Error::LinkInactive))
+    );
+
+    // Open-amount link lets the payer choose, until the merchant turns it off
+    let tips = client.create_payment_link(&merchant_id, &None, &usdc, &0, &expires_at);
+    assert_eq!(client.pay_link(&tips.link_id, &payer, &1_234).amount, 1_234);
+    assert_eq!(
+        client.try_deactivate_link(&Address::generate(&env), &tips.link_id),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.deactivate_link(&merchant_id, &tips.link_id);
+    assert_eq!(
+        client.try_pay_link(&tips.link_id, &payer, &1_234),
+        Err(Ok(Error::LinkInactive))
+    );
+    assert_eq!(client.get_merchant_links(&merchant_id).len(), 2);
+}
+
+#[test]
+fn test_merchant_limits_cap_new_payments() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let registry = MerchantRegistryClient::new(&env, &client.get_merchant_registry().unwrap());
+    registry.set_merchant_limits(
+        &admin,
+        &merchant_id,
+        &MerchantLimits {
+            max_single_payment: 5_000,
+            max_daily_volume: 8_000,
+            max_weekly_volume: 12_000,
+        },
+    );
+
+    let usdc = Symbol::new(&env, "USDC");
+    let create = |i: u64, amount: i128| {
+        client.try_create_payment(
+            &IdBuilder::new("capped_").push_u64(i).build(&env),
+            &merchant_id,
+            &amount,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    assert_eq!(create(1, 5_001), Err(Ok(Error::LimitExceeded)));
+    assert!(create(1, 5_000).is_ok());
+    assert_eq!(create(2, 3_001), Err(Ok(Error::LimitExceeded)));
+    assert!(create(2, 3_000).is_ok());
+
+    // The daily window rolls over, but the weekly one still counts the first day
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + 24 * 3600);
+    assert_eq!(create(3, 4_001), Err(Ok(Error::LimitExceeded)));
+    assert!(create(3, 4_000).is_ok());
+}
+
+#[test]
+fn test_payment_id_validation() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let merchant_id = register_merchant(&env, &client);
+
+    let usdc = Symbol::new(&env, "USDC");
+    let create = |payment_id: &str| {
+        client.try_create_payment(
+            &String::from_str(&env, payment_id),
+            &merchant_id,
+            &1000i128,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        )
+    };
+    assert_eq!(create(""), Err(Ok(Error::InvalidPaymentId)));
+    assert_eq!(create("pay me"), Err(Ok(Error::InvalidIdCharacter)));
+    assert_eq!(create("orders/42"), Err(Ok(Error::InvalidIdCharacter)));
+    assert_eq!(
+        create("a123456789b123456789c123456789d123456789e123456789f123456789g1234"),
+        Err(Ok(Error::IdTooLong))
+    );
+
+    // Prefixes the contract generates ids under are off limits to callers
+    assert_eq!(create("refund_1"), Err(Ok(Error::ReservedIdPrefix)));
+    assert_eq!(create("link_1_1"), Err(Ok(Error::ReservedIdPrefix)));
+    assert_eq!(create("sub_1_1"), Err(Ok(Error::ReservedIdPrefix)));
+
+    assert!(create("order-2024.001_A~1").is_ok());
+}
+
+#[test]
+fn test_refund_ids_are_sequential() {
+    let env = Env::default();
+    let (_admin, client) = setup_contract(&env);
+
+    let payment_id = String::from_str(&env, "payment_123");
+    let reason = String::from_str(&env, "Partial refund");
+    let requester = Address::generate(&env);
+    let mut last = String::from_str(&env, "");
+    for _ in 0..12 {
+        last = client.create_refund(
+            &payment_id,
+            &10i128,
+            &RefundReason::CustomerRequest,
+            &Some(reason.clone()),
+            &requester,
+        );
+    }
+    assert_eq!(last, String::from_str(&env, "refund_12"));
+    assert_eq!(client.get_refund(&last).amount, 10i128);
+    assert_eq!(
+        client
+            .get_refund(&String::from_str(&env, "refund_11"))
+            .amount,
+        10i128
+    );
+
+    let result = client.try_create_refund(
+        &String::from_str(&env, "bad id"),
+        &10i128,
+        &RefundReason::CustomerRequest,
+        &Some(reason.clone()),
+        &requester,
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidIdCharacter)));
+}
+
+#[test]
+fn test_payer_limits_cap_subscription_charges() {
+    let env = Env::default();
+    let (oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    client.set_feature(&admin, &Symbol::new(&env, "SUBSCRIPTIONS"), &true);
+
+    let merchant_id = register_merchant(&env, &client);
+    let payer = Address::generate(&env);
+    let first_due_at = env.ledger().timestamp() + 100;
+    let subscribe = |amount: i128| {
+        client
+            .create_subscription(
+                &payer,
+                &merchant_id,
+                &amount,
+                &Symbol::new(&env, "USDC"),
+                &(24 * 3600),
+                &first_due_at,
+            )
+            .subscription_id
+    };
+    let basic = subscribe(5_000);
+    let premium = subscribe(6_000);
+    let enterprise = subscribe(7_000);
+
+    client.set_payer_limits(
+        &payer,
+        &PayerLimits {
+            max_single_payment: 6_000,
+            max_daily_total: 10_000,
+        },
+    );
+    env.ledger().set_timestamp(first_due_at);
+    let deposit_address = Address::generate(&env);
+
+    let result = client.try_charge_subscription(&oracle, &enterprise, &deposit_address);
+    assert_eq!(result, Err(Ok(Error::PayerLimitExceeded)));
+    client.charge_subscription(&oracle, &basic, &deposit_address);
+    let result = client.try_charge_subscription(&oracle, &premium, &deposit_address);
+    assert_eq!(result, Err(Ok(Error::PayerLimitExceeded)));
+    assert_eq!(client.get_payer_daily_total(&payer, &merchant_id), 5_000);
+
+    // The daily total resets, and clearing the limits lifts the single-charge cap too
+    env.ledger().set_timestamp(first_due_at + 24 * 3600);
+    client.charge_subscription(&oracle, &premium, &deposit_address);
+    client.clear_payer_limits(&payer);
+    assert_eq!(client.get_payer_limits(&payer), None);
+    client.charge_subscription(&oracle, &enterprise, &deposit_address);
+}
+
+#[test]
+fn test_migrate_range_resumes_without_repeating_records() {
+    let env = Env::default();
+    let (_oracle, client) = setup_payment_processor(&env);
+    let admin = client.get_admin().unwrap();
+    let merchant_id = register_merchant(&env, &client);
+    let usdc = Symbol::new(&env, "USDC");
+    let create = |i: u64| {
+        client.create_payment(
+            &IdBuilder::new("legacy_").push_u64(i).build(&env),
+            &merchant_id,
+            &1000i128,
+            &usdc,
+            &Address::generate(&env),
+            &(env.ledger().timestamp() + 3600),
+            &String::from_str(&env, ""),
+            &None,
+            &None,
+            &None,
+            &None,
+        );
+    };
+    let first_bucket = env.ledger().timestamp() / 3600;
+    for i in 1..=3 {
+        create(i);
+    }
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + 2 * 3600);
+    for i in 4..=5 {
+        create(i);
+    }
+    assert_eq!(client.get_schema_version(&RecordKind::Payment), 0);
+
+    let origin = MigrationCursor {
+        bucket: first_bucket,
+        offset: 0,
+    };
+    let next = client
+        .migrate_range(&admin, &RecordKind::Payment, &origin, &2)
+        .unwrap();
+    assert_eq!(
+        next,
+        MigrationCursor {
+            bucket: first_bucket,
+            offset: 2
+        }
+    );
+
+    // A retry from the stale origin carries on from the saved progress
+    let next = client
+        .migrate_range(&admin, &RecordKind::Payment, &origin, &2)
+        .unwrap();
+    assert_eq!(
+        next,
+        MigrationCursor {
+            bucket: first_bucket + 2,
+            offset: 1
+        }
+    );
+    let progress = client.get_migration_progress(&RecordKind::Payment).unwrap();
+    assert_eq!(progress.migrated, 4);
+    assert_eq!(progress.completed_at, None);
+
+    // Jumping ahead of the progress would skip records
+    let ahead = MigrationCursor {
+        bucket: first_bucket + 5,
+        offset: 0,
+    };
+    let result = client.try_migrate_range(&admin, &RecordKind::Payment, &ahead, &2);
+    assert_eq!(result, Err(Ok(Error::MigrationCursorAhead)));
+    let result = client.try_migrate_range(&admin, &RecordKind::Refund, &next, &2);
+    assert_eq!(result, Err(Ok(Error::UnsupportedRecordKind)));
+
+    assert_eq!(
+        client.migrate_range(&admin, &RecordKind::Payment, &next, &MAX_BATCH_SIZE),
+        None
+    );
+    let progress = client.get_migration_progress(&RecordKind::Payment).unwrap();
+    assert_eq!(progress.migrated, 5);
+    assert!(progress.completed_at.is_some());
+    assert_eq!(
+        client.get_schema_version(&RecordKind::Payment),
+        SCHEMA_VERSION
+    );
+    assert_eq!(
+        client.migrate_range(&admin, &RecordKind::Payment, &origin, &2),
+        None
+    );
 }
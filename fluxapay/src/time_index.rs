@@ -0,0 +1,87 @@
+use soroban_sdk::{contracttype, vec, Env, String, Vec};
+
+use crate::Error;
+
+// Hour-bucketed index of created records, for support lookups by time
+pub const BUCKET_SECONDS: u64 = 3600;
+pub const MAX_BUCKETS_PER_QUERY: u64 = 7 * 24;
+pub const PAGE_SIZE: u32 = 20;
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RecordKind {
+    Payment,
+    Refund,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimeIndexEntry {
+    pub kind: RecordKind,
+    pub id: String,
+    pub amount: i128,
+    pub created_at: u64,
+}
+
+#[contracttype]
+pub enum TimeIndexDataKey {
+    Bucket(RecordKind, u64), // (kind, created_at / BUCKET_SECONDS) -> Vec<TimeIndexEntry>
+}
+
+pub struct TimeIndex;
+
+impl TimeIndex {
+    pub fn record(env: &Env, kind: RecordKind, id: String, amount: i128) {
+        let created_at = env.ledger().timestamp();
+        let key = TimeIndexDataKey::Bucket(kind, created_at / BUCKET_SECONDS);
+        let mut entries: Vec<TimeIndexEntry> =
+            env.storage().persistent().get(&key).unwrap_or(vec![env]);
+        entries.push_back(TimeIndexEntry {
+            kind,
+            id,
+            amount,
+            created_at,
+        });
+        env.storage().persistent().set(&key, &entries);
+    }
+
+    /// Every entry of `kind` in one bucket, in creation order
+    pub fn bucket(env: &Env, kind: RecordKind, bucket: u64) -> Vec<TimeIndexEntry> {
+        env.storage()
+            .persistent()
+            .get(&TimeIndexDataKey::Bucket(kind, bucket))
+            .unwrap_or(vec![env])
+    }
+
+    /// Entries of `kind` created within [from, to], `PAGE_SIZE` per page
+    pub fn find(
+        env: &Env,
+        kind: RecordKind,
+        from: u64,
+        to: u64,
+        page: u32,
+    ) -> Result<Vec<TimeIndexEntry>, Error> {
+        if from > to || to / BUCKET_SECONDS - from / BUCKET_SECONDS >= MAX_BUCKETS_PER_QUERY {
+            return Err(Error::InvalidTimeRange);
+        }
+
+        let skip = page.saturating_mul(PAGE_SIZE);
+        let mut seen = 0u32;
+        let mut results = vec![env];
+        for bucket in from / BUCKET_SECONDS..=to / BUCKET_SECONDS {
+            for entry in Self::bucket(env, kind, bucket).iter() {
+                if entry.created_at < from || entry.created_at > to {
+                    continue;
+                }
+                if seen >= skip {
+                    results.push_back(entry);
+                    if results.len() == PAGE_SIZE {
+                        return Ok(results);
+                    }
+                }
+                seen += 1;
+            }
+        }
+        Ok(results)
+    }
+}
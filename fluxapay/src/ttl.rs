@@ -0,0 +1,26 @@
+use soroban_sdk::{Env, IntoVal, Val};
+
+// Persistent entries are archived once their TTL runs out. Records the contracts read or
+// create are extended so live payments, refunds, merchants and roles stay available, and
+// integrators can push long-lived records further out explicitly.
+pub const DAY_IN_LEDGERS: u32 = 17_280; // ~5 second ledgers
+pub const BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+pub const BUMP_THRESHOLD: u32 = BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+/// Extend an existing persistent entry to `BUMP_AMOUNT` once it drops below `BUMP_THRESHOLD`
+pub fn extend<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+    env.storage()
+        .persistent()
+        .extend_ttl(key, BUMP_THRESHOLD, BUMP_AMOUNT);
+}
+
+/// Keep a persistent entry live for at least `ledgers` more ledgers, capped at the network
+/// maximum; returns the TTL requested, or `None` if there is no such entry
+pub fn extend_by<K: IntoVal<Env, Val>>(env: &Env, key: &K, ledgers: u32) -> Option<u32> {
+    if !env.storage().persistent().has(key) {
+        return None;
+    }
+    let ledgers = ledgers.min(env.storage().max_ttl());
+    env.storage().persistent().extend_ttl(key, ledgers, ledgers);
+    Some(ledgers)
+}